@@ -0,0 +1,540 @@
+//! Computing the [`Section`]s for the differences between two texts.
+
+mod algorithm;
+
+use std::borrow::Cow;
+
+use scm_record::{ChangeType, Section, SectionChangedLine};
+
+use crate::Opts;
+
+use algorithm::Edit;
+
+/// The algorithm used to match up lines between the old and new contents.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum DiffAlgorithm {
+    /// Find a minimal set of changes using Myers' algorithm.
+    #[default]
+    Myers,
+
+    /// Match up lines which are unique in both the old and new contents
+    /// first. This tends to produce more readable diffs for code where
+    /// boilerplate lines such as braces are moved around.
+    Patience,
+
+    /// Like patience diff, but also uses lines which are repeated a small
+    /// number of times, preferring the least frequent ones.
+    Histogram,
+}
+
+/// How whitespace differences should be treated when comparing lines.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, clap::ValueEnum)]
+pub enum WhitespaceMode {
+    /// Lines must match exactly.
+    #[default]
+    Exact,
+
+    /// Ignore differences in line endings, such as between `\n` and `\r\n`, or
+    /// a missing newline at the end of the file.
+    IgnoreLineEndings,
+
+    /// Ignore whitespace at the end of lines (including line endings).
+    IgnoreTrailing,
+
+    /// Ignore changes in the amount of whitespace, such as reindentation, but
+    /// not whitespace being added where there was none before. Implies
+    /// `IgnoreTrailing`.
+    IgnoreAmount,
+
+    /// Ignore all whitespace.
+    IgnoreAll,
+}
+
+impl WhitespaceMode {
+    /// Get the version of `line` (which may include its line ending) which
+    /// should be used to compare it to other lines.
+    pub fn normalize_line(self, line: &str) -> Cow<'_, str> {
+        let without_line_ending = line
+            .strip_suffix('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+            .unwrap_or(line);
+        match self {
+            WhitespaceMode::Exact => Cow::Borrowed(line),
+            WhitespaceMode::IgnoreLineEndings => Cow::Borrowed(without_line_ending),
+            WhitespaceMode::IgnoreTrailing => Cow::Borrowed(without_line_ending.trim_end()),
+            WhitespaceMode::IgnoreAmount => {
+                let mut result = String::new();
+                for word in without_line_ending.split_whitespace() {
+                    if !result.is_empty() || without_line_ending.starts_with(char::is_whitespace) {
+                        result.push(' ');
+                    }
+                    result.push_str(word);
+                }
+                Cow::Owned(result)
+            }
+            WhitespaceMode::IgnoreAll => Cow::Owned(
+                without_line_ending
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect(),
+            ),
+        }
+    }
+
+    fn normalize_lines<'a>(self, lines: &[&'a str]) -> Vec<Cow<'a, str>> {
+        lines.iter().map(|line| self.normalize_line(line)).collect()
+    }
+}
+
+/// Options for computing a diff.
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    /// The algorithm used to match up lines.
+    pub algorithm: DiffAlgorithm,
+
+    /// How whitespace differences should be treated.
+    pub whitespace: WhitespaceMode,
+
+    /// Text files with more bytes than this, on either side, are summarized as
+    /// a [`Section::Summary`] instead of being diffed.
+    pub max_file_size: Option<u64>,
+
+    /// Text files with more lines than this, on either side, are summarized as
+    /// a [`Section::Summary`] instead of being diffed.
+    pub max_file_lines: Option<usize>,
+
+    /// Whether text files which appear to be generated, such as lockfiles, are
+    /// summarized as a [`Section::Summary`] instead of being diffed. See
+    /// [`crate::summary::is_generated_file`].
+    pub summarize_generated: bool,
+}
+
+impl DiffOptions {
+    /// The options for diffing files as requested on the command line. A limit
+    /// of 0 means that there's no limit.
+    pub fn from_opts(opts: &Opts) -> Self {
+        let Opts {
+            dir_diff: _,
+            left: _,
+            right: _,
+            base: _,
+            output: _,
+            read_only: _,
+            dry_run: _,
+            find_renames: _,
+            find_copies: _,
+            include: _,
+            exclude: _,
+            gitignore: _,
+            whitespace,
+            diff_algorithm,
+            max_file_size,
+            max_file_lines,
+            show_generated,
+            encoding: _,
+            preselect: _,
+            script: _,
+        } = opts;
+        Self {
+            algorithm: *diff_algorithm,
+            whitespace: *whitespace,
+            max_file_size: Some(*max_file_size).filter(|size| *size > 0),
+            max_file_lines: Some(*max_file_lines).filter(|lines| *lines > 0),
+            summarize_generated: !show_generated,
+        }
+    }
+}
+
+/// Compute the [`Section`]s for the changes from `old_contents` to
+/// `new_contents`.
+///
+/// If whitespace differences are ignored, then lines which only differ in
+/// whitespace are not used to split up or align hunks, but are still reported
+/// as a removed line and an added line. These lines start out checked, so
+/// by default the whitespace changes are kept, but unchecking them restores
+/// the line from `old_contents`.
+///
+/// The contents are always diffed in full. The limits in `options` are checked
+/// by [`crate::summary::summarize_changes`] instead.
+pub fn create_diff(
+    old_contents: &str,
+    new_contents: &str,
+    options: &DiffOptions,
+) -> Vec<Section<'static>> {
+    let DiffOptions {
+        algorithm,
+        whitespace,
+        max_file_size: _,
+        max_file_lines: _,
+        summarize_generated: _,
+    } = options;
+    let (old_lines, new_lines): (Vec<&str>, Vec<&str>) = (
+        old_contents.split_inclusive('\n').collect(),
+        new_contents.split_inclusive('\n').collect(),
+    );
+
+    let edits = match algorithm {
+        DiffAlgorithm::Myers => diffy_edits(&old_lines, &new_lines, *whitespace),
+        DiffAlgorithm::Patience => algorithm::patience(
+            &whitespace.normalize_lines(&old_lines),
+            &whitespace.normalize_lines(&new_lines),
+        ),
+        DiffAlgorithm::Histogram => algorithm::histogram(
+            &whitespace.normalize_lines(&old_lines),
+            &whitespace.normalize_lines(&new_lines),
+        ),
+    };
+    if edits.iter().all(|edit| *edit == Edit::Equal) && old_lines == new_lines {
+        return Vec::new();
+    }
+
+    // Map the edits back to the original lines. Lines which are only equal up
+    // to whitespace are reported as a checked removal and addition.
+    let (mut old_idx, mut new_idx) = (0, 0);
+    let mut sections = Vec::new();
+    for edit in edits {
+        match edit {
+            Edit::Equal => {
+                let (old_line, new_line) = (old_lines[old_idx], new_lines[new_idx]);
+                old_idx += 1;
+                new_idx += 1;
+                if old_line == new_line {
+                    let line = Cow::Owned(new_line.to_owned());
+                    match sections.last_mut() {
+                        Some(Section::Unchanged { lines }) => {
+                            lines.push(line);
+                        }
+                        _ => {
+                            sections.push(Section::Unchanged { lines: vec![line] });
+                        }
+                    }
+                } else {
+                    push_changed_line(
+                        &mut sections,
+                        SectionChangedLine {
+                            is_checked: true,
+                            change_type: ChangeType::Removed,
+                            line: Cow::Owned(old_line.to_owned()),
+                        },
+                    );
+                    push_changed_line(
+                        &mut sections,
+                        SectionChangedLine {
+                            is_checked: true,
+                            change_type: ChangeType::Added,
+                            line: Cow::Owned(new_line.to_owned()),
+                        },
+                    );
+                }
+            }
+            Edit::Delete => {
+                old_idx += 1;
+                push_changed_line(
+                    &mut sections,
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: ChangeType::Removed,
+                        line: Cow::Owned(old_lines[old_idx - 1].to_owned()),
+                    },
+                );
+            }
+            Edit::Insert => {
+                new_idx += 1;
+                push_changed_line(
+                    &mut sections,
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: ChangeType::Added,
+                        line: Cow::Owned(new_lines[new_idx - 1].to_owned()),
+                    },
+                );
+            }
+        }
+    }
+    sections
+}
+
+/// Append `line` to the trailing [`Section::Changed`] in `sections`, starting a
+/// new one if necessary.
+fn push_changed_line(sections: &mut Vec<Section<'static>>, line: SectionChangedLine<'static>) {
+    match sections.last_mut() {
+        Some(Section::Changed { lines }) => {
+            lines.push(line);
+        }
+        _ => {
+            sections.push(Section::Changed { lines: vec![line] });
+        }
+    }
+}
+
+/// Compute the edits between `old_lines` and `new_lines` using `diffy`, which
+/// implements Myers' algorithm.
+fn diffy_edits(old_lines: &[&str], new_lines: &[&str], whitespace: WhitespaceMode) -> Vec<Edit> {
+    let normalize = |lines: &[&str]| -> String {
+        match whitespace {
+            WhitespaceMode::Exact => lines.concat(),
+            WhitespaceMode::IgnoreLineEndings
+            | WhitespaceMode::IgnoreTrailing
+            | WhitespaceMode::IgnoreAmount
+            | WhitespaceMode::IgnoreAll => lines
+                .iter()
+                .map(|line| format!("{}\n", whitespace.normalize_line(line)))
+                .collect(),
+        }
+    };
+    let (old_normalized, new_normalized) = (normalize(old_lines), normalize(new_lines));
+
+    let patch = {
+        // Set the context length to the maximum number of lines in either file,
+        // because we will handle abbreviating context ourselves.
+        let max_lines = old_lines.len().max(new_lines.len());
+        let mut diff_options = diffy::DiffOptions::new();
+        diff_options.set_context_len(max_lines);
+        diff_options.create_patch(&old_normalized, &new_normalized)
+    };
+
+    let mut edits = Vec::new();
+    let mut old_idx = 0;
+    for hunk in patch.hunks() {
+        let hunk_start = hunk.old_range().start().saturating_sub(1);
+        edits.extend(std::iter::repeat_n(Edit::Equal, hunk_start - old_idx));
+        old_idx = hunk_start;
+        for line in hunk.lines() {
+            match line {
+                diffy::Line::Context(_) => {
+                    edits.push(Edit::Equal);
+                    old_idx += 1;
+                }
+                diffy::Line::Delete(_) => {
+                    edits.push(Edit::Delete);
+                    old_idx += 1;
+                }
+                diffy::Line::Insert(_) => {
+                    edits.push(Edit::Insert);
+                }
+            }
+        }
+    }
+    edits.extend(std::iter::repeat_n(
+        Edit::Equal,
+        old_lines.len().saturating_sub(old_idx),
+    ));
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use scm_record::{FileMode, SelectedContents};
+
+    use super::*;
+
+    #[test]
+    fn test_normalize_line() {
+        let line = "  foo \t bar  \r\n";
+        assert_eq!(WhitespaceMode::Exact.normalize_line(line), line);
+        assert_eq!(
+            WhitespaceMode::IgnoreLineEndings.normalize_line(line),
+            "  foo \t bar  "
+        );
+        assert_eq!(
+            WhitespaceMode::IgnoreTrailing.normalize_line(line),
+            "  foo \t bar"
+        );
+        assert_eq!(
+            WhitespaceMode::IgnoreAmount.normalize_line(line),
+            " foo bar"
+        );
+        assert_eq!(WhitespaceMode::IgnoreAll.normalize_line(line), "foobar");
+        assert_ne!(
+            WhitespaceMode::IgnoreAmount.normalize_line("foo\n"),
+            WhitespaceMode::IgnoreAmount.normalize_line(" foo\n"),
+        );
+    }
+
+    #[test]
+    fn test_diff_options_from_opts() {
+        let opts = Opts::try_parse_from(["scm-diff-editor", "left", "right"]).unwrap();
+        insta::assert_debug_snapshot!(DiffOptions::from_opts(&opts), @"
+        DiffOptions {
+            algorithm: Myers,
+            whitespace: Exact,
+            max_file_size: Some(
+                1000000,
+            ),
+            max_file_lines: Some(
+                20000,
+            ),
+            summarize_generated: true,
+        }
+        ");
+
+        let opts = Opts::try_parse_from([
+            "scm-diff-editor",
+            "--diff-algorithm",
+            "patience",
+            "--max-file-size",
+            "0",
+            "--max-file-lines",
+            "0",
+            "--show-generated",
+            "left",
+            "right",
+        ])
+        .unwrap();
+        insta::assert_debug_snapshot!(DiffOptions::from_opts(&opts), @"
+        DiffOptions {
+            algorithm: Patience,
+            whitespace: Exact,
+            max_file_size: None,
+            max_file_lines: None,
+            summarize_generated: false,
+        }
+        ");
+    }
+
+    #[test]
+    fn test_algorithms_agree_on_trivial_inputs() {
+        let cases = [
+            ("", ""),
+            ("foo\n", "foo\n"),
+            ("", "foo\nbar\n"),
+            ("foo\nbar\n", ""),
+            ("foo\nbar\nbaz\n", "foo\nqux\nbaz\n"),
+            ("foo\nbar\n", "foo\nbar\nbaz\n"),
+            ("foo\nbar", "foo\nbar\n"),
+        ];
+        for (old, new) in cases {
+            let myers = create_diff(old, new, &DiffOptions::default());
+            for algorithm in [DiffAlgorithm::Patience, DiffAlgorithm::Histogram] {
+                let options = DiffOptions {
+                    algorithm,
+                    whitespace: WhitespaceMode::Exact,
+                    ..Default::default()
+                };
+                assert_eq!(
+                    create_diff(old, new, &options),
+                    myers,
+                    "{algorithm:?} differs from Myers for {old:?} -> {new:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_patience_diff() {
+        let old = "fn foo() {\n    foo();\n}\n\nfn bar() {\n    bar();\n}\n";
+        let new = "fn bar() {\n    bar();\n}\n";
+        let options = DiffOptions {
+            algorithm: DiffAlgorithm::Patience,
+            whitespace: WhitespaceMode::Exact,
+            ..Default::default()
+        };
+        insta::assert_debug_snapshot!(create_diff(old, new, &options), @r#"
+        [
+            Changed {
+                lines: [
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Removed,
+                        line: "fn foo() {\n",
+                    },
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Removed,
+                        line: "    foo();\n",
+                    },
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Removed,
+                        line: "}\n",
+                    },
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Removed,
+                        line: "\n",
+                    },
+                ],
+            },
+            Unchanged {
+                lines: [
+                    "fn bar() {\n",
+                    "    bar();\n",
+                    "}\n",
+                ],
+            },
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_ignored_whitespace_changes_are_selectable() {
+        let old = "fn foo() {\n  foo();\n  bar();\n}\n";
+        let new = "fn foo() {\n    foo();\n    baz();\n}\n";
+        let options = DiffOptions {
+            whitespace: WhitespaceMode::IgnoreAmount,
+            ..Default::default()
+        };
+        let sections = create_diff(old, new, &options);
+        insta::assert_debug_snapshot!(sections, @r#"
+        [
+            Unchanged {
+                lines: [
+                    "fn foo() {\n",
+                ],
+            },
+            Changed {
+                lines: [
+                    SectionChangedLine {
+                        is_checked: true,
+                        change_type: Removed,
+                        line: "  foo();\n",
+                    },
+                    SectionChangedLine {
+                        is_checked: true,
+                        change_type: Added,
+                        line: "    foo();\n",
+                    },
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Removed,
+                        line: "  bar();\n",
+                    },
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: Added,
+                        line: "    baz();\n",
+                    },
+                ],
+            },
+            Unchanged {
+                lines: [
+                    "}\n",
+                ],
+            },
+        ]
+        "#);
+
+        let mut file = scm_record::File {
+            old_path: None,
+            path: Cow::Borrowed(std::path::Path::new("foo.rs")),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections,
+        };
+        let selected_contents = |file: &scm_record::File| {
+            let (selected, _unselected) = file.get_selected_contents();
+            match selected.contents {
+                SelectedContents::Text { contents } => contents,
+                contents => panic!("unexpected contents: {contents:?}"),
+            }
+        };
+        assert_eq!(
+            selected_contents(&file),
+            "fn foo() {\n    foo();\n  bar();\n}\n"
+        );
+        file.set_checked(false);
+        assert_eq!(selected_contents(&file), old);
+        file.set_checked(true);
+        assert_eq!(selected_contents(&file), new);
+    }
+}
@@ -0,0 +1,483 @@
+//! Line-based diff algorithms which aren't provided by `diffy`.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// A single step in the edit script which transforms the old lines into the
+/// new lines.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Edit {
+    /// The next old line and the next new line are the same.
+    Equal,
+
+    /// The next old line was deleted.
+    Delete,
+
+    /// The next new line was inserted.
+    Insert,
+}
+
+/// The maximum number of times that a line may appear in the old lines for it
+/// to be used as the basis of a match by the histogram algorithm. This avoids
+/// quadratic behavior when there are many repeated lines.
+const MAX_HISTOGRAM_CHAIN_LEN: usize = 64;
+
+/// Compute an edit script using the patience diff algorithm, which only
+/// matches up lines which are unique in both the old and new lines, and then
+/// recurses between those matches.
+pub fn patience<T: Eq + Hash>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    diff_range(old, new, &mut edits, patience_anchors);
+    edits
+}
+
+/// Compute an edit script using the histogram diff algorithm, which repeatedly
+/// matches up the longest common region which contains the least-frequent
+/// lines, and then recurses on either side of it.
+pub fn histogram<T: Eq + Hash>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    diff_range(old, new, &mut edits, histogram_anchors);
+    edits
+}
+
+/// A pair of `(old_idx, new_idx, len)` describing equal ranges of lines.
+type Anchor = (usize, usize, usize);
+
+/// Diff `old` against `new` by stripping their common prefix and suffix, then
+/// using `find_anchors` to find equal ranges to recurse around. If no anchors
+/// are found, falls back to [`myers`].
+fn diff_range<T: Eq + Hash>(
+    old: &[T],
+    new: &[T],
+    edits: &mut Vec<Edit>,
+    find_anchors: fn(&[T], &[T]) -> Vec<Anchor>,
+) {
+    let prefix_len = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let (old, new) = (&old[prefix_len..], &new[prefix_len..]);
+    let suffix_len = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let (old, new) = (
+        &old[..old.len() - suffix_len],
+        &new[..new.len() - suffix_len],
+    );
+
+    edits.extend(std::iter::repeat_n(Edit::Equal, prefix_len));
+    if old.is_empty() || new.is_empty() {
+        edits.extend(std::iter::repeat_n(Edit::Delete, old.len()));
+        edits.extend(std::iter::repeat_n(Edit::Insert, new.len()));
+    } else {
+        let anchors = find_anchors(old, new);
+        if anchors.is_empty() {
+            edits.extend(myers(old, new));
+        } else {
+            let (mut old_idx, mut new_idx) = (0, 0);
+            for (anchor_old_idx, anchor_new_idx, len) in anchors {
+                diff_range(
+                    &old[old_idx..anchor_old_idx],
+                    &new[new_idx..anchor_new_idx],
+                    edits,
+                    find_anchors,
+                );
+                edits.extend(std::iter::repeat_n(Edit::Equal, len));
+                old_idx = anchor_old_idx + len;
+                new_idx = anchor_new_idx + len;
+            }
+            diff_range(&old[old_idx..], &new[new_idx..], edits, find_anchors);
+        }
+    }
+    edits.extend(std::iter::repeat_n(Edit::Equal, suffix_len));
+}
+
+/// Find the longest increasing sequence of lines which appear exactly once in
+/// each of `old` and `new`.
+fn patience_anchors<T: Eq + Hash>(old: &[T], new: &[T]) -> Vec<Anchor> {
+    // Map each line to its `(old_idx, new_idx)`, if it appears exactly once in
+    // both.
+    let mut occurrences: HashMap<&T, (Option<usize>, Option<usize>, bool)> = HashMap::new();
+    for (old_idx, line) in old.iter().enumerate() {
+        let entry = occurrences.entry(line).or_insert((None, None, true));
+        if entry.0.is_some() {
+            entry.2 = false;
+        }
+        entry.0 = Some(old_idx);
+    }
+    for (new_idx, line) in new.iter().enumerate() {
+        if let Some(entry) = occurrences.get_mut(line) {
+            if entry.1.is_some() {
+                entry.2 = false;
+            }
+            entry.1 = Some(new_idx);
+        }
+    }
+    let mut unique_pairs: Vec<(usize, usize)> = occurrences
+        .into_values()
+        .filter_map(|(old_idx, new_idx, is_unique)| match (old_idx, new_idx) {
+            (Some(old_idx), Some(new_idx)) if is_unique => Some((old_idx, new_idx)),
+            _ => None,
+        })
+        .collect();
+    unique_pairs.sort_unstable();
+
+    // Patience sorting: find the longest subsequence of pairs whose new indices
+    // are increasing. `piles[i]` is the index into `unique_pairs` of the top of
+    // the `i`th pile, and `predecessors` links each pair to the top of the
+    // previous pile at the time it was placed.
+    let mut piles: Vec<usize> = Vec::new();
+    let mut predecessors: Vec<Option<usize>> = Vec::with_capacity(unique_pairs.len());
+    for (pair_idx, (_old_idx, new_idx)) in unique_pairs.iter().enumerate() {
+        let pile_idx = piles.partition_point(|top| unique_pairs[*top].1 < *new_idx);
+        predecessors.push(pile_idx.checked_sub(1).map(|prev| piles[prev]));
+        if pile_idx == piles.len() {
+            piles.push(pair_idx);
+        } else {
+            piles[pile_idx] = pair_idx;
+        }
+    }
+
+    let mut anchors = Vec::new();
+    let mut pair_idx = piles.last().copied();
+    while let Some(idx) = pair_idx {
+        let (old_idx, new_idx) = unique_pairs[idx];
+        anchors.push((old_idx, new_idx, 1));
+        pair_idx = predecessors[idx];
+    }
+    anchors.reverse();
+    anchors
+}
+
+/// Find the longest common region of `old` and `new` whose least frequent line
+/// occurs the fewest number of times in `old`.
+fn histogram_anchors<T: Eq + Hash>(old: &[T], new: &[T]) -> Vec<Anchor> {
+    let mut old_occurrences: HashMap<&T, Vec<usize>> = HashMap::new();
+    for (old_idx, line) in old.iter().enumerate() {
+        old_occurrences.entry(line).or_default().push(old_idx);
+    }
+
+    // The best region so far, as `(min_occurrences, anchor)`.
+    let mut best: Option<(usize, Anchor)> = None;
+    let mut new_idx = 0;
+    while new_idx < new.len() {
+        let old_indices = match old_occurrences.get(&new[new_idx]) {
+            Some(old_indices) if old_indices.len() <= MAX_HISTOGRAM_CHAIN_LEN => old_indices,
+            Some(_) | None => {
+                new_idx += 1;
+                continue;
+            }
+        };
+        if best.is_some_and(|(min_occurrences, _)| old_indices.len() > min_occurrences) {
+            new_idx += 1;
+            continue;
+        }
+
+        let mut next_new_idx = new_idx + 1;
+        for old_idx in old_indices {
+            let (mut start_old, mut start_new) = (*old_idx, new_idx);
+            while start_old > 0 && start_new > 0 && old[start_old - 1] == new[start_new - 1] {
+                start_old -= 1;
+                start_new -= 1;
+            }
+            let (mut end_old, mut end_new) = (*old_idx + 1, new_idx + 1);
+            while end_old < old.len() && end_new < new.len() && old[end_old] == new[end_new] {
+                end_old += 1;
+                end_new += 1;
+            }
+            let min_occurrences = old[start_old..end_old]
+                .iter()
+                .map(|line| old_occurrences[line].len())
+                .min()
+                .unwrap_or(usize::MAX);
+            let len = end_old - start_old;
+            let is_better = match best {
+                None => true,
+                Some((best_min_occurrences, (_, _, best_len))) => {
+                    min_occurrences < best_min_occurrences
+                        || (min_occurrences == best_min_occurrences && len > best_len)
+                }
+            };
+            if is_better {
+                best = Some((min_occurrences, (start_old, start_new, len)));
+            }
+            next_new_idx = next_new_idx.max(end_new);
+        }
+        new_idx = next_new_idx;
+    }
+
+    match best {
+        Some((_, anchor)) => vec![anchor],
+        None => Vec::new(),
+    }
+}
+
+/// Compute a minimal edit script using Myers' algorithm.
+///
+/// This uses the linear-space variant of the algorithm, which finds the middle
+/// of an optimal path by searching forwards and backwards at the same time,
+/// and then recurses on either side of it, rather than keeping every step of
+/// the search in memory.
+pub fn myers<T: Eq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    myers_range(old, new, &mut edits);
+    edits
+}
+
+fn myers_range<T: Eq>(old: &[T], new: &[T], edits: &mut Vec<Edit>) {
+    let prefix_len = common_prefix_len(old, new);
+    let (old, new) = (&old[prefix_len..], &new[prefix_len..]);
+    let suffix_len = common_suffix_len(old, new);
+    let (old, new) = (
+        &old[..old.len() - suffix_len],
+        &new[..new.len() - suffix_len],
+    );
+
+    edits.extend(std::iter::repeat_n(Edit::Equal, prefix_len));
+    if old.is_empty() || new.is_empty() {
+        edits.extend(std::iter::repeat_n(Edit::Delete, old.len()));
+        edits.extend(std::iter::repeat_n(Edit::Insert, new.len()));
+    } else {
+        let (old_idx, new_idx) = find_middle_snake(old, new);
+        myers_range(&old[..old_idx], &new[..new_idx], edits);
+        myers_range(&old[old_idx..], &new[new_idx..], edits);
+    }
+    edits.extend(std::iter::repeat_n(Edit::Equal, suffix_len));
+}
+
+fn common_prefix_len<T: Eq>(old: &[T], new: &[T]) -> usize {
+    old.iter()
+        .zip(new.iter())
+        .take_while(|(old, new)| old == new)
+        .count()
+}
+
+fn common_suffix_len<T: Eq>(old: &[T], new: &[T]) -> usize {
+    old.iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count()
+}
+
+/// Find a point `(old_idx, new_idx)` around the middle of an optimal path
+/// through the edit graph of `old` and `new`, which must be non-empty and
+/// have no common prefix or suffix, by extending paths forwards from the start
+/// and backwards from the end until they overlap.
+fn find_middle_snake<T: Eq>(old: &[T], new: &[T]) -> (usize, usize) {
+    let to_isize = |len: usize| isize::try_from(len).unwrap();
+    let to_usize = |idx: isize| usize::try_from(idx).unwrap();
+    let (n, m) = (to_isize(old.len()), to_isize(new.len()));
+    let max_d = (n + m + 1) / 2;
+    // `forward[k + offset]` is the furthest `x` reached from the start on the
+    // diagonal `k = x - y`, and `backward[k + offset]` is the furthest
+    // distance reached from the end on the diagonal `k` of the reversed
+    // sequences, or -1 if the diagonal hasn't been reached yet.
+    let offset = max_d + 1;
+    let mut forward = vec![-1; to_usize(2 * offset + 1)];
+    let mut backward = forward.clone();
+    forward[to_usize(offset + 1)] = 0;
+    backward[to_usize(offset + 1)] = 0;
+
+    // A diagonal `k` of the backward search corresponds to the diagonal
+    // `delta - k` of the forward search. If `delta` is odd, then the paths
+    // can only overlap after a forward step, and otherwise after a backward
+    // step.
+    let delta = n - m;
+    let is_odd = delta % 2 != 0;
+    // The diagonals which have run off the edge of the edit graph are
+    // skipped.
+    let (mut forward_k_start, mut forward_k_end) = (0, 0);
+    let (mut backward_k_start, mut backward_k_end) = (0, 0);
+    for d in 0..=max_d {
+        for k in (-d + forward_k_start..=d - forward_k_end).step_by(2) {
+            let k_idx = to_usize(offset + k);
+            let mut x = if k == -d || (k != d && forward[k_idx - 1] < forward[k_idx + 1]) {
+                forward[k_idx + 1]
+            } else {
+                forward[k_idx - 1] + 1
+            };
+            let mut y = x - k;
+            if x < n && y < m {
+                let snake_len =
+                    to_isize(common_prefix_len(&old[to_usize(x)..], &new[to_usize(y)..]));
+                x += snake_len;
+                y += snake_len;
+            }
+            forward[k_idx] = x;
+            if x > n {
+                forward_k_end += 2;
+            } else if y > m {
+                forward_k_start += 2;
+            } else if is_odd {
+                let backward_k_idx = offset + delta - k;
+                if (0..to_isize(backward.len())).contains(&backward_k_idx) {
+                    let backward_x = backward[to_usize(backward_k_idx)];
+                    if backward_x != -1 && x >= n - backward_x {
+                        return (to_usize(x), to_usize(y));
+                    }
+                }
+            }
+        }
+
+        for k in (-d + backward_k_start..=d - backward_k_end).step_by(2) {
+            let k_idx = to_usize(offset + k);
+            let mut x = if k == -d || (k != d && backward[k_idx - 1] < backward[k_idx + 1]) {
+                backward[k_idx + 1]
+            } else {
+                backward[k_idx - 1] + 1
+            };
+            let mut y = x - k;
+            if x < n && y < m {
+                let snake_len = to_isize(common_suffix_len(
+                    &old[..to_usize(n - x)],
+                    &new[..to_usize(m - y)],
+                ));
+                x += snake_len;
+                y += snake_len;
+            }
+            backward[k_idx] = x;
+            if x > n {
+                backward_k_end += 2;
+            } else if y > m {
+                backward_k_start += 2;
+            } else if !is_odd {
+                let forward_k_idx = offset + delta - k;
+                if (0..to_isize(forward.len())).contains(&forward_k_idx) {
+                    let forward_x = forward[to_usize(forward_k_idx)];
+                    if forward_x != -1 && forward_x >= n - x {
+                        let forward_y = forward_x - (delta - k);
+                        return (to_usize(forward_x), to_usize(forward_y));
+                    }
+                }
+            }
+        }
+    }
+    unreachable!("the forward and backward paths always overlap")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply `edits` to `old`, checking that they're consistent with `new`.
+    fn check_edits(old: &[&str], new: &[&str], edits: &[Edit]) {
+        let (mut old_idx, mut new_idx) = (0, 0);
+        for edit in edits {
+            match edit {
+                Edit::Equal => {
+                    assert_eq!(old[old_idx], new[new_idx]);
+                    old_idx += 1;
+                    new_idx += 1;
+                }
+                Edit::Delete => old_idx += 1,
+                Edit::Insert => new_idx += 1,
+            }
+        }
+        assert_eq!((old_idx, new_idx), (old.len(), new.len()));
+    }
+
+    fn num_changes(edits: &[Edit]) -> usize {
+        edits.iter().filter(|edit| **edit != Edit::Equal).count()
+    }
+
+    #[test]
+    fn test_algorithms_are_consistent() {
+        let cases: &[(&[&str], &[&str])] = &[
+            (&[], &[]),
+            (&["a"], &[]),
+            (&[], &["a"]),
+            (&["a", "b", "c"], &["a", "b", "c"]),
+            (
+                &["a", "b", "c", "a", "b", "b", "a"],
+                &["c", "b", "a", "b", "a", "c"],
+            ),
+            (
+                &["fn foo() {", "}", "", "fn bar() {", "}"],
+                &["fn bar() {", "}", "", "fn foo() {", "}"],
+            ),
+            (&["x", "x", "x"], &["x", "y", "x"]),
+        ];
+        for (old, new) in cases {
+            let myers_edits = myers(old, new);
+            check_edits(old, new, &myers_edits);
+            check_edits(old, new, &patience(old, new));
+            check_edits(old, new, &histogram(old, new));
+            assert_eq!(
+                num_changes(&myers_edits),
+                old.len() + new.len() - 2 * lcs_len(old, new),
+                "myers should be minimal for {old:?} -> {new:?}"
+            );
+        }
+    }
+
+    fn lcs_len(old: &[&str], new: &[&str]) -> usize {
+        let mut table = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for (i, old_line) in old.iter().enumerate() {
+            for (j, new_line) in new.iter().enumerate() {
+                table[i + 1][j + 1] = if old_line == new_line {
+                    table[i][j] + 1
+                } else {
+                    table[i][j + 1].max(table[i + 1][j])
+                };
+            }
+        }
+        table[old.len()][new.len()]
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_myers_is_minimal(
+            old in proptest::collection::vec(0..4u8, 0..30),
+            new in proptest::collection::vec(0..4u8, 0..30),
+        ) {
+            let to_lines = |lines: &[u8]| -> Vec<String> {
+                lines.iter().map(|line| line.to_string()).collect()
+            };
+            let (old, new) = (to_lines(&old), to_lines(&new));
+            let old: Vec<&str> = old.iter().map(String::as_str).collect();
+            let new: Vec<&str> = new.iter().map(String::as_str).collect();
+            let edits = myers(&old, &new);
+            check_edits(&old, &new, &edits);
+            proptest::prop_assert_eq!(
+                num_changes(&edits),
+                old.len() + new.len() - 2 * lcs_len(&old, &new)
+            );
+        }
+    }
+
+    #[test]
+    fn test_myers_unrelated_files() {
+        // Every line differs, which is the worst case for Myers' algorithm.
+        let old: Vec<String> = (0..2000).map(|i| format!("old {i}")).collect();
+        let new: Vec<String> = (0..2000).map(|i| format!("new {i}")).collect();
+        let edits = myers(&old, &new);
+        assert_eq!(edits.len(), 4000);
+        assert_eq!(num_changes(&edits), 4000);
+    }
+
+    #[test]
+    fn test_patience_prefers_unique_lines() {
+        let old = ["a", "{", "x", "}", "b", "{", "y", "}"];
+        let new = ["b", "{", "y", "}"];
+        let edits = patience(&old, &new);
+        check_edits(&old, &new, &edits);
+        assert_eq!(
+            edits,
+            [
+                Edit::Delete,
+                Edit::Delete,
+                Edit::Delete,
+                Edit::Delete,
+                Edit::Equal,
+                Edit::Equal,
+                Edit::Equal,
+                Edit::Equal,
+            ]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Detecting the encodings of text files, so that files which aren't UTF-8 can
+//! be diffed as text and written back without changing any other bytes.
+
+use std::fmt;
+use std::str::FromStr;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+/// The encoding of a text file on disk. Text files are decoded into UTF-8 to be
+/// diffed, and the selected contents are encoded back when they're written.
+///
+/// A byte order mark is decoded as part of the file's first line, rather than
+/// being stripped, so it's kept as-is unless the first line is changed.
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
+pub struct TextEncoding(&'static Encoding);
+
+impl TextEncoding {
+    /// The UTF-8 encoding.
+    pub const UTF_8: Self = Self(UTF_8);
+
+    /// Look up an encoding by one of its labels, such as `latin1`, `utf-16le`
+    /// or `shift_jis`. See <https://encoding.spec.whatwg.org/#names-and-labels>.
+    pub fn for_label(label: &str) -> Option<Self> {
+        Encoding::for_label(label.as_bytes()).map(Self)
+    }
+
+    /// The canonical name of this encoding, such as `windows-1252`.
+    pub fn name(&self) -> &'static str {
+        let Self(encoding) = self;
+        encoding.name()
+    }
+
+    fn is_utf16(&self) -> bool {
+        let Self(encoding) = self;
+        *encoding == UTF_16LE || *encoding == UTF_16BE
+    }
+
+    /// Guess the encoding of `contents`, or return `None` if they appear to be
+    /// binary.
+    ///
+    /// UTF-16 is only detected if the contents start with a byte order mark.
+    /// Contents which aren't valid UTF-8 are assumed to be `windows-1252` (a
+    /// superset of Latin-1) if they don't contain any control characters other
+    /// than whitespace.
+    pub fn detect(contents: &[u8]) -> Option<Self> {
+        if let Some((encoding, _bom_length)) = Encoding::for_bom(contents) {
+            return Some(Self(encoding));
+        }
+        if contents.contains(&0) {
+            None
+        } else if std::str::from_utf8(contents).is_ok() {
+            Some(Self::UTF_8)
+        } else if contents
+            .iter()
+            .any(|byte| byte.is_ascii_control() && !b"\t\n\x0c\r\x7f".contains(byte))
+        {
+            None
+        } else {
+            Some(Self(WINDOWS_1252))
+        }
+    }
+
+    /// Decode `contents` from this encoding, if encoding the result again would
+    /// give back exactly the same bytes. Otherwise, returns `None`.
+    pub fn decode(&self, contents: Vec<u8>) -> Option<String> {
+        let Self(encoding) = self;
+        if *encoding == UTF_8 {
+            return String::from_utf8(contents).ok();
+        }
+        if contents.contains(&0) && !self.is_utf16() {
+            return None;
+        }
+        let decoded = encoding
+            .decode_without_bom_handling_and_without_replacement(&contents)?
+            .into_owned();
+        if self.encode(&decoded) == contents {
+            Some(decoded)
+        } else {
+            None
+        }
+    }
+
+    /// Encode `contents` into this encoding. Characters which can't be
+    /// represented in this encoding are written as HTML numeric character
+    /// references, but this can't happen for contents which were decoded from
+    /// this encoding.
+    pub fn encode(&self, contents: &str) -> Vec<u8> {
+        let Self(encoding) = self;
+        // `encoding_rs` only decodes UTF-16, so it has to be encoded here.
+        if *encoding == UTF_16LE {
+            contents
+                .encode_utf16()
+                .flat_map(|code_unit| code_unit.to_le_bytes())
+                .collect()
+        } else if *encoding == UTF_16BE {
+            contents
+                .encode_utf16()
+                .flat_map(|code_unit| code_unit.to_be_bytes())
+                .collect()
+        } else {
+            let (encoded, _output_encoding, _had_unmappable_characters) = encoding.encode(contents);
+            encoded.into_owned()
+        }
+    }
+}
+
+impl Default for TextEncoding {
+    fn default() -> Self {
+        Self::UTF_8
+    }
+}
+
+impl fmt::Debug for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for TextEncoding {
+    type Err = String;
+
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        Self::for_label(label).ok_or_else(|| format!("unknown encoding: {label}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoding(label: &str) -> TextEncoding {
+        TextEncoding::for_label(label).unwrap()
+    }
+
+    #[test]
+    fn test_detect() {
+        let detect = |contents: &[u8]| TextEncoding::detect(contents).map(|e| e.name());
+        assert_eq!(detect(b""), Some("UTF-8"));
+        assert_eq!(detect("caf\u{e9}\n".as_bytes()), Some("UTF-8"));
+        assert_eq!(detect(b"\xef\xbb\xbfcafe\n"), Some("UTF-8"));
+        assert_eq!(detect(b"caf\xe9\n"), Some("windows-1252"));
+        assert_eq!(detect(b"\xff\xfec\x00a\x00"), Some("UTF-16LE"));
+        assert_eq!(detect(b"\xfe\xff\x00c\x00a"), Some("UTF-16BE"));
+        assert_eq!(detect(b"c\x00a\x00"), None);
+        assert_eq!(detect(b"\x89PNG\r\n\x1a\n"), None);
+    }
+
+    #[test]
+    fn test_decode_round_trip() {
+        let cases: &[(&str, &[u8])] = &[
+            ("utf-8", b"\xef\xbb\xbfcafe\n"),
+            ("latin1", b"caf\xe9\n\x81\x8d\x8f\x90\x9d\n"),
+            ("utf-16le", b"\xff\xfec\x00a\x00\n\x00"),
+            ("utf-16be", b"\xfe\xff\x00c\x00a\x00\n"),
+            ("shift_jis", b"\x82\xa0\n"),
+        ];
+        for (label, contents) in cases {
+            let encoding = encoding(label);
+            let decoded = encoding.decode(contents.to_vec()).unwrap();
+            assert_eq!(encoding.encode(&decoded), *contents, "{label}");
+        }
+
+        // The byte order mark is kept as part of the first line.
+        assert_eq!(
+            encoding("utf-16le").decode(b"\xff\xfea\x00".to_vec()),
+            Some("\u{feff}a".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_lossy() {
+        assert_eq!(encoding("utf-8").decode(b"caf\xe9".to_vec()), None);
+        // An unpaired surrogate.
+        assert_eq!(encoding("utf-16le").decode(b"\x00\xd8".to_vec()), None);
+        // An odd number of bytes.
+        assert_eq!(encoding("utf-16le").decode(b"a\x00b".to_vec()), None);
+        assert_eq!(encoding("latin1").decode(b"a\x00b".to_vec()), None);
+    }
+}
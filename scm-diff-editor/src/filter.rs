@@ -0,0 +1,159 @@
+//! Rules for which paths to consider when comparing directories.
+
+use std::path::Path;
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::{Error, Result};
+
+/// Rules for which paths to consider when walking the directories for a
+/// `--dir-diff`.
+///
+/// Glob patterns which don't contain a `/` are matched against the name of
+/// each file or directory, like in `.gitignore` files. Patterns which do
+/// contain a `/` are matched against the whole path relative to the directory
+/// being compared.
+#[derive(Clone, Debug, Default)]
+pub struct DirDiffFilter {
+    includes: PathGlobs,
+    excludes: PathGlobs,
+    use_gitignore: bool,
+}
+
+impl DirDiffFilter {
+    /// Construct a filter.
+    ///
+    /// - If `includes` is non-empty, only files matching at least one of the
+    ///   patterns are considered.
+    /// - Files and directories matching any of the `excludes` patterns are not
+    ///   considered. Excluded directories are not walked at all.
+    /// - If `use_gitignore` is set, then paths ignored by `.gitignore` files in
+    ///   the compared directories, as well as `.git` directories, are excluded.
+    pub fn new(includes: &[String], excludes: &[String], use_gitignore: bool) -> Result<Self> {
+        Ok(Self {
+            includes: PathGlobs::new(includes)?,
+            excludes: PathGlobs::new(excludes)?,
+            use_gitignore,
+        })
+    }
+
+    /// Whether or not `.gitignore` files should be respected. This is up to
+    /// the [`crate::Filesystem`] to implement.
+    pub fn use_gitignore(&self) -> bool {
+        self.use_gitignore
+    }
+
+    /// Whether or not the given file or directory, relative to the directory
+    /// being compared, should be skipped. This does not check whether any of
+    /// the path's parent directories are excluded, nor does it check
+    /// `.gitignore` files.
+    pub fn is_excluded(&self, relative_path: &Path, is_dir: bool) -> bool {
+        let Self {
+            includes,
+            excludes,
+            use_gitignore,
+        } = self;
+        if *use_gitignore && is_dir && relative_path.file_name() == Some(".git".as_ref()) {
+            return true;
+        }
+        if excludes.is_match(relative_path) {
+            return true;
+        }
+        // Include patterns describe files, so they can't be used to rule out
+        // directories (which may contain matching files).
+        !is_dir && !includes.is_empty() && !includes.is_match(relative_path)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct PathGlobs {
+    /// Patterns matched against the file name.
+    name_globs: GlobSet,
+
+    /// Patterns matched against the whole relative path.
+    path_globs: GlobSet,
+}
+
+impl PathGlobs {
+    fn new(patterns: &[String]) -> Result<Self> {
+        let mut name_globs = GlobSetBuilder::new();
+        let mut path_globs = GlobSetBuilder::new();
+        for pattern in patterns {
+            // As in `.gitignore` files, a leading `/` anchors the pattern to
+            // the root, and a trailing `/` is allowed for directories.
+            let trimmed_pattern = pattern.trim_end_matches('/');
+            let is_path_pattern = trimmed_pattern.contains('/');
+            let glob = GlobBuilder::new(trimmed_pattern.trim_start_matches('/'))
+                .literal_separator(true)
+                .build()
+                .map_err(|err| Error::InvalidGlob {
+                    pattern: pattern.clone(),
+                    source: err,
+                })?;
+            if is_path_pattern {
+                path_globs.add(glob);
+            } else {
+                name_globs.add(glob);
+            }
+        }
+        let build = |builder: GlobSetBuilder| {
+            builder.build().map_err(|err| Error::InvalidGlob {
+                pattern: patterns.join(" "),
+                source: err,
+            })
+        };
+        Ok(Self {
+            name_globs: build(name_globs)?,
+            path_globs: build(path_globs)?,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        let Self {
+            name_globs,
+            path_globs,
+        } = self;
+        name_globs.is_empty() && path_globs.is_empty()
+    }
+
+    fn is_match(&self, relative_path: &Path) -> bool {
+        let Self {
+            name_globs,
+            path_globs,
+        } = self;
+        let name_matches = match relative_path.file_name() {
+            Some(file_name) => name_globs.is_match(file_name),
+            None => false,
+        };
+        name_matches || path_globs.is_match(relative_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_excluded() -> Result<()> {
+        let filter = DirDiffFilter::new(
+            &["*.rs".to_owned()],
+            &["target".to_owned(), "/src/generated/".to_owned()],
+            false,
+        )?;
+        assert!(!filter.is_excluded(Path::new("src/lib.rs"), false));
+        assert!(filter.is_excluded(Path::new("src/lib.txt"), false));
+        assert!(!filter.is_excluded(Path::new("src"), true));
+        assert!(filter.is_excluded(Path::new("target"), true));
+        assert!(filter.is_excluded(Path::new("nested/target"), true));
+        assert!(filter.is_excluded(Path::new("src/generated"), true));
+        assert!(!filter.is_excluded(Path::new("nested/src/generated"), true));
+        assert!(!filter.is_excluded(Path::new(".git"), true));
+
+        let filter = DirDiffFilter::new(&[], &[], true)?;
+        assert!(filter.is_excluded(Path::new(".git"), true));
+        assert!(!filter.is_excluded(Path::new("README.md"), false));
+
+        assert!(DirDiffFilter::new(&[], &["a[".to_owned()], false).is_err());
+        Ok(())
+    }
+}
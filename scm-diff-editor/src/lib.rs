@@ -9,25 +9,43 @@
 )]
 #![allow(clippy::too_many_arguments)]
 
+pub mod diff;
+mod encoding;
+mod filter;
+mod loader;
+mod renames;
 mod render;
+pub mod summary;
 pub mod testing;
 
 use std::borrow::Cow;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf, StripPrefixError};
+#[cfg(feature = "git")]
+use std::process::Command;
+use std::sync::Arc;
 
 use clap::Parser;
 use sha1::Digest;
 use thiserror::Error;
 use walkdir::WalkDir;
 
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use scm_record::helpers::CrosstermInput;
+use scm_record::script::{ScriptError, SelectionScript};
 use scm_record::{
-    File, FileMode, RecordError, RecordState, Recorder, SelectedChanges, SelectedContents,
+    Event, File, FileMode, RecordError, RecordInput, RecordState, Recorder, SelectedChanges,
+    SelectedContents, TerminalKind,
 };
 
+use diff::{DiffAlgorithm, DiffOptions, WhitespaceMode};
+pub use encoding::TextEncoding;
+pub use filter::DirDiffFilter;
+pub use loader::FileLoader;
+use loader::UnloadedFile;
+
 /// Render a partial commit selector for use as a difftool or mergetool.
 ///
 /// This can be used to interactively select changes to include as part of a
@@ -69,6 +87,116 @@ pub struct Opts {
     /// Write the resolved merge conflicts to this file.
     #[clap(short = 'o', long = "output", conflicts_with("dir_diff"))]
     pub output: Option<PathBuf>,
+
+    /// When comparing directories, detect files which were renamed between the
+    /// left and right directories. A file is considered renamed if its contents
+    /// are at least this similar (as a percentage, default 50) to a deleted
+    /// file's.
+    #[clap(
+        short = 'M',
+        long = "find-renames",
+        value_name = "PERCENT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "50",
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        requires("dir_diff")
+    )]
+    pub find_renames: Option<u8>,
+
+    /// When comparing directories, detect added files which were copied from
+    /// an existing file in the left directory. A file is considered copied if
+    /// its contents are at least this similar (as a percentage, default 50) to
+    /// the source file's. Implies `--find-renames`.
+    #[clap(
+        short = 'C',
+        long = "find-copies",
+        value_name = "PERCENT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "50",
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        requires("dir_diff")
+    )]
+    pub find_copies: Option<u8>,
+
+    /// When comparing directories, only consider files matching this glob.
+    /// May be passed multiple times.
+    #[clap(long = "include", value_name = "GLOB", requires("dir_diff"))]
+    pub include: Vec<String>,
+
+    /// When comparing directories, skip files and directories matching this
+    /// glob. May be passed multiple times.
+    #[clap(long = "exclude", value_name = "GLOB", requires("dir_diff"))]
+    pub exclude: Vec<String>,
+
+    /// When comparing directories, skip files which are ignored by the
+    /// `.gitignore` files inside of that directory, as well as `.git`
+    /// directories.
+    #[clap(long = "gitignore", requires("dir_diff"))]
+    pub gitignore: bool,
+
+    /// How to treat whitespace differences when comparing lines. Lines which
+    /// only differ in ignored whitespace don't affect how hunks are matched
+    /// up, but are still shown as changes, which start out selected.
+    #[clap(long = "whitespace", value_name = "MODE", value_enum, default_value_t)]
+    pub whitespace: WhitespaceMode,
+
+    /// The algorithm used to match up lines between the old and new contents.
+    #[clap(
+        long = "diff-algorithm",
+        value_name = "ALGORITHM",
+        value_enum,
+        default_value_t
+    )]
+    pub diff_algorithm: DiffAlgorithm,
+
+    /// Summarize the changes to text files larger than this many bytes as a
+    /// single item, instead of diffing them, which can be slow. The full diff
+    /// can still be shown in the UI. Pass 0 for no limit.
+    #[clap(
+        long = "max-file-size",
+        value_name = "BYTES",
+        default_value_t = summary::DEFAULT_MAX_FILE_SIZE
+    )]
+    pub max_file_size: u64,
+
+    /// Like `--max-file-size`, but for text files with more than this many
+    /// lines. Pass 0 for no limit.
+    #[clap(
+        long = "max-file-lines",
+        value_name = "LINES",
+        default_value_t = summary::DEFAULT_MAX_FILE_LINES
+    )]
+    pub max_file_lines: usize,
+
+    /// Diff files which appear to be generated, such as lockfiles and files
+    /// marked `@generated`, instead of summarizing their changes as a single
+    /// item.
+    #[clap(long = "show-generated")]
+    pub show_generated: bool,
+
+    /// Read and write text files in this encoding, such as `latin1` or
+    /// `utf-16le`, instead of detecting it. By default, files are decoded as
+    /// UTF-8 or, if they start with a byte order mark, as UTF-16, and other
+    /// files are decoded as `windows-1252` (a superset of Latin-1) unless they
+    /// look binary. Files which can't be decoded and encoded again without
+    /// changing them are treated as binary.
+    #[clap(long = "encoding", value_name = "ENCODING")]
+    pub encoding: Option<TextEncoding>,
+
+    /// Before selecting changes, pre-select the changes described by this
+    /// rule, such as `select all` or `deselect file Cargo.lock`. Rules use the
+    /// same syntax as the commands of a `--script`, and are applied in order.
+    /// May be passed multiple times.
+    #[clap(long = "preselect", value_name = "RULE", conflicts_with("read_only"))]
+    pub preselect: Vec<String>,
+
+    /// Instead of opening the interactive UI, select changes by applying the
+    /// commands in this file (or standard input, if `-` is passed). See
+    /// `scm_record::script` for the syntax.
+    #[clap(long = "script", value_name = "FILE", conflicts_with("read_only"))]
+    pub script: Option<PathBuf>,
 }
 
 #[derive(Debug, Error)]
@@ -83,6 +211,18 @@ pub enum Error {
     #[error("walking directory: {source}")]
     WalkDir { source: walkdir::Error },
 
+    #[error("invalid glob {pattern}: {source}")]
+    InvalidGlob {
+        pattern: String,
+        source: globset::Error,
+    },
+
+    #[error("reading gitignore file {path}: {source}")]
+    ReadGitignore {
+        path: PathBuf,
+        source: ignore::Error,
+    },
+
     #[error("stripping directory prefix {root} from {path}: {source}")]
     StripPrefix {
         root: PathBuf,
@@ -115,8 +255,27 @@ pub enum Error {
     #[error("file was not text: {path}")]
     BinaryMergeFile { path: PathBuf },
 
+    #[error("could not resolve HEAD for submodule: {path}")]
+    ResolveSubmoduleHead { path: PathBuf },
+
+    #[error("updating submodule {path} to {commit_id}: {message}")]
+    UpdateSubmodule {
+        path: PathBuf,
+        commit_id: String,
+        message: String,
+    },
+
+    #[error("removing submodule {path}: {message}")]
+    RemoveSubmodule { path: PathBuf, message: String },
+
     #[error("recording changes: {source}")]
     Record { source: RecordError },
+
+    #[error("applying --preselect rules: {source}")]
+    Preselect { source: ScriptError },
+
+    #[error("applying selection script: {source}")]
+    Script { source: ScriptError },
 }
 
 /// Result type alias.
@@ -147,8 +306,12 @@ pub enum FileContents {
         /// The hash of `contents`.
         hash: String,
 
-        /// The size of `contents`, in bytes.
+        /// The size of the file, in bytes. This may differ from the size of
+        /// `contents` if the file isn't encoded as UTF-8.
         num_bytes: u64,
+
+        /// The encoding of the file, which `contents` was decoded from.
+        encoding: TextEncoding,
     },
 
     /// The file is a binary file (not able to be displayed directly in the UI).
@@ -159,57 +322,157 @@ pub enum FileContents {
         /// The size of the file's contents, in bytes.
         num_bytes: u64,
     },
+
+    /// The file is a submodule (gitlink) pointing to the given commit.
+    Submodule {
+        /// The commit ID that the submodule points to.
+        commit_id: String,
+    },
 }
 
 /// Abstraction over the filesystem.
 pub trait Filesystem {
-    /// Find the set of files that appear in either `left` or `right`.
-    fn read_dir_diff_paths(&self, left: &Path, right: &Path) -> Result<BTreeSet<PathBuf>>;
+    /// Find the set of files that appear in either `left` or `right`, skipping
+    /// those excluded by `filter`.
+    fn read_dir_diff_paths(
+        &self,
+        left: &Path,
+        right: &Path,
+        filter: &DirDiffFilter,
+    ) -> Result<BTreeSet<PathBuf>>;
 
     /// Read the [`FileInfo`] for the provided `path`.
     fn read_file_info(&self, path: &Path) -> Result<FileInfo>;
 
-    /// Write new file contents to `path`.
-    fn write_file(&mut self, path: &Path, contents: &str) -> Result<()>;
+    /// Read only the [`FileMode`] for the provided `path`, which should be
+    /// cheaper than reading its contents. By default, this calls
+    /// [`Filesystem::read_file_info`].
+    fn read_file_mode(&self, path: &Path) -> Result<FileMode> {
+        Ok(self.read_file_info(path)?.file_mode)
+    }
+
+    /// Write new file contents to `path`, encoded with `encoding`.
+    fn write_file(&mut self, path: &Path, contents: &str, encoding: TextEncoding) -> Result<()>;
+
+    /// Update the submodule (gitlink) at `path` to point to `commit_id`. This
+    /// should not recurse into the submodule's contents.
+    ///
+    /// On the real filesystem, a checked-out submodule is updated by running
+    /// `git checkout`, which needs the `git` feature and the `git` binary.
+    /// Other submodules are written as `git difftool --dir-diff` placeholder
+    /// files.
+    fn write_submodule(&mut self, path: &Path, commit_id: &str) -> Result<()>;
 
     /// Copy the file at `old_path` to `new_path`. (This can be more efficient
     /// than reading and writing the entire contents, particularly for large
     /// binary files.)
     fn copy_file(&mut self, old_path: &Path, new_path: &Path) -> Result<()>;
 
-    /// Delete the file at `path`.
+    /// Delete the file at `path`, which may be a submodule. On the real
+    /// filesystem, removing a checked-out submodule runs `git status` to check
+    /// for local changes, as with [`Filesystem::write_submodule`].
     fn remove_file(&mut self, path: &Path) -> Result<()>;
 
     /// Create the directory `path` and any parent directories as necessary.
     fn create_dir_all(&mut self, path: &Path) -> Result<()>;
 }
 
-struct RealFilesystem;
+struct RealFilesystem {
+    /// The encoding of text files, or `None` to detect it for each file.
+    encoding: Option<TextEncoding>,
+}
 
 impl Filesystem for RealFilesystem {
-    fn read_dir_diff_paths(&self, left: &Path, right: &Path) -> Result<BTreeSet<PathBuf>> {
-        fn walk_dir(dir: &Path) -> Result<BTreeSet<PathBuf>> {
+    fn read_dir_diff_paths(
+        &self,
+        left: &Path,
+        right: &Path,
+        filter: &DirDiffFilter,
+    ) -> Result<BTreeSet<PathBuf>> {
+        fn walk_dir(dir: &Path, filter: &DirDiffFilter) -> Result<BTreeSet<PathBuf>> {
             let mut files = BTreeSet::new();
-            for entry in WalkDir::new(dir) {
+            // The `.gitignore` files which apply to the current entry, along
+            // with the depth of the directory that they were found in.
+            let mut gitignores: Vec<(usize, Gitignore)> = Vec::new();
+            let mut iter = WalkDir::new(dir).into_iter();
+            while let Some(entry) = iter.next() {
                 let entry = entry.map_err(|err| Error::WalkDir { source: err })?;
-                if entry.file_type().is_file() || entry.file_type().is_symlink() {
-                    let relative_path = match entry.path().strip_prefix(dir) {
-                        Ok(path) => path.to_owned(),
-                        Err(err) => {
-                            return Err(Error::StripPrefix {
-                                root: dir.to_owned(),
-                                path: entry.path().to_owned(),
-                                source: err,
-                            })
-                        }
-                    };
+                let relative_path = match entry.path().strip_prefix(dir) {
+                    Ok(path) => path.to_owned(),
+                    Err(err) => {
+                        return Err(Error::StripPrefix {
+                            root: dir.to_owned(),
+                            path: entry.path().to_owned(),
+                            source: err,
+                        })
+                    }
+                };
+                let is_dir = entry.file_type().is_dir();
+
+                while gitignores
+                    .last()
+                    .is_some_and(|(depth, _)| *depth >= entry.depth())
+                {
+                    gitignores.pop();
+                }
+                if entry.depth() > 0
+                    && (filter.is_excluded(&relative_path, is_dir)
+                        || is_gitignored(&gitignores, entry.path(), is_dir))
+                {
+                    // Skip the directory's contents without reading them.
+                    if is_dir {
+                        iter.skip_current_dir();
+                    }
+                    continue;
+                }
+
+                // Submodules are reported as a single entry; their contents
+                // are not part of the diff.
+                let is_submodule = entry.depth() > 0 && submodule_git_dir(entry.path()).is_some();
+                if is_submodule {
+                    iter.skip_current_dir();
+                } else if is_dir && filter.use_gitignore() {
+                    if let Some(gitignore) = read_gitignore(entry.path())? {
+                        gitignores.push((entry.depth(), gitignore));
+                    }
+                }
+
+                if entry.file_type().is_file() || entry.file_type().is_symlink() || is_submodule {
                     files.insert(relative_path);
                 }
             }
             Ok(files)
         }
-        let left_files = walk_dir(left)?;
-        let right_files = walk_dir(right)?;
+
+        fn read_gitignore(dir: &Path) -> Result<Option<Gitignore>> {
+            let path = dir.join(".gitignore");
+            if !path.is_file() {
+                return Ok(None);
+            }
+            let mut builder = GitignoreBuilder::new(dir);
+            if let Some(err) = builder.add(&path) {
+                return Err(Error::ReadGitignore { path, source: err });
+            }
+            let gitignore = builder
+                .build()
+                .map_err(|err| Error::ReadGitignore { path, source: err })?;
+            Ok(Some(gitignore))
+        }
+
+        fn is_gitignored(gitignores: &[(usize, Gitignore)], path: &Path, is_dir: bool) -> bool {
+            // More deeply-nested `.gitignore` files take precedence.
+            for (_depth, gitignore) in gitignores.iter().rev() {
+                match gitignore.matched(path, is_dir) {
+                    ignore::Match::None => continue,
+                    ignore::Match::Ignore(_) => return true,
+                    ignore::Match::Whitelist(_) => return false,
+                }
+            }
+            false
+        }
+
+        let left_files = walk_dir(left, filter)?;
+        let right_files = walk_dir(right, filter)?;
         let paths = left_files
             .into_iter()
             .chain(right_files)
@@ -218,9 +481,71 @@ impl Filesystem for RealFilesystem {
     }
 
     fn read_file_info(&self, path: &Path) -> Result<FileInfo> {
+        if let Some(git_dir) = submodule_git_dir(path) {
+            let commit_id =
+                read_submodule_head(&git_dir)?.ok_or_else(|| Error::ResolveSubmoduleHead {
+                    path: path.to_owned(),
+                })?;
+            return Ok(FileInfo {
+                file_mode: FileMode::GITLINK,
+                contents: FileContents::Submodule { commit_id },
+            });
+        }
+
+        let file_mode = self.read_file_mode(path)?;
+        let contents = match fs::read(path) {
+            Ok(contents) => {
+                let hash = {
+                    let mut hasher = sha1::Sha1::new();
+                    hasher.update(&contents);
+                    format!("{:x}", hasher.finalize())
+                };
+                let num_bytes: u64 = contents.len().try_into().unwrap();
+                let encoding = self.encoding.or_else(|| TextEncoding::detect(&contents));
+                match encoding {
+                    None => FileContents::Binary { hash, num_bytes },
+                    Some(encoding) => match encoding.decode(contents) {
+                        Some(contents) => match parse_submodule_placeholder(&contents) {
+                            Some(commit_id) => {
+                                return Ok(FileInfo {
+                                    file_mode: FileMode::GITLINK,
+                                    contents: FileContents::Submodule {
+                                        commit_id: commit_id.to_owned(),
+                                    },
+                                })
+                            }
+                            None => FileContents::Text {
+                                contents,
+                                hash,
+                                num_bytes,
+                                encoding,
+                            },
+                        },
+                        None => FileContents::Binary { hash, num_bytes },
+                    },
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => FileContents::Absent,
+            Err(err) => {
+                return Err(Error::ReadFile {
+                    path: path.to_owned(),
+                    source: err,
+                })
+            }
+        };
+        Ok(FileInfo {
+            file_mode,
+            contents,
+        })
+    }
+
+    fn read_file_mode(&self, path: &Path) -> Result<FileMode> {
+        if submodule_git_dir(path).is_some() {
+            return Ok(FileMode::GITLINK);
+        }
+
         let file_mode = match fs::metadata(path) {
             Ok(metadata) => {
-                // TODO: no support for gitlinks (submodules).
                 if metadata.is_symlink() {
                     FileMode::Unix(0o120000)
                 } else {
@@ -247,48 +572,43 @@ impl Filesystem for RealFilesystem {
                 })
             }
         };
-        let contents = match fs::read(path) {
-            Ok(contents) => {
-                let hash = {
-                    let mut hasher = sha1::Sha1::new();
-                    hasher.update(&contents);
-                    format!("{:x}", hasher.finalize())
-                };
-                let num_bytes: u64 = contents.len().try_into().unwrap();
-                if contents.contains(&0) {
-                    FileContents::Binary { hash, num_bytes }
-                } else {
-                    match String::from_utf8(contents) {
-                        Ok(contents) => FileContents::Text {
-                            contents,
-                            hash,
-                            num_bytes,
-                        },
-                        Err(_) => FileContents::Binary { hash, num_bytes },
-                    }
-                }
-            }
-            Err(err) if err.kind() == io::ErrorKind::NotFound => FileContents::Absent,
-            Err(err) => {
-                return Err(Error::ReadFile {
-                    path: path.to_owned(),
-                    source: err,
-                })
-            }
-        };
-        Ok(FileInfo {
-            file_mode,
-            contents,
-        })
+        Ok(file_mode)
     }
 
-    fn write_file(&mut self, path: &Path, contents: &str) -> Result<()> {
-        fs::write(path, contents).map_err(|err| Error::WriteFile {
+    fn write_file(&mut self, path: &Path, contents: &str, encoding: TextEncoding) -> Result<()> {
+        fs::write(path, encoding.encode(contents)).map_err(|err| Error::WriteFile {
             path: path.to_owned(),
             source: err,
         })
     }
 
+    fn write_submodule(&mut self, path: &Path, commit_id: &str) -> Result<()> {
+        match submodule_git_dir(path) {
+            Some(git_dir) => {
+                // A checked-out submodule: its gitlink is whatever its `HEAD`
+                // points to, so check out the new commit with Git, which keeps
+                // the submodule's index and working copy in sync with `HEAD`
+                // (or refuses if that would lose local changes).
+                if read_submodule_head(&git_dir)?.as_deref() == Some(commit_id) {
+                    return Ok(());
+                }
+                run_git(path, &["checkout", "--quiet", "--detach", commit_id]).map_err(
+                    |message| Error::UpdateSubmodule {
+                        path: path.to_owned(),
+                        commit_id: commit_id.to_owned(),
+                        message,
+                    },
+                )?;
+                Ok(())
+            }
+            None => self.write_file(
+                path,
+                &make_submodule_placeholder(commit_id),
+                TextEncoding::UTF_8,
+            ),
+        }
+    }
+
     fn copy_file(&mut self, old_path: &Path, new_path: &Path) -> Result<()> {
         fs::copy(old_path, new_path).map_err(|err| Error::CopyFile {
             old_path: old_path.to_owned(),
@@ -299,6 +619,9 @@ impl Filesystem for RealFilesystem {
     }
 
     fn remove_file(&mut self, path: &Path) -> Result<()> {
+        if let Some(git_dir) = submodule_git_dir(path) {
+            return remove_submodule(path, &git_dir);
+        }
         match fs::remove_file(path) {
             Ok(()) => Ok(()),
             Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
@@ -318,6 +641,133 @@ impl Filesystem for RealFilesystem {
     }
 }
 
+/// Run `git` with `args` in `dir`, returning its standard output, or the
+/// reason that it failed.
+#[cfg(feature = "git")]
+fn run_git(dir: &Path, args: &[&str]) -> std::result::Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|err| {
+            format!("the `git` binary is needed for submodules, but failed to run: {err}")
+        })?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr)
+            .trim_end()
+            .to_owned())
+    }
+}
+
+/// Without the `git` feature, checked-out submodules can't be changed.
+#[cfg(not(feature = "git"))]
+fn run_git(dir: &Path, args: &[&str]) -> std::result::Result<String, String> {
+    let _ = (dir, args);
+    Err(
+        "scm-diff-editor was built without the `git` feature, which is needed for submodules"
+            .to_owned(),
+    )
+}
+
+/// Remove the working copy of the submodule at `path`. Its
+/// repository is kept in the superproject's `.git/modules` directory, so only
+/// a submodule without local changes can be removed, and a submodule whose
+/// repository is inside its working copy is never removed.
+fn remove_submodule(path: &Path, git_dir: &Path) -> Result<()> {
+    let error = |message: String| Error::RemoveSubmodule {
+        path: path.to_owned(),
+        message,
+    };
+    if git_dir == path.join(".git") {
+        return Err(error(
+            "its repository is stored inside it and would be lost".to_owned(),
+        ));
+    }
+    let status = run_git(path, &["status", "--porcelain", "--ignored"]).map_err(error)?;
+    if !status.is_empty() {
+        return Err(error("it has local changes".to_owned()));
+    }
+    fs::remove_dir_all(path).map_err(|err| Error::RemoveFile {
+        path: path.to_owned(),
+        source: err,
+    })
+}
+
+/// The contents of the placeholder file that `git difftool --dir-diff` writes
+/// in place of a submodule.
+fn make_submodule_placeholder(commit_id: &str) -> String {
+    format!("Subproject commit {commit_id}")
+}
+
+/// Parse the commit ID out of a placeholder file written by
+/// `git difftool --dir-diff`. The commit ID may carry a `-dirty` suffix if the
+/// submodule's working copy has changes, which is stripped, since the gitlink
+/// can only point to the commit itself.
+fn parse_submodule_placeholder(contents: &str) -> Option<&str> {
+    let commit_id = contents.strip_prefix("Subproject commit ")?;
+    let commit_id = commit_id.strip_suffix('\n').unwrap_or(commit_id);
+    let commit_id = commit_id.strip_suffix("-dirty").unwrap_or(commit_id);
+    let is_hash =
+        matches!(commit_id.len(), 40 | 64) && commit_id.chars().all(|c| c.is_ascii_hexdigit());
+    if is_hash {
+        Some(commit_id)
+    } else {
+        None
+    }
+}
+
+/// If `path` is a checked-out submodule, return the path to its Git directory.
+fn submodule_git_dir(path: &Path) -> Option<PathBuf> {
+    if !path.is_dir() {
+        return None;
+    }
+    let dot_git = path.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    // Submodules usually store their repository in the superproject's
+    // `.git/modules` directory and have a `.git` file pointing to it.
+    let contents = fs::read_to_string(&dot_git).ok()?;
+    let git_dir = contents.trim_end().strip_prefix("gitdir: ")?;
+    Some(path.join(git_dir))
+}
+
+/// Resolve the commit ID that `HEAD` points to in the given Git directory.
+/// Returns `None` if `HEAD` is a symbolic reference to a branch that doesn't
+/// exist.
+fn read_submodule_head(git_dir: &Path) -> Result<Option<String>> {
+    let read = |path: PathBuf| match fs::read_to_string(&path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::ReadFile { path, source: err }),
+    };
+
+    let head = match read(git_dir.join("HEAD"))? {
+        Some(head) => head,
+        None => return Ok(None),
+    };
+    let ref_name = match head.trim_end().strip_prefix("ref: ") {
+        Some(ref_name) => ref_name,
+        None => return Ok(Some(head.trim_end().to_owned())),
+    };
+    if let Some(commit_id) = read(git_dir.join(ref_name))? {
+        return Ok(Some(commit_id.trim_end().to_owned()));
+    }
+    let packed_refs = read(git_dir.join("packed-refs"))?.unwrap_or_default();
+    let commit_id = packed_refs.lines().find_map(|line| {
+        let (commit_id, name) = line.split_once(' ')?;
+        if name == ref_name {
+            Some(commit_id.to_owned())
+        } else {
+            None
+        }
+    });
+    Ok(commit_id)
+}
+
 /// Information about the files to display/diff in the UI.
 #[derive(Debug)]
 pub struct DiffContext {
@@ -330,6 +780,12 @@ pub struct DiffContext {
     /// each `File`'s path. It may be empty (indicating to overwrite the file
     /// in-place).
     pub write_root: PathBuf,
+
+    /// The encodings of the files which aren't encoded as UTF-8, by the path of
+    /// their `File`. The selected contents of these files should be written in
+    /// the same encoding. Placeholder files aren't included until they've
+    /// been loaded; see [`FileLoader::encodings`].
+    pub encodings: BTreeMap<PathBuf, TextEncoding>,
 }
 
 /// Process the command-line options to find the files to diff.
@@ -343,17 +799,35 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             output: _,
             read_only: _,
             dry_run: _,
+            find_renames: _,
+            find_copies: _,
+            include: _,
+            exclude: _,
+            gitignore: _,
+            whitespace: _,
+            diff_algorithm: _,
+            max_file_size: _,
+            max_file_lines: _,
+            show_generated: _,
+            encoding: _,
+            preselect: _,
+            script: _,
         } => {
+            let diff_options = DiffOptions::from_opts(opts);
+            let mut encodings = BTreeMap::new();
             let files = vec![render::create_file(
                 filesystem,
                 left.clone(),
                 left.clone(),
                 right.clone(),
                 right.clone(),
+                &diff_options,
+                &mut encodings,
             )?];
             DiffContext {
                 files,
                 write_root: PathBuf::new(),
+                encodings,
             }
         }
 
@@ -365,21 +839,57 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             output: _,
             read_only: _,
             dry_run: _,
+            find_renames,
+            find_copies,
+            include,
+            exclude,
+            gitignore,
+            whitespace: _,
+            diff_algorithm: _,
+            max_file_size: _,
+            max_file_lines: _,
+            show_generated: _,
+            encoding: _,
+            preselect: _,
+            script: _,
         } => {
-            let display_paths = filesystem.read_dir_diff_paths(left, right)?;
-            let mut files = Vec::new();
-            for display_path in display_paths {
-                files.push(render::create_file(
+            let filter = DirDiffFilter::new(include, exclude, *gitignore)?;
+            let diff_options = DiffOptions::from_opts(opts);
+            let display_paths = filesystem.read_dir_diff_paths(left, right, &filter)?;
+            let mut encodings = BTreeMap::new();
+            let files = if find_renames.is_some() || find_copies.is_some() {
+                renames::create_files_with_renames(
                     filesystem,
-                    left.join(&display_path),
-                    display_path.clone(),
-                    right.join(&display_path),
-                    display_path.clone(),
-                )?);
-            }
+                    left,
+                    right,
+                    display_paths,
+                    renames::RenameOptions {
+                        rename_threshold: find_renames
+                            .unwrap_or(renames::DEFAULT_SIMILARITY_THRESHOLD),
+                        copy_threshold: *find_copies,
+                    },
+                    &diff_options,
+                    &mut encodings,
+                )?
+            } else {
+                let mut files = Vec::new();
+                for display_path in display_paths {
+                    files.push(render::create_file(
+                        filesystem,
+                        left.join(&display_path),
+                        display_path.clone(),
+                        right.join(&display_path),
+                        display_path.clone(),
+                        &diff_options,
+                        &mut encodings,
+                    )?);
+                }
+                files
+            };
             DiffContext {
                 files,
                 write_root: right.clone(),
+                encodings,
             }
         }
 
@@ -391,17 +901,33 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             output: Some(output),
             read_only: _,
             dry_run: _,
+            find_renames: _,
+            find_copies: _,
+            include: _,
+            exclude: _,
+            gitignore: _,
+            whitespace: _,
+            diff_algorithm: _,
+            max_file_size: _,
+            max_file_lines: _,
+            show_generated: _,
+            encoding: _,
+            preselect: _,
+            script: _,
         } => {
+            let mut encodings = BTreeMap::new();
             let files = vec![render::create_merge_file(
                 filesystem,
                 base.clone(),
                 left.clone(),
                 right.clone(),
                 output.clone(),
+                &mut encodings,
             )?];
             DiffContext {
                 files,
                 write_root: PathBuf::new(),
+                encodings,
             }
         }
 
@@ -413,6 +939,19 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             output: None,
             read_only: _,
             dry_run: _,
+            find_renames: _,
+            find_copies: _,
+            include: _,
+            exclude: _,
+            gitignore: _,
+            whitespace: _,
+            diff_algorithm: _,
+            max_file_size: _,
+            max_file_lines: _,
+            show_generated: _,
+            encoding: _,
+            preselect: _,
+            script: _,
         } => {
             unreachable!("--output is required when --base is provided");
         }
@@ -425,6 +964,19 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
             output: _,
             read_only: _,
             dry_run: _,
+            find_renames: _,
+            find_copies: _,
+            include: _,
+            exclude: _,
+            gitignore: _,
+            whitespace: _,
+            diff_algorithm: _,
+            max_file_size: _,
+            max_file_lines: _,
+            show_generated: _,
+            encoding: _,
+            preselect: _,
+            script: _,
         } => {
             unimplemented!("--base cannot be used with --dir-diff");
         }
@@ -432,6 +984,141 @@ pub fn process_opts(filesystem: &dyn Filesystem, opts: &Opts) -> Result<DiffCont
     Ok(result)
 }
 
+/// Like [`process_opts`], but when comparing directories, only the paths and
+/// file modes of the files are read. The files are returned as placeholders,
+/// whose contents can be loaded with the returned [`FileLoader`] as they're
+/// needed.
+///
+/// Renames and copies can only be detected once every file has been read, so
+/// the files are loaded up-front if `--find-renames` or `--find-copies` is
+/// passed.
+pub fn process_opts_lazily(
+    filesystem: Arc<dyn Filesystem + Send + Sync>,
+    opts: &Opts,
+) -> Result<(DiffContext, FileLoader)> {
+    match opts {
+        Opts {
+            dir_diff: true,
+            left,
+            right,
+            base: None,
+            output: _,
+            read_only: _,
+            dry_run: _,
+            find_renames: None,
+            find_copies: None,
+            include,
+            exclude,
+            gitignore,
+            whitespace: _,
+            diff_algorithm: _,
+            max_file_size: _,
+            max_file_lines: _,
+            show_generated: _,
+            encoding: _,
+            preselect: _,
+            script: _,
+        } => {
+            let filter = DirDiffFilter::new(include, exclude, *gitignore)?;
+            let diff_options = DiffOptions::from_opts(opts);
+            let display_paths = filesystem.read_dir_diff_paths(left, right, &filter)?;
+            let mut files = Vec::new();
+            let mut unloaded_files = Vec::new();
+            for (file_idx, display_path) in display_paths.into_iter().enumerate() {
+                let unloaded_file = UnloadedFile {
+                    left_path: left.join(&display_path),
+                    left_display_path: display_path.clone(),
+                    right_path: right.join(&display_path),
+                    right_display_path: display_path,
+                };
+                files.push(render::create_placeholder_file(
+                    filesystem.as_ref(),
+                    &unloaded_file.left_path,
+                    unloaded_file.left_display_path.clone(),
+                    &unloaded_file.right_path,
+                    unloaded_file.right_display_path.clone(),
+                )?);
+                unloaded_files.push((file_idx, unloaded_file));
+            }
+            let diff_context = DiffContext {
+                files,
+                write_root: right.clone(),
+                encodings: BTreeMap::new(),
+            };
+            let loader = FileLoader::new(filesystem, diff_options, unloaded_files);
+            Ok((diff_context, loader))
+        }
+
+        opts => {
+            let diff_context = process_opts(filesystem.as_ref(), opts)?;
+            // The loader is still used to show the full diff of summarized
+            // files, which doesn't depend on the limits.
+            let diff_options = DiffOptions {
+                max_file_size: None,
+                max_file_lines: None,
+                summarize_generated: false,
+                ..DiffOptions::from_opts(opts)
+            };
+            let loader = FileLoader::new(filesystem, diff_options, Vec::new());
+            Ok((diff_context, loader))
+        }
+    }
+}
+
+/// Forwards to `input`, but loads the contents of placeholder files with
+/// `loader` as the UI needs them.
+struct LoadingInput<'a> {
+    input: &'a mut dyn RecordInput,
+    loader: &'a mut FileLoader,
+}
+
+impl RecordInput for LoadingInput<'_> {
+    fn terminal_kind(&self) -> TerminalKind {
+        self.input.terminal_kind()
+    }
+
+    fn next_events(&mut self) -> std::result::Result<Vec<Event>, RecordError> {
+        self.input.next_events()
+    }
+
+    fn edit_commit_message(&mut self, message: &str) -> std::result::Result<String, RecordError> {
+        self.input.edit_commit_message(message)
+    }
+
+    fn edit_commit_message_for_files(
+        &mut self,
+        message: &str,
+        files: &[File],
+    ) -> std::result::Result<String, RecordError> {
+        self.input.edit_commit_message_for_files(message, files)
+    }
+
+    fn edit_commit_metadata(
+        &mut self,
+        metadata: &str,
+    ) -> std::result::Result<Option<String>, RecordError> {
+        self.input.edit_commit_metadata(metadata)
+    }
+
+    fn load_file(
+        &mut self,
+        file_idx: usize,
+        _file: &File,
+    ) -> std::result::Result<Option<File<'static>>, RecordError> {
+        self.loader
+            .load_file(file_idx)
+            .map_err(|err| RecordError::ReadFile(Box::new(err)))
+    }
+
+    fn load_full_diff(
+        &mut self,
+        _file_idx: usize,
+        file: &File,
+    ) -> std::result::Result<Option<File<'static>>, RecordError> {
+        Ok(self.loader.load_full_diff(file))
+    }
+}
+
 fn print_dry_run(write_root: &Path, state: RecordState) {
     let RecordState {
         is_read_only: _,
@@ -439,8 +1126,7 @@ fn print_dry_run(write_root: &Path, state: RecordState) {
         files,
     } = state;
     for file in files {
-        let file_path = write_root.join(file.path.clone());
-        let (selected_contents, _unselected_contents) = file.get_selected_contents();
+        let (selected_contents, unselected_contents) = file.get_selected_contents();
 
         let File {
             file_mode: old_file_mode,
@@ -448,9 +1134,19 @@ fn print_dry_run(write_root: &Path, state: RecordState) {
         } = file;
 
         let SelectedChanges {
+            path,
             contents,
             file_mode,
         } = selected_contents;
+        let file_path = write_root.join(&path);
+
+        if path != unselected_contents.path {
+            println!(
+                "Would rename file: {} => {}",
+                write_root.join(&unselected_contents.path).display(),
+                file_path.display()
+            );
+        }
 
         if file_mode == FileMode::Absent {
             println!("Would delete file: {}", file_path.display());
@@ -489,15 +1185,21 @@ fn print_dry_run(write_root: &Path, state: RecordState) {
                     println!("  {line}");
                 }
             }
+            SelectedContents::Submodule { commit_id } => {
+                println!("Would update submodule: {}", file_path.display());
+                println!("  Commit: {commit_id}");
+            }
         }
     }
 }
 
 /// After the user has selected changes in the provided [`RecordState`], write
-/// the results to the provided [`Filesystem`].
+/// the results to the provided [`Filesystem`]. Text files are written in their
+/// encodings from `encodings`, or as UTF-8 if they don't have one.
 pub fn apply_changes(
     filesystem: &mut dyn Filesystem,
     write_root: &Path,
+    encodings: &BTreeMap<PathBuf, TextEncoding>,
     state: RecordState,
 ) -> Result<()> {
     let RecordState {
@@ -509,16 +1211,30 @@ pub fn apply_changes(
         return Ok(());
     }
     for file in files {
-        let file_path = write_root.join(file.path.clone());
-        let (selected_changes, _unselected_changes) = file.get_selected_contents();
+        let (selected_changes, unselected_changes) = file.get_selected_contents();
 
         let SelectedChanges {
+            path,
             contents,
             file_mode,
         } = selected_changes;
+        let file_path = write_root.join(&path);
+
+        // If the file was renamed, then it currently exists at `file.path`,
+        // but should end up at the selected path only.
+        if path != unselected_changes.path {
+            if path != file.path && contents == SelectedContents::Unchanged {
+                if let Some(parent_dir) = file_path.parent() {
+                    filesystem.create_dir_all(parent_dir)?;
+                }
+                filesystem.copy_file(&write_root.join(&file.path), &file_path)?;
+            }
+            filesystem.remove_file(&write_root.join(&unselected_changes.path))?;
+        }
 
         if file_mode == FileMode::Absent {
             filesystem.remove_file(&file_path)?;
+            continue;
         }
 
         match contents {
@@ -542,7 +1258,17 @@ pub fn apply_changes(
                 }
 
                 // TODO: Respect executable bit
-                filesystem.write_file(&file_path, &contents)?;
+                let encoding = encodings
+                    .get(file.path.as_ref())
+                    .copied()
+                    .unwrap_or_default();
+                filesystem.write_file(&file_path, &contents, encoding)?;
+            }
+            SelectedContents::Submodule { commit_id } => {
+                if let Some(parent_dir) = file_path.parent() {
+                    filesystem.create_dir_all(parent_dir)?;
+                }
+                filesystem.write_submodule(&file_path, &commit_id)?;
             }
         }
     }
@@ -551,31 +1277,86 @@ pub fn apply_changes(
 
 /// Select changes interactively and apply them to disk.
 pub fn run(opts: Opts) -> Result<()> {
-    let filesystem = RealFilesystem;
-    let DiffContext { files, write_root } = process_opts(&filesystem, &opts)?;
+    let (
+        DiffContext {
+            mut files,
+            write_root,
+            mut encodings,
+        },
+        mut loader,
+    ) = process_opts_lazily(
+        Arc::new(RealFilesystem {
+            encoding: opts.encoding,
+        }),
+        &opts,
+    )?;
+    if !opts.preselect.is_empty() || opts.script.is_some() {
+        // Selection rules may apply to any file, so they need every file's
+        // contents.
+        loader.load_all(&mut files)?;
+    }
     let state = RecordState {
         is_read_only: opts.read_only,
         commits: Default::default(),
         files,
     };
-    let mut input = CrosstermInput;
-    let recorder = Recorder::new(state, &mut input);
-    match recorder.run() {
-        Ok(state) => {
-            if opts.dry_run {
-                print_dry_run(&write_root, state);
-                Err(Error::DryRun)
-            } else {
-                let mut filesystem = filesystem;
-                apply_changes(&mut filesystem, &write_root, state)?;
-                Ok(())
+    let state = apply_preselect(&opts, state)?;
+    let state = match &opts.script {
+        Some(script_path) => {
+            let script = read_script(script_path)?;
+            SelectionScript::parse(&script)
+                .and_then(|script| script.apply(state))
+                .map_err(|err| Error::Script { source: err })?
+        }
+        None => {
+            let mut input = LoadingInput {
+                input: &mut CrosstermInput,
+                loader: &mut loader,
+            };
+            let recorder = Recorder::new(state, &mut input);
+            match recorder.run() {
+                Ok(state) => state,
+                Err(RecordError::Cancelled) => return Err(Error::Cancelled),
+                Err(err) => return Err(Error::Record { source: err }),
             }
         }
-        Err(RecordError::Cancelled) => Err(Error::Cancelled),
-        Err(err) => Err(Error::Record { source: err }),
+    };
+    if opts.dry_run {
+        print_dry_run(&write_root, state);
+        Err(Error::DryRun)
+    } else {
+        encodings.extend(loader.encodings().clone());
+        let mut filesystem = RealFilesystem {
+            encoding: opts.encoding,
+        };
+        apply_changes(&mut filesystem, &write_root, &encodings, state)?;
+        Ok(())
     }
 }
 
+/// Apply the `--preselect` rules from `opts` to `state`, as is done before
+/// opening the UI or applying `--script`. Each rule is a line of a
+/// [`SelectionScript`].
+pub fn apply_preselect<'a>(opts: &Opts, state: RecordState<'a>) -> Result<RecordState<'a>> {
+    SelectionScript::parse(&opts.preselect.join("\n"))
+        .and_then(|preselect| preselect.apply(state))
+        .map_err(|err| Error::Preselect { source: err })
+}
+
+/// Read a selection script from the given path, or from standard input if the
+/// path is `-`.
+fn read_script(path: &Path) -> Result<String> {
+    let result = if path == Path::new("-") {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(path)
+    };
+    result.map_err(|err| Error::ReadFile {
+        path: path.to_owned(),
+        source: err,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use insta::assert_debug_snapshot;
@@ -613,7 +1394,12 @@ mod tests {
     }
 
     impl Filesystem for TestFilesystem {
-        fn read_dir_diff_paths(&self, left: &Path, right: &Path) -> Result<BTreeSet<PathBuf>> {
+        fn read_dir_diff_paths(
+            &self,
+            left: &Path,
+            right: &Path,
+            filter: &DirDiffFilter,
+        ) -> Result<BTreeSet<PathBuf>> {
             let left_files = self
                 .files
                 .keys()
@@ -624,6 +1410,14 @@ mod tests {
                 .filter_map(|path| path.strip_prefix(right).ok());
             Ok(left_files
                 .chain(right_files)
+                .filter(|path| {
+                    let is_dir_excluded = path
+                        .ancestors()
+                        .skip(1)
+                        .filter(|dir| !dir.as_os_str().is_empty())
+                        .any(|dir| filter.is_excluded(dir, true));
+                    !is_dir_excluded && !filter.is_excluded(path, false)
+                })
                 .map(|path| path.to_path_buf())
                 .collect())
         }
@@ -644,12 +1438,31 @@ mod tests {
             }
         }
 
-        fn write_file(&mut self, path: &Path, contents: &str) -> Result<()> {
+        fn write_file(
+            &mut self,
+            path: &Path,
+            contents: &str,
+            _encoding: TextEncoding,
+        ) -> Result<()> {
             self.assert_parent_dir_exists(path);
             self.files.insert(path.to_owned(), file_info(contents));
             Ok(())
         }
 
+        fn write_submodule(&mut self, path: &Path, commit_id: &str) -> Result<()> {
+            self.assert_parent_dir_exists(path);
+            self.files.insert(
+                path.to_owned(),
+                FileInfo {
+                    file_mode: FileMode::GITLINK,
+                    contents: FileContents::Submodule {
+                        commit_id: commit_id.to_owned(),
+                    },
+                },
+            );
+            Ok(())
+        }
+
         fn copy_file(&mut self, old_path: &Path, new_path: &Path) -> Result<()> {
             self.assert_parent_dir_exists(new_path);
             let file_info = self.read_file_info(old_path)?;
@@ -677,6 +1490,7 @@ mod tests {
                 contents,
                 hash: "abc123".to_string(),
                 num_bytes,
+                encoding: TextEncoding::UTF_8,
             },
         }
     }
@@ -706,6 +1520,7 @@ qux2
         let DiffContext {
             mut files,
             write_root,
+            encodings,
         } = process_opts(
             &filesystem,
             &Opts {
@@ -716,6 +1531,19 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                find_renames: None,
+                find_copies: None,
+                include: vec![],
+                exclude: vec![],
+                gitignore: false,
+                whitespace: WhitespaceMode::Exact,
+                diff_algorithm: DiffAlgorithm::Myers,
+                max_file_size: 0,
+                max_file_lines: 0,
+                show_generated: false,
+                encoding: None,
+                preselect: vec![],
+                script: None,
             },
         )?;
         assert_debug_snapshot!(files, @r###"
@@ -772,13 +1600,14 @@ qux2
         apply_changes(
             &mut filesystem,
             &write_root,
+            &encodings,
             RecordState {
                 is_read_only: false,
                 commits: Default::default(),
                 files,
             },
         )?;
-        insta::assert_debug_snapshot!(filesystem, @r###"
+        insta::assert_debug_snapshot!(filesystem, @r#"
         TestFilesystem {
             files: {
                 "left": FileInfo {
@@ -789,6 +1618,7 @@ qux2
                         contents: "foo\ncommon1\ncommon2\nbar\n",
                         hash: "abc123",
                         num_bytes: 24,
+                        encoding: UTF-8,
                     },
                 },
                 "right": FileInfo {
@@ -799,6 +1629,7 @@ qux2
                         contents: "qux1\ncommon1\ncommon2\nqux2\n",
                         hash: "abc123",
                         num_bytes: 26,
+                        encoding: UTF-8,
                     },
                 },
             },
@@ -806,7 +1637,7 @@ qux2
                 "",
             },
         }
-        "###);
+        "#);
 
         Ok(())
     }
@@ -827,7 +1658,11 @@ common2
 qux2
 "),
         });
-        let DiffContext { files, write_root } = process_opts(
+        let DiffContext {
+            files,
+            write_root,
+            encodings,
+        } = process_opts(
             &filesystem,
             &Opts {
                 dir_diff: false,
@@ -837,19 +1672,33 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                find_renames: None,
+                find_copies: None,
+                include: vec![],
+                exclude: vec![],
+                gitignore: false,
+                whitespace: WhitespaceMode::Exact,
+                diff_algorithm: DiffAlgorithm::Myers,
+                max_file_size: 0,
+                max_file_lines: 0,
+                show_generated: false,
+                encoding: None,
+                preselect: vec![],
+                script: None,
             },
         )?;
 
         apply_changes(
             &mut filesystem,
             &write_root,
+            &encodings,
             RecordState {
                 is_read_only: false,
                 commits: Default::default(),
                 files,
             },
         )?;
-        insta::assert_debug_snapshot!(filesystem, @r###"
+        insta::assert_debug_snapshot!(filesystem, @r#"
         TestFilesystem {
             files: {
                 "left": FileInfo {
@@ -860,6 +1709,7 @@ qux2
                         contents: "foo\ncommon1\ncommon2\nbar\n",
                         hash: "abc123",
                         num_bytes: 24,
+                        encoding: UTF-8,
                     },
                 },
                 "right": FileInfo {
@@ -870,6 +1720,7 @@ qux2
                         contents: "foo\ncommon1\ncommon2\nbar\n",
                         hash: "abc123",
                         num_bytes: 24,
+                        encoding: UTF-8,
                     },
                 },
             },
@@ -877,7 +1728,7 @@ qux2
                 "",
             },
         }
-        "###);
+        "#);
 
         Ok(())
     }
@@ -890,6 +1741,7 @@ qux2
         let DiffContext {
             mut files,
             write_root,
+            encodings,
         } = process_opts(
             &filesystem,
             &Opts {
@@ -900,6 +1752,19 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                find_renames: None,
+                find_copies: None,
+                include: vec![],
+                exclude: vec![],
+                gitignore: false,
+                whitespace: WhitespaceMode::Exact,
+                diff_algorithm: DiffAlgorithm::Myers,
+                max_file_size: 0,
+                max_file_lines: 0,
+                show_generated: false,
+                encoding: None,
+                preselect: vec![],
+                script: None,
             },
         )?;
         assert_debug_snapshot!(files, @r###"
@@ -935,13 +1800,14 @@ qux2
         apply_changes(
             &mut filesystem,
             &write_root,
+            &encodings,
             RecordState {
                 is_read_only: false,
                 commits: Default::default(),
                 files,
             },
         )?;
-        insta::assert_debug_snapshot!(filesystem, @r###"
+        insta::assert_debug_snapshot!(filesystem, @r#"
         TestFilesystem {
             files: {
                 "right": FileInfo {
@@ -952,6 +1818,7 @@ qux2
                         contents: "right\n",
                         hash: "abc123",
                         num_bytes: 6,
+                        encoding: UTF-8,
                     },
                 },
             },
@@ -959,7 +1826,7 @@ qux2
                 "",
             },
         }
-        "###);
+        "#);
 
         Ok(())
     }
@@ -972,6 +1839,7 @@ qux2
         let DiffContext {
             mut files,
             write_root,
+            encodings,
         } = process_opts(
             &filesystem,
             &Opts {
@@ -982,6 +1850,19 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                find_renames: None,
+                find_copies: None,
+                include: vec![],
+                exclude: vec![],
+                gitignore: false,
+                whitespace: WhitespaceMode::Exact,
+                diff_algorithm: DiffAlgorithm::Myers,
+                max_file_size: 0,
+                max_file_lines: 0,
+                show_generated: false,
+                encoding: None,
+                preselect: vec![],
+                script: None,
             },
         )?;
         assert_debug_snapshot!(files, @r###"
@@ -1017,13 +1898,14 @@ qux2
         apply_changes(
             &mut filesystem,
             &write_root,
+            &encodings,
             RecordState {
                 is_read_only: false,
                 commits: Default::default(),
                 files,
             },
         )?;
-        insta::assert_debug_snapshot!(filesystem, @r###"
+        insta::assert_debug_snapshot!(filesystem, @r#"
         TestFilesystem {
             files: {
                 "left": FileInfo {
@@ -1034,6 +1916,7 @@ qux2
                         contents: "left\n",
                         hash: "abc123",
                         num_bytes: 5,
+                        encoding: UTF-8,
                     },
                 },
             },
@@ -1041,7 +1924,7 @@ qux2
                 "",
             },
         }
-        "###);
+        "#);
 
         Ok(())
     }
@@ -1062,6 +1945,19 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                find_renames: None,
+                find_copies: None,
+                include: vec![],
+                exclude: vec![],
+                gitignore: false,
+                whitespace: WhitespaceMode::Exact,
+                diff_algorithm: DiffAlgorithm::Myers,
+                max_file_size: 0,
+                max_file_lines: 0,
+                show_generated: false,
+                encoding: None,
+                preselect: vec![],
+                script: None,
             },
         );
         insta::assert_debug_snapshot!(result, @r###"
@@ -1086,7 +1982,11 @@ qux2
             PathBuf::from("right/foo") => file_info("right contents\n"),
         });
 
-        let DiffContext { files, write_root } = process_opts(
+        let DiffContext {
+            files,
+            write_root,
+            encodings,
+        } = process_opts(
             &filesystem,
             &Opts {
                 dir_diff: false,
@@ -1096,19 +1996,33 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                find_renames: None,
+                find_copies: None,
+                include: vec![],
+                exclude: vec![],
+                gitignore: false,
+                whitespace: WhitespaceMode::Exact,
+                diff_algorithm: DiffAlgorithm::Myers,
+                max_file_size: 0,
+                max_file_lines: 0,
+                show_generated: false,
+                encoding: None,
+                preselect: vec![],
+                script: None,
             },
         )?;
 
         apply_changes(
             &mut filesystem,
             &write_root,
+            &encodings,
             RecordState {
                 is_read_only: false,
                 commits: Default::default(),
                 files,
             },
         )?;
-        assert_debug_snapshot!(filesystem, @r###"
+        assert_debug_snapshot!(filesystem, @r#"
         TestFilesystem {
             files: {
                 "left/foo": FileInfo {
@@ -1119,6 +2033,7 @@ qux2
                         contents: "left contents\n",
                         hash: "abc123",
                         num_bytes: 14,
+                        encoding: UTF-8,
                     },
                 },
                 "right/foo": FileInfo {
@@ -1129,6 +2044,7 @@ qux2
                         contents: "left contents\n",
                         hash: "abc123",
                         num_bytes: 14,
+                        encoding: UTF-8,
                     },
                 },
             },
@@ -1138,7 +2054,7 @@ qux2
                 "right",
             },
         }
-        "###);
+        "#);
 
         Ok(())
     }
@@ -1150,7 +2066,11 @@ qux2
             PathBuf::from("right/foo") => file_info("right contents\n"),
         });
 
-        let DiffContext { files, write_root } = process_opts(
+        let DiffContext {
+            files,
+            write_root,
+            encodings,
+        } = process_opts(
             &filesystem,
             &Opts {
                 dir_diff: false,
@@ -1160,19 +2080,33 @@ qux2
                 output: None,
                 read_only: false,
                 dry_run: false,
+                find_renames: None,
+                find_copies: None,
+                include: vec![],
+                exclude: vec![],
+                gitignore: false,
+                whitespace: WhitespaceMode::Exact,
+                diff_algorithm: DiffAlgorithm::Myers,
+                max_file_size: 0,
+                max_file_lines: 0,
+                show_generated: false,
+                encoding: None,
+                preselect: vec![],
+                script: None,
             },
         )?;
 
         apply_changes(
             &mut filesystem,
             &write_root,
+            &encodings,
             RecordState {
                 is_read_only: false,
                 commits: Default::default(),
                 files,
             },
         )?;
-        assert_debug_snapshot!(filesystem, @r###"
+        assert_debug_snapshot!(filesystem, @r#"
         TestFilesystem {
             files: {
                 "left/foo": FileInfo {
@@ -1183,6 +2117,7 @@ qux2
                         contents: "left contents\n",
                         hash: "abc123",
                         num_bytes: 14,
+                        encoding: UTF-8,
                     },
                 },
                 "right/foo": FileInfo {
@@ -1193,6 +2128,7 @@ qux2
                         contents: "left contents\n",
                         hash: "abc123",
                         num_bytes: 14,
+                        encoding: UTF-8,
                     },
                 },
             },
@@ -1202,7 +2138,7 @@ qux2
                 "right",
             },
         }
-        "###);
+        "#);
 
         Ok(())
     }
@@ -1236,6 +2172,7 @@ Hello world 4
         let DiffContext {
             mut files,
             write_root,
+            encodings,
         } = process_opts(
             &filesystem,
             &Opts {
@@ -1244,6 +2181,19 @@ Hello world 4
                 right: "right".into(),
                 read_only: false,
                 dry_run: false,
+                find_renames: None,
+                find_copies: None,
+                include: vec![],
+                exclude: vec![],
+                gitignore: false,
+                whitespace: WhitespaceMode::Exact,
+                diff_algorithm: DiffAlgorithm::Myers,
+                max_file_size: 0,
+                max_file_lines: 0,
+                show_generated: false,
+                encoding: None,
+                preselect: vec![],
+                script: None,
                 base: Some("base".into()),
                 output: Some("output".into()),
             },
@@ -1298,6 +2248,7 @@ Hello world 4
         apply_changes(
             &mut filesystem,
             &write_root,
+            &encodings,
             RecordState {
                 is_read_only: false,
                 commits: Default::default(),
@@ -1305,7 +2256,7 @@ Hello world 4
             },
         )?;
 
-        assert_debug_snapshot!(filesystem, @r###"
+        assert_debug_snapshot!(filesystem, @r#"
         TestFilesystem {
             files: {
                 "base": FileInfo {
@@ -1316,6 +2267,7 @@ Hello world 4
                         contents: "Hello world 1\nHello world 2\nHello world 3\nHello world 4\n",
                         hash: "abc123",
                         num_bytes: 56,
+                        encoding: UTF-8,
                     },
                 },
                 "left": FileInfo {
@@ -1326,6 +2278,7 @@ Hello world 4
                         contents: "Hello world 1\nHello world 2\nHello world L\nHello world 4\n",
                         hash: "abc123",
                         num_bytes: 56,
+                        encoding: UTF-8,
                     },
                 },
                 "output": FileInfo {
@@ -1336,6 +2289,7 @@ Hello world 4
                         contents: "Hello world 1\nHello world 2\nHello world L\nHello world R\nHello world 4\n",
                         hash: "abc123",
                         num_bytes: 70,
+                        encoding: UTF-8,
                     },
                 },
                 "right": FileInfo {
@@ -1346,6 +2300,7 @@ Hello world 4
                         contents: "Hello world 1\nHello world 2\nHello world R\nHello world 4\n",
                         hash: "abc123",
                         num_bytes: 56,
+                        encoding: UTF-8,
                     },
                 },
             },
@@ -1353,7 +2308,7 @@ Hello world 4
                 "",
             },
         }
-        "###);
+        "#);
 
         Ok(())
     }
@@ -1371,6 +2326,7 @@ Hello world 2
         let DiffContext {
             mut files,
             write_root,
+            encodings,
         } = process_opts(
             &filesystem,
             &Opts {
@@ -1379,6 +2335,19 @@ Hello world 2
                 right: "right".into(),
                 read_only: false,
                 dry_run: false,
+                find_renames: None,
+                find_copies: None,
+                include: vec![],
+                exclude: vec![],
+                gitignore: false,
+                whitespace: WhitespaceMode::Exact,
+                diff_algorithm: DiffAlgorithm::Myers,
+                max_file_size: 0,
+                max_file_lines: 0,
+                show_generated: false,
+                encoding: None,
+                preselect: vec![],
+                script: None,
                 base: None,
                 output: None,
             },
@@ -1421,6 +2390,7 @@ Hello world 2
         apply_changes(
             &mut filesystem,
             &write_root,
+            &encodings,
             RecordState {
                 is_read_only: false,
                 commits: Default::default(),
@@ -1441,13 +2411,14 @@ Hello world 2
         apply_changes(
             &mut filesystem,
             &write_root,
+            &encodings,
             RecordState {
                 is_read_only: false,
                 commits: Default::default(),
                 files: files.clone(),
             },
         )?;
-        insta::assert_debug_snapshot!(filesystem, @r###"
+        insta::assert_debug_snapshot!(filesystem, @r#"
         TestFilesystem {
             files: {
                 "right": FileInfo {
@@ -1458,6 +2429,7 @@ Hello world 2
                         contents: "Hello world 1\nHello world 2\n",
                         hash: "abc123",
                         num_bytes: 28,
+                        encoding: UTF-8,
                     },
                 },
             },
@@ -1465,7 +2437,7 @@ Hello world 2
                 "",
             },
         }
-        "###);
+        "#);
 
         // Select only some changes from new file.
         match files[0].sections.get_mut(1).unwrap() {
@@ -1475,13 +2447,14 @@ Hello world 2
         apply_changes(
             &mut filesystem,
             &write_root,
+            &encodings,
             RecordState {
                 is_read_only: false,
                 commits: Default::default(),
                 files: files.clone(),
             },
         )?;
-        insta::assert_debug_snapshot!(filesystem, @r###"
+        insta::assert_debug_snapshot!(filesystem, @r#"
         TestFilesystem {
             files: {
                 "right": FileInfo {
@@ -1492,6 +2465,7 @@ Hello world 2
                         contents: "Hello world 2\n",
                         hash: "abc123",
                         num_bytes: 14,
+                        encoding: UTF-8,
                     },
                 },
             },
@@ -1499,8 +2473,163 @@ Hello world 2
                 "",
             },
         }
-        "###);
+        "#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_submodule_placeholder() {
+        let commit_id = "0123456789abcdef0123456789abcdef01234567";
+        assert_eq!(
+            parse_submodule_placeholder(&make_submodule_placeholder(commit_id)),
+            Some(commit_id)
+        );
+        assert_eq!(
+            parse_submodule_placeholder(&format!("Subproject commit {commit_id}-dirty\n")),
+            Some(commit_id)
+        );
+        assert_eq!(
+            parse_submodule_placeholder("Subproject commit abc123"),
+            None
+        );
+        assert_eq!(
+            parse_submodule_placeholder(&format!("Subproject commit {commit_id}\nmore\n")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_real_filesystem_encodings() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!(
+            "scm-diff-editor-test-encodings-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        let read_contents = |filesystem: &RealFilesystem, path: &Path| match filesystem
+            .read_file_info(path)
+            .unwrap()
+            .contents
+        {
+            FileContents::Text {
+                contents,
+                hash: _,
+                num_bytes: _,
+                encoding,
+            } => Some((contents, encoding.name())),
+            FileContents::Absent | FileContents::Binary { .. } | FileContents::Submodule { .. } => {
+                None
+            }
+        };
+
+        let mut filesystem = RealFilesystem { encoding: None };
+        let utf16_path = dir.join("utf16");
+        fs::write(&utf16_path, b"\xff\xfea\x00\n\x00").unwrap();
+        assert_eq!(
+            read_contents(&filesystem, &utf16_path),
+            Some(("\u{feff}a\n".to_owned(), "UTF-16LE"))
+        );
+        // The byte order mark is only written if it's part of the contents.
+        let utf16 = TextEncoding::for_label("utf-16le").unwrap();
+        filesystem.write_file(&utf16_path, "\u{feff}b\n", utf16)?;
+        assert_eq!(fs::read(&utf16_path).unwrap(), b"\xff\xfeb\x00\n\x00");
+        filesystem.write_file(&utf16_path, "b\n", utf16)?;
+        assert_eq!(fs::read(&utf16_path).unwrap(), b"b\x00\n\x00");
+
+        let latin1_path = dir.join("latin1");
+        fs::write(&latin1_path, b"caf\xe9\n").unwrap();
+        assert_eq!(
+            read_contents(&filesystem, &latin1_path),
+            Some(("caf\u{e9}\n".to_owned(), "windows-1252"))
+        );
+
+        // The contents aren't valid UTF-8, so they're treated as binary if the
+        // encoding is overridden.
+        let filesystem = RealFilesystem {
+            encoding: Some(TextEncoding::UTF_8),
+        };
+        assert_eq!(read_contents(&filesystem, &latin1_path), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
 
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_real_filesystem_submodules() -> Result<()> {
+        if Command::new("git").arg("--version").output().is_err() {
+            eprintln!("skipping test because `git` isn't available");
+            return Ok(());
+        }
+        let dir = std::env::temp_dir().join(format!(
+            "scm-diff-editor-test-submodules-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let git = |dir: &Path, args: &[&str]| {
+            run_git(
+                dir,
+                &[
+                    &["-c", "user.name=Test", "-c", "user.email=test@example.com"],
+                    args,
+                ]
+                .concat(),
+            )
+            .unwrap()
+            .trim_end()
+            .to_owned()
+        };
+        let make_submodule = |path: &Path, git_args: &[&str]| {
+            fs::create_dir_all(path).unwrap();
+            git(path, &[&["init", "--quiet"], git_args].concat());
+            let mut commit_ids = Vec::new();
+            for contents in ["foo\n", "bar\n"] {
+                fs::write(path.join("file"), contents).unwrap();
+                git(path, &["add", "file"]);
+                git(path, &["commit", "--quiet", "-m", contents]);
+                commit_ids.push(git(path, &["rev-parse", "HEAD"]));
+            }
+            commit_ids
+        };
+
+        let mut filesystem = RealFilesystem { encoding: None };
+        let embedded_path = dir.join("embedded");
+        let commit_ids = make_submodule(&embedded_path, &[]);
+        filesystem.write_submodule(&embedded_path, &commit_ids[0])?;
+        assert_eq!(
+            read_submodule_head(&embedded_path.join(".git"))?,
+            Some(commit_ids[0].clone())
+        );
+        // The working copy is updated along with `HEAD`.
+        assert_eq!(
+            fs::read_to_string(embedded_path.join("file")).unwrap(),
+            "foo\n"
+        );
+        assert_eq!(git(&embedded_path, &["status", "--porcelain"]), "");
+        // Removing the submodule would delete its repository.
+        assert!(matches!(
+            filesystem.remove_file(&embedded_path),
+            Err(Error::RemoveSubmodule { .. })
+        ));
+
+        let absorbed_path = dir.join("absorbed");
+        let absorbed_git_dir = dir.join("modules").join("absorbed");
+        fs::create_dir_all(dir.join("modules")).unwrap();
+        make_submodule(
+            &absorbed_path,
+            &["--separate-git-dir", absorbed_git_dir.to_str().unwrap()],
+        );
+        fs::write(absorbed_path.join("file"), "baz\n").unwrap();
+        assert!(matches!(
+            filesystem.remove_file(&absorbed_path),
+            Err(Error::RemoveSubmodule { .. })
+        ));
+        git(&absorbed_path, &["checkout", "--quiet", "file"]);
+        filesystem.remove_file(&absorbed_path)?;
+        assert!(!absorbed_path.exists());
+        assert!(absorbed_git_dir.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
         Ok(())
     }
 }
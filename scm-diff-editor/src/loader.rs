@@ -0,0 +1,332 @@
+//! Loading the contents of files in a `--dir-diff` as they're needed.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use scm_record::File;
+
+use crate::diff::DiffOptions;
+use crate::render::{create_file_from_infos, create_full_diff_file};
+use crate::{FileInfo, Filesystem, Result, TextEncoding};
+
+/// The paths of a file whose contents haven't been loaded yet.
+#[derive(Clone, Debug)]
+pub(crate) struct UnloadedFile {
+    pub left_path: PathBuf,
+    pub left_display_path: PathBuf,
+    pub right_path: PathBuf,
+    pub right_display_path: PathBuf,
+}
+
+/// The result of reading the left and right versions of a file.
+type ReadResult = Result<(FileInfo, FileInfo)>;
+
+/// The number of files after the last loaded file which are read ahead by the
+/// background thread.
+const READ_AHEAD_LEN: usize = 32;
+
+fn read_file_infos(filesystem: &dyn Filesystem, left_path: &Path, right_path: &Path) -> ReadResult {
+    let left_info = filesystem.read_file_info(left_path)?;
+    let right_info = filesystem.read_file_info(right_path)?;
+    Ok((left_info, right_info))
+}
+
+/// Loads the contents of the placeholder files created by
+/// [`crate::process_opts_lazily`].
+///
+/// A background thread reads the files just after the last loaded one, so that
+/// they're usually ready by the time that they're scrolled into view. Only a
+/// limited number of files are read ahead, so the memory used doesn't depend on
+/// the size of the diff. A file which hasn't been read yet is read immediately
+/// instead. Dropping the loader stops the background thread once it finishes
+/// reading its current file.
+pub struct FileLoader {
+    filesystem: Arc<dyn Filesystem + Send + Sync>,
+    diff_options: DiffOptions,
+
+    /// The files which haven't been loaded yet, by index.
+    unloaded_files: HashMap<usize, UnloadedFile>,
+
+    /// The files which were read by the background thread, but haven't been
+    /// loaded yet. Only files in the read-ahead window are kept.
+    read_files: HashMap<usize, ReadResult>,
+
+    /// The background thread, if it's still running.
+    read_ahead: Option<ReadAhead>,
+
+    /// The encodings of the loaded files which aren't encoded as UTF-8.
+    encodings: BTreeMap<PathBuf, TextEncoding>,
+}
+
+/// The channels to the background thread which reads files ahead of time.
+struct ReadAhead {
+    /// Sends the indexes and paths of the files to read.
+    requests: mpsc::Sender<(usize, PathBuf, PathBuf)>,
+
+    /// Receives the files which were read. This channel is bounded, so the
+    /// thread waits for the files to be received before reading any more.
+    results: mpsc::Receiver<(usize, ReadResult)>,
+
+    /// The indexes of the files which should be read ahead. The background
+    /// thread skips any requested files which are no longer in this window,
+    /// such as after scrolling past them.
+    window: Arc<AtomicUsize>,
+
+    /// The indexes of the files which were requested for the current window.
+    requested: Range<usize>,
+}
+
+impl ReadAhead {
+    fn spawn(filesystem: Arc<dyn Filesystem + Send + Sync>) -> Self {
+        let (requests, request_receiver) = mpsc::channel::<(usize, PathBuf, PathBuf)>();
+        let (result_sender, results) = mpsc::sync_channel(READ_AHEAD_LEN);
+        let window = Arc::new(AtomicUsize::new(0));
+        let window_start = Arc::clone(&window);
+        thread::spawn(move || {
+            for (file_idx, left_path, right_path) in request_receiver {
+                let start = window_start.load(Ordering::Relaxed);
+                if !(start..start + READ_AHEAD_LEN).contains(&file_idx) {
+                    continue;
+                }
+                let result = read_file_infos(filesystem.as_ref(), &left_path, &right_path);
+                if result_sender.send((file_idx, result)).is_err() {
+                    // The loader was dropped, so the rest of the files
+                    // aren't needed.
+                    break;
+                }
+            }
+        });
+        Self {
+            requests,
+            results,
+            window,
+            requested: 0..0,
+        }
+    }
+}
+
+impl FileLoader {
+    pub(crate) fn new(
+        filesystem: Arc<dyn Filesystem + Send + Sync>,
+        diff_options: DiffOptions,
+        unloaded_files: Vec<(usize, UnloadedFile)>,
+    ) -> Self {
+        let read_ahead = if unloaded_files.is_empty() {
+            None
+        } else {
+            Some(ReadAhead::spawn(Arc::clone(&filesystem)))
+        };
+        let mut loader = Self {
+            filesystem,
+            diff_options,
+            unloaded_files: unloaded_files.into_iter().collect(),
+            read_files: Default::default(),
+            read_ahead,
+            encodings: Default::default(),
+        };
+        loader.read_ahead_from(0);
+        loader
+    }
+
+    /// Receive the files which the background thread has read so far, and
+    /// keep the ones which are still in the read-ahead window.
+    fn receive_read_files(&mut self) {
+        let read_ahead = match &self.read_ahead {
+            Some(read_ahead) => read_ahead,
+            None => return,
+        };
+        for (file_idx, result) in read_ahead.results.try_iter() {
+            // The file may have already been read on demand.
+            if read_ahead.requested.contains(&file_idx)
+                && self.unloaded_files.contains_key(&file_idx)
+            {
+                self.read_files.insert(file_idx, result);
+            }
+        }
+    }
+
+    /// Move the read-ahead window to start at `start`, forgetting the files
+    /// which were read before it and asking the background thread to read the
+    /// files in it.
+    fn read_ahead_from(&mut self, start: usize) {
+        let read_ahead = match &mut self.read_ahead {
+            Some(read_ahead) => read_ahead,
+            None => return,
+        };
+        let window = start..start + READ_AHEAD_LEN;
+        read_ahead.window.store(start, Ordering::Relaxed);
+        self.read_files
+            .retain(|file_idx, _| window.contains(file_idx));
+        for file_idx in window.clone() {
+            if read_ahead.requested.contains(&file_idx) {
+                continue;
+            }
+            if let Some(unloaded_file) = self.unloaded_files.get(&file_idx) {
+                let request = (
+                    file_idx,
+                    unloaded_file.left_path.clone(),
+                    unloaded_file.right_path.clone(),
+                );
+                if read_ahead.requests.send(request).is_err() {
+                    // The thread can only stop if the loader is dropped, but
+                    // the files can still be read on demand.
+                    self.read_ahead = None;
+                    return;
+                }
+            }
+        }
+        read_ahead.requested = window;
+    }
+
+    /// Load the file at `file_idx`, if it's a placeholder which hasn't been
+    /// loaded yet. Otherwise, returns `None`.
+    pub fn load_file(&mut self, file_idx: usize) -> Result<Option<File<'static>>> {
+        self.receive_read_files();
+        let file = self.load_file_now(file_idx)?;
+        if file.is_some() {
+            self.read_ahead_from(file_idx + 1);
+        }
+        Ok(file)
+    }
+
+    /// Load the file at `file_idx` without reading any other files ahead.
+    fn load_file_now(&mut self, file_idx: usize) -> Result<Option<File<'static>>> {
+        let UnloadedFile {
+            left_path,
+            left_display_path,
+            right_path,
+            right_display_path,
+        } = match self.unloaded_files.remove(&file_idx) {
+            Some(unloaded_file) => unloaded_file,
+            None => return Ok(None),
+        };
+        let (left_info, right_info) = match self.read_files.remove(&file_idx) {
+            Some(result) => result?,
+            None => read_file_infos(self.filesystem.as_ref(), &left_path, &right_path)?,
+        };
+        Ok(Some(create_file_from_infos(
+            left_info,
+            left_display_path,
+            right_info,
+            right_display_path,
+            &self.diff_options,
+            &mut self.encodings,
+        )))
+    }
+
+    /// The encodings of the files loaded so far which aren't encoded as UTF-8,
+    /// by path. See [`crate::DiffContext::encodings`].
+    pub fn encodings(&self) -> &BTreeMap<PathBuf, TextEncoding> {
+        &self.encodings
+    }
+
+    /// Diff the contents of `file` in full, if its changes are only
+    /// summarized. Otherwise, returns `None`.
+    pub fn load_full_diff(&self, file: &File) -> Option<File<'static>> {
+        create_full_diff_file(file, &self.diff_options)
+    }
+
+    /// Replace each of the placeholders in `files` which hasn't been loaded yet
+    /// with its full contents.
+    pub fn load_all(&mut self, files: &mut [File<'static>]) -> Result<()> {
+        // Use the files which were already read ahead, and stop the
+        // background thread, since the rest of the files are read here.
+        self.receive_read_files();
+        self.read_ahead = None;
+
+        let mut file_idxs: Vec<_> = self.unloaded_files.keys().copied().collect();
+        file_idxs.sort_unstable();
+        for file_idx in file_idxs {
+            if let Some(file) = self.load_file_now(file_idx)? {
+                files[file_idx] = file;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::time::{Duration, Instant};
+
+    use clap::Parser;
+    use scm_record::FileMode;
+
+    use super::*;
+    use crate::testing::{file_info, TestFilesystem};
+    use crate::Opts;
+
+    #[test]
+    fn test_read_ahead_window() -> Result<()> {
+        let num_files = 100;
+        let filesystem = TestFilesystem::new(
+            (0..num_files)
+                .map(|i| (PathBuf::from(format!("right/{i}")), file_info("foo\n")))
+                .collect(),
+        );
+        let unloaded_files = (0..num_files)
+            .map(|i| {
+                let path = PathBuf::from(i.to_string());
+                (
+                    i,
+                    UnloadedFile {
+                        left_path: Path::new("left").join(&path),
+                        left_display_path: path.clone(),
+                        right_path: Path::new("right").join(&path),
+                        right_display_path: path,
+                    },
+                )
+            })
+            .collect();
+        let opts = Opts::try_parse_from(["scm-diff-editor", "left", "right"]).unwrap();
+        let mut loader = FileLoader::new(
+            Arc::new(filesystem),
+            DiffOptions::from_opts(&opts),
+            unloaded_files,
+        );
+
+        // Only the files just after the loaded file are read ahead.
+        assert!(loader.load_file(0)?.is_some());
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while loader.read_files.len() < READ_AHEAD_LEN && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+            loader.receive_read_files();
+        }
+        let mut read_file_idxs: Vec<_> = loader.read_files.keys().copied().collect();
+        read_file_idxs.sort_unstable();
+        assert_eq!(read_file_idxs, (1..READ_AHEAD_LEN + 1).collect::<Vec<_>>());
+
+        // After jumping ahead, the files which were read ahead are forgotten.
+        assert!(loader.load_file(60)?.is_some());
+        assert!(loader.read_files.is_empty());
+        assert_eq!(
+            loader
+                .read_ahead
+                .as_ref()
+                .map(|read_ahead| read_ahead.requested.clone()),
+            Some(61..61 + READ_AHEAD_LEN)
+        );
+
+        // Files which were read ahead and forgotten are read again on demand.
+        assert!(loader.load_file(1)?.is_some());
+        assert_eq!(loader.load_file(1)?, None);
+
+        let mut files: Vec<_> = (0..num_files)
+            .map(|i| File {
+                old_path: None,
+                path: Cow::Owned(PathBuf::from(i.to_string())),
+                file_mode: FileMode::Absent,
+                sections: Vec::new(),
+            })
+            .collect();
+        loader.load_all(&mut files)?;
+        assert!(loader.read_ahead.is_none());
+        assert!(loader.unloaded_files.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+//! Detection of renamed and copied files when comparing directories.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use scm_record::{File, FileMode, Section};
+
+use crate::diff::DiffOptions;
+use crate::render::create_file_from_infos;
+use crate::{FileContents, FileInfo, Filesystem, Result, TextEncoding};
+
+/// The similarity percentage used when `--find-renames` or `--find-copies` is
+/// passed without an explicit value.
+pub const DEFAULT_SIMILARITY_THRESHOLD: u8 = 50;
+
+/// Options controlling rename and copy detection.
+#[derive(Clone, Copy, Debug)]
+pub struct RenameOptions {
+    /// The minimum similarity percentage for a deleted file and an added file
+    /// to be considered a rename.
+    pub rename_threshold: u8,
+
+    /// The minimum similarity percentage for an added file to be considered a
+    /// copy of an existing file, or `None` to not detect copies.
+    pub copy_threshold: Option<u8>,
+}
+
+/// Create the [`File`]s to diff for each of `display_paths` between the `left`
+/// and `right` directories, pairing up renamed and copied files.
+///
+/// A renamed file is rendered as a single [`File`] with `old_path` set and a
+/// [`Section::Rename`] which can be selected independently of the content
+/// changes. A copied file is rendered as the creation of a new file, diffed
+/// against its source.
+pub fn create_files_with_renames(
+    filesystem: &dyn Filesystem,
+    left: &Path,
+    right: &Path,
+    display_paths: BTreeSet<PathBuf>,
+    options: RenameOptions,
+    diff_options: &DiffOptions,
+    encodings: &mut BTreeMap<PathBuf, TextEncoding>,
+) -> Result<Vec<File<'static>>> {
+    let RenameOptions {
+        rename_threshold,
+        copy_threshold,
+    } = options;
+
+    let mut infos = BTreeMap::new();
+    for display_path in display_paths {
+        let left_info = filesystem.read_file_info(&left.join(&display_path))?;
+        let right_info = filesystem.read_file_info(&right.join(&display_path))?;
+        infos.insert(display_path, (left_info, right_info));
+    }
+
+    let deleted_paths: Vec<&PathBuf> = infos
+        .iter()
+        .filter(|(_, (left_info, right_info))| is_present(left_info) && !is_present(right_info))
+        .map(|(path, _)| path)
+        .collect();
+    let added_paths: Vec<&PathBuf> = infos
+        .iter()
+        .filter(|(_, (left_info, right_info))| !is_present(left_info) && is_present(right_info))
+        .map(|(path, _)| path)
+        .collect();
+
+    // Pair up renames greedily, starting with the most similar pairs. Ties are
+    // broken by path so that the result is deterministic.
+    let mut rename_candidates = Vec::new();
+    for old_path in &deleted_paths {
+        let (old_info, _) = &infos[*old_path];
+        for new_path in &added_paths {
+            let (_, new_info) = &infos[*new_path];
+            if let Some(score) = similarity(&old_info.contents, &new_info.contents) {
+                if score >= rename_threshold {
+                    rename_candidates.push((score, *old_path, *new_path));
+                }
+            }
+        }
+    }
+    rename_candidates.sort_by(
+        |(lhs_score, lhs_old, lhs_new), (rhs_score, rhs_old, rhs_new)| {
+            rhs_score
+                .cmp(lhs_score)
+                .then_with(|| lhs_old.cmp(rhs_old))
+                .then_with(|| lhs_new.cmp(rhs_new))
+        },
+    );
+    let mut renamed_from: HashMap<&PathBuf, &PathBuf> = HashMap::new();
+    let mut renamed_to: HashMap<&PathBuf, &PathBuf> = HashMap::new();
+    for (_score, old_path, new_path) in rename_candidates {
+        if renamed_from.contains_key(new_path) || renamed_to.contains_key(old_path) {
+            continue;
+        }
+        renamed_from.insert(new_path, old_path);
+        renamed_to.insert(old_path, new_path);
+    }
+
+    // Any remaining added files may have been copied from any file which
+    // existed in the left directory. Only text files are considered, since a
+    // copied binary file would be indistinguishable from a newly-added one in
+    // the selected contents.
+    let mut copied_from: HashMap<&PathBuf, &PathBuf> = HashMap::new();
+    if let Some(copy_threshold) = copy_threshold {
+        for new_path in &added_paths {
+            if renamed_from.contains_key(*new_path) {
+                continue;
+            }
+            let (_, new_info) = &infos[*new_path];
+            if !matches!(new_info.contents, FileContents::Text { .. }) {
+                continue;
+            }
+            let mut best: Option<(u8, &PathBuf)> = None;
+            for (old_path, (old_info, _)) in &infos {
+                if !is_present(old_info) {
+                    continue;
+                }
+                let score = match similarity(&old_info.contents, &new_info.contents) {
+                    Some(score) if score >= copy_threshold => score,
+                    Some(_) | None => continue,
+                };
+                // `infos` is iterated in path order, so only replace the best
+                // candidate with a strictly better one.
+                if best.is_none_or(|(best_score, _)| score > best_score) {
+                    best = Some((score, old_path));
+                }
+            }
+            if let Some((_score, old_path)) = best {
+                copied_from.insert(*new_path, old_path);
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    for (display_path, (left_info, right_info)) in &infos {
+        if renamed_to.contains_key(display_path) {
+            // Rendered as part of the file that it was renamed to.
+            continue;
+        }
+
+        if let Some(old_path) = renamed_from.get(display_path) {
+            let (old_info, _) = &infos[*old_path];
+            let mut file = create_file_from_infos(
+                old_info.clone(),
+                (*old_path).clone(),
+                right_info.clone(),
+                display_path.clone(),
+                diff_options,
+                encodings,
+            );
+            file.sections
+                .insert(0, Section::Rename { is_checked: false });
+            files.push(file);
+        } else if let Some(old_path) = copied_from.get(display_path) {
+            let (old_info, _) = &infos[*old_path];
+            let mut file = create_file_from_infos(
+                old_info.clone(),
+                (*old_path).clone(),
+                right_info.clone(),
+                display_path.clone(),
+                diff_options,
+                encodings,
+            );
+            // The copy is a new file, so it's created with the new mode
+            // regardless of the source file's mode.
+            file.file_mode = FileMode::Absent;
+            file.sections
+                .retain(|section| !matches!(section, Section::FileMode { .. }));
+            file.sections.insert(
+                0,
+                Section::FileMode {
+                    is_checked: false,
+                    mode: right_info.file_mode,
+                },
+            );
+            files.push(file);
+        } else {
+            files.push(create_file_from_infos(
+                left_info.clone(),
+                display_path.clone(),
+                right_info.clone(),
+                display_path.clone(),
+                diff_options,
+                encodings,
+            ));
+        }
+    }
+    Ok(files)
+}
+
+fn is_present(file_info: &FileInfo) -> bool {
+    match file_info.contents {
+        FileContents::Absent => false,
+        FileContents::Text { .. }
+        | FileContents::Binary { .. }
+        | FileContents::Submodule { .. } => true,
+    }
+}
+
+/// Calculate how similar two files are, as a percentage, or `None` if they
+/// should never be paired up.
+///
+/// Text files are compared by the number of bytes in the lines that they have
+/// in common. Binary files can't be meaningfully diffed, so they're only paired
+/// up if they're identical. Empty files and submodules are never paired up,
+/// since there's nothing to identify them by.
+fn similarity(old_contents: &FileContents, new_contents: &FileContents) -> Option<u8> {
+    match (old_contents, new_contents) {
+        (
+            FileContents::Text {
+                contents: old_contents,
+                hash: _,
+                num_bytes: _,
+                encoding: _,
+            },
+            FileContents::Text {
+                contents: new_contents,
+                hash: _,
+                num_bytes: _,
+                encoding: _,
+            },
+        ) => {
+            let max_len = old_contents.len().max(new_contents.len());
+            if max_len == 0 {
+                return None;
+            }
+            if old_contents == new_contents {
+                return Some(100);
+            }
+
+            let mut old_lines: HashMap<&str, usize> = HashMap::new();
+            for line in old_contents.split_inclusive('\n') {
+                *old_lines.entry(line).or_default() += 1;
+            }
+            let mut common_len = 0;
+            for line in new_contents.split_inclusive('\n') {
+                if let Some(count) = old_lines.get_mut(line) {
+                    if *count > 0 {
+                        *count -= 1;
+                        common_len += line.len();
+                    }
+                }
+            }
+            let score = common_len * 100 / max_len;
+            Some(u8::try_from(score).unwrap_or(100))
+        }
+
+        (
+            FileContents::Binary {
+                hash: old_hash,
+                num_bytes: old_num_bytes,
+            },
+            FileContents::Binary {
+                hash: new_hash,
+                num_bytes: new_num_bytes,
+            },
+        ) => {
+            if *old_num_bytes > 0 && (old_hash, old_num_bytes) == (new_hash, new_num_bytes) {
+                Some(100)
+            } else {
+                None
+            }
+        }
+
+        (
+            FileContents::Absent
+            | FileContents::Text { .. }
+            | FileContents::Binary { .. }
+            | FileContents::Submodule { .. },
+            _,
+        ) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(contents: &str) -> FileContents {
+        FileContents::Text {
+            contents: contents.to_owned(),
+            hash: "abc123".to_owned(),
+            num_bytes: contents.len().try_into().unwrap(),
+            encoding: TextEncoding::UTF_8,
+        }
+    }
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(
+            similarity(&text("foo\nbar\n"), &text("foo\nbar\n")),
+            Some(100)
+        );
+        assert_eq!(
+            similarity(&text("foo\nbar\n"), &text("foo\nbaz\n")),
+            Some(50)
+        );
+        assert_eq!(similarity(&text("foo\n"), &text("foo\nfoo\n")), Some(50));
+        assert_eq!(similarity(&text("foo\n"), &text("bar\n")), Some(0));
+        assert_eq!(similarity(&text(""), &text("")), None);
+        assert_eq!(
+            similarity(
+                &FileContents::Submodule {
+                    commit_id: "abc123".to_owned()
+                },
+                &FileContents::Submodule {
+                    commit_id: "abc123".to_owned()
+                },
+            ),
+            None
+        );
+    }
+}
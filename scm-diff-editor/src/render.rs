@@ -1,11 +1,14 @@
 use std::borrow::Cow;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 use scm_record::helpers::make_binary_description;
 use scm_record::{ChangeType, File, Section, SectionChangedLine};
 use tracing::warn;
 
-use super::{Error, FileContents, FileInfo, Filesystem};
+use super::diff::{create_diff, DiffOptions};
+use super::summary::summarize_changes;
+use super::{Error, FileContents, FileInfo, Filesystem, TextEncoding};
 
 fn make_section_changed_lines(
     contents: &str,
@@ -21,23 +24,125 @@ fn make_section_changed_lines(
         .collect()
 }
 
+fn make_contents_description(contents: &FileContents) -> Option<Cow<'static, str>> {
+    match contents {
+        FileContents::Absent => None,
+        FileContents::Text {
+            contents: _,
+            hash,
+            num_bytes,
+            encoding: _,
+        }
+        | FileContents::Binary { hash, num_bytes } => {
+            Some(Cow::Owned(make_binary_description(hash, *num_bytes)))
+        }
+        FileContents::Submodule { commit_id } => {
+            Some(Cow::Owned(format!("submodule commit {commit_id}")))
+        }
+    }
+}
+
 pub fn create_file(
     filesystem: &dyn Filesystem,
     left_path: PathBuf,
     left_display_path: PathBuf,
     right_path: PathBuf,
     right_display_path: PathBuf,
+    diff_options: &DiffOptions,
+    encodings: &mut BTreeMap<PathBuf, TextEncoding>,
+) -> Result<File<'static>, Error> {
+    let left_info = filesystem.read_file_info(&left_path)?;
+    let right_info = filesystem.read_file_info(&right_path)?;
+    Ok(create_file_from_infos(
+        left_info,
+        left_display_path,
+        right_info,
+        right_display_path,
+        diff_options,
+        encodings,
+    ))
+}
+
+/// Create a placeholder for the file that [`create_file`] would create, with
+/// only its paths and any change to its file mode, so that its contents don't
+/// need to be read yet.
+pub fn create_placeholder_file(
+    filesystem: &dyn Filesystem,
+    left_path: &Path,
+    left_display_path: PathBuf,
+    right_path: &Path,
+    right_display_path: PathBuf,
 ) -> Result<File<'static>, Error> {
+    let left_file_mode = filesystem.read_file_mode(left_path)?;
+    let right_file_mode = filesystem.read_file_mode(right_path)?;
+    let mut sections = Vec::new();
+    if left_file_mode != right_file_mode {
+        sections.push(Section::FileMode {
+            is_checked: false,
+            mode: right_file_mode,
+        });
+    }
+    Ok(File {
+        old_path: if left_display_path != right_display_path {
+            Some(Cow::Owned(left_display_path))
+        } else {
+            None
+        },
+        path: Cow::Owned(right_display_path),
+        file_mode: left_file_mode,
+        sections,
+    })
+}
+
+/// Like [`create_file`], but for files whose [`FileInfo`]s have already been
+/// read.
+///
+/// If the file isn't encoded as UTF-8, its encoding is recorded in `encodings`
+/// under its path, so that its selected contents can be written in the same
+/// encoding.
+pub fn create_file_from_infos(
+    left_info: FileInfo,
+    left_display_path: PathBuf,
+    right_info: FileInfo,
+    right_display_path: PathBuf,
+    diff_options: &DiffOptions,
+    encodings: &mut BTreeMap<PathBuf, TextEncoding>,
+) -> File<'static> {
     let FileInfo {
         file_mode: left_file_mode,
         contents: left_contents,
-    } = filesystem.read_file_info(&left_path)?;
+    } = left_info;
     let FileInfo {
         file_mode: right_file_mode,
         contents: right_contents,
-    } = filesystem.read_file_info(&right_path)?;
+    } = right_info;
     let mut sections = Vec::new();
 
+    let encoding = match (&left_contents, &right_contents) {
+        (
+            _,
+            FileContents::Text {
+                contents: _,
+                hash: _,
+                num_bytes: _,
+                encoding,
+            },
+        )
+        | (
+            FileContents::Text {
+                contents: _,
+                hash: _,
+                num_bytes: _,
+                encoding,
+            },
+            _,
+        ) => *encoding,
+        _ => TextEncoding::UTF_8,
+    };
+    if encoding != TextEncoding::UTF_8 {
+        encodings.insert(right_display_path.clone(), encoding);
+    }
+
     if left_file_mode != right_file_mode {
         sections.push(Section::FileMode {
             is_checked: false,
@@ -53,10 +158,19 @@ pub fn create_file(
                 contents,
                 hash: _,
                 num_bytes: _,
+                encoding: _,
             },
-        ) => sections.push(Section::Changed {
-            lines: make_section_changed_lines(&contents, ChangeType::Added),
-        }),
+        ) => match summarize_changes(&right_display_path, "", &contents, diff_options) {
+            Some(description) => sections.push(Section::Summary {
+                is_checked: false,
+                description: Cow::Owned(description),
+                old_contents: Cow::Borrowed(""),
+                new_contents: Cow::Owned(contents),
+            }),
+            None => sections.push(Section::Changed {
+                lines: make_section_changed_lines(&contents, ChangeType::Added),
+            }),
+        },
 
         (FileContents::Absent, FileContents::Binary { hash, num_bytes }) => {
             sections.push(Section::Binary {
@@ -71,32 +185,61 @@ pub fn create_file(
                 contents,
                 hash: _,
                 num_bytes: _,
+                encoding: _,
             },
             FileContents::Absent,
-        ) => sections.push(Section::Changed {
-            lines: make_section_changed_lines(&contents, ChangeType::Removed),
-        }),
+        ) => match summarize_changes(&left_display_path, &contents, "", diff_options) {
+            Some(description) => sections.push(Section::Summary {
+                is_checked: false,
+                description: Cow::Owned(description),
+                old_contents: Cow::Owned(contents),
+                new_contents: Cow::Borrowed(""),
+            }),
+            None => sections.push(Section::Changed {
+                lines: make_section_changed_lines(&contents, ChangeType::Removed),
+            }),
+        },
 
         (
             FileContents::Text {
                 contents: old_contents,
                 hash: _,
                 num_bytes: _,
+                encoding: old_encoding,
             },
             FileContents::Text {
                 contents: new_contents,
                 hash: _,
                 num_bytes: _,
+                encoding: new_encoding,
             },
-        ) => {
-            sections.extend(create_diff(&old_contents, &new_contents));
+        ) if old_encoding == new_encoding => {
+            match summarize_changes(
+                &right_display_path,
+                &old_contents,
+                &new_contents,
+                diff_options,
+            ) {
+                Some(description) => sections.push(Section::Summary {
+                    is_checked: false,
+                    description: Cow::Owned(description),
+                    old_contents: Cow::Owned(old_contents),
+                    new_contents: Cow::Owned(new_contents),
+                }),
+                None => {
+                    sections.extend(create_diff(&old_contents, &new_contents, diff_options));
+                }
+            }
         }
 
+        // If the encoding of the file changed, then selecting only some of its
+        // lines would mix the encodings, so treat it like a binary change.
         (
             FileContents::Text {
                 contents: _,
                 hash: old_hash,
                 num_bytes: old_num_bytes,
+                encoding: _,
             }
             | FileContents::Binary {
                 hash: old_hash,
@@ -106,22 +249,29 @@ pub fn create_file(
                 contents: _,
                 hash: new_hash,
                 num_bytes: new_num_bytes,
+                encoding: _,
             }
             | FileContents::Binary {
                 hash: new_hash,
                 num_bytes: new_num_bytes,
             },
-        ) => sections.push(Section::Binary {
-            is_checked: false,
-            old_description: Some(Cow::Owned(make_binary_description(
-                &old_hash,
-                old_num_bytes,
-            ))),
-            new_description: Some(Cow::Owned(make_binary_description(
-                &new_hash,
-                new_num_bytes,
-            ))),
-        }),
+        ) => {
+            // Identical contents can show up if the file was only renamed or
+            // had its mode changed.
+            if (&old_hash, old_num_bytes) != (&new_hash, new_num_bytes) {
+                sections.push(Section::Binary {
+                    is_checked: false,
+                    old_description: Some(Cow::Owned(make_binary_description(
+                        &old_hash,
+                        old_num_bytes,
+                    ))),
+                    new_description: Some(Cow::Owned(make_binary_description(
+                        &new_hash,
+                        new_num_bytes,
+                    ))),
+                })
+            }
+        }
 
         (FileContents::Binary { hash, num_bytes }, FileContents::Absent) => {
             sections.push(Section::Binary {
@@ -130,9 +280,57 @@ pub fn create_file(
                 new_description: None,
             })
         }
+
+        (FileContents::Absent, FileContents::Submodule { commit_id }) => {
+            sections.push(Section::Submodule {
+                is_checked: false,
+                old_commit_id: None,
+                new_commit_id: Some(Cow::Owned(commit_id)),
+            })
+        }
+
+        (FileContents::Submodule { commit_id }, FileContents::Absent) => {
+            sections.push(Section::Submodule {
+                is_checked: false,
+                old_commit_id: Some(Cow::Owned(commit_id)),
+                new_commit_id: None,
+            })
+        }
+
+        (
+            FileContents::Submodule {
+                commit_id: old_commit_id,
+            },
+            FileContents::Submodule {
+                commit_id: new_commit_id,
+            },
+        ) => {
+            if old_commit_id != new_commit_id {
+                sections.push(Section::Submodule {
+                    is_checked: false,
+                    old_commit_id: Some(Cow::Owned(old_commit_id)),
+                    new_commit_id: Some(Cow::Owned(new_commit_id)),
+                })
+            }
+        }
+
+        // A submodule was replaced by a regular file or vice-versa. There's no
+        // meaningful diff between the two, so treat it like a binary change.
+        (
+            old_contents @ FileContents::Submodule { .. },
+            new_contents @ (FileContents::Text { .. } | FileContents::Binary { .. }),
+        )
+        | (
+            old_contents @ (FileContents::Text { .. } | FileContents::Binary { .. }),
+            new_contents @ FileContents::Submodule { .. },
+        ) => sections.push(Section::Binary {
+            is_checked: false,
+            old_description: make_contents_description(&old_contents),
+            new_description: make_contents_description(&new_contents),
+        }),
     }
 
-    Ok(File {
+    File {
         old_path: if left_display_path != right_display_path {
             Some(Cow::Owned(left_display_path))
         } else {
@@ -141,15 +339,122 @@ pub fn create_file(
         path: Cow::Owned(right_display_path),
         file_mode: left_file_mode,
         sections,
+    }
+}
+
+fn section_to_owned(section: &Section) -> Section<'static> {
+    let to_owned = |s: &Cow<str>| Cow::Owned(s.to_string());
+    match section {
+        Section::Unchanged { lines } => Section::Unchanged {
+            lines: lines.iter().map(to_owned).collect(),
+        },
+        Section::Changed { lines } => Section::Changed {
+            lines: lines
+                .iter()
+                .map(
+                    |SectionChangedLine {
+                         is_checked,
+                         change_type,
+                         line,
+                     }| SectionChangedLine {
+                        is_checked: *is_checked,
+                        change_type: *change_type,
+                        line: to_owned(line),
+                    },
+                )
+                .collect(),
+        },
+        Section::FileMode { is_checked, mode } => Section::FileMode {
+            is_checked: *is_checked,
+            mode: *mode,
+        },
+        Section::Binary {
+            is_checked,
+            old_description,
+            new_description,
+        } => Section::Binary {
+            is_checked: *is_checked,
+            old_description: old_description.as_ref().map(to_owned),
+            new_description: new_description.as_ref().map(to_owned),
+        },
+        Section::Submodule {
+            is_checked,
+            old_commit_id,
+            new_commit_id,
+        } => Section::Submodule {
+            is_checked: *is_checked,
+            old_commit_id: old_commit_id.as_ref().map(to_owned),
+            new_commit_id: new_commit_id.as_ref().map(to_owned),
+        },
+        Section::Summary {
+            is_checked,
+            description,
+            old_contents,
+            new_contents,
+        } => Section::Summary {
+            is_checked: *is_checked,
+            description: to_owned(description),
+            old_contents: to_owned(old_contents),
+            new_contents: to_owned(new_contents),
+        },
+        Section::Rename { is_checked } => Section::Rename {
+            is_checked: *is_checked,
+        },
+    }
+}
+
+/// Replace the [`Section::Summary`] in `file` with the full diff of its
+/// contents, whose changes are selected if the summarized changes were.
+/// Returns `None` if the changes to `file` aren't summarized.
+pub fn create_full_diff_file(file: &File, diff_options: &DiffOptions) -> Option<File<'static>> {
+    let File {
+        old_path,
+        path,
+        file_mode,
+        sections,
+    } = file;
+    let mut is_summarized = false;
+    let mut full_sections = Vec::new();
+    for section in sections {
+        match section {
+            Section::Summary {
+                is_checked,
+                description: _,
+                old_contents,
+                new_contents,
+            } => {
+                is_summarized = true;
+                for mut section in create_diff(old_contents, new_contents, diff_options) {
+                    section.set_checked(*is_checked);
+                    full_sections.push(section);
+                }
+            }
+            section => full_sections.push(section_to_owned(section)),
+        }
+    }
+    if !is_summarized {
+        return None;
+    }
+    Some(File {
+        old_path: old_path
+            .as_ref()
+            .map(|old_path| Cow::Owned(old_path.to_path_buf())),
+        path: Cow::Owned(path.to_path_buf()),
+        file_mode: *file_mode,
+        sections: full_sections,
     })
 }
 
+/// Create the file to resolve the conflicts between `left_path` and
+/// `right_path` in. The merged contents are written in the encoding of
+/// `left_path`, which is recorded in `encodings` if it isn't UTF-8.
 pub fn create_merge_file(
     filesystem: &dyn Filesystem,
     base_path: PathBuf,
     left_path: PathBuf,
     right_path: PathBuf,
     output_path: PathBuf,
+    encodings: &mut BTreeMap<PathBuf, TextEncoding>,
 ) -> Result<File<'static>, Error> {
     let FileInfo {
         file_mode: left_file_mode,
@@ -184,23 +489,40 @@ pub fn create_merge_file(
             (_, _, FileContents::Binary { .. }) => {
                 return Err(Error::BinaryMergeFile { path: right_path })
             }
+            (FileContents::Submodule { .. }, _, _) => {
+                return Err(Error::BinaryMergeFile { path: base_path })
+            }
+            (_, FileContents::Submodule { .. }, _) => {
+                return Err(Error::BinaryMergeFile { path: left_path })
+            }
+            (_, _, FileContents::Submodule { .. }) => {
+                return Err(Error::BinaryMergeFile { path: right_path })
+            }
             (
                 FileContents::Text {
                     contents: base_contents,
                     hash: _,
                     num_bytes: _,
+                    encoding: _,
                 },
                 FileContents::Text {
                     contents: left_contents,
                     hash: _,
                     num_bytes: _,
+                    encoding,
                 },
                 FileContents::Text {
                     contents: right_contents,
                     hash: _,
                     num_bytes: _,
+                    encoding: _,
                 },
-            ) => (base_contents, left_contents, right_contents),
+            ) => {
+                if encoding != TextEncoding::UTF_8 {
+                    encodings.insert(output_path.clone(), encoding);
+                }
+                (base_contents, left_contents, right_contents)
+            }
         };
 
     let sections = create_merge(&base_contents, &left_contents, &right_contents);
@@ -212,70 +534,6 @@ pub fn create_merge_file(
     })
 }
 
-fn create_diff(old_contents: &str, new_contents: &str) -> Vec<Section<'static>> {
-    let patch = {
-        // Set the context length to the maximum number of lines in either file,
-        // because we will handle abbreviating context ourselves.
-        let max_lines = old_contents
-            .lines()
-            .count()
-            .max(new_contents.lines().count());
-        let mut diff_options = diffy::DiffOptions::new();
-        diff_options.set_context_len(max_lines);
-        diff_options.create_patch(old_contents, new_contents)
-    };
-
-    let mut sections = Vec::new();
-    for hunk in patch.hunks() {
-        sections.extend(hunk.lines().iter().fold(Vec::new(), |mut acc, line| {
-            match line {
-                diffy::Line::Context(line) => match acc.last_mut() {
-                    Some(Section::Unchanged { lines }) => {
-                        lines.push(Cow::Owned((*line).to_owned()));
-                    }
-                    _ => {
-                        acc.push(Section::Unchanged {
-                            lines: vec![Cow::Owned((*line).to_owned())],
-                        });
-                    }
-                },
-                diffy::Line::Delete(line) => {
-                    let line = SectionChangedLine {
-                        is_checked: false,
-                        change_type: ChangeType::Removed,
-                        line: Cow::Owned((*line).to_owned()),
-                    };
-                    match acc.last_mut() {
-                        Some(Section::Changed { lines }) => {
-                            lines.push(line);
-                        }
-                        _ => {
-                            acc.push(Section::Changed { lines: vec![line] });
-                        }
-                    }
-                }
-                diffy::Line::Insert(line) => {
-                    let line = SectionChangedLine {
-                        is_checked: false,
-                        change_type: ChangeType::Added,
-                        line: Cow::Owned((*line).to_owned()),
-                    };
-                    match acc.last_mut() {
-                        Some(Section::Changed { lines }) => {
-                            lines.push(line);
-                        }
-                        _ => {
-                            acc.push(Section::Changed { lines: vec![line] });
-                        }
-                    }
-                }
-            }
-            acc
-        }));
-    }
-    sections
-}
-
 fn make_conflict_markers(base: &str, left: &str, right: &str) -> (String, String, String, String) {
     let all = [base, left, right].concat();
     let left_char = "<";
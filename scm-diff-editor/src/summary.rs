@@ -0,0 +1,206 @@
+//! Summarizing the changes to large and generated files, rather than diffing
+//! them in full.
+
+use std::path::Path;
+
+use crate::diff::DiffOptions;
+
+/// The default value of `--max-file-size`.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1_000_000;
+
+/// The default value of `--max-file-lines`.
+pub const DEFAULT_MAX_FILE_LINES: usize = 20_000;
+
+/// The names of lockfiles and similar files, which are written by tools rather
+/// than by hand.
+const GENERATED_FILE_NAMES: &[&str] = &[
+    "Cargo.lock",
+    "Gemfile.lock",
+    "Pipfile.lock",
+    "composer.lock",
+    "flake.lock",
+    "go.sum",
+    "npm-shrinkwrap.json",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "poetry.lock",
+    "uv.lock",
+    "yarn.lock",
+];
+
+/// The suffixes of the names of minified files.
+const GENERATED_FILE_SUFFIXES: &[&str] = &[".min.css", ".min.js"];
+
+/// Comments which mark a file as generated, such as `// @generated` or Go's
+/// `// Code generated by stringer; DO NOT EDIT.`.
+const GENERATED_FILE_MARKERS: &[&str] = &["@generated", "DO NOT EDIT"];
+
+/// The number of lines at the start of a file which are searched for one of
+/// the [`GENERATED_FILE_MARKERS`].
+const NUM_MARKER_LINES: usize = 5;
+
+/// Whether the file at `path` appears to have been generated by a tool, such as
+/// a lockfile or a file which is marked `@generated` near its top.
+pub fn is_generated_file(path: &Path, contents: &str) -> bool {
+    let file_name = path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .unwrap_or_default();
+    GENERATED_FILE_NAMES.contains(&file_name)
+        || GENERATED_FILE_SUFFIXES
+            .iter()
+            .any(|suffix| file_name.ends_with(suffix))
+        || contents.lines().take(NUM_MARKER_LINES).any(|line| {
+            GENERATED_FILE_MARKERS
+                .iter()
+                .any(|marker| line.contains(marker))
+        })
+}
+
+/// If the changes from `old_contents` to `new_contents` in the file at `path`
+/// should be summarized instead of diffed, according to `options`, returns the
+/// description of the summary, such as `12,345 lines changed in large file`.
+pub fn summarize_changes(
+    path: &Path,
+    old_contents: &str,
+    new_contents: &str,
+    options: &DiffOptions,
+) -> Option<String> {
+    let DiffOptions {
+        algorithm: _,
+        whitespace: _,
+        max_file_size,
+        max_file_lines,
+        summarize_generated,
+    } = options;
+    let old_lines: Vec<&str> = old_contents.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new_contents.split_inclusive('\n').collect();
+
+    let is_large = max_file_size.is_some_and(|max_file_size| {
+        u64::try_from(old_contents.len().max(new_contents.len())).unwrap() > max_file_size
+    }) || max_file_lines
+        .is_some_and(|max_file_lines| old_lines.len().max(new_lines.len()) > max_file_lines);
+    let kind = if is_large {
+        "large file"
+    } else if *summarize_generated
+        && (is_generated_file(path, old_contents) || is_generated_file(path, new_contents))
+    {
+        "generated file"
+    } else {
+        return None;
+    };
+
+    // Only count the lines which differ after skipping the common lines at the
+    // start and end of the file, which is cheap but overestimates the number
+    // of changed lines if there are changes in multiple places.
+    let num_common_prefix_lines = old_lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let num_common_suffix_lines = old_lines[num_common_prefix_lines..]
+        .iter()
+        .rev()
+        .zip(new_lines[num_common_prefix_lines..].iter().rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let num_common_lines = num_common_prefix_lines + num_common_suffix_lines;
+    let num_changed_lines =
+        (old_lines.len() - num_common_lines) + (new_lines.len() - num_common_lines);
+    match num_changed_lines {
+        // Identical contents can show up if the file was only renamed or had
+        // its mode changed.
+        0 => None,
+        1 => Some(format!("1 line changed in {kind}")),
+        num_changed_lines => Some(format!(
+            "{} lines changed in {kind}",
+            format_count(num_changed_lines)
+        )),
+    }
+}
+
+/// Format `count` with thousands separators, such as `12,345`.
+fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut result = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            result.push(',');
+        }
+        result.push(digit);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_generated_file() {
+        assert!(is_generated_file(Path::new("Cargo.lock"), ""));
+        assert!(is_generated_file(Path::new("web/yarn.lock"), ""));
+        assert!(is_generated_file(Path::new("static/app.min.js"), ""));
+        assert!(is_generated_file(
+            Path::new("foo.rs"),
+            "// @generated by build.rs\nfn foo() {}\n"
+        ));
+        assert!(is_generated_file(
+            Path::new("foo_string.go"),
+            "// Code generated by \"stringer -type=Foo\"; DO NOT EDIT.\n"
+        ));
+
+        assert!(!is_generated_file(Path::new("Cargo.toml"), ""));
+        assert!(!is_generated_file(Path::new("app.js"), "let x = 1;\n"));
+        assert!(!is_generated_file(
+            Path::new("foo.rs"),
+            "a\nb\nc\nd\ne\n// @generated is only checked near the top\n"
+        ));
+    }
+
+    #[test]
+    fn test_summarize_changes() {
+        let options = DiffOptions {
+            max_file_size: Some(20),
+            max_file_lines: Some(3),
+            summarize_generated: true,
+            ..Default::default()
+        };
+        let summarize = |path: &str, old_contents: &str, new_contents: &str| {
+            summarize_changes(Path::new(path), old_contents, new_contents, &options)
+        };
+
+        insta::assert_debug_snapshot!(summarize("foo", "a\nb\n", "a\nc\n"), @"None");
+        insta::assert_debug_snapshot!(summarize("foo", "a\nb\nc\nd\n", "a\nB\nc\nd\n"), @r#"
+        Some(
+            "2 lines changed in large file",
+        )
+        "#);
+        insta::assert_debug_snapshot!(summarize("foo", "", "0123456789\n0123456789\n"), @r#"
+        Some(
+            "2 lines changed in large file",
+        )
+        "#);
+        insta::assert_debug_snapshot!(summarize("Cargo.lock", "a\n", "a\nb\n"), @r#"
+        Some(
+            "1 line changed in generated file",
+        )
+        "#);
+        insta::assert_debug_snapshot!(summarize("Cargo.lock", "a\nb\n", "a\nb\n"), @"None");
+
+        let options = DiffOptions::default();
+        insta::assert_debug_snapshot!(
+            summarize_changes(Path::new("Cargo.lock"), "a\n", "b\n", &options),
+            @"None"
+        );
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1000), "1,000");
+        assert_eq!(format_count(12345), "12,345");
+        assert_eq!(format_count(1234567), "1,234,567");
+    }
+}
@@ -5,7 +5,7 @@ use std::path::{Path, PathBuf};
 
 use scm_record::{File, FileMode};
 
-use crate::{Error, FileContents, FileInfo, Filesystem, Result};
+use crate::{DirDiffFilter, Error, FileContents, FileInfo, Filesystem, Result, TextEncoding};
 
 /// In-memory filesystem for testing purposes.
 #[derive(Debug)]
@@ -36,7 +36,12 @@ impl TestFilesystem {
 }
 
 impl Filesystem for TestFilesystem {
-    fn read_dir_diff_paths(&self, left: &Path, right: &Path) -> Result<BTreeSet<PathBuf>> {
+    fn read_dir_diff_paths(
+        &self,
+        left: &Path,
+        right: &Path,
+        filter: &DirDiffFilter,
+    ) -> Result<BTreeSet<PathBuf>> {
         let left_files = self
             .files
             .keys()
@@ -47,6 +52,14 @@ impl Filesystem for TestFilesystem {
             .filter_map(|path| path.strip_prefix(right).ok());
         Ok(left_files
             .chain(right_files)
+            .filter(|path| {
+                let is_dir_excluded = path
+                    .ancestors()
+                    .skip(1)
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .any(|dir| filter.is_excluded(dir, true));
+                !is_dir_excluded && !filter.is_excluded(path, false)
+            })
             .map(|path| path.to_path_buf())
             .collect())
     }
@@ -67,9 +80,17 @@ impl Filesystem for TestFilesystem {
         }
     }
 
-    fn write_file(&mut self, path: &Path, contents: &str) -> Result<()> {
+    fn write_file(&mut self, path: &Path, contents: &str, encoding: TextEncoding) -> Result<()> {
         self.assert_parent_dir_exists(path);
-        self.files.insert(path.to_owned(), file_info(contents));
+        self.files
+            .insert(path.to_owned(), encoded_file_info(contents, encoding));
+        Ok(())
+    }
+
+    fn write_submodule(&mut self, path: &Path, commit_id: &str) -> Result<()> {
+        self.assert_parent_dir_exists(path);
+        self.files
+            .insert(path.to_owned(), submodule_info(commit_id));
         Ok(())
     }
 
@@ -94,14 +115,31 @@ impl Filesystem for TestFilesystem {
 /// Helper function to create a `FileInfo` object containing the provided file
 /// contents and a default hash and file mode.
 pub fn file_info(contents: impl Into<String>) -> FileInfo {
+    encoded_file_info(contents, TextEncoding::UTF_8)
+}
+
+/// Like [`file_info`], but for a file which is stored in `encoding`.
+pub fn encoded_file_info(contents: impl Into<String>, encoding: TextEncoding) -> FileInfo {
     let contents = contents.into();
-    let num_bytes = contents.len().try_into().unwrap();
+    let num_bytes = encoding.encode(&contents).len().try_into().unwrap();
     FileInfo {
         file_mode: FileMode::Unix(0o100644),
         contents: FileContents::Text {
             contents,
             hash: "abc123".to_string(),
             num_bytes,
+            encoding,
+        },
+    }
+}
+
+/// Helper function to create a `FileInfo` object for a submodule pointing to
+/// the provided commit.
+pub fn submodule_info(commit_id: impl Into<String>) -> FileInfo {
+    FileInfo {
+        file_mode: FileMode::GITLINK,
+        contents: FileContents::Submodule {
+            commit_id: commit_id.into(),
         },
     }
 }
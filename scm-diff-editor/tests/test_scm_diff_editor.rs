@@ -1,11 +1,23 @@
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use clap::Parser;
 use insta::assert_debug_snapshot;
 use maplit::btreemap;
 
-use scm_diff_editor::testing::{file_info, select_all, TestFilesystem};
-use scm_diff_editor::{apply_changes, process_opts, DiffContext, Opts, Result};
-use scm_record::{RecordState, Section};
+use scm_diff_editor::diff::{DiffAlgorithm, WhitespaceMode};
+use scm_diff_editor::testing::{
+    encoded_file_info, file_info, select_all, submodule_info, TestFilesystem,
+};
+use scm_diff_editor::{
+    apply_changes, apply_preselect, process_opts, process_opts_lazily, DiffContext, Error,
+    FileLoader, Opts, Result, TextEncoding,
+};
+use scm_record::helpers::TestingInput;
+use scm_record::script::SelectionScript;
+use scm_record::{
+    Event, File, RecordError, RecordInput, RecordState, Recorder, Section, TerminalKind,
+};
 
 #[test]
 fn test_diff() -> Result<()> {
@@ -26,6 +38,7 @@ qux2
     let DiffContext {
         mut files,
         write_root,
+        encodings,
     } = process_opts(
         &filesystem,
         &Opts {
@@ -36,6 +49,19 @@ qux2
             output: None,
             read_only: false,
             dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
         },
     )?;
     assert_debug_snapshot!(files, @r###"
@@ -92,13 +118,14 @@ qux2
     apply_changes(
         &mut filesystem,
         &write_root,
+        &encodings,
         RecordState {
             is_read_only: false,
             commits: Default::default(),
             files,
         },
     )?;
-    insta::assert_debug_snapshot!(filesystem, @r###"
+    insta::assert_debug_snapshot!(filesystem, @r#"
     TestFilesystem {
         files: {
             "left": FileInfo {
@@ -109,6 +136,7 @@ qux2
                     contents: "foo\ncommon1\ncommon2\nbar\n",
                     hash: "abc123",
                     num_bytes: 24,
+                    encoding: UTF-8,
                 },
             },
             "right": FileInfo {
@@ -119,6 +147,7 @@ qux2
                     contents: "qux1\ncommon1\ncommon2\nqux2\n",
                     hash: "abc123",
                     num_bytes: 26,
+                    encoding: UTF-8,
                 },
             },
         },
@@ -126,7 +155,7 @@ qux2
             "",
         },
     }
-    "###);
+    "#);
 
     Ok(())
 }
@@ -147,7 +176,11 @@ common2
 qux2
 "),
     });
-    let DiffContext { files, write_root } = process_opts(
+    let DiffContext {
+        files,
+        write_root,
+        encodings,
+    } = process_opts(
         &filesystem,
         &Opts {
             dir_diff: false,
@@ -157,19 +190,33 @@ qux2
             output: None,
             read_only: false,
             dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
         },
     )?;
 
     apply_changes(
         &mut filesystem,
         &write_root,
+        &encodings,
         RecordState {
             is_read_only: false,
             commits: Default::default(),
             files,
         },
     )?;
-    insta::assert_debug_snapshot!(filesystem, @r###"
+    insta::assert_debug_snapshot!(filesystem, @r#"
     TestFilesystem {
         files: {
             "left": FileInfo {
@@ -180,6 +227,7 @@ qux2
                     contents: "foo\ncommon1\ncommon2\nbar\n",
                     hash: "abc123",
                     num_bytes: 24,
+                    encoding: UTF-8,
                 },
             },
             "right": FileInfo {
@@ -190,6 +238,7 @@ qux2
                     contents: "foo\ncommon1\ncommon2\nbar\n",
                     hash: "abc123",
                     num_bytes: 24,
+                    encoding: UTF-8,
                 },
             },
         },
@@ -197,7 +246,7 @@ qux2
             "",
         },
     }
-    "###);
+    "#);
 
     Ok(())
 }
@@ -210,6 +259,7 @@ fn test_diff_absent_left() -> Result<()> {
     let DiffContext {
         mut files,
         write_root,
+        encodings,
     } = process_opts(
         &filesystem,
         &Opts {
@@ -220,6 +270,19 @@ fn test_diff_absent_left() -> Result<()> {
             output: None,
             read_only: false,
             dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
         },
     )?;
     assert_debug_snapshot!(files, @r###"
@@ -255,13 +318,14 @@ fn test_diff_absent_left() -> Result<()> {
     apply_changes(
         &mut filesystem,
         &write_root,
+        &encodings,
         RecordState {
             is_read_only: false,
             commits: Default::default(),
             files,
         },
     )?;
-    insta::assert_debug_snapshot!(filesystem, @r###"
+    insta::assert_debug_snapshot!(filesystem, @r#"
     TestFilesystem {
         files: {
             "right": FileInfo {
@@ -272,6 +336,7 @@ fn test_diff_absent_left() -> Result<()> {
                     contents: "right\n",
                     hash: "abc123",
                     num_bytes: 6,
+                    encoding: UTF-8,
                 },
             },
         },
@@ -279,7 +344,7 @@ fn test_diff_absent_left() -> Result<()> {
             "",
         },
     }
-    "###);
+    "#);
 
     Ok(())
 }
@@ -292,6 +357,7 @@ fn test_diff_absent_right() -> Result<()> {
     let DiffContext {
         mut files,
         write_root,
+        encodings,
     } = process_opts(
         &filesystem,
         &Opts {
@@ -302,6 +368,19 @@ fn test_diff_absent_right() -> Result<()> {
             output: None,
             read_only: false,
             dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
         },
     )?;
     assert_debug_snapshot!(files, @r###"
@@ -337,13 +416,14 @@ fn test_diff_absent_right() -> Result<()> {
     apply_changes(
         &mut filesystem,
         &write_root,
+        &encodings,
         RecordState {
             is_read_only: false,
             commits: Default::default(),
             files,
         },
     )?;
-    insta::assert_debug_snapshot!(filesystem, @r###"
+    insta::assert_debug_snapshot!(filesystem, @r#"
     TestFilesystem {
         files: {
             "left": FileInfo {
@@ -354,6 +434,7 @@ fn test_diff_absent_right() -> Result<()> {
                     contents: "left\n",
                     hash: "abc123",
                     num_bytes: 5,
+                    encoding: UTF-8,
                 },
             },
         },
@@ -361,7 +442,7 @@ fn test_diff_absent_right() -> Result<()> {
             "",
         },
     }
-    "###);
+    "#);
 
     Ok(())
 }
@@ -382,6 +463,19 @@ fn test_reject_diff_non_files() -> Result<()> {
             output: None,
             read_only: false,
             dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
         },
     );
     insta::assert_debug_snapshot!(result, @r###"
@@ -406,7 +500,11 @@ fn test_diff_files_in_subdirectories() -> Result<()> {
         PathBuf::from("right/foo") => file_info("right contents\n"),
     });
 
-    let DiffContext { files, write_root } = process_opts(
+    let DiffContext {
+        files,
+        write_root,
+        encodings,
+    } = process_opts(
         &filesystem,
         &Opts {
             dir_diff: false,
@@ -416,19 +514,33 @@ fn test_diff_files_in_subdirectories() -> Result<()> {
             output: None,
             read_only: false,
             dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
         },
     )?;
 
     apply_changes(
         &mut filesystem,
         &write_root,
+        &encodings,
         RecordState {
             is_read_only: false,
             commits: Default::default(),
             files,
         },
     )?;
-    assert_debug_snapshot!(filesystem, @r###"
+    assert_debug_snapshot!(filesystem, @r#"
     TestFilesystem {
         files: {
             "left/foo": FileInfo {
@@ -439,6 +551,7 @@ fn test_diff_files_in_subdirectories() -> Result<()> {
                     contents: "left contents\n",
                     hash: "abc123",
                     num_bytes: 14,
+                    encoding: UTF-8,
                 },
             },
             "right/foo": FileInfo {
@@ -449,6 +562,7 @@ fn test_diff_files_in_subdirectories() -> Result<()> {
                     contents: "left contents\n",
                     hash: "abc123",
                     num_bytes: 14,
+                    encoding: UTF-8,
                 },
             },
         },
@@ -458,7 +572,7 @@ fn test_diff_files_in_subdirectories() -> Result<()> {
             "right",
         },
     }
-    "###);
+    "#);
 
     Ok(())
 }
@@ -470,7 +584,11 @@ fn test_dir_diff_no_changes() -> Result<()> {
         PathBuf::from("right/foo") => file_info("right contents\n"),
     });
 
-    let DiffContext { files, write_root } = process_opts(
+    let DiffContext {
+        files,
+        write_root,
+        encodings,
+    } = process_opts(
         &filesystem,
         &Opts {
             dir_diff: false,
@@ -480,19 +598,33 @@ fn test_dir_diff_no_changes() -> Result<()> {
             output: None,
             read_only: false,
             dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
         },
     )?;
 
     apply_changes(
         &mut filesystem,
         &write_root,
+        &encodings,
         RecordState {
             is_read_only: false,
             commits: Default::default(),
             files,
         },
     )?;
-    assert_debug_snapshot!(filesystem, @r###"
+    assert_debug_snapshot!(filesystem, @r#"
     TestFilesystem {
         files: {
             "left/foo": FileInfo {
@@ -503,6 +635,7 @@ fn test_dir_diff_no_changes() -> Result<()> {
                     contents: "left contents\n",
                     hash: "abc123",
                     num_bytes: 14,
+                    encoding: UTF-8,
                 },
             },
             "right/foo": FileInfo {
@@ -513,6 +646,7 @@ fn test_dir_diff_no_changes() -> Result<()> {
                     contents: "left contents\n",
                     hash: "abc123",
                     num_bytes: 14,
+                    encoding: UTF-8,
                 },
             },
         },
@@ -522,7 +656,7 @@ fn test_dir_diff_no_changes() -> Result<()> {
             "right",
         },
     }
-    "###);
+    "#);
 
     Ok(())
 }
@@ -556,6 +690,7 @@ Hello world 4
     let DiffContext {
         mut files,
         write_root,
+        encodings,
     } = process_opts(
         &filesystem,
         &Opts {
@@ -564,6 +699,19 @@ Hello world 4
             right: "right".into(),
             read_only: false,
             dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
             base: Some("base".into()),
             output: Some("output".into()),
         },
@@ -618,6 +766,7 @@ Hello world 4
     apply_changes(
         &mut filesystem,
         &write_root,
+        &encodings,
         RecordState {
             is_read_only: false,
             commits: Default::default(),
@@ -625,7 +774,7 @@ Hello world 4
         },
     )?;
 
-    assert_debug_snapshot!(filesystem, @r###"
+    assert_debug_snapshot!(filesystem, @r#"
     TestFilesystem {
         files: {
             "base": FileInfo {
@@ -636,6 +785,7 @@ Hello world 4
                     contents: "Hello world 1\nHello world 2\nHello world 3\nHello world 4\n",
                     hash: "abc123",
                     num_bytes: 56,
+                    encoding: UTF-8,
                 },
             },
             "left": FileInfo {
@@ -646,6 +796,7 @@ Hello world 4
                     contents: "Hello world 1\nHello world 2\nHello world L\nHello world 4\n",
                     hash: "abc123",
                     num_bytes: 56,
+                    encoding: UTF-8,
                 },
             },
             "output": FileInfo {
@@ -656,6 +807,7 @@ Hello world 4
                     contents: "Hello world 1\nHello world 2\nHello world L\nHello world R\nHello world 4\n",
                     hash: "abc123",
                     num_bytes: 70,
+                    encoding: UTF-8,
                 },
             },
             "right": FileInfo {
@@ -666,6 +818,7 @@ Hello world 4
                     contents: "Hello world 1\nHello world 2\nHello world R\nHello world 4\n",
                     hash: "abc123",
                     num_bytes: 56,
+                    encoding: UTF-8,
                 },
             },
         },
@@ -673,7 +826,7 @@ Hello world 4
             "",
         },
     }
-    "###);
+    "#);
 
     Ok(())
 }
@@ -691,6 +844,7 @@ Hello world 2
     let DiffContext {
         mut files,
         write_root,
+        encodings,
     } = process_opts(
         &filesystem,
         &Opts {
@@ -699,6 +853,19 @@ Hello world 2
             right: "right".into(),
             read_only: false,
             dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
             base: None,
             output: None,
         },
@@ -741,6 +908,7 @@ Hello world 2
     apply_changes(
         &mut filesystem,
         &write_root,
+        &encodings,
         RecordState {
             is_read_only: false,
             commits: Default::default(),
@@ -761,13 +929,14 @@ Hello world 2
     apply_changes(
         &mut filesystem,
         &write_root,
+        &encodings,
         RecordState {
             is_read_only: false,
             commits: Default::default(),
             files: files.clone(),
         },
     )?;
-    insta::assert_debug_snapshot!(filesystem, @r###"
+    insta::assert_debug_snapshot!(filesystem, @r#"
     TestFilesystem {
         files: {
             "right": FileInfo {
@@ -778,6 +947,7 @@ Hello world 2
                     contents: "Hello world 1\nHello world 2\n",
                     hash: "abc123",
                     num_bytes: 28,
+                    encoding: UTF-8,
                 },
             },
         },
@@ -785,7 +955,7 @@ Hello world 2
             "",
         },
     }
-    "###);
+    "#);
 
     // Select only some changes from new file.
     match files[0].sections.get_mut(1).unwrap() {
@@ -795,13 +965,14 @@ Hello world 2
     apply_changes(
         &mut filesystem,
         &write_root,
+        &encodings,
         RecordState {
             is_read_only: false,
             commits: Default::default(),
             files: files.clone(),
         },
     )?;
-    insta::assert_debug_snapshot!(filesystem, @r###"
+    insta::assert_debug_snapshot!(filesystem, @r#"
     TestFilesystem {
         files: {
             "right": FileInfo {
@@ -812,6 +983,7 @@ Hello world 2
                     contents: "Hello world 2\n",
                     hash: "abc123",
                     num_bytes: 14,
+                    encoding: UTF-8,
                 },
             },
         },
@@ -819,7 +991,1611 @@ Hello world 2
             "",
         },
     }
-    "###);
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_dir_diff_submodule() -> Result<()> {
+    let old_commit_id = "1111111111111111111111111111111111111111";
+    let new_commit_id = "2222222222222222222222222222222222222222";
+    let mut filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/sub") => submodule_info(old_commit_id),
+        PathBuf::from("right/sub") => submodule_info(new_commit_id),
+        PathBuf::from("right/new-sub") => submodule_info(new_commit_id),
+    });
+
+    let DiffContext {
+        mut files,
+        write_root,
+        encodings,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: true,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
+        },
+    )?;
+    assert_debug_snapshot!(files, @r#"
+    [
+        File {
+            old_path: None,
+            path: "new-sub",
+            file_mode: Absent,
+            sections: [
+                FileMode {
+                    is_checked: false,
+                    mode: Unix(
+                        57344,
+                    ),
+                },
+                Submodule {
+                    is_checked: false,
+                    old_commit_id: None,
+                    new_commit_id: Some(
+                        "2222222222222222222222222222222222222222",
+                    ),
+                },
+            ],
+        },
+        File {
+            old_path: None,
+            path: "sub",
+            file_mode: Unix(
+                57344,
+            ),
+            sections: [
+                Submodule {
+                    is_checked: false,
+                    old_commit_id: Some(
+                        "1111111111111111111111111111111111111111",
+                    ),
+                    new_commit_id: Some(
+                        "2222222222222222222222222222222222222222",
+                    ),
+                },
+            ],
+        },
+    ]
+    "#);
+
+    // Select no changes: the submodule points to the old commit again, and the
+    // new submodule is removed.
+    apply_changes(
+        &mut filesystem,
+        &write_root,
+        &encodings,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files: files.clone(),
+        },
+    )?;
+    assert_debug_snapshot!(filesystem, @r#"
+    TestFilesystem {
+        files: {
+            "left/sub": FileInfo {
+                file_mode: Unix(
+                    57344,
+                ),
+                contents: Submodule {
+                    commit_id: "1111111111111111111111111111111111111111",
+                },
+            },
+            "right/sub": FileInfo {
+                file_mode: Unix(
+                    57344,
+                ),
+                contents: Submodule {
+                    commit_id: "1111111111111111111111111111111111111111",
+                },
+            },
+        },
+        dirs: {
+            "",
+            "left",
+            "right",
+        },
+    }
+    "#);
+
+    select_all(&mut files);
+    apply_changes(
+        &mut filesystem,
+        &write_root,
+        &encodings,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        },
+    )?;
+    assert_debug_snapshot!(filesystem, @r#"
+    TestFilesystem {
+        files: {
+            "left/sub": FileInfo {
+                file_mode: Unix(
+                    57344,
+                ),
+                contents: Submodule {
+                    commit_id: "1111111111111111111111111111111111111111",
+                },
+            },
+            "right/new-sub": FileInfo {
+                file_mode: Unix(
+                    57344,
+                ),
+                contents: Submodule {
+                    commit_id: "2222222222222222222222222222222222222222",
+                },
+            },
+            "right/sub": FileInfo {
+                file_mode: Unix(
+                    57344,
+                ),
+                contents: Submodule {
+                    commit_id: "2222222222222222222222222222222222222222",
+                },
+            },
+        },
+        dirs: {
+            "",
+            "left",
+            "right",
+        },
+    }
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_dir_diff_renames() -> Result<()> {
+    let make_filesystem = || {
+        TestFilesystem::new(btreemap! {
+            PathBuf::from("left/foo") => file_info("a\nb\nc\nd\n"),
+            PathBuf::from("right/bar") => file_info("a\nb\nc\nD\n"),
+            PathBuf::from("left/baz") => file_info("x\ny\nz\n"),
+            PathBuf::from("right/baz") => file_info("x\ny\nz\n"),
+            PathBuf::from("right/qux") => file_info("x\ny\nz\nw\n"),
+        })
+    };
+    let filesystem = make_filesystem();
+
+    let DiffContext {
+        files,
+        write_root,
+        encodings,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: true,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            find_renames: Some(50),
+            find_copies: Some(50),
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
+        },
+    )?;
+    assert_debug_snapshot!(files, @r#"
+    [
+        File {
+            old_path: Some(
+                "foo",
+            ),
+            path: "bar",
+            file_mode: Unix(
+                33188,
+            ),
+            sections: [
+                Rename {
+                    is_checked: false,
+                },
+                Unchanged {
+                    lines: [
+                        "a\n",
+                        "b\n",
+                        "c\n",
+                    ],
+                },
+                Changed {
+                    lines: [
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: Removed,
+                            line: "d\n",
+                        },
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: Added,
+                            line: "D\n",
+                        },
+                    ],
+                },
+            ],
+        },
+        File {
+            old_path: None,
+            path: "baz",
+            file_mode: Unix(
+                33188,
+            ),
+            sections: [],
+        },
+        File {
+            old_path: Some(
+                "baz",
+            ),
+            path: "qux",
+            file_mode: Absent,
+            sections: [
+                FileMode {
+                    is_checked: false,
+                    mode: Unix(
+                        33188,
+                    ),
+                },
+                Unchanged {
+                    lines: [
+                        "x\n",
+                        "y\n",
+                        "z\n",
+                    ],
+                },
+                Changed {
+                    lines: [
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: Added,
+                            line: "w\n",
+                        },
+                    ],
+                },
+            ],
+        },
+    ]
+    "#);
+
+    // Select only the rename: the file is moved, but keeps its old contents.
+    // The copy is not created.
+    let mut filesystem = make_filesystem();
+    let mut rename_only_files = files.clone();
+    rename_only_files[0].sections[0].set_checked(true);
+    apply_changes(
+        &mut filesystem,
+        &write_root,
+        &encodings,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files: rename_only_files,
+        },
+    )?;
+    assert_debug_snapshot!(filesystem, @r#"
+    TestFilesystem {
+        files: {
+            "left/baz": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "x\ny\nz\n",
+                    hash: "abc123",
+                    num_bytes: 6,
+                    encoding: UTF-8,
+                },
+            },
+            "left/foo": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "a\nb\nc\nd\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+            "right/bar": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "a\nb\nc\nd\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+            "right/baz": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "x\ny\nz\n",
+                    hash: "abc123",
+                    num_bytes: 6,
+                    encoding: UTF-8,
+                },
+            },
+        },
+        dirs: {
+            "",
+            "left",
+            "right",
+        },
+    }
+    "#);
+
+    // Select no changes: the file is moved back to its old path.
+    let mut filesystem = make_filesystem();
+    apply_changes(
+        &mut filesystem,
+        &write_root,
+        &encodings,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files: files.clone(),
+        },
+    )?;
+    assert_debug_snapshot!(filesystem, @r#"
+    TestFilesystem {
+        files: {
+            "left/baz": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "x\ny\nz\n",
+                    hash: "abc123",
+                    num_bytes: 6,
+                    encoding: UTF-8,
+                },
+            },
+            "left/foo": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "a\nb\nc\nd\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+            "right/baz": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "x\ny\nz\n",
+                    hash: "abc123",
+                    num_bytes: 6,
+                    encoding: UTF-8,
+                },
+            },
+            "right/foo": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "a\nb\nc\nd\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+        },
+        dirs: {
+            "",
+            "left",
+            "right",
+        },
+    }
+    "#);
+
+    let mut filesystem = make_filesystem();
+    let mut files = files;
+    select_all(&mut files);
+    apply_changes(
+        &mut filesystem,
+        &write_root,
+        &encodings,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        },
+    )?;
+    assert_debug_snapshot!(filesystem, @r#"
+    TestFilesystem {
+        files: {
+            "left/baz": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "x\ny\nz\n",
+                    hash: "abc123",
+                    num_bytes: 6,
+                    encoding: UTF-8,
+                },
+            },
+            "left/foo": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "a\nb\nc\nd\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+            "right/bar": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "a\nb\nc\nD\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+            "right/baz": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "x\ny\nz\n",
+                    hash: "abc123",
+                    num_bytes: 6,
+                    encoding: UTF-8,
+                },
+            },
+            "right/qux": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "x\ny\nz\nw\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+        },
+        dirs: {
+            "",
+            "left",
+            "right",
+        },
+    }
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_dir_diff_include_exclude() -> Result<()> {
+    let filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/src/lib.rs") => file_info("foo\n"),
+        PathBuf::from("right/src/lib.rs") => file_info("bar\n"),
+        PathBuf::from("right/src/notes.txt") => file_info("notes\n"),
+        PathBuf::from("left/target/debug/build.rs") => file_info("foo\n"),
+        PathBuf::from("right/target/debug/build.rs") => file_info("bar\n"),
+    });
+
+    let DiffContext {
+        files,
+        write_root: _,
+        encodings: _,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: true,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec!["*.rs".to_string()],
+            exclude: vec!["target".to_string()],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
+        },
+    )?;
+    let paths: Vec<_> = files.iter().map(|file| file.path.clone()).collect();
+    assert_debug_snapshot!(paths, @r#"
+    [
+        "src/lib.rs",
+    ]
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_ignore_whitespace() -> Result<()> {
+    let mut filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left") => file_info("fn foo() {\n  bar();\n}\n"),
+        PathBuf::from("right") => file_info("fn foo() {\r\n    bar();\r\n    baz();\r\n}\r\n"),
+    });
+    let DiffContext {
+        mut files,
+        write_root,
+        encodings,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: false,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::IgnoreAmount,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
+        },
+    )?;
+    assert_debug_snapshot!(files, @r#"
+    [
+        File {
+            old_path: Some(
+                "left",
+            ),
+            path: "right",
+            file_mode: Unix(
+                33188,
+            ),
+            sections: [
+                Changed {
+                    lines: [
+                        SectionChangedLine {
+                            is_checked: true,
+                            change_type: Removed,
+                            line: "fn foo() {\n",
+                        },
+                        SectionChangedLine {
+                            is_checked: true,
+                            change_type: Added,
+                            line: "fn foo() {\r\n",
+                        },
+                        SectionChangedLine {
+                            is_checked: true,
+                            change_type: Removed,
+                            line: "  bar();\n",
+                        },
+                        SectionChangedLine {
+                            is_checked: true,
+                            change_type: Added,
+                            line: "    bar();\r\n",
+                        },
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: Added,
+                            line: "    baz();\r\n",
+                        },
+                        SectionChangedLine {
+                            is_checked: true,
+                            change_type: Removed,
+                            line: "}\n",
+                        },
+                        SectionChangedLine {
+                            is_checked: true,
+                            change_type: Added,
+                            line: "}\r\n",
+                        },
+                    ],
+                },
+            ],
+        },
+    ]
+    "#);
+
+    // Whitespace changes start out selected, so selecting everything writes
+    // the new contents exactly.
+    select_all(&mut files);
+    apply_changes(
+        &mut filesystem,
+        &write_root,
+        &encodings,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files: files.clone(),
+        },
+    )?;
+    assert_debug_snapshot!(filesystem, @r#"
+    TestFilesystem {
+        files: {
+            "left": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "fn foo() {\n  bar();\n}\n",
+                    hash: "abc123",
+                    num_bytes: 22,
+                    encoding: UTF-8,
+                },
+            },
+            "right": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "fn foo() {\r\n    bar();\r\n    baz();\r\n}\r\n",
+                    hash: "abc123",
+                    num_bytes: 39,
+                    encoding: UTF-8,
+                },
+            },
+        },
+        dirs: {
+            "",
+        },
+    }
+    "#);
+
+    // Deselecting everything restores the old contents, whitespace included.
+    for file in &mut files {
+        file.set_checked(false);
+    }
+    apply_changes(
+        &mut filesystem,
+        &write_root,
+        &encodings,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        },
+    )?;
+    assert_debug_snapshot!(filesystem, @r#"
+    TestFilesystem {
+        files: {
+            "left": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "fn foo() {\n  bar();\n}\n",
+                    hash: "abc123",
+                    num_bytes: 22,
+                    encoding: UTF-8,
+                },
+            },
+            "right": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "fn foo() {\n  bar();\n}\n",
+                    hash: "abc123",
+                    num_bytes: 22,
+                    encoding: UTF-8,
+                },
+            },
+        },
+        dirs: {
+            "",
+        },
+    }
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_with_script() -> Result<()> {
+    let mut filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left") => file_info("foo\ncommon1\ncommon2\nbar\nbaz\n"),
+        PathBuf::from("right") => file_info("qux1\ncommon1\ncommon2\nqux2\n"),
+    });
+    let DiffContext {
+        files,
+        write_root,
+        encodings,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: false,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
+        },
+    )?;
+
+    // Select only the second hunk, except for the removal of `bar`.
+    let script =
+        SelectionScript::parse("select hunk 2 of right\ndeselect lines matching ^bar$ in right")
+            .map_err(|err| Error::Script { source: err })?;
+    let state = script
+        .apply(RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        })
+        .map_err(|err| Error::Script { source: err })?;
+    apply_changes(&mut filesystem, &write_root, &encodings, state)?;
+    assert_debug_snapshot!(filesystem, @r#"
+    TestFilesystem {
+        files: {
+            "left": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "foo\ncommon1\ncommon2\nbar\nbaz\n",
+                    hash: "abc123",
+                    num_bytes: 28,
+                    encoding: UTF-8,
+                },
+            },
+            "right": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "foo\ncommon1\ncommon2\nbar\nqux2\n",
+                    hash: "abc123",
+                    num_bytes: 29,
+                    encoding: UTF-8,
+                },
+            },
+        },
+        dirs: {
+            "",
+        },
+    }
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_dir_diff_load_lazily() -> Result<()> {
+    let make_filesystem = || {
+        TestFilesystem::new(btreemap! {
+            PathBuf::from("left/changed") => file_info("foo\n"),
+            PathBuf::from("right/changed") => file_info("bar\n"),
+            PathBuf::from("left/deleted") => file_info("foo\n"),
+            PathBuf::from("right/added") => file_info("bar\n"),
+        })
+    };
+    let opts = Opts {
+        dir_diff: true,
+        left: PathBuf::from("left"),
+        right: PathBuf::from("right"),
+        base: None,
+        output: None,
+        read_only: false,
+        dry_run: false,
+        find_renames: None,
+        find_copies: None,
+        include: vec![],
+        exclude: vec![],
+        gitignore: false,
+        whitespace: WhitespaceMode::Exact,
+        diff_algorithm: DiffAlgorithm::Myers,
+        max_file_size: 0,
+        max_file_lines: 0,
+        show_generated: false,
+        encoding: None,
+        preselect: vec![],
+        script: None,
+    };
+    let DiffContext {
+        files: expected_files,
+        write_root: _,
+        encodings: _,
+    } = process_opts(&make_filesystem(), &opts)?;
+
+    let (
+        DiffContext {
+            mut files,
+            write_root,
+            encodings: _,
+        },
+        mut loader,
+    ) = process_opts_lazily(Arc::new(make_filesystem()), &opts)?;
+    assert_eq!(write_root, PathBuf::from("right"));
+    assert_debug_snapshot!(files, @r#"
+    [
+        File {
+            old_path: None,
+            path: "added",
+            file_mode: Absent,
+            sections: [
+                FileMode {
+                    is_checked: false,
+                    mode: Unix(
+                        33188,
+                    ),
+                },
+            ],
+        },
+        File {
+            old_path: None,
+            path: "changed",
+            file_mode: Unix(
+                33188,
+            ),
+            sections: [],
+        },
+        File {
+            old_path: None,
+            path: "deleted",
+            file_mode: Unix(
+                33188,
+            ),
+            sections: [
+                FileMode {
+                    is_checked: false,
+                    mode: Absent,
+                },
+            ],
+        },
+    ]
+    "#);
+
+    let changed_file = loader.load_file(1)?;
+    assert_eq!(changed_file.as_ref(), Some(&expected_files[1]));
+    files[1] = changed_file.unwrap();
+    assert_eq!(loader.load_file(1)?, None);
+
+    loader.load_all(&mut files)?;
+    assert_eq!(files, expected_files);
+    assert_eq!(loader.load_file(0)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_dir_diff_accept_without_loading() -> Result<()> {
+    struct LoadingInput {
+        input: TestingInput,
+        loader: FileLoader,
+    }
+
+    impl RecordInput for LoadingInput {
+        fn terminal_kind(&self) -> TerminalKind {
+            self.input.terminal_kind()
+        }
+
+        fn next_events(&mut self) -> std::result::Result<Vec<Event>, RecordError> {
+            self.input.next_events()
+        }
+
+        fn edit_commit_message(
+            &mut self,
+            message: &str,
+        ) -> std::result::Result<String, RecordError> {
+            self.input.edit_commit_message(message)
+        }
+
+        fn load_file(
+            &mut self,
+            file_idx: usize,
+            _file: &File,
+        ) -> std::result::Result<Option<File<'static>>, RecordError> {
+            self.loader
+                .load_file(file_idx)
+                .map_err(|err| RecordError::ReadFile(Box::new(err)))
+        }
+    }
+
+    let make_filesystem = || {
+        TestFilesystem::new(btreemap! {
+            PathBuf::from("left/changed") => file_info("foo\n"),
+            PathBuf::from("right/changed") => file_info("bar\n"),
+            PathBuf::from("left/deleted") => file_info("foo\n"),
+            PathBuf::from("right/added") => file_info("bar\n"),
+            PathBuf::from("left/unchanged") => file_info("foo\n"),
+            PathBuf::from("right/unchanged") => file_info("foo\n"),
+        })
+    };
+    let opts = Opts::try_parse_from(["scm-diff-editor", "--dir-diff", "left", "right"]).unwrap();
+
+    let mut expected_filesystem = make_filesystem();
+    let DiffContext {
+        files,
+        write_root,
+        encodings,
+    } = process_opts(&expected_filesystem, &opts)?;
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files,
+    };
+    apply_changes(&mut expected_filesystem, &write_root, &encodings, state)?;
+
+    // Accept the changes without drawing the UI, so that none of the files
+    // are loaded while it's shown.
+    let mut filesystem = make_filesystem();
+    let (
+        DiffContext {
+            files,
+            write_root,
+            encodings,
+        },
+        loader,
+    ) = process_opts_lazily(Arc::new(make_filesystem()), &opts)?;
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files,
+    };
+    let mut input = LoadingInput {
+        input: TestingInput::new(80, 24, []),
+        loader,
+    };
+    let mut recorder = Recorder::new(state, &mut input);
+    recorder
+        .process_event(Event::QuitAccept)
+        .map_err(|err| Error::Record { source: err })?;
+    let state = recorder
+        .finish()
+        .map_err(|err| Error::Record { source: err })?;
+    apply_changes(&mut filesystem, &write_root, &encodings, state)?;
+    assert_eq!(
+        format!("{filesystem:#?}"),
+        format!("{expected_filesystem:#?}")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_summarize_large_and_generated_files() -> Result<()> {
+    let make_filesystem = || {
+        TestFilesystem::new(btreemap! {
+            PathBuf::from("left/Cargo.lock") => file_info("foo 1.0\n"),
+            PathBuf::from("right/Cargo.lock") => file_info("foo 1.1\n"),
+            PathBuf::from("right/added") => file_info("1\n2\n3\n4\n"),
+            PathBuf::from("left/large") => file_info("1\n2\n3\n4\n"),
+            PathBuf::from("right/large") => file_info("1\n2\nthree\n4\n"),
+            PathBuf::from("left/small") => file_info("foo\n"),
+            PathBuf::from("right/small") => file_info("bar\n"),
+        })
+    };
+    let opts = Opts {
+        dir_diff: true,
+        left: PathBuf::from("left"),
+        right: PathBuf::from("right"),
+        base: None,
+        output: None,
+        read_only: false,
+        dry_run: false,
+        find_renames: None,
+        find_copies: None,
+        include: vec![],
+        exclude: vec![],
+        gitignore: false,
+        whitespace: WhitespaceMode::Exact,
+        diff_algorithm: DiffAlgorithm::Myers,
+        max_file_size: 0,
+        max_file_lines: 3,
+        show_generated: false,
+        encoding: None,
+        preselect: vec![],
+        script: None,
+    };
+    let (
+        DiffContext {
+            mut files,
+            write_root,
+            encodings: _,
+        },
+        mut loader,
+    ) = process_opts_lazily(Arc::new(make_filesystem()), &opts)?;
+    loader.load_all(&mut files)?;
+    let sections: Vec<_> = files
+        .iter()
+        .map(|file| (file.path.display().to_string(), file.sections.clone()))
+        .collect();
+    insta::assert_debug_snapshot!(sections, @r#"
+    [
+        (
+            "Cargo.lock",
+            [
+                Summary {
+                    is_checked: false,
+                    description: "2 lines changed in generated file",
+                    old_contents: "foo 1.0\n",
+                    new_contents: "foo 1.1\n",
+                },
+            ],
+        ),
+        (
+            "added",
+            [
+                FileMode {
+                    is_checked: false,
+                    mode: Unix(
+                        33188,
+                    ),
+                },
+                Summary {
+                    is_checked: false,
+                    description: "4 lines changed in large file",
+                    old_contents: "",
+                    new_contents: "1\n2\n3\n4\n",
+                },
+            ],
+        ),
+        (
+            "large",
+            [
+                Summary {
+                    is_checked: false,
+                    description: "2 lines changed in large file",
+                    old_contents: "1\n2\n3\n4\n",
+                    new_contents: "1\n2\nthree\n4\n",
+                },
+            ],
+        ),
+        (
+            "small",
+            [
+                Changed {
+                    lines: [
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: Removed,
+                            line: "foo\n",
+                        },
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: Added,
+                            line: "bar\n",
+                        },
+                    ],
+                },
+            ],
+        ),
+    ]
+    "#);
+
+    // The full diff keeps the selection of the summarized changes.
+    let mut large_file = files[2].clone();
+    large_file.set_checked(true);
+    let large_file = loader.load_full_diff(&large_file).unwrap();
+    insta::assert_debug_snapshot!(large_file.sections, @r#"
+    [
+        Unchanged {
+            lines: [
+                "1\n",
+                "2\n",
+            ],
+        },
+        Changed {
+            lines: [
+                SectionChangedLine {
+                    is_checked: true,
+                    change_type: Removed,
+                    line: "3\n",
+                },
+                SectionChangedLine {
+                    is_checked: true,
+                    change_type: Added,
+                    line: "three\n",
+                },
+            ],
+        },
+        Unchanged {
+            lines: [
+                "4\n",
+            ],
+        },
+    ]
+    "#);
+    assert_eq!(loader.load_full_diff(&files[3]), None);
+
+    // Selecting a summary selects the whole change, and leaving it unselected
+    // keeps the old contents.
+    files[0].set_checked(true);
+    files[1].set_checked(true);
+
+    let mut filesystem = make_filesystem();
+    apply_changes(
+        &mut filesystem,
+        &write_root,
+        loader.encodings(),
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        },
+    )?;
+    insta::assert_debug_snapshot!(filesystem, @r#"
+    TestFilesystem {
+        files: {
+            "left/Cargo.lock": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "foo 1.0\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+            "left/large": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "1\n2\n3\n4\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+            "left/small": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "foo\n",
+                    hash: "abc123",
+                    num_bytes: 4,
+                    encoding: UTF-8,
+                },
+            },
+            "right/Cargo.lock": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "foo 1.1\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+            "right/added": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "1\n2\n3\n4\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+            "right/large": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "1\n2\n3\n4\n",
+                    hash: "abc123",
+                    num_bytes: 8,
+                    encoding: UTF-8,
+                },
+            },
+            "right/small": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "foo\n",
+                    hash: "abc123",
+                    num_bytes: 4,
+                    encoding: UTF-8,
+                },
+            },
+        },
+        dirs: {
+            "",
+            "left",
+            "right",
+        },
+    }
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_dir_diff_encodings() -> Result<()> {
+    let latin1 = TextEncoding::for_label("latin1").unwrap();
+    let mut filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/latin1") => encoded_file_info("caf\u{e9}\nfoo\n", latin1),
+        PathBuf::from("right/latin1") => encoded_file_info("caf\u{e9}\nbar\n", latin1),
+        PathBuf::from("left/reencoded") => file_info("caf\u{e9}\n"),
+        PathBuf::from("right/reencoded") => encoded_file_info("caf\u{e9}\n", latin1),
+    });
+    let DiffContext {
+        mut files,
+        write_root,
+        encodings,
+    } = process_opts(
+        &filesystem,
+        &Opts {
+            dir_diff: true,
+            left: PathBuf::from("left"),
+            right: PathBuf::from("right"),
+            base: None,
+            output: None,
+            read_only: false,
+            dry_run: false,
+            find_renames: None,
+            find_copies: None,
+            include: vec![],
+            exclude: vec![],
+            gitignore: false,
+            whitespace: WhitespaceMode::Exact,
+            diff_algorithm: DiffAlgorithm::Myers,
+            max_file_size: 0,
+            max_file_lines: 0,
+            show_generated: false,
+            encoding: None,
+            preselect: vec![],
+            script: None,
+        },
+    )?;
+    assert_debug_snapshot!(encodings, @r#"
+    {
+        "latin1": windows-1252,
+        "reencoded": windows-1252,
+    }
+    "#);
+
+    // The file whose encoding changed is shown as a binary change, since its
+    // lines could only be partially selected by mixing the encodings.
+    assert_debug_snapshot!(files, @r#"
+    [
+        File {
+            old_path: None,
+            path: "latin1",
+            file_mode: Unix(
+                33188,
+            ),
+            sections: [
+                Unchanged {
+                    lines: [
+                        "café\n",
+                    ],
+                },
+                Changed {
+                    lines: [
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: Removed,
+                            line: "foo\n",
+                        },
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: Added,
+                            line: "bar\n",
+                        },
+                    ],
+                },
+            ],
+        },
+        File {
+            old_path: None,
+            path: "reencoded",
+            file_mode: Unix(
+                33188,
+            ),
+            sections: [
+                Binary {
+                    is_checked: false,
+                    old_description: Some(
+                        "abc123 (6 bytes)",
+                    ),
+                    new_description: Some(
+                        "abc123 (5 bytes)",
+                    ),
+                },
+            ],
+        },
+    ]
+    "#);
+
+    // Only select the removal of `foo`, so that the file is written with both
+    // its selected and unselected lines in its original encoding.
+    match &mut files[0].sections[1] {
+        Section::Changed { lines } => lines[0].is_checked = true,
+        section => panic!("Expected changed section, got: {section:?}"),
+    }
+    apply_changes(
+        &mut filesystem,
+        &write_root,
+        &encodings,
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files,
+        },
+    )?;
+    assert_debug_snapshot!(filesystem, @r#"
+    TestFilesystem {
+        files: {
+            "left/latin1": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "café\nfoo\n",
+                    hash: "abc123",
+                    num_bytes: 9,
+                    encoding: windows-1252,
+                },
+            },
+            "left/reencoded": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "café\n",
+                    hash: "abc123",
+                    num_bytes: 6,
+                    encoding: UTF-8,
+                },
+            },
+            "right/latin1": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "café\n",
+                    hash: "abc123",
+                    num_bytes: 5,
+                    encoding: windows-1252,
+                },
+            },
+            "right/reencoded": FileInfo {
+                file_mode: Unix(
+                    33188,
+                ),
+                contents: Text {
+                    contents: "café\n",
+                    hash: "abc123",
+                    num_bytes: 5,
+                    encoding: windows-1252,
+                },
+            },
+        },
+        dirs: {
+            "",
+            "left",
+            "right",
+        },
+    }
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_preselect() -> Result<()> {
+    let filesystem = TestFilesystem::new(btreemap! {
+        PathBuf::from("left/main.rs") => file_info("foo\nbar\n"),
+        PathBuf::from("right/main.rs") => file_info("foo\nbaz\n"),
+        PathBuf::from("left/notes.txt") => file_info("old\n"),
+        PathBuf::from("right/notes.txt") => file_info("new\n"),
+    });
+    let make_opts = |preselect: &[&str]| Opts {
+        dir_diff: true,
+        left: PathBuf::from("left"),
+        right: PathBuf::from("right"),
+        base: None,
+        output: None,
+        read_only: false,
+        dry_run: false,
+        find_renames: None,
+        find_copies: None,
+        include: vec![],
+        exclude: vec![],
+        gitignore: false,
+        whitespace: WhitespaceMode::Exact,
+        diff_algorithm: DiffAlgorithm::Myers,
+        max_file_size: 0,
+        max_file_lines: 0,
+        show_generated: false,
+        encoding: None,
+        preselect: preselect.iter().map(|rule| rule.to_string()).collect(),
+        script: None,
+    };
+    // The lines of each file, and whether they're selected.
+    type FileLines = (String, Vec<(String, bool)>);
+    let preselect = |opts: &Opts| -> Result<Vec<FileLines>> {
+        let DiffContext {
+            files,
+            write_root: _,
+            encodings: _,
+        } = process_opts(&filesystem, opts)?;
+        let state = apply_preselect(
+            opts,
+            RecordState {
+                is_read_only: false,
+                commits: Default::default(),
+                files,
+            },
+        )?;
+        Ok(state
+            .files
+            .iter()
+            .map(|file| {
+                let lines = file
+                    .sections
+                    .iter()
+                    .flat_map(|section| match section {
+                        Section::Changed { lines } => lines
+                            .iter()
+                            .map(|line| (line.line.to_string(), line.is_checked))
+                            .collect(),
+                        _ => Vec::new(),
+                    })
+                    .collect();
+                (file.path.display().to_string(), lines)
+            })
+            .collect())
+    };
+
+    let opts = make_opts(&[
+        "select all",
+        "deselect file *.txt",
+        "deselect removed lines in *.rs",
+    ]);
+    assert_debug_snapshot!(preselect(&opts)?, @r#"
+    [
+        (
+            "main.rs",
+            [
+                (
+                    "bar\n",
+                    false,
+                ),
+                (
+                    "baz\n",
+                    true,
+                ),
+            ],
+        ),
+        (
+            "notes.txt",
+            [
+                (
+                    "old\n",
+                    false,
+                ),
+                (
+                    "new\n",
+                    false,
+                ),
+            ],
+        ),
+    ]
+    "#);
+
+    // Without any rules, nothing is selected.
+    let opts = make_opts(&[]);
+    assert_debug_snapshot!(preselect(&opts)?, @r#"
+    [
+        (
+            "main.rs",
+            [
+                (
+                    "bar\n",
+                    false,
+                ),
+                (
+                    "baz\n",
+                    false,
+                ),
+            ],
+        ),
+        (
+            "notes.txt",
+            [
+                (
+                    "old\n",
+                    false,
+                ),
+                (
+                    "new\n",
+                    false,
+                ),
+            ],
+        ),
+    ]
+    "#);
+
+    let opts = make_opts(&["select everything"]);
+    assert!(matches!(preselect(&opts), Err(Error::Preselect { .. })));
+
+    // Rules can't be passed if nothing can be selected.
+    let err = Opts::try_parse_from([
+        "scm-diff-editor",
+        "--read-only",
+        "--preselect",
+        "select all",
+        "left",
+        "right",
+    ])
+    .unwrap_err();
+    assert_eq!(err.kind(), clap::error::ErrorKind::ArgumentConflict);
 
     Ok(())
 }
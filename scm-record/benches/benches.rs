@@ -1,4 +1,8 @@
-use std::{borrow::Cow, path::Path};
+use std::{
+    borrow::Cow,
+    iter,
+    path::{Path, PathBuf},
+};
 
 use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 
@@ -7,38 +11,21 @@ use scm_record::{
     SectionChangedLine,
 };
 
-fn bench_record(c: &mut Criterion) {
-    c.bench_function("scm_record: toggle line", |b| {
-        let before_line = SectionChangedLine {
-            line: Cow::Borrowed("foo"),
-            is_checked: false,
-            change_type: ChangeType::Removed,
-        };
-        let after_line = SectionChangedLine {
-            line: Cow::Borrowed("foo"),
-            is_checked: false,
-            change_type: ChangeType::Added,
-        };
-        let record_state = RecordState {
-            is_read_only: false,
-            commits: Default::default(),
-            files: vec![File {
-                old_path: None,
-                path: Cow::Borrowed(Path::new("foo")),
-                file_mode: FileMode::FILE_DEFAULT,
-                sections: vec![Section::Changed {
-                    lines: [vec![before_line; 1000], vec![after_line; 1000]].concat(),
-                }],
-            }],
-        };
-        let mut input = TestingInput::new(
-            80,
-            24,
-            [Event::ToggleItem, Event::ToggleItem, Event::QuitAccept],
-        );
+/// Run the recorder on `record_state` with the given events, which should end
+/// by quitting.
+fn bench_events(
+    c: &mut Criterion,
+    name: &str,
+    record_state: &RecordState<'static>,
+    events: &[Event],
+) {
+    c.bench_function(name, |b| {
         b.iter_batched(
-            || record_state.clone(),
-            |record_state| {
+            || {
+                let input = TestingInput::new(80, 24, events.to_vec());
+                (record_state.clone(), input)
+            },
+            |(record_state, mut input)| {
                 let recorder = Recorder::new(record_state, &mut input);
                 recorder.run()
             },
@@ -47,9 +34,118 @@ fn bench_record(c: &mut Criterion) {
     });
 }
 
+/// A diff of 100 files, each with 10 sections of 50 removed and 50 added
+/// lines, for a total of 100,000 changed lines.
+fn large_record_state() -> RecordState<'static> {
+    let section = Section::Changed {
+        lines: [ChangeType::Removed, ChangeType::Added]
+            .into_iter()
+            .flat_map(|change_type| {
+                iter::repeat_n(
+                    SectionChangedLine {
+                        line: Cow::Borrowed("foo\n"),
+                        is_checked: false,
+                        change_type,
+                    },
+                    50,
+                )
+            })
+            .collect(),
+    };
+    RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files: (0..100)
+            .map(|i| File {
+                old_path: None,
+                path: Cow::Owned(PathBuf::from(format!("foo{i}"))),
+                file_mode: FileMode::FILE_DEFAULT,
+                sections: vec![section.clone(); 10],
+            })
+            .collect(),
+    }
+}
+
+fn bench_record(c: &mut Criterion) {
+    let before_line = SectionChangedLine {
+        line: Cow::Borrowed("foo"),
+        is_checked: false,
+        change_type: ChangeType::Removed,
+    };
+    let after_line = SectionChangedLine {
+        line: Cow::Borrowed("foo"),
+        is_checked: false,
+        change_type: ChangeType::Added,
+    };
+    let record_state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files: vec![File {
+            old_path: None,
+            path: Cow::Borrowed(Path::new("foo")),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections: vec![Section::Changed {
+                lines: [vec![before_line; 1000], vec![after_line; 1000]].concat(),
+            }],
+        }],
+    };
+    bench_events(
+        c,
+        "scm_record: toggle line",
+        &record_state,
+        &[Event::ToggleItem, Event::ToggleItem, Event::QuitAccept],
+    );
+}
+
+fn bench_large_diff(c: &mut Criterion) {
+    let record_state = large_record_state();
+    bench_events(
+        c,
+        "scm_record: toggle line (100k lines)",
+        &record_state,
+        &[
+            Event::ExpandAll,
+            Event::FocusNext,
+            Event::FocusNext,
+            Event::ToggleItem,
+            Event::ToggleItem,
+            Event::QuitAccept,
+        ],
+    );
+    bench_events(
+        c,
+        "scm_record: toggle file (100k lines)",
+        &record_state,
+        &[Event::ToggleItem, Event::ToggleItem, Event::QuitAccept],
+    );
+    bench_events(
+        c,
+        "scm_record: scroll (100k lines)",
+        &record_state,
+        &[Event::ExpandAll]
+            .into_iter()
+            .chain(iter::repeat_n(Event::PageDown, 10))
+            .chain(iter::repeat_n(Event::ScrollDown, 10))
+            .chain([Event::QuitAccept])
+            .collect::<Vec<_>>(),
+    );
+    bench_events(
+        c,
+        "scm_record: navigate (100k lines)",
+        &record_state,
+        &[Event::ExpandAll]
+            .into_iter()
+            .chain(iter::repeat_n(Event::FocusNext, 10))
+            .chain(iter::repeat_n(Event::FocusNextPage, 10))
+            .chain(iter::repeat_n(Event::FocusNextSameKind, 10))
+            .chain([Event::QuitAccept])
+            .collect::<Vec<_>>(),
+    );
+}
+
 criterion_group!(
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = bench_record,
+    targets = bench_record, bench_large_diff,
 );
 criterion_main!(benches);
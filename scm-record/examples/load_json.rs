@@ -39,6 +39,7 @@ fn main() {
                 let (selected, _unselected) = file.get_selected_contents();
 
                 let SelectedChanges {
+                    path: _,
                     contents,
                     file_mode,
                 } = selected;
@@ -59,6 +60,9 @@ fn main() {
                                 new_description: Some(description),
                             } => format!("<binary description={description}>\n"),
                             SelectedContents::Text { contents } => contents.clone(),
+                            SelectedContents::Submodule { commit_id } => {
+                                format!("<submodule commit={commit_id}>\n")
+                            }
                         }
                     );
                 }
@@ -110,6 +110,7 @@ fn main() {
                 let (selected, _unselected) = file.get_selected_contents();
 
                 let SelectedChanges {
+                    path: _,
                     contents,
                     file_mode,
                 } = selected;
@@ -129,6 +130,9 @@ fn main() {
                                 new_description: Some(description),
                             } => format!("<binary description={description}>\n"),
                             SelectedContents::Text { contents } => contents.clone(),
+                            SelectedContents::Submodule { commit_id } => {
+                                format!("<submodule commit={commit_id}>\n")
+                            }
                             SelectedContents::Unchanged => "<unchanged\n>".to_string(),
                         }
                     );
@@ -11,3 +11,7 @@ pub const DUMP_UI_STATE_FILENAME: &str = "scm_record_ui_state.json";
 /// Render a debug pane over the file. Only works if compiled with the `debug`
 /// feature.
 pub const ENV_VAR_DEBUG_UI: &str = "SCM_RECORD_DEBUG_UI";
+
+/// If set to a non-empty value, render the UI without colours, regardless of
+/// the configured [`crate::theme::Theme`]. See <https://no-color.org>.
+pub const ENV_VAR_NO_COLOR: &str = "NO_COLOR";
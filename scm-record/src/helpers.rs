@@ -1,14 +1,220 @@
 //! Helper functions for rendering UI components.
 
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
 use std::{collections::VecDeque, time::Duration};
 
-use crate::{Event, RecordError, RecordInput, TerminalKind};
+use crate::{Event, File, RecordError, RecordInput, TerminalKind, Tristate};
 
 /// Generate a one-line description of a binary file change.
 pub fn make_binary_description(hash: &str, num_bytes: u64) -> String {
     format!("{hash} ({num_bytes} bytes)")
 }
 
+/// Make git-style comments to show below a commit message while it's edited
+/// with [`edit_message_in_editor`], listing the files with selected changes.
+pub fn make_commit_message_comments(files: &[File]) -> String {
+    let mut comments = String::from(
+        "\
+# Please enter the commit message for your changes. Lines starting
+# with '#' will be ignored, and an empty message cancels the edit.
+#
+",
+    );
+    let selected_files: Vec<_> = files
+        .iter()
+        .filter_map(|file| match file.tristate() {
+            Tristate::False => None,
+            Tristate::Partial => Some(format!("#\t{} (partially)\n", file.path.display())),
+            Tristate::True => Some(format!("#\t{}\n", file.path.display())),
+        })
+        .collect();
+    if selected_files.is_empty() {
+        comments.push_str("# No changes are selected.\n");
+    } else {
+        comments.push_str("# Changes to be committed:\n");
+        comments.extend(selected_files);
+    }
+    comments
+}
+
+/// Remove comment lines, which start with `#`, from a commit message, like
+/// `git commit --cleanup=strip`. Trailing whitespace, leading and trailing
+/// blank lines, and consecutive blank lines are also removed. If the result
+/// isn't empty, then it ends with a newline.
+pub fn strip_commit_message_comments(message: &str) -> String {
+    let mut result = String::new();
+    let mut is_blank_line_pending = false;
+    for line in message.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            is_blank_line_pending = !result.is_empty();
+            continue;
+        }
+        if is_blank_line_pending {
+            result.push('\n');
+            is_blank_line_pending = false;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+    result
+}
+
+/// Edit a commit message in the user's preferred editor, as determined by the
+/// `VISUAL` or `EDITOR` environment variables, or `vi` if neither is set. The
+/// given comments, such as from [`make_commit_message_comments`], are shown
+/// below the message and removed afterwards with
+/// [`strip_commit_message_comments`].
+///
+/// Returns `None` if the resulting message is empty, in which case the edit
+/// should be treated as cancelled.
+///
+/// This can be used to implement [`RecordInput::edit_commit_message_for_files`]:
+///
+/// ```no_run
+/// # use scm_record::helpers::{edit_message_in_editor, make_commit_message_comments};
+/// # use scm_record::{File, RecordError};
+/// fn edit_commit_message_for_files(
+///     message: &str,
+///     files: &[File],
+/// ) -> Result<String, RecordError> {
+///     let comments = make_commit_message_comments(files);
+///     let new_message = edit_message_in_editor(message, &comments)?;
+///     Ok(new_message.unwrap_or_else(|| message.to_owned()))
+/// }
+/// ```
+pub fn edit_message_in_editor(
+    message: &str,
+    comments: &str,
+) -> Result<Option<String>, RecordError> {
+    let editor = get_editor_command(|name| std::env::var_os(name));
+    edit_message_with_command(&editor, message, comments)
+}
+
+fn get_editor_command(get_env_var: impl Fn(&str) -> Option<OsString>) -> OsString {
+    ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(get_env_var)
+        .find(|value| !value.is_empty())
+        .unwrap_or_else(|| OsString::from("vi"))
+}
+
+fn edit_message_with_command(
+    editor: &OsStr,
+    message: &str,
+    comments: &str,
+) -> Result<Option<String>, RecordError> {
+    let separator = if message.is_empty() || message.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    let file = TempFile::create(
+        "COMMIT_EDITMSG",
+        &format!("{message}{separator}\n{comments}"),
+    )
+    .map_err(|err| RecordError::WriteFile(err.into()))?;
+
+    let status = editor_command(editor, file.path())
+        .status()
+        .map_err(|err| RecordError::RunEditor(err.into()))?;
+    if !status.success() {
+        return Err(RecordError::RunEditor(
+            format!("{} exited with {status}", editor.to_string_lossy()).into(),
+        ));
+    }
+
+    let new_message =
+        fs::read_to_string(file.path()).map_err(|err| RecordError::ReadFile(err.into()))?;
+    let new_message = strip_commit_message_comments(&new_message);
+    if new_message.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(new_message))
+    }
+}
+
+/// A file in the system's temporary directory, which is deleted when it's
+/// dropped.
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Create a new file whose name starts with `prefix`, which can only be
+    /// read by the current user, and write `contents` to it.
+    fn create(prefix: &str, contents: &str) -> io::Result<Self> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|duration| duration.subsec_nanos())
+            .unwrap_or_default();
+        loop {
+            let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+            let path =
+                std::env::temp_dir().join(format!("{prefix}-{}-{id}-{nanos}", std::process::id()));
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            match options.open(&path) {
+                Ok(mut file) => {
+                    // Construct the guard first so that the file is removed
+                    // if writing to it fails.
+                    let temp_file = Self { path };
+                    file.write_all(contents.as_bytes())?;
+                    file.flush()?;
+                    return Ok(temp_file);
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Run the editor with the shell, like Git does, so that the editor command can
+/// include arguments.
+#[cfg(unix)]
+fn editor_command(editor: &OsStr, path: &Path) -> Command {
+    let mut script = editor.to_owned();
+    script.push(r#" "$@""#);
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script).arg(editor).arg(path);
+    command
+}
+
+#[cfg(not(unix))]
+fn editor_command(editor: &OsStr, path: &Path) -> Command {
+    let editor = editor.to_string_lossy();
+    let mut args = editor.split_whitespace();
+    let mut command = Command::new(args.next().unwrap_or("notepad"));
+    command.args(args).arg(path);
+    command
+}
+
 /// Reads input events from the terminal using `crossterm`.
 ///
 /// Its default implementation of `edit_commit_message` returns the provided
@@ -21,21 +227,7 @@ impl RecordInput for CrosstermInput {
     }
 
     fn next_events(&mut self) -> Result<Vec<Event>, RecordError> {
-        // Ensure we block for at least one event.
-        let first_event =
-            crossterm::event::read().map_err(|err| RecordError::ReadInput(err.into()))?;
-        let mut events = vec![first_event.into()];
-        // Some events, like scrolling, are generated more quickly than
-        // we can render the UI. In those cases, batch up all available
-        // events and process them before the next render.
-        while crossterm::event::poll(Duration::ZERO)
-            .map_err(|err| RecordError::ReadInput(err.into()))?
-        {
-            let event =
-                crossterm::event::read().map_err(|err| RecordError::ReadInput(err.into()))?;
-            events.push(event.into());
-        }
-        Ok(events)
+        read_crossterm_events()
     }
 
     fn edit_commit_message(&mut self, message: &str) -> Result<String, RecordError> {
@@ -43,6 +235,48 @@ impl RecordInput for CrosstermInput {
     }
 }
 
+/// Reads input events from the terminal using `crossterm`, and renders the UI
+/// inline below the cursor rather than on the alternate screen. See
+/// [`TerminalKind::CrosstermInline`].
+///
+/// Its default implementation of `edit_commit_message` returns the provided
+/// message unchanged.
+pub struct CrosstermInlineInput {
+    /// The number of lines to render the UI in.
+    pub height: usize,
+}
+
+impl RecordInput for CrosstermInlineInput {
+    fn terminal_kind(&self) -> TerminalKind {
+        let Self { height } = self;
+        TerminalKind::CrosstermInline { height: *height }
+    }
+
+    fn next_events(&mut self) -> Result<Vec<Event>, RecordError> {
+        read_crossterm_events()
+    }
+
+    fn edit_commit_message(&mut self, message: &str) -> Result<String, RecordError> {
+        Ok(message.to_owned())
+    }
+}
+
+fn read_crossterm_events() -> Result<Vec<Event>, RecordError> {
+    // Ensure we block for at least one event.
+    let first_event = crossterm::event::read().map_err(|err| RecordError::ReadInput(err.into()))?;
+    let mut events = vec![first_event.into()];
+    // Some events, like scrolling, are generated more quickly than
+    // we can render the UI. In those cases, batch up all available
+    // events and process them before the next render.
+    while crossterm::event::poll(Duration::ZERO)
+        .map_err(|err| RecordError::ReadInput(err.into()))?
+    {
+        let event = crossterm::event::read().map_err(|err| RecordError::ReadInput(err.into()))?;
+        events.push(event.into());
+    }
+    Ok(events)
+}
+
 /// Reads events from the provided sequence of events.
 pub struct TestingInput {
     /// The width of the virtual terminal in columns.
@@ -54,7 +288,8 @@ pub struct TestingInput {
     /// The sequence of events to emit.
     pub events: Box<dyn Iterator<Item = Event>>,
 
-    /// Commit messages to use when the commit editor is opened.
+    /// Commit messages to use when the commit editor is opened. Edits to the
+    /// commit metadata are taken from the same queue.
     pub commit_messages: VecDeque<String>,
 }
 
@@ -97,4 +332,130 @@ impl RecordInput for TestingInput {
             .pop_front()
             .ok_or_else(|| RecordError::Other("No more commit messages available".to_string()))
     }
+
+    fn edit_commit_metadata(&mut self, metadata: &str) -> Result<Option<String>, RecordError> {
+        self.edit_commit_message(metadata).map(Some)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::borrow::Cow;
+
+    use crate::{ChangeType, FileMode, Section, SectionChangedLine};
+
+    use super::*;
+
+    /// Create a file at `path` with one added line for each entry of
+    /// `is_checked`, which are selected accordingly.
+    pub(crate) fn file_with_added_lines(path: &'static str, is_checked: &[bool]) -> File<'static> {
+        File {
+            old_path: None,
+            path: Cow::Borrowed(Path::new(path)),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections: vec![Section::Changed {
+                lines: is_checked
+                    .iter()
+                    .map(|&is_checked| SectionChangedLine {
+                        is_checked,
+                        change_type: ChangeType::Added,
+                        line: Cow::Borrowed("foo\n"),
+                    })
+                    .collect(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_get_editor_command() {
+        let get_editor_command = |vars: &[(&str, &str)]| {
+            get_editor_command(|name| {
+                vars.iter()
+                    .find(|(var_name, _)| *var_name == name)
+                    .map(|(_, value)| OsString::from(value))
+            })
+        };
+        assert_eq!(get_editor_command(&[]), "vi");
+        assert_eq!(get_editor_command(&[("EDITOR", "nano")]), "nano");
+        assert_eq!(
+            get_editor_command(&[("VISUAL", "code --wait"), ("EDITOR", "nano")]),
+            "code --wait"
+        );
+        assert_eq!(
+            get_editor_command(&[("VISUAL", ""), ("EDITOR", "nano")]),
+            "nano"
+        );
+    }
+
+    #[test]
+    fn test_make_commit_message_comments() {
+        let files = [
+            file_with_added_lines("foo", &[true]),
+            file_with_added_lines("bar", &[true, false]),
+            file_with_added_lines("baz", &[false]),
+        ];
+        insta::assert_snapshot!(make_commit_message_comments(&files), @"
+        # Please enter the commit message for your changes. Lines starting
+        # with '#' will be ignored, and an empty message cancels the edit.
+        #
+        # Changes to be committed:
+        #	foo
+        #	bar (partially)
+        ");
+        insta::assert_snapshot!(make_commit_message_comments(&files[2..]), @"
+        # Please enter the commit message for your changes. Lines starting
+        # with '#' will be ignored, and an empty message cancels the edit.
+        #
+        # No changes are selected.
+        ");
+    }
+
+    #[test]
+    fn test_strip_commit_message_comments() {
+        assert_eq!(strip_commit_message_comments(""), "");
+        assert_eq!(strip_commit_message_comments("# comment\n\n  \n"), "");
+        assert_eq!(
+            strip_commit_message_comments(
+                "\n\nSubject  \n# comment\n\n\n\nBody\n#comment\nmore body\n\n"
+            ),
+            "Subject\n\nBody\nmore body\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_message_with_command() -> Result<(), RecordError> {
+        // The editor command is run with the path to the message as its
+        // argument.
+        let editor = OsStr::new(
+            r#"sh -c 'sed -e s/old/new/ -e "s/^# Please edit$/body/" "$0" > "$0.new" && mv "$0.new" "$0"'"#,
+        );
+        assert_eq!(
+            edit_message_with_command(editor, "old subject", "# Please edit\n")?,
+            Some("new subject\n\nbody\n".to_string())
+        );
+
+        let editor = OsStr::new(": >");
+        assert_eq!(
+            edit_message_with_command(editor, "old subject", "# Please edit\n")?,
+            None
+        );
+
+        // The message is written to a temporary file, which is removed
+        // afterwards.
+        let editor = OsStr::new(r#"sh -c 'echo "$0" >> "$0"'"#);
+        let new_message = edit_message_with_command(editor, "old subject", "# Please edit\n")?
+            .expect("message should not be empty");
+        let path = new_message.lines().last().unwrap();
+        assert!(path.contains("COMMIT_EDITMSG"), "{path}");
+        assert!(!Path::new(path).exists());
+
+        let editor = OsStr::new("false");
+        assert!(matches!(
+            edit_message_with_command(editor, "old subject", "# Please edit\n"),
+            Err(RecordError::RunEditor(_))
+        ));
+
+        Ok(())
+    }
 }
@@ -10,14 +10,22 @@
 #![allow(clippy::too_many_arguments)]
 
 mod render;
+mod selection_counts;
+mod text_editor;
 mod types;
 mod ui;
 mod util;
 
 pub mod consts;
 pub mod helpers;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod theme;
+pub mod widget;
 pub use types::{
     ChangeType, Commit, File, FileMode, RecordError, RecordState, Section, SectionChangedLine,
-    SelectedChanges, SelectedContents, Tristate,
+    SelectedChanges, SelectedContents, Trailer, Tristate,
+};
+pub use ui::{
+    Event, RecordInput, Recorder, RecorderStatus, RenderOptions, TerminalKind, TestingScreenshot,
 };
-pub use ui::{Event, RecordInput, Recorder, TerminalKind, TestingScreenshot};
@@ -11,7 +11,6 @@ use ratatui::buffer::Buffer;
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::{StatefulWidget, Widget};
-use ratatui::Frame;
 use unicode_width::UnicodeWidthStr;
 
 use crate::util::{IsizeExt, UsizeExt};
@@ -358,7 +357,9 @@ pub(crate) type DrawnRects<C> = HashMap<C, DrawnRect>;
 #[derive(Debug)]
 pub(crate) struct Viewport<'a, ComponentId> {
     buf: &'a mut Buffer,
+    buf_area: ratatui::layout::Rect,
     rect: Rect,
+    use_unicode: bool,
     mask: Option<Mask>,
     timestamp: usize,
     trace: Vec<DrawTrace<ComponentId>>,
@@ -366,10 +367,20 @@ pub(crate) struct Viewport<'a, ComponentId> {
 }
 
 impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
-    pub fn new(buf: &'a mut Buffer, rect: Rect) -> Self {
+    /// Constructor. The portion `rect` of the virtual canvas will be rendered
+    /// into the area `buf_area` of `buf`, which should have the same
+    /// dimensions.
+    pub fn new(
+        buf: &'a mut Buffer,
+        buf_area: ratatui::layout::Rect,
+        rect: Rect,
+        use_unicode: bool,
+    ) -> Self {
         Self {
             buf,
+            buf_area,
             rect,
+            use_unicode,
             mask: Default::default(),
             timestamp: Default::default(),
             trace: vec![Default::default()],
@@ -383,6 +394,12 @@ impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
         self.rect
     }
 
+    /// Whether non-ASCII characters, such as box-drawing characters, may be
+    /// drawn.
+    pub fn use_unicode(&self) -> bool {
+        self.use_unicode
+    }
+
     /// The mask used for rendering. Calls to `draw_span` will only render
     /// inside the mask area. This can be used to overlay one component on top
     /// of another in a fixed area.
@@ -401,18 +418,74 @@ impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
         self.mask().apply(self.rect())
     }
 
-    /// Render the provided component using the given `Frame`. Returns a mapping
-    /// indicating where each component was drawn on the screen.
+    /// The portion of the virtual canvas in which components should actually
+    /// be drawn. This is the visible area extended by one screenful above and
+    /// below, so that the components which can be reached by scrolling or
+    /// navigating by a page are still recorded in the `DrawnRects`.
+    pub fn layout_rect(&self) -> Rect {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = self.rect;
+        Rect {
+            x,
+            y: y - height.unwrap_isize(),
+            width,
+            height: height * 3,
+        }
+    }
+
+    /// Whether any row of `rect` lies in `Viewport::layout_rect`. Components
+    /// which can compute the height of their children ahead of time can use
+    /// this to skip drawing the children which are far off-screen.
+    pub fn is_in_layout(&self, rect: Rect) -> bool {
+        let layout_rect = self.layout_rect();
+        !rect.is_empty() && rect.y < layout_rect.end_y() && layout_rect.y < rect.end_y()
+    }
+
+    /// Record that the component with the given ID occupies `rect`, but
+    /// without drawing it. This can be used in place of
+    /// `Viewport::draw_component` for components which aren't in
+    /// `Viewport::layout_rect`, and which may not even have been built. Its
+    /// children are not recorded.
+    pub fn skip_component(&mut self, rect: Rect, id: ComponentId) {
+        let timestamp = {
+            let timestamp = self.timestamp;
+            self.timestamp += 1;
+            timestamp
+        };
+        let trace = self.current_trace_mut();
+        trace.merge_rect(rect);
+        trace.components.insert(id, DrawnRect { rect, timestamp });
+    }
+
+    /// Include `rect` in the area occupied by the current component, but
+    /// without drawing anything there.
+    pub fn reserve_rect(&mut self, rect: Rect) {
+        self.current_trace_mut().merge_rect(rect);
+    }
+
+    /// Render the provided component into the given area of the `Buffer`.
+    /// Returns a mapping indicating where each component was drawn on the
+    /// virtual canvas.
     pub fn render_top_level<C: Component>(
-        frame: &mut Frame,
+        buf: &mut Buffer,
+        area: ratatui::layout::Rect,
         x: isize,
         y: isize,
+        use_unicode: bool,
         component: &C,
     ) -> DrawnRects<C::Id> {
-        let widget = TopLevelWidget { component, x, y };
-        let term_area = frame.area();
+        let widget = TopLevelWidget {
+            component,
+            x,
+            y,
+            use_unicode,
+        };
         let mut drawn_rects = Default::default();
-        frame.render_stateful_widget(widget, term_area, &mut drawn_rects);
+        widget.render(area, buf, &mut drawn_rects);
         drawn_rects
     }
 
@@ -554,7 +627,8 @@ impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
 
         let mask_rect = self.mask_rect();
         if line_rect.end_x() > mask_rect.end_x() {
-            self.draw_span(mask_rect.end_x() - 1, line_rect.y, &Span::raw("…"));
+            let ellipsis = if self.use_unicode { "…" } else { ">" };
+            self.draw_span(mask_rect.end_x() - 1, line_rect.y, &Span::raw(ellipsis));
         }
         line_rect
     }
@@ -580,8 +654,8 @@ impl<'a, ComponentId: Clone + Debug + Eq + Hash> Viewport<'a, ComponentId> {
     /// on-screen.
     pub fn translate_rect(&self, rect: impl Into<Rect>) -> ratatui::layout::Rect {
         let draw_rect = self.rect.intersect(rect.into());
-        let x = draw_rect.x - self.rect.x;
-        let y = draw_rect.y - self.rect.y;
+        let x = draw_rect.x - self.rect.x + usize::from(self.buf_area.x).unwrap_isize();
+        let y = draw_rect.y - self.rect.y + usize::from(self.buf_area.y).unwrap_isize();
         let width = draw_rect.width;
         let height = draw_rect.height;
         ratatui::layout::Rect {
@@ -598,21 +672,29 @@ struct TopLevelWidget<'a, C> {
     component: &'a C,
     x: isize,
     y: isize,
+    use_unicode: bool,
 }
 
 impl<C: Component> StatefulWidget for TopLevelWidget<'_, C> {
     type State = DrawnRects<C::Id>;
 
     fn render(self, area: ratatui::layout::Rect, buf: &mut Buffer, state: &mut Self::State) {
-        let Self { component, x, y } = self;
+        let Self {
+            component,
+            x,
+            y,
+            use_unicode,
+        } = self;
         let mut viewport: Viewport<C::Id> = Viewport::new(
             buf,
+            area,
             Rect {
                 x,
                 y,
                 width: area.width.into(),
                 height: area.height.into(),
             },
+            use_unicode,
         );
         viewport.draw_component(0, 0, component);
         *state = viewport.trace.pop().unwrap().components;
@@ -0,0 +1,782 @@
+//! Select changes without a terminal by applying a script, such as for
+//! automated tools or reproducible tests.
+//!
+//! A script consists of one command per line. Blank lines and lines starting
+//! with `#` are ignored. Each command either `select`s or `deselect`s a set of
+//! changes, and commands are applied in order, so later commands take
+//! precedence over earlier ones:
+//!
+//! ```text
+//! # Select everything except changes to the lockfile.
+//! select all
+//! deselect file Cargo.lock
+//!
+//! # Select the third section of changes in `src/main.rs`.
+//! select hunk 3 of src/main.rs
+//!
+//! # Leave out any added or removed debugging statements.
+//! deselect lines matching ^\s*dbg!\( in *.rs
+//! ```
+//!
+//! The supported sets of changes are:
+//!
+//! - `all`: every change in every file.
+//! - `file <GLOB>`: every change in the files matching `GLOB`.
+//! - `hunk <N> of <GLOB>`: the `N`th section of changes in each file matching
+//!   `GLOB`, counting from 1, as numbered in the UI. This includes sections for
+//!   file mode changes, renames, etc. It's an error if a matching file doesn't
+//!   have that many sections.
+//! - `lines matching <REGEX> in <GLOB>`: the added and removed lines in the
+//!   files matching `GLOB` whose contents match `REGEX`. The regex is matched
+//!   against each line without its line ending. The `matching <REGEX>` part may
+//!   be omitted to match all lines, and the command may be prefixed with
+//!   `added` or `removed` to only match lines of that type, as in `added lines
+//!   in *.rs`.
+//! - `mode changes [in <GLOB>]`: changes to the file mode of any file, or of
+//!   the files matching `GLOB`.
+//! - `binary files [in <GLOB>]`: every change in any file with binary contents,
+//!   or in such files matching `GLOB`.
+//!
+//! As in the UI, selecting the deletion of a file also selects all of its
+//! removed lines, and deselecting any of those lines deselects the deletion.
+//!
+//! Glob patterns which don't contain a `/` are matched against the name of each
+//! file. Patterns which do contain a `/` are matched against the whole path.
+//!
+//! Scripts can also be used to pre-select changes before the user makes their
+//! own selection, by applying them to the [`RecordState`] before passing it to
+//! [`crate::Recorder::new`].
+
+use std::iter;
+use std::path::{Path, PathBuf};
+
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use thiserror::Error;
+
+use crate::{ChangeType, Commit, File, RecordState, Section};
+
+/// An error which occurred when parsing or applying a [`SelectionScript`].
+#[allow(missing_docs)]
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    /// A line of the script couldn't be parsed.
+    #[error("line {line_num}: {message}")]
+    Parse { line_num: usize, message: String },
+
+    #[error("line {line_num}: invalid glob {pattern}: {source}")]
+    InvalidGlob {
+        line_num: usize,
+        pattern: String,
+        source: globset::Error,
+    },
+
+    #[error("line {line_num}: invalid regex {pattern}: {source}")]
+    InvalidRegex {
+        line_num: usize,
+        pattern: String,
+        source: regex::Error,
+    },
+
+    /// A `hunk` command referred to a section which doesn't exist in one of the
+    /// matching files.
+    #[error("line {line_num}: {path} has no hunk {hunk} (it has {num_hunks})")]
+    NoSuchHunk {
+        line_num: usize,
+        path: PathBuf,
+        hunk: usize,
+        num_hunks: usize,
+    },
+
+    /// The script tried to change the selection of a read-only
+    /// [`RecordState`].
+    #[error("cannot change the selection of read-only changes")]
+    ReadOnly,
+}
+
+/// A parsed script which selects changes in a [`RecordState`]. See the
+/// [module-level documentation](self) for the syntax.
+#[derive(Clone, Debug)]
+pub struct SelectionScript {
+    commands: Vec<Command>,
+}
+
+impl SelectionScript {
+    /// Parse the given script.
+    pub fn parse(script: &str) -> Result<Self, ScriptError> {
+        let mut commands = Vec::new();
+        for (line_idx, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            commands.push(Command::parse(line_idx + 1, line)?);
+        }
+        Ok(Self { commands })
+    }
+
+    /// Apply the script to `state` and return the updated state, in the same
+    /// way that [`crate::Recorder::run`] would return it after the user
+    /// selected the changes interactively.
+    pub fn apply<'a>(&self, state: RecordState<'a>) -> Result<RecordState<'a>, ScriptError> {
+        let Self { commands } = self;
+        let RecordState {
+            is_read_only,
+            mut commits,
+            mut files,
+        } = state;
+        if is_read_only && !commands.is_empty() {
+            return Err(ScriptError::ReadOnly);
+        }
+
+        for command in commands {
+            command.apply(&mut files)?;
+        }
+
+        // Pad the commits in the same way as `Recorder::new`.
+        commits
+            .extend(iter::repeat_with(Commit::default).take(2_usize.saturating_sub(commits.len())));
+        Ok(RecordState {
+            is_read_only,
+            commits,
+            files,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Command {
+    line_num: usize,
+    is_checked: bool,
+    target: Target,
+}
+
+#[derive(Clone, Debug)]
+enum Target {
+    All,
+    File {
+        path: PathPattern,
+    },
+    Hunk {
+        hunk: usize,
+        path: PathPattern,
+    },
+    Lines {
+        change_type: Option<ChangeType>,
+        pattern: Option<Regex>,
+        path: PathPattern,
+    },
+    ModeChanges {
+        path: Option<PathPattern>,
+    },
+    BinaryFiles {
+        path: Option<PathPattern>,
+    },
+}
+
+impl Command {
+    fn parse(line_num: usize, line: &str) -> Result<Self, ScriptError> {
+        let parse_error = |message: String| ScriptError::Parse { line_num, message };
+
+        let (action, target) = split_word(line);
+        let is_checked = match action {
+            "select" => true,
+            "deselect" => false,
+            action => {
+                return Err(parse_error(format!(
+                    "expected `select` or `deselect`, got `{action}`"
+                )))
+            }
+        };
+
+        let target = match split_word(target) {
+            ("all", "") => Target::All,
+            ("file", path) if !path.is_empty() => Target::File {
+                path: PathPattern::new(line_num, path)?,
+            },
+            ("hunk", args) => {
+                let (hunk, path) = args
+                    .split_once(" of ")
+                    .ok_or_else(|| parse_error("expected `hunk <N> of <GLOB>`".to_string()))?;
+                let hunk = match hunk.trim().parse::<usize>() {
+                    Ok(hunk) if hunk > 0 => hunk,
+                    Ok(_) | Err(_) => {
+                        return Err(parse_error(format!(
+                            "expected a hunk number starting from 1, got `{}`",
+                            hunk.trim()
+                        )))
+                    }
+                };
+                Target::Hunk {
+                    hunk,
+                    path: PathPattern::new(line_num, path.trim())?,
+                }
+            }
+            ("lines", args) => Self::parse_lines(line_num, None, args)?,
+            ("added", args) => match split_word(args) {
+                ("lines", args) => Self::parse_lines(line_num, Some(ChangeType::Added), args)?,
+                _ => return Err(parse_error("expected `added lines`".to_string())),
+            },
+            ("removed", args) => match split_word(args) {
+                ("lines", args) => Self::parse_lines(line_num, Some(ChangeType::Removed), args)?,
+                _ => return Err(parse_error("expected `removed lines`".to_string())),
+            },
+            ("mode", args) => match split_word(args) {
+                ("changes", args) => Target::ModeChanges {
+                    path: Self::parse_optional_path(line_num, args)?,
+                },
+                _ => return Err(parse_error("expected `mode changes`".to_string())),
+            },
+            ("binary", args) => match split_word(args) {
+                ("files", args) => Target::BinaryFiles {
+                    path: Self::parse_optional_path(line_num, args)?,
+                },
+                _ => return Err(parse_error("expected `binary files`".to_string())),
+            },
+            (target, _) => {
+                return Err(parse_error(format!(
+                    "expected `all`, `file <GLOB>`, `hunk <N> of <GLOB>`, `lines matching <REGEX> in <GLOB>`, `mode changes` or `binary files`, got `{target}`"
+                )))
+            }
+        };
+
+        Ok(Self {
+            line_num,
+            is_checked,
+            target,
+        })
+    }
+
+    /// Parse the arguments to `lines`, which are `[matching <REGEX>] in <GLOB>`.
+    fn parse_lines(
+        line_num: usize,
+        change_type: Option<ChangeType>,
+        args: &str,
+    ) -> Result<Target, ScriptError> {
+        let (pattern, path) = match args.strip_prefix("matching ") {
+            Some(args) => match args.rsplit_once(" in ") {
+                Some((pattern, path)) => (Some(pattern.trim()), path),
+                None => (None, ""),
+            },
+            None => (None, args.strip_prefix("in ").unwrap_or_default()),
+        };
+        if path.trim().is_empty() {
+            return Err(ScriptError::Parse {
+                line_num,
+                message: "expected `lines [matching <REGEX>] in <GLOB>`".to_string(),
+            });
+        }
+        let pattern = match pattern {
+            Some(pattern) => {
+                Some(
+                    Regex::new(pattern).map_err(|err| ScriptError::InvalidRegex {
+                        line_num,
+                        pattern: pattern.to_string(),
+                        source: err,
+                    })?,
+                )
+            }
+            None => None,
+        };
+        Ok(Target::Lines {
+            change_type,
+            pattern,
+            path: PathPattern::new(line_num, path.trim())?,
+        })
+    }
+
+    /// Parse an optional `in <GLOB>`.
+    fn parse_optional_path(
+        line_num: usize,
+        args: &str,
+    ) -> Result<Option<PathPattern>, ScriptError> {
+        if args.is_empty() {
+            return Ok(None);
+        }
+        match split_word(args) {
+            ("in", path) if !path.is_empty() => Ok(Some(PathPattern::new(line_num, path)?)),
+            _ => Err(ScriptError::Parse {
+                line_num,
+                message: format!("expected `in <GLOB>`, got `{args}`"),
+            }),
+        }
+    }
+
+    fn apply(&self, files: &mut [File]) -> Result<(), ScriptError> {
+        let Self {
+            line_num,
+            is_checked,
+            target,
+        } = self;
+        match target {
+            Target::All => {
+                for file in files {
+                    file.set_checked(*is_checked);
+                }
+            }
+
+            Target::File { path } => {
+                for file in files.iter_mut().filter(|file| path.is_match(&file.path)) {
+                    file.set_checked(*is_checked);
+                }
+            }
+
+            Target::Hunk { hunk, path } => {
+                for file in files.iter_mut().filter(|file| path.is_match(&file.path)) {
+                    let section_idx = file
+                        .sections
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, section)| section.is_editable())
+                        .map(|(section_idx, _)| section_idx)
+                        .nth(hunk - 1);
+                    match section_idx {
+                        Some(section_idx) => file.set_section_checked(section_idx, *is_checked),
+                        None => {
+                            return Err(ScriptError::NoSuchHunk {
+                                line_num: *line_num,
+                                path: file.path.to_path_buf(),
+                                hunk: *hunk,
+                                num_hunks: file
+                                    .sections
+                                    .iter()
+                                    .filter(|section| section.is_editable())
+                                    .count(),
+                            })
+                        }
+                    }
+                }
+            }
+
+            Target::Lines {
+                change_type,
+                pattern,
+                path,
+            } => {
+                for file in files.iter_mut().filter(|file| path.is_match(&file.path)) {
+                    let mut line_idxs = Vec::new();
+                    for (section_idx, section) in file.sections.iter().enumerate() {
+                        match section {
+                            Section::Changed { lines } => {
+                                for (line_idx, line) in lines.iter().enumerate() {
+                                    let type_matches = match change_type {
+                                        Some(change_type) => line.change_type == *change_type,
+                                        None => true,
+                                    };
+                                    let contents = line.line.trim_end_matches(['\n', '\r']);
+                                    let contents_match = match pattern {
+                                        Some(pattern) => pattern.is_match(contents),
+                                        None => true,
+                                    };
+                                    if type_matches && contents_match {
+                                        line_idxs.push((section_idx, line_idx));
+                                    }
+                                }
+                            }
+                            Section::Unchanged { .. }
+                            | Section::FileMode { .. }
+                            | Section::Binary { .. }
+                            | Section::Submodule { .. }
+                            | Section::Summary { .. }
+                            | Section::Rename { .. } => {}
+                        }
+                    }
+                    for (section_idx, line_idx) in line_idxs {
+                        file.set_line_checked(section_idx, line_idx, *is_checked);
+                    }
+                }
+            }
+
+            Target::ModeChanges { path } => {
+                for file in files
+                    .iter_mut()
+                    .filter(|file| PathPattern::is_optional_match(path, &file.path))
+                {
+                    let section_idxs: Vec<_> = file
+                        .sections
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(section_idx, section)| match section {
+                            Section::FileMode { .. } => Some(section_idx),
+                            Section::Unchanged { .. }
+                            | Section::Changed { .. }
+                            | Section::Binary { .. }
+                            | Section::Submodule { .. }
+                            | Section::Summary { .. }
+                            | Section::Rename { .. } => None,
+                        })
+                        .collect();
+                    for section_idx in section_idxs {
+                        file.set_section_checked(section_idx, *is_checked);
+                    }
+                }
+            }
+
+            Target::BinaryFiles { path } => {
+                for file in files
+                    .iter_mut()
+                    .filter(|file| PathPattern::is_optional_match(path, &file.path))
+                {
+                    let is_binary = file
+                        .sections
+                        .iter()
+                        .any(|section| matches!(section, Section::Binary { .. }));
+                    if is_binary {
+                        file.set_checked(*is_checked);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split off the first whitespace-separated word of `s`.
+fn split_word(s: &str) -> (&str, &str) {
+    match s.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim_start()),
+        None => (s, ""),
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PathPattern {
+    matcher: GlobMatcher,
+
+    /// Whether the pattern is matched against the whole path, rather than
+    /// just the file name.
+    is_path_pattern: bool,
+}
+
+impl PathPattern {
+    fn new(line_num: usize, pattern: &str) -> Result<Self, ScriptError> {
+        let is_path_pattern = pattern.contains('/');
+        let glob = GlobBuilder::new(pattern.trim_start_matches('/'))
+            .literal_separator(true)
+            .build()
+            .map_err(|err| ScriptError::InvalidGlob {
+                line_num,
+                pattern: pattern.to_string(),
+                source: err,
+            })?;
+        Ok(Self {
+            matcher: glob.compile_matcher(),
+            is_path_pattern,
+        })
+    }
+
+    /// Whether `path` matches `pattern`, where a missing pattern matches every
+    /// path.
+    fn is_optional_match(pattern: &Option<Self>, path: &Path) -> bool {
+        match pattern {
+            Some(pattern) => pattern.is_match(path),
+            None => true,
+        }
+    }
+
+    fn is_match(&self, path: &Path) -> bool {
+        let Self {
+            matcher,
+            is_path_pattern,
+        } = self;
+        if *is_path_pattern {
+            matcher.is_match(path)
+        } else {
+            match path.file_name() {
+                Some(file_name) => matcher.is_match(file_name),
+                None => false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use assert_matches::assert_matches;
+
+    use crate::helpers::TestingInput;
+    use crate::{Event, FileMode, Recorder, SectionChangedLine};
+
+    use super::*;
+
+    fn changed_line(change_type: ChangeType, line: &'static str) -> SectionChangedLine<'static> {
+        SectionChangedLine {
+            is_checked: false,
+            change_type,
+            line: Cow::Borrowed(line),
+        }
+    }
+
+    fn example_state() -> RecordState<'static> {
+        RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files: vec![
+                File {
+                    old_path: None,
+                    path: Cow::Borrowed(Path::new("Cargo.lock")),
+                    file_mode: FileMode::FILE_DEFAULT,
+                    sections: vec![Section::Changed {
+                        lines: vec![changed_line(ChangeType::Added, "version = 2\n")],
+                    }],
+                },
+                File {
+                    old_path: None,
+                    path: Cow::Borrowed(Path::new("src/main.rs")),
+                    file_mode: FileMode::FILE_DEFAULT,
+                    sections: vec![
+                        Section::Changed {
+                            lines: vec![
+                                changed_line(ChangeType::Removed, "fn foo() {}\n"),
+                                changed_line(ChangeType::Added, "fn foo() {\n"),
+                                changed_line(ChangeType::Added, "    dbg!(1);\n"),
+                                changed_line(ChangeType::Added, "}\n"),
+                            ],
+                        },
+                        Section::Unchanged {
+                            lines: vec![Cow::Borrowed("\n")],
+                        },
+                        Section::Changed {
+                            lines: vec![changed_line(ChangeType::Added, "fn bar() {}\n")],
+                        },
+                    ],
+                },
+                File {
+                    old_path: None,
+                    path: Cow::Borrowed(Path::new("logo.png")),
+                    file_mode: FileMode::FILE_DEFAULT,
+                    sections: vec![Section::Binary {
+                        is_checked: false,
+                        old_description: None,
+                        new_description: Some(Cow::Borrowed("1 KB")),
+                    }],
+                },
+                File {
+                    old_path: None,
+                    path: Cow::Borrowed(Path::new("run.sh")),
+                    file_mode: FileMode::FILE_DEFAULT,
+                    sections: vec![
+                        Section::FileMode {
+                            is_checked: false,
+                            mode: FileMode::Unix(0o100755),
+                        },
+                        Section::Changed {
+                            lines: vec![changed_line(ChangeType::Removed, "exit 1\n")],
+                        },
+                    ],
+                },
+            ],
+        }
+    }
+
+    fn selected_lines(state: &RecordState) -> Vec<String> {
+        let mut result = Vec::new();
+        for file in &state.files {
+            for section in &file.sections {
+                match section {
+                    Section::Changed { lines } => {
+                        for line in lines.iter().filter(|line| line.is_checked) {
+                            result.push(format!(
+                                "{}: {}",
+                                file.path.display(),
+                                line.line.trim_end()
+                            ));
+                        }
+                    }
+                    Section::FileMode {
+                        is_checked: true,
+                        mode,
+                    } => {
+                        result.push(format!("{}: mode {mode}", file.path.display()));
+                    }
+                    Section::Binary {
+                        is_checked: true, ..
+                    } => {
+                        result.push(format!("{}: binary", file.path.display()));
+                    }
+                    Section::Unchanged { .. }
+                    | Section::FileMode { .. }
+                    | Section::Binary { .. }
+                    | Section::Submodule { .. }
+                    | Section::Summary { .. }
+                    | Section::Rename { .. } => {}
+                }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_apply_script() -> Result<(), ScriptError> {
+        let script = SelectionScript::parse(
+            r"
+            # Everything except the lockfile and debugging statements.
+            select all
+            deselect file Cargo.lock
+            deselect lines matching ^\s*dbg!\( in src/*.rs
+            ",
+        )?;
+        let state = script.apply(example_state())?;
+        insta::assert_debug_snapshot!(selected_lines(&state), @r#"
+        [
+            "src/main.rs: fn foo() {}",
+            "src/main.rs: fn foo() {",
+            "src/main.rs: }",
+            "src/main.rs: fn bar() {}",
+            "logo.png: binary",
+            "run.sh: mode 100755",
+            "run.sh: exit 1",
+        ]
+        "#);
+
+        let script = SelectionScript::parse("select hunk 2 of main.rs")?;
+        let state = script.apply(example_state())?;
+        insta::assert_debug_snapshot!(selected_lines(&state), @r#"
+        [
+            "src/main.rs: fn bar() {}",
+        ]
+        "#);
+
+        let script = SelectionScript::parse(
+            "
+            select added lines in src/*
+            select removed lines matching exit in *.sh
+            select mode changes
+            select binary files in *.png
+            ",
+        )?;
+        let state = script.apply(example_state())?;
+        insta::assert_debug_snapshot!(selected_lines(&state), @r#"
+        [
+            "src/main.rs: fn foo() {",
+            "src/main.rs:     dbg!(1);",
+            "src/main.rs: }",
+            "src/main.rs: fn bar() {}",
+            "logo.png: binary",
+            "run.sh: mode 100755",
+            "run.sh: exit 1",
+        ]
+        "#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_script_matches_recorder() -> Result<(), Box<dyn std::error::Error>> {
+        let script = SelectionScript::parse("select file main.rs")?;
+        let scripted_state = script.apply(example_state())?;
+
+        let mut input = TestingInput::new(
+            80,
+            20,
+            [
+                Event::FocusNextSameKind,
+                Event::ToggleItem,
+                Event::QuitAccept,
+            ],
+        );
+        let recorded_state = Recorder::new(example_state(), &mut input).run()?;
+
+        assert_eq!(scripted_state, recorded_state);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_script_deleted_file() -> Result<(), Box<dyn std::error::Error>> {
+        let state = RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files: vec![File {
+                old_path: None,
+                path: Cow::Borrowed(Path::new("old.txt")),
+                file_mode: FileMode::FILE_DEFAULT,
+                sections: vec![
+                    Section::FileMode {
+                        is_checked: false,
+                        mode: FileMode::Absent,
+                    },
+                    Section::Changed {
+                        lines: vec![
+                            changed_line(ChangeType::Removed, "foo\n"),
+                            changed_line(ChangeType::Removed, "bar\n"),
+                        ],
+                    },
+                ],
+            }],
+        };
+
+        // Selecting the deletion selects every line.
+        let script = SelectionScript::parse("select mode changes")?;
+        let scripted_state = script.apply(state.clone())?;
+        insta::assert_debug_snapshot!(selected_lines(&scripted_state), @r#"
+        [
+            "old.txt: mode <absent>",
+            "old.txt: foo",
+            "old.txt: bar",
+        ]
+        "#);
+
+        // Keeping a line means that the file can't be deleted.
+        let script =
+            SelectionScript::parse("select mode changes\ndeselect lines matching foo in *")?;
+        let scripted_state = script.apply(state.clone())?;
+        insta::assert_debug_snapshot!(selected_lines(&scripted_state), @r#"
+        [
+            "old.txt: bar",
+        ]
+        "#);
+
+        let mut input = TestingInput::new(
+            80,
+            20,
+            [
+                Event::ExpandItem,
+                Event::FocusNext,
+                Event::ToggleItem,
+                Event::FocusNext,
+                Event::FocusNext,
+                Event::ToggleItem,
+                Event::QuitAccept,
+            ],
+        );
+        let recorded_state = Recorder::new(state, &mut input).run()?;
+        assert_eq!(scripted_state, recorded_state);
+        Ok(())
+    }
+
+    #[test]
+    fn test_script_errors() {
+        insta::assert_snapshot!(SelectionScript::parse("# comment\nfoo all").unwrap_err(), @"line 2: expected `select` or `deselect`, got `foo`");
+        insta::assert_snapshot!(SelectionScript::parse("select everything").unwrap_err(), @"line 1: expected `all`, `file <GLOB>`, `hunk <N> of <GLOB>`, `lines matching <REGEX> in <GLOB>`, `mode changes` or `binary files`, got `everything`");
+        insta::assert_snapshot!(SelectionScript::parse("select lines matching foo").unwrap_err(), @"line 1: expected `lines [matching <REGEX>] in <GLOB>`");
+        insta::assert_snapshot!(SelectionScript::parse("select mode changes for foo").unwrap_err(), @"line 1: expected `in <GLOB>`, got `for foo`");
+        insta::assert_snapshot!(SelectionScript::parse("select hunk 0 of foo").unwrap_err(), @"line 1: expected a hunk number starting from 1, got `0`");
+        insta::assert_snapshot!(
+            SelectionScript::parse("select lines matching ( in foo").unwrap_err(),
+            @"
+        line 1: invalid regex (: regex parse error:
+            (
+            ^
+        error: unclosed group
+        "
+        );
+        insta::assert_snapshot!(
+            SelectionScript::parse("select hunk 3 of src/main.rs")
+                .unwrap()
+                .apply(example_state())
+                .unwrap_err(),
+            @"line 1: src/main.rs has no hunk 3 (it has 2)"
+        );
+        assert_matches!(
+            SelectionScript::parse("select all")
+                .unwrap()
+                .apply(RecordState {
+                    is_read_only: true,
+                    ..example_state()
+                }),
+            Err(ScriptError::ReadOnly)
+        );
+    }
+}
@@ -0,0 +1,323 @@
+//! Counts of the selected items in each file and section, so that their
+//! tristates can be found without scanning all of their lines.
+
+use crate::{File, Section, Tristate};
+
+/// The number of items in a file or section, and how many of them are
+/// selected. An item is a changed line, or a section which is toggled as a
+/// whole, such as a mode change.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct SelectionCount {
+    pub selected: usize,
+    pub total: usize,
+}
+
+impl SelectionCount {
+    /// Count the items in `section`.
+    pub fn of_section(section: &Section) -> Self {
+        match section {
+            Section::Unchanged { .. } => Self::default(),
+            Section::Changed { lines } => Self {
+                selected: lines.iter().filter(|line| line.is_checked).count(),
+                total: lines.len(),
+            },
+            Section::FileMode {
+                is_checked,
+                mode: _,
+            }
+            | Section::Binary {
+                is_checked,
+                old_description: _,
+                new_description: _,
+            }
+            | Section::Submodule {
+                is_checked,
+                old_commit_id: _,
+                new_commit_id: _,
+            }
+            | Section::Summary {
+                is_checked,
+                description: _,
+                old_contents: _,
+                new_contents: _,
+            }
+            | Section::Rename { is_checked } => Self {
+                selected: usize::from(*is_checked),
+                total: 1,
+            },
+        }
+    }
+
+    /// The tristate value for these items. This is the same as
+    /// [`Section::tristate`] or [`File::tristate`] for the items that were
+    /// counted.
+    pub fn tristate(self) -> Tristate {
+        let Self { selected, total } = self;
+        if selected == 0 {
+            Tristate::False
+        } else if selected == total {
+            Tristate::True
+        } else {
+            Tristate::Partial
+        }
+    }
+
+    /// The same items, but with all of them selected or unselected.
+    fn with_checked(self, checked: bool) -> Self {
+        let Self { selected: _, total } = self;
+        Self {
+            selected: if checked { total } else { 0 },
+            total,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct FileCounts {
+    file: SelectionCount,
+    sections: Vec<SelectionCount>,
+}
+
+impl FileCounts {
+    /// Replace the count for the section at `section_idx`, keeping the count
+    /// for the whole file consistent.
+    fn set_section(&mut self, section_idx: usize, count: SelectionCount) {
+        let Self { file, sections } = self;
+        let old_count = &mut sections[section_idx];
+        file.selected = file.selected - old_count.selected + count.selected;
+        file.total = file.total - old_count.total + count.total;
+        *old_count = count;
+    }
+}
+
+/// The [`SelectionCount`]s for every file and section in a set of files. These
+/// need to be updated whenever the files' items are checked or unchecked.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct SelectionCounts {
+    files: Vec<FileCounts>,
+}
+
+impl SelectionCounts {
+    /// Count the items in all of `files`.
+    pub fn new(files: &[File]) -> Self {
+        Self {
+            files: files.iter().map(Self::count_file).collect(),
+        }
+    }
+
+    fn count_file(file: &File) -> FileCounts {
+        let sections: Vec<_> = file
+            .sections
+            .iter()
+            .map(SelectionCount::of_section)
+            .collect();
+        let file = sections.iter().fold(
+            SelectionCount::default(),
+            |acc, SelectionCount { selected, total }| SelectionCount {
+                selected: acc.selected + selected,
+                total: acc.total + total,
+            },
+        );
+        FileCounts { file, sections }
+    }
+
+    /// The count for the file at `file_idx`, if there is one.
+    pub fn file(&self, file_idx: usize) -> Option<SelectionCount> {
+        self.files.get(file_idx).map(|file_counts| file_counts.file)
+    }
+
+    /// The count for the section at `section_idx` in the file at `file_idx`,
+    /// if there is one.
+    pub fn section(&self, file_idx: usize, section_idx: usize) -> Option<SelectionCount> {
+        self.files
+            .get(file_idx)
+            .and_then(|file_counts| file_counts.sections.get(section_idx))
+            .copied()
+    }
+
+    /// Update the counts after a single line in a section was checked or
+    /// unchecked.
+    pub fn toggle_line(&mut self, file_idx: usize, section_idx: usize, is_checked: bool) {
+        let file_counts = &mut self.files[file_idx];
+        let SelectionCount { selected, total } = file_counts.sections[section_idx];
+        // Clamp the count, so that a toggle which didn't change the line
+        // can't make it out of range.
+        let selected = if is_checked {
+            (selected + 1).min(total)
+        } else {
+            selected.saturating_sub(1)
+        };
+        file_counts.set_section(section_idx, SelectionCount { selected, total });
+    }
+
+    /// Update the counts after all of the items in a section were checked or
+    /// unchecked, as with [`Section::set_checked`].
+    pub fn set_section_checked(&mut self, file_idx: usize, section_idx: usize, checked: bool) {
+        let file_counts = &mut self.files[file_idx];
+        let count = file_counts.sections[section_idx].with_checked(checked);
+        file_counts.set_section(section_idx, count);
+    }
+
+    /// Update the counts after all of the items in a file were checked or
+    /// unchecked, as with [`File::set_checked`].
+    pub fn set_file_checked(&mut self, file_idx: usize, checked: bool) {
+        let FileCounts { file, sections } = &mut self.files[file_idx];
+        *file = file.with_checked(checked);
+        for section in sections {
+            *section = section.with_checked(checked);
+        }
+    }
+
+    /// Count the items in `section` again, after some of them were checked or
+    /// unchecked.
+    pub fn update_section(&mut self, file_idx: usize, section_idx: usize, section: &Section) {
+        self.files[file_idx].set_section(section_idx, SelectionCount::of_section(section));
+    }
+
+    /// Count the items in `file` again, after some of them were checked or
+    /// unchecked.
+    pub fn update_file(&mut self, file_idx: usize, file: &File) {
+        self.files[file_idx] = Self::count_file(file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::path::Path;
+
+    use proptest::prelude::*;
+
+    use crate::{ChangeType, FileMode, SectionChangedLine};
+
+    use super::*;
+
+    fn section_strategy() -> impl Strategy<Value = Section<'static>> {
+        prop_oneof![
+            Just(Section::Unchanged {
+                lines: vec![Cow::Borrowed("foo\n")],
+            }),
+            proptest::collection::vec(any::<bool>(), 0..8).prop_map(|is_checked| {
+                Section::Changed {
+                    lines: is_checked
+                        .into_iter()
+                        .map(|is_checked| SectionChangedLine {
+                            is_checked,
+                            change_type: ChangeType::Added,
+                            line: Cow::Borrowed("foo\n"),
+                        })
+                        .collect(),
+                }
+            }),
+            any::<bool>().prop_map(|is_checked| Section::FileMode {
+                is_checked,
+                mode: FileMode::Absent,
+            }),
+            any::<bool>().prop_map(|is_checked| Section::Rename { is_checked }),
+        ]
+    }
+
+    fn files_strategy() -> impl Strategy<Value = Vec<File<'static>>> {
+        proptest::collection::vec(
+            proptest::collection::vec(section_strategy(), 0..5).prop_map(|sections| File {
+                old_path: None,
+                path: Cow::Borrowed(Path::new("foo")),
+                file_mode: FileMode::FILE_DEFAULT,
+                sections,
+            }),
+            1..4,
+        )
+    }
+
+    #[derive(Clone, Debug)]
+    enum Operation {
+        ToggleLine(usize, usize, usize),
+        SetSectionChecked(usize, usize, bool),
+        SetFileChecked(usize, bool),
+        ToggleSection(usize, usize),
+    }
+
+    fn operation_strategy() -> impl Strategy<Value = Operation> {
+        prop_oneof![
+            (any::<usize>(), any::<usize>(), any::<usize>())
+                .prop_map(|(i, j, k)| Operation::ToggleLine(i, j, k)),
+            (any::<usize>(), any::<usize>(), any::<bool>())
+                .prop_map(|(i, j, checked)| Operation::SetSectionChecked(i, j, checked)),
+            (any::<usize>(), any::<bool>())
+                .prop_map(|(i, checked)| Operation::SetFileChecked(i, checked)),
+            (any::<usize>(), any::<usize>()).prop_map(|(i, j)| Operation::ToggleSection(i, j)),
+        ]
+    }
+
+    /// Apply `operation` to both `files` and `counts`. Indexes are taken
+    /// modulo the number of files, sections or lines.
+    fn apply(files: &mut [File], counts: &mut SelectionCounts, operation: Operation) {
+        let file_idx = |i: usize| i % files.len();
+        match operation {
+            Operation::ToggleLine(i, j, k) => {
+                let file_idx = file_idx(i);
+                let sections = &mut files[file_idx].sections;
+                if sections.is_empty() {
+                    return;
+                }
+                let section_idx = j % sections.len();
+                if let Section::Changed { lines } = &mut sections[section_idx] {
+                    if !lines.is_empty() {
+                        let line_idx = k % lines.len();
+                        let line = &mut lines[line_idx];
+                        line.is_checked = !line.is_checked;
+                        counts.toggle_line(file_idx, section_idx, line.is_checked);
+                    }
+                }
+            }
+            Operation::SetSectionChecked(i, j, checked) => {
+                let file_idx = file_idx(i);
+                let sections = &mut files[file_idx].sections;
+                if sections.is_empty() {
+                    return;
+                }
+                let section_idx = j % sections.len();
+                sections[section_idx].set_checked(checked);
+                counts.set_section_checked(file_idx, section_idx, checked);
+            }
+            Operation::SetFileChecked(i, checked) => {
+                let file_idx = file_idx(i);
+                files[file_idx].set_checked(checked);
+                counts.set_file_checked(file_idx, checked);
+            }
+            Operation::ToggleSection(i, j) => {
+                let file_idx = file_idx(i);
+                let sections = &mut files[file_idx].sections;
+                if sections.is_empty() {
+                    return;
+                }
+                let section_idx = j % sections.len();
+                sections[section_idx].toggle_all();
+                counts.update_section(file_idx, section_idx, &sections[section_idx]);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_selection_counts_match_recount(
+            mut files in files_strategy(),
+            operations in proptest::collection::vec(operation_strategy(), 0..20),
+        ) {
+            let mut counts = SelectionCounts::new(&files);
+            for operation in operations {
+                apply(&mut files, &mut counts, operation);
+                prop_assert_eq!(&counts, &SelectionCounts::new(&files));
+            }
+            for (file_idx, file) in files.iter().enumerate() {
+                prop_assert_eq!(counts.file(file_idx).unwrap().tristate(), file.tristate());
+                for (section_idx, section) in file.sections.iter().enumerate() {
+                    prop_assert_eq!(
+                        counts.section(file_idx, section_idx).unwrap().tristate(),
+                        section.tristate()
+                    );
+                }
+            }
+        }
+    }
+}
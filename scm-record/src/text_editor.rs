@@ -0,0 +1,231 @@
+//! A minimal multi-line text editor, used to edit commit messages without
+//! leaving the UI.
+
+/// The contents of a text editor and the position of its cursor. Positions
+/// are measured in `char`s.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEditor {
+    lines: Vec<String>,
+    cursor_row: usize,
+    cursor_col: usize,
+}
+
+impl TextEditor {
+    /// Constructor. The cursor starts at the beginning of the text.
+    pub fn new(text: &str) -> Self {
+        Self {
+            lines: text.split('\n').map(str::to_owned).collect(),
+            cursor_row: 0,
+            cursor_col: 0,
+        }
+    }
+
+    /// The edited text.
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// The lines of the edited text, not including their line endings.
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    /// The row and column of the cursor.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.cursor_row, self.cursor_col)
+    }
+
+    fn current_line(&self) -> &str {
+        &self.lines[self.cursor_row]
+    }
+
+    fn current_line_len(&self) -> usize {
+        self.current_line().chars().count()
+    }
+
+    /// The byte offset of the cursor into the current line.
+    fn cursor_byte_idx(&self) -> usize {
+        let line = self.current_line();
+        line.char_indices()
+            .nth(self.cursor_col)
+            .map(|(idx, _)| idx)
+            .unwrap_or(line.len())
+    }
+
+    /// Insert a character at the cursor and move the cursor past it.
+    pub fn insert_char(&mut self, c: char) {
+        if c == '\n' {
+            self.insert_newline();
+        } else {
+            let idx = self.cursor_byte_idx();
+            self.lines[self.cursor_row].insert(idx, c);
+            self.cursor_col += 1;
+        }
+    }
+
+    /// Insert text at the cursor, such as when it's pasted, and move the
+    /// cursor past it. Any line endings are normalized to `\n`.
+    pub fn insert_str(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        for c in text.chars() {
+            self.insert_char(c);
+        }
+    }
+
+    /// Split the current line at the cursor.
+    pub fn insert_newline(&mut self) {
+        let idx = self.cursor_byte_idx();
+        let rest = self.lines[self.cursor_row].split_off(idx);
+        self.cursor_row += 1;
+        self.cursor_col = 0;
+        self.lines.insert(self.cursor_row, rest);
+    }
+
+    /// Delete the character before the cursor, joining the current line onto
+    /// the previous one if the cursor is at the start of the line.
+    pub fn delete_backward(&mut self) {
+        if self.cursor_col > 0 {
+            self.cursor_col -= 1;
+            let idx = self.cursor_byte_idx();
+            self.lines[self.cursor_row].remove(idx);
+        } else if self.cursor_row > 0 {
+            let line = self.lines.remove(self.cursor_row);
+            self.cursor_row -= 1;
+            self.cursor_col = self.current_line_len();
+            self.lines[self.cursor_row].push_str(&line);
+        }
+    }
+
+    /// Delete the character after the cursor, joining the next line onto the
+    /// current one if the cursor is at the end of the line.
+    pub fn delete_forward(&mut self) {
+        if self.cursor_col < self.current_line_len() {
+            let idx = self.cursor_byte_idx();
+            self.lines[self.cursor_row].remove(idx);
+        } else if self.cursor_row + 1 < self.lines.len() {
+            let line = self.lines.remove(self.cursor_row + 1);
+            self.lines[self.cursor_row].push_str(&line);
+        }
+    }
+
+    /// Move the cursor one character to the left, wrapping to the end of the
+    /// previous line.
+    pub fn move_left(&mut self) {
+        if self.cursor_col > 0 {
+            self.cursor_col -= 1;
+        } else if self.cursor_row > 0 {
+            self.cursor_row -= 1;
+            self.cursor_col = self.current_line_len();
+        }
+    }
+
+    /// Move the cursor one character to the right, wrapping to the start of
+    /// the next line.
+    pub fn move_right(&mut self) {
+        if self.cursor_col < self.current_line_len() {
+            self.cursor_col += 1;
+        } else if self.cursor_row + 1 < self.lines.len() {
+            self.cursor_row += 1;
+            self.cursor_col = 0;
+        }
+    }
+
+    /// Move the cursor to the previous line, or to the start of the text if
+    /// it's on the first line.
+    pub fn move_up(&mut self) {
+        if self.cursor_row > 0 {
+            self.cursor_row -= 1;
+            self.cursor_col = self.cursor_col.min(self.current_line_len());
+        } else {
+            self.cursor_col = 0;
+        }
+    }
+
+    /// Move the cursor to the next line, or to the end of the text if it's on
+    /// the last line.
+    pub fn move_down(&mut self) {
+        if self.cursor_row + 1 < self.lines.len() {
+            self.cursor_row += 1;
+            self.cursor_col = self.cursor_col.min(self.current_line_len());
+        } else {
+            self.cursor_col = self.current_line_len();
+        }
+    }
+
+    /// Move the cursor to the start of the current line.
+    pub fn move_home(&mut self) {
+        self.cursor_col = 0;
+    }
+
+    /// Move the cursor to the end of the current line.
+    pub fn move_end(&mut self) {
+        self.cursor_col = self.current_line_len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render the text with a `|` at the cursor position.
+    fn show(editor: &TextEditor) -> String {
+        let (cursor_row, cursor_col) = editor.cursor();
+        let mut lines = editor.lines().to_vec();
+        let line = &mut lines[cursor_row];
+        let idx = line
+            .char_indices()
+            .nth(cursor_col)
+            .map(|(idx, _)| idx)
+            .unwrap_or(line.len());
+        line.insert(idx, '|');
+        lines.join("\n")
+    }
+
+    #[test]
+    fn test_insert_and_delete() {
+        let mut editor = TextEditor::new("");
+        editor.insert_str("héllo\r\nworld");
+        assert_eq!(show(&editor), "héllo\nworld|");
+
+        editor.move_up();
+        assert_eq!(show(&editor), "héllo|\nworld");
+        editor.move_left();
+        editor.move_left();
+        editor.move_left();
+        editor.move_left();
+        editor.delete_backward();
+        assert_eq!(show(&editor), "|éllo\nworld");
+        editor.delete_backward();
+        assert_eq!(show(&editor), "|éllo\nworld");
+        editor.insert_char('H');
+        editor.move_end();
+        editor.delete_forward();
+        assert_eq!(show(&editor), "Héllo|world");
+        editor.insert_newline();
+        editor.insert_newline();
+        assert_eq!(show(&editor), "Héllo\n\n|world");
+        editor.delete_backward();
+        assert_eq!(show(&editor), "Héllo\n|world");
+        assert_eq!(editor.text(), "Héllo\nworld");
+    }
+
+    #[test]
+    fn test_move_cursor() {
+        let mut editor = TextEditor::new("a long line\nshort\n");
+        editor.move_end();
+        editor.move_down();
+        assert_eq!(show(&editor), "a long line\nshort|\n");
+        editor.move_right();
+        assert_eq!(show(&editor), "a long line\nshort\n|");
+        editor.move_down();
+        editor.move_right();
+        assert_eq!(show(&editor), "a long line\nshort\n|");
+        editor.move_left();
+        editor.move_up();
+        assert_eq!(show(&editor), "a lon|g line\nshort\n");
+        editor.move_up();
+        assert_eq!(show(&editor), "|a long line\nshort\n");
+        editor.move_left();
+        assert_eq!(show(&editor), "|a long line\nshort\n");
+    }
+}
@@ -0,0 +1,215 @@
+//! Colours and styles used to render the UI.
+
+use std::ffi::OsStr;
+
+pub use ratatui::style::{Color, Modifier, Style};
+
+use crate::consts::ENV_VAR_NO_COLOR;
+
+/// The styles used to render each part of the UI.
+///
+/// Use one of the presets, such as [`Theme::dark`] or [`Theme::light`], or
+/// construct a custom theme. The default theme is [`Theme::dark`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Theme {
+    /// The style of added lines.
+    pub added_line: Style,
+
+    /// The style of removed lines.
+    pub removed_line: Style,
+
+    /// The style of unchanged lines shown for context.
+    pub unchanged_line: Style,
+
+    /// The style of the markers used in place of control characters and
+    /// other invisible characters.
+    pub control_character: Style,
+
+    /// The style of trailing whitespace in added lines, if it is highlighted.
+    pub trailing_whitespace: Style,
+
+    /// The style of descriptions of non-line changes, such as file mode
+    /// changes and renames.
+    pub section_info: Style,
+
+    /// The style of the path of the focused file.
+    pub focused_path: Style,
+
+    /// The style of checkboxes and expand boxes.
+    pub toggle_box: Style,
+
+    /// The style of checkboxes when the UI is read-only.
+    pub read_only_toggle_box: Style,
+
+    /// The style of warnings, such as about commit messages which don't
+    /// follow the usual conventions.
+    pub warning: Style,
+
+    /// The style applied on top of the focused item and the menu bar.
+    pub highlight: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    /// A theme suitable for terminals with a dark background.
+    pub fn dark() -> Self {
+        Self {
+            added_line: Style::new().fg(Color::Green),
+            removed_line: Style::new().fg(Color::Red),
+            unchanged_line: Style::new().add_modifier(Modifier::DIM),
+            control_character: Style::new().fg(Color::DarkGray),
+            trailing_whitespace: Style::new().bg(Color::Red),
+            section_info: Style::new().fg(Color::Blue),
+            focused_path: Style::new().fg(Color::Blue),
+            toggle_box: Style::new().add_modifier(Modifier::BOLD),
+            read_only_toggle_box: Style::new().fg(Color::Gray).add_modifier(Modifier::DIM),
+            warning: Style::new().fg(Color::Yellow),
+            highlight: Style::new().add_modifier(Modifier::REVERSED),
+        }
+    }
+
+    /// A theme suitable for terminals with a light background.
+    pub fn light() -> Self {
+        Self {
+            added_line: Style::new().fg(Color::Indexed(28)),
+            removed_line: Style::new().fg(Color::Indexed(124)),
+            unchanged_line: Style::new().fg(Color::Indexed(242)),
+            control_character: Style::new().fg(Color::Indexed(247)),
+            trailing_whitespace: Style::new().bg(Color::Indexed(217)),
+            section_info: Style::new().fg(Color::Indexed(25)),
+            focused_path: Style::new().fg(Color::Indexed(25)),
+            toggle_box: Style::new().add_modifier(Modifier::BOLD),
+            read_only_toggle_box: Style::new().fg(Color::Indexed(247)),
+            warning: Style::new().fg(Color::Indexed(130)),
+            highlight: Style::new().add_modifier(Modifier::REVERSED),
+        }
+    }
+
+    /// A theme which uses bright colours and avoids dimmed text.
+    pub fn high_contrast() -> Self {
+        Self {
+            added_line: Style::new()
+                .fg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+            removed_line: Style::new()
+                .fg(Color::LightRed)
+                .add_modifier(Modifier::BOLD),
+            unchanged_line: Style::new(),
+            control_character: Style::new().fg(Color::Yellow),
+            trailing_whitespace: Style::new().bg(Color::LightRed),
+            section_info: Style::new()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+            focused_path: Style::new()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+            toggle_box: Style::new().add_modifier(Modifier::BOLD),
+            read_only_toggle_box: Style::new().fg(Color::Gray),
+            warning: Style::new()
+                .fg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+            highlight: Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+        }
+    }
+
+    /// A theme which distinguishes added and removed lines with blue and
+    /// orange, rather than green and red, for users with red-green colour
+    /// blindness.
+    pub fn colour_blind() -> Self {
+        Self {
+            added_line: Style::new().fg(Color::Indexed(33)),
+            removed_line: Style::new().fg(Color::Indexed(208)),
+            trailing_whitespace: Style::new().bg(Color::Indexed(208)),
+            section_info: Style::new().fg(Color::Indexed(45)),
+            focused_path: Style::new().fg(Color::Indexed(45)),
+            ..Self::dark()
+        }
+    }
+
+    /// A theme which doesn't use any colours. Changes are distinguished only
+    /// by their text markers (such as `+` and `-`), and the focused item by
+    /// reversed video.
+    pub fn monochrome() -> Self {
+        Self {
+            added_line: Style::new(),
+            removed_line: Style::new(),
+            unchanged_line: Style::new().add_modifier(Modifier::DIM),
+            control_character: Style::new(),
+            trailing_whitespace: Style::new().add_modifier(Modifier::REVERSED),
+            section_info: Style::new(),
+            focused_path: Style::new().add_modifier(Modifier::BOLD),
+            toggle_box: Style::new().add_modifier(Modifier::BOLD),
+            read_only_toggle_box: Style::new().add_modifier(Modifier::DIM),
+            warning: Style::new().add_modifier(Modifier::UNDERLINED),
+            highlight: Style::new().add_modifier(Modifier::REVERSED),
+        }
+    }
+
+    /// Return [`Theme::monochrome`] if the user has requested that no colours
+    /// be used by setting the `NO_COLOR` environment variable, and `self`
+    /// otherwise.
+    pub fn respecting_no_color(self) -> Self {
+        if is_no_color_requested(std::env::var_os(ENV_VAR_NO_COLOR).as_deref()) {
+            Self::monochrome()
+        } else {
+            self
+        }
+    }
+}
+
+fn is_no_color_requested(value: Option<&OsStr>) -> bool {
+    match value {
+        Some(value) => !value.is_empty(),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_no_color_requested() {
+        assert!(!is_no_color_requested(None));
+        assert!(!is_no_color_requested(Some(OsStr::new(""))));
+        assert!(is_no_color_requested(Some(OsStr::new("1"))));
+    }
+
+    #[test]
+    fn test_monochrome_has_no_colours() {
+        let Theme {
+            added_line,
+            removed_line,
+            unchanged_line,
+            control_character,
+            trailing_whitespace,
+            section_info,
+            focused_path,
+            toggle_box,
+            read_only_toggle_box,
+            warning,
+            highlight,
+        } = Theme::monochrome();
+        for style in [
+            added_line,
+            removed_line,
+            unchanged_line,
+            control_character,
+            trailing_whitespace,
+            section_info,
+            focused_path,
+            toggle_box,
+            read_only_toggle_box,
+            warning,
+            highlight,
+        ] {
+            assert_eq!(style.fg, None);
+            assert_eq!(style.bg, None);
+        }
+    }
+}
@@ -66,6 +66,12 @@ pub enum RecordError {
     #[error("failed to wrote file: {0}")]
     WriteFile(#[source] Box<dyn std::error::Error + Send + Sync>),
 
+    #[error("failed to read file: {0}")]
+    ReadFile(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("failed to run editor: {0}")]
+    RunEditor(#[source] Box<dyn std::error::Error + Send + Sync>),
+
     #[error("{0}")]
     Other(String),
 
@@ -87,6 +93,9 @@ pub enum FileMode {
 impl FileMode {
     /// The default Unix permissions for files.
     pub const FILE_DEFAULT: FileMode = FileMode::Unix(0o100644);
+
+    /// The Git file mode for submodules (gitlinks).
+    pub const GITLINK: FileMode = FileMode::Unix(0o160000);
 }
 
 impl Display for FileMode {
@@ -152,6 +161,37 @@ pub struct Commit {
     /// in the UI and the user will be able to edit it. If `None`, the commit
     /// message will not be shown or editable.
     pub message: Option<String>,
+
+    /// The author of the commit, such as `Jane Doe <jane@example.com>`. If
+    /// `Some`, then the author will be shown in the UI and the user will be
+    /// able to edit it. If `None`, the author will not be shown or editable.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub author: Option<String>,
+
+    /// The trailers to add to the commit message, such as `Signed-off-by` or
+    /// `Change-Id`. If `Some`, then the trailers will be shown in the UI and
+    /// the user will be able to add, edit and remove them. If `None`, the
+    /// trailers will not be shown or editable.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub trailers: Option<Vec<Trailer>>,
+
+    /// The name of the branch or bookmark which should point to the commit.
+    /// If `Some`, then the branch will be shown in the UI and the user will be
+    /// able to edit it. If `None`, the branch will not be shown or editable.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub branch: Option<String>,
+}
+
+/// A `Key: value` line at the end of a commit message, such as
+/// `Signed-off-by: Jane Doe <jane@example.com>`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct Trailer {
+    /// The key of the trailer, such as `Signed-off-by`.
+    pub key: String,
+
+    /// The value of the trailer.
+    pub value: String,
 }
 
 /// The state of a file to be recorded.
@@ -180,6 +220,12 @@ pub struct File<'a> {
 /// The changes for a particular file selected as part of the record operation.
 #[derive(Debug)]
 pub struct SelectedChanges<'a> {
+    /// The path that the file should be written to. This differs from
+    /// [`File::path`] if the file was renamed or copied (i.e. has an
+    /// [`File::old_path`]) and the [`Section::Rename`] was not selected on this
+    /// side.
+    pub path: Cow<'a, Path>,
+
     /// The file's mode.
     pub file_mode: FileMode,
 
@@ -206,6 +252,13 @@ pub enum SelectedContents<'a> {
         /// The contents of the file.
         contents: String,
     },
+
+    /// The file is a submodule (gitlink) which should point to the given
+    /// commit.
+    Submodule {
+        /// The commit ID that the submodule should point to.
+        commit_id: Cow<'a, str>,
+    },
 }
 
 impl SelectedContents<'_> {
@@ -219,7 +272,8 @@ impl SelectedContents<'_> {
             SelectedContents::Binary {
                 old_description: _,
                 new_description: _,
-            } => {
+            }
+            | SelectedContents::Submodule { commit_id: _ } => {
                 // Do nothing.
             }
             SelectedContents::Text { contents } => {
@@ -238,14 +292,19 @@ impl File<'_> {
         let mut acc_unselected = SelectedContents::Unchanged;
 
         let Self {
-            old_path: _,
-            path: _,
+            old_path,
+            path,
             file_mode,
             sections,
         } = self;
 
         let file_mode_section = sections.iter().find_map(|section| match section {
-            Section::Unchanged { .. } | Section::Changed { .. } | Section::Binary { .. } => None,
+            Section::Unchanged { .. }
+            | Section::Changed { .. }
+            | Section::Binary { .. }
+            | Section::Submodule { .. }
+            | Section::Summary { .. }
+            | Section::Rename { .. } => None,
 
             Section::FileMode { is_checked, mode } => Some((mode, is_checked)),
         });
@@ -264,6 +323,24 @@ impl File<'_> {
             .map(|(change, _)| *change)
             .unwrap_or(*file_mode);
 
+        // If the file was renamed, then the side which includes the rename is
+        // written to the new path, and the other side to the old path.
+        let rename_section = sections.iter().find_map(|section| match section {
+            Section::Unchanged { .. }
+            | Section::Changed { .. }
+            | Section::FileMode { .. }
+            | Section::Binary { .. }
+            | Section::Submodule { .. }
+            | Section::Summary { .. } => None,
+
+            Section::Rename { is_checked } => Some(*is_checked),
+        });
+        let (selected_path, unselected_path) = match (old_path, rename_section) {
+            (Some(old_path), Some(true)) => (path.clone(), old_path.clone()),
+            (Some(old_path), Some(false)) => (old_path.clone(), path.clone()),
+            (None, _) | (Some(_), None) => (path.clone(), path.clone()),
+        };
+
         for section in sections {
             match section {
                 Section::Unchanged { lines } => {
@@ -299,7 +376,7 @@ impl File<'_> {
                     }
                 }
 
-                Section::FileMode { .. } => {
+                Section::FileMode { .. } | Section::Rename { .. } => {
                     // Do nothing - this is handled outside of the loop
                 }
 
@@ -320,6 +397,55 @@ impl File<'_> {
                         acc_unselected = selected_contents;
                     }
                 }
+
+                Section::Submodule {
+                    is_checked,
+                    old_commit_id,
+                    new_commit_id,
+                } => {
+                    // Unlike binary contents, both sides of a submodule change
+                    // are known exactly, so report the commit that each side
+                    // should point to. If that side doesn't have a submodule,
+                    // then the file mode section will have marked it as absent.
+                    let (selected_commit_id, unselected_commit_id) = if *is_checked {
+                        (new_commit_id, old_commit_id)
+                    } else {
+                        (old_commit_id, new_commit_id)
+                    };
+                    acc_selected = match selected_commit_id {
+                        Some(commit_id) => SelectedContents::Submodule {
+                            commit_id: commit_id.clone(),
+                        },
+                        None => SelectedContents::Unchanged,
+                    };
+                    acc_unselected = match unselected_commit_id {
+                        Some(commit_id) => SelectedContents::Submodule {
+                            commit_id: commit_id.clone(),
+                        },
+                        None => SelectedContents::Unchanged,
+                    };
+                }
+
+                Section::Summary {
+                    is_checked,
+                    description: _,
+                    old_contents,
+                    new_contents,
+                } => {
+                    let (selected_contents, unselected_contents) = if *is_checked {
+                        (new_contents, old_contents)
+                    } else {
+                        (old_contents, new_contents)
+                    };
+                    // If that side doesn't have the file, then the file mode
+                    // section will have marked it as absent.
+                    if selected_file_mode != FileMode::Absent {
+                        acc_selected.push_str(selected_contents);
+                    }
+                    if unselected_file_mode != FileMode::Absent {
+                        acc_unselected.push_str(unselected_contents);
+                    }
+                }
             }
         }
 
@@ -341,10 +467,12 @@ impl File<'_> {
 
         (
             SelectedChanges {
+                path: selected_path,
                 contents: acc_selected,
                 file_mode: selected_file_mode,
             },
             SelectedChanges {
+                path: unselected_path,
                 contents: acc_unselected,
                 file_mode: unselected_file_mode,
             },
@@ -382,7 +510,19 @@ impl File<'_> {
                     is_checked,
                     old_description: _,
                     new_description: _,
-                } => {
+                }
+                | Section::Submodule {
+                    is_checked,
+                    old_commit_id: _,
+                    new_commit_id: _,
+                }
+                | Section::Summary {
+                    is_checked,
+                    description: _,
+                    old_contents: _,
+                    new_contents: _,
+                }
+                | Section::Rename { is_checked } => {
                     seen_value = match (seen_value, is_checked) {
                         (None, is_checked) => Some(*is_checked),
                         (Some(true), true) => Some(true),
@@ -423,6 +563,101 @@ impl File<'_> {
             section.toggle_all();
         }
     }
+
+    /// Check or uncheck the section at `section_idx`, along with the other
+    /// sections which have to follow it, as when the section is toggled in the
+    /// UI. Checking the deletion of the file checks all of its changes,
+    /// unchecking the creation of the file unchecks everything, and checking
+    /// or unchecking changes updates whether the file is deleted or created.
+    pub(crate) fn set_section_checked(&mut self, section_idx: usize, is_checked: bool) {
+        let old_file_mode = self.file_mode;
+        let section = match self.sections.get_mut(section_idx) {
+            Some(section) => section,
+            None => return,
+        };
+        section.set_checked(is_checked);
+        match section {
+            Section::FileMode {
+                is_checked: _,
+                mode: new_file_mode,
+            } => {
+                let new_file_mode = *new_file_mode;
+                // If we check a deletion, all lines in the file must be deleted
+                if is_checked && new_file_mode == FileMode::Absent {
+                    for section in &mut self.sections {
+                        if matches!(
+                            section,
+                            Section::Changed { .. }
+                                | Section::Submodule { .. }
+                                | Section::Summary { .. }
+                        ) {
+                            section.set_checked(true);
+                        }
+                    }
+                }
+
+                // If we uncheck a creation, no lines in the file can be added
+                if !is_checked && old_file_mode == FileMode::Absent {
+                    for section in &mut self.sections {
+                        section.set_checked(false);
+                    }
+                }
+            }
+            Section::Changed { .. } | Section::Submodule { .. } | Section::Summary { .. } => {
+                self.update_mode_sections_for_change(is_checked);
+            }
+            Section::Unchanged { .. } | Section::Binary { .. } | Section::Rename { .. } => {}
+        }
+    }
+
+    /// Check or uncheck the line at `line_idx` of the section at
+    /// `section_idx`, and update whether the file is deleted or created to
+    /// match, as when the line is toggled in the UI.
+    pub(crate) fn set_line_checked(
+        &mut self,
+        section_idx: usize,
+        line_idx: usize,
+        is_checked: bool,
+    ) {
+        let line = match self.sections.get_mut(section_idx) {
+            Some(Section::Changed { lines }) => lines.get_mut(line_idx),
+            _ => None,
+        };
+        if let Some(line) = line {
+            line.is_checked = is_checked;
+            self.update_mode_sections_for_change(is_checked);
+        }
+    }
+
+    /// Update the file mode sections after some of the changes to the file's
+    /// contents were checked or unchecked.
+    fn update_mode_sections_for_change(&mut self, is_checked: bool) {
+        let Self {
+            old_path: _,
+            path: _,
+            file_mode,
+            sections,
+        } = self;
+        for section in sections {
+            if let Section::FileMode {
+                mode,
+                is_checked: is_mode_checked,
+            } = section
+            {
+                // If we removed a line and the file was being deleted, it can no longer
+                // be deleted as it needs to contain that line
+                if !is_checked && *mode == FileMode::Absent {
+                    *is_mode_checked = false;
+                }
+
+                // If we added a line and the file was not being created, it must be created
+                // in order to contain that line
+                if is_checked && *file_mode == FileMode::Absent {
+                    *is_mode_checked = true;
+                }
+            }
+        }
+    }
 }
 
 /// A section of a file to be rendered and recorded.
@@ -472,6 +707,50 @@ pub enum Section<'a> {
         /// The description of the new binary contents, for use in the UI only.
         new_description: Option<Cow<'a, str>>,
     },
+
+    /// This file is a submodule (gitlink), and the commit that it points to
+    /// changed.
+    Submodule {
+        /// Whether or not the submodule change was selected for inclusion in
+        /// the UI.
+        is_checked: bool,
+
+        /// The commit ID that the submodule pointed to before the change, if
+        /// the submodule existed.
+        old_commit_id: Option<Cow<'a, str>>,
+
+        /// The commit ID that the submodule points to after the change, if the
+        /// submodule still exists.
+        new_commit_id: Option<Cow<'a, str>>,
+    },
+
+    /// The contents of this text file changed, but the change is only
+    /// summarized rather than shown line by line, such as because the file is
+    /// very large or was generated. The change is selected as a whole.
+    Summary {
+        /// Whether or not the change was selected for inclusion in the UI.
+        is_checked: bool,
+
+        /// The description of the change, such as the number of changed
+        /// lines, for use in the UI only.
+        description: Cow<'a, str>,
+
+        /// The contents of the file before the change, or the empty string if
+        /// the file didn't exist.
+        old_contents: Cow<'a, str>,
+
+        /// The contents of the file after the change, or the empty string if
+        /// the file no longer exists.
+        new_contents: Cow<'a, str>,
+    },
+
+    /// This file was renamed from [`File::old_path`] to [`File::path`], and the
+    /// user needs to accept the rename or not, independently of any changes to
+    /// the file's contents.
+    Rename {
+        /// Whether or not the rename was selected for inclusion in the UI.
+        is_checked: bool,
+    },
 }
 
 impl Section<'_> {
@@ -480,7 +759,12 @@ impl Section<'_> {
     pub fn is_editable(&self) -> bool {
         match self {
             Section::Unchanged { .. } => false,
-            Section::Changed { .. } | Section::FileMode { .. } | Section::Binary { .. } => true,
+            Section::Changed { .. }
+            | Section::FileMode { .. }
+            | Section::Binary { .. }
+            | Section::Submodule { .. }
+            | Section::Summary { .. }
+            | Section::Rename { .. } => true,
         }
     }
 
@@ -508,7 +792,19 @@ impl Section<'_> {
                 is_checked,
                 old_description: _,
                 new_description: _,
-            } => {
+            }
+            | Section::Submodule {
+                is_checked,
+                old_commit_id: _,
+                new_commit_id: _,
+            }
+            | Section::Summary {
+                is_checked,
+                description: _,
+                old_contents: _,
+                new_contents: _,
+            }
+            | Section::Rename { is_checked } => {
                 seen_value = match (seen_value, is_checked) {
                     (None, is_checked) => Some(*is_checked),
                     (Some(true), true) => Some(true),
@@ -538,7 +834,10 @@ impl Section<'_> {
             } => {
                 *is_checked = checked;
             }
-            Section::Binary { is_checked, .. } => {
+            Section::Binary { is_checked, .. }
+            | Section::Submodule { is_checked, .. }
+            | Section::Summary { is_checked, .. }
+            | Section::Rename { is_checked } => {
                 *is_checked = checked;
             }
         }
@@ -556,7 +855,10 @@ impl Section<'_> {
             Section::FileMode { is_checked, .. } => {
                 *is_checked = !*is_checked;
             }
-            Section::Binary { is_checked, .. } => {
+            Section::Binary { is_checked, .. }
+            | Section::Submodule { is_checked, .. }
+            | Section::Summary { is_checked, .. }
+            | Section::Rename { is_checked } => {
                 *is_checked = !*is_checked;
             }
         }
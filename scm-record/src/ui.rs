@@ -3,29 +3,32 @@
 use std::any::Any;
 use std::borrow::Cow;
 use std::cell::RefCell;
-use std::cmp::min;
-use std::collections::{BTreeMap, HashSet};
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::OsString;
 use std::fmt::Write;
-use std::fmt::{Debug, Display};
+use std::fmt::{self, Debug, Display};
 use std::hash::Hash;
 use std::path::Path;
 use std::rc::Rc;
 use std::{io, iter, mem, panic};
 
 use crossterm::event::{
-    DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
-    MouseButton, MouseEvent, MouseEventKind,
+    DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, KeyCode,
+    KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
 };
+use crossterm::style::Print;
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, is_raw_mode_enabled, EnterAlternateScreen,
     LeaveAlternateScreen,
 };
 use ratatui::backend::{Backend, TestBackend};
 use ratatui::buffer::Buffer;
-use ratatui::style::{Color, Modifier, Style};
+use ratatui::style::{Modifier, Style};
+use ratatui::symbols::border;
 use ratatui::text::{Line, Span, Text};
-use ratatui::widgets::{Block, Borders, Clear, Paragraph};
-use ratatui::{backend::CrosstermBackend, Terminal};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Widget};
+use ratatui::{backend::CrosstermBackend, Terminal, TerminalOptions};
 use tracing::warn;
 use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
@@ -33,12 +36,21 @@ use crate::consts::ENV_VAR_DEBUG_UI;
 use crate::render::{
     centered_rect, Component, DrawnRect, DrawnRects, Mask, Rect, RectSize, Viewport,
 };
-use crate::types::{ChangeType, Commit, RecordError, RecordState, Tristate};
+use crate::selection_counts::SelectionCounts;
+use crate::text_editor::TextEditor;
+use crate::theme::Theme;
+use crate::types::{ChangeType, Commit, RecordError, RecordState, Trailer, Tristate};
 use crate::util::{IsizeExt, UsizeExt};
 use crate::{File, FileMode, Section, SectionChangedLine};
 
 const NUM_CONTEXT_LINES: usize = 3;
 
+/// The number of columns to scroll horizontally by at a time.
+const HORIZONTAL_SCROLL_STEP: usize = 4;
+
+/// The maximum recommended length of the first line of a commit message.
+const MAX_SUBJECT_LENGTH: usize = 72;
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
 struct FileKey {
     commit_idx: usize,
@@ -80,6 +92,69 @@ impl Default for SelectionKey {
     }
 }
 
+impl SelectionKey {
+    fn commit_idx(&self) -> Option<usize> {
+        match self {
+            SelectionKey::None => None,
+            SelectionKey::File(FileKey {
+                commit_idx,
+                file_idx: _,
+            })
+            | SelectionKey::Section(SectionKey {
+                commit_idx,
+                file_idx: _,
+                section_idx: _,
+            })
+            | SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx: _,
+                section_idx: _,
+                line_idx: _,
+            }) => Some(*commit_idx),
+        }
+    }
+
+    fn file_idx(&self) -> Option<usize> {
+        match self {
+            SelectionKey::None => None,
+            SelectionKey::File(FileKey {
+                commit_idx: _,
+                file_idx,
+            })
+            | SelectionKey::Section(SectionKey {
+                commit_idx: _,
+                file_idx,
+                section_idx: _,
+            })
+            | SelectionKey::Line(LineKey {
+                commit_idx: _,
+                file_idx,
+                section_idx: _,
+                line_idx: _,
+            }) => Some(*file_idx),
+        }
+    }
+
+    /// The key of the same item as viewed in the given commit.
+    fn with_commit_idx(self, commit_idx: usize) -> Self {
+        match self {
+            SelectionKey::None => SelectionKey::None,
+            SelectionKey::File(file_key) => SelectionKey::File(FileKey {
+                commit_idx,
+                ..file_key
+            }),
+            SelectionKey::Section(section_key) => SelectionKey::Section(SectionKey {
+                commit_idx,
+                ..section_key
+            }),
+            SelectionKey::Line(line_key) => SelectionKey::Line(LineKey {
+                commit_idx,
+                ..line_key
+            }),
+        }
+    }
+}
+
 /// A copy of the contents of the screen at a certain point in time.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct TestingScreenshot {
@@ -121,6 +196,10 @@ pub enum Event {
     EnsureSelectionInViewport,
     ScrollUp,
     ScrollDown,
+    /// Scroll the contents of the lines to the left, i.e. to earlier columns.
+    ScrollLeft,
+    /// Scroll the contents of the lines to the right, i.e. to later columns.
+    ScrollRight,
     PageUp,
     PageDown,
     FocusPrev,
@@ -148,207 +227,326 @@ pub enum Event {
         row: usize,
         column: usize,
     },
-    ToggleCommitViewMode, // no key binding currently
+    /// Toggle between showing only the changes in the first commit and
+    /// showing each commit side-by-side.
+    ToggleCommitViewMode,
     EditCommitMessage,
+    /// Edit the author, trailers and branch of the focused commit.
+    EditCommitMetadata,
+    /// Show or hide the full message of the focused commit above its files.
+    ToggleCommitMessagePreview,
+    /// Toggle whether long lines are wrapped onto continuation rows.
+    ToggleWrapLines,
+    /// Replace the summary of the focused file's changes with the full diff,
+    /// using [`RecordInput::load_full_diff`].
+    LoadFullDiff,
     Help,
+    /// A key press. Outside of the commit message editor, it's interpreted
+    /// using the default key bindings.
+    Key(KeyEvent),
+    /// Text pasted into the terminal, which is inserted into the commit
+    /// message editor if it's open.
+    Paste(String),
 }
 
 impl From<crossterm::event::Event> for Event {
+    /// Convert a `crossterm` event. Key presses are converted to
+    /// [`Event::Key`], so that they can be interpreted according to the
+    /// current state of the UI. For example, they insert text while a commit
+    /// message is being edited.
     fn from(event: crossterm::event::Event) -> Self {
         use crossterm::event::Event;
         match event {
-            Event::Key(KeyEvent {
+            Event::Key(key_event) => Self::Key(key_event),
+            Event::Paste(text) => Self::Paste(text),
+
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollUp,
+                column: _,
+                row: _,
+                modifiers: KeyModifiers::SHIFT,
+            })
+            | Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollLeft,
+                column: _,
+                row: _,
+                modifiers: _,
+            }) => Self::ScrollLeft,
+
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollDown,
+                column: _,
+                row: _,
+                modifiers: KeyModifiers::SHIFT,
+            })
+            | Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollRight,
+                column: _,
+                row: _,
+                modifiers: _,
+            }) => Self::ScrollRight,
+
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollUp,
+                column: _,
+                row: _,
+                modifiers: _,
+            }) => Self::ScrollUp,
+
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollDown,
+                column: _,
+                row: _,
+                modifiers: _,
+            }) => Self::ScrollDown,
+
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                modifiers: _,
+            }) => Self::Click {
+                row: row.into(),
+                column: column.into(),
+            },
+
+            _event => Self::None,
+        }
+    }
+}
+
+impl Event {
+    /// Interpret a key press using the default key bindings.
+    fn from_key(key_event: KeyEvent) -> Self {
+        match key_event {
+            KeyEvent {
                 code: KeyCode::Char('q'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::QuitCancel,
+            } => Self::QuitCancel,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Esc,
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::QuitEscape,
+            } => Self::QuitEscape,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Char('c'),
                 modifiers: KeyModifiers::CONTROL,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::QuitInterrupt,
+            } => Self::QuitInterrupt,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Char('c'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::QuitAccept,
+            } => Self::QuitAccept,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Char('?'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::Help,
+            } => Self::Help,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
+                code: KeyCode::Left,
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Self::ScrollLeft,
+
+            KeyEvent {
+                code: KeyCode::Right,
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Self::ScrollRight,
+
+            KeyEvent {
                 code: KeyCode::Up | KeyCode::Char('y'),
                 modifiers: KeyModifiers::CONTROL,
                 kind: KeyEventKind::Press,
                 state: _,
-            })
-            | Event::Mouse(MouseEvent {
-                kind: MouseEventKind::ScrollUp,
-                column: _,
-                row: _,
-                modifiers: _,
-            }) => Self::ScrollUp,
-            Event::Key(KeyEvent {
+            } => Self::ScrollUp,
+
+            KeyEvent {
                 code: KeyCode::Down | KeyCode::Char('e'),
                 modifiers: KeyModifiers::CONTROL,
                 kind: KeyEventKind::Press,
                 state: _,
-            })
-            | Event::Mouse(MouseEvent {
-                kind: MouseEventKind::ScrollDown,
-                column: _,
-                row: _,
-                modifiers: _,
-            }) => Self::ScrollDown,
+            } => Self::ScrollDown,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::PageUp | KeyCode::Char('b'),
                 modifiers: KeyModifiers::CONTROL,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::PageUp,
-            Event::Key(KeyEvent {
+            } => Self::PageUp,
+
+            KeyEvent {
                 code: KeyCode::PageDown | KeyCode::Char('f'),
                 modifiers: KeyModifiers::CONTROL,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::PageDown,
+            } => Self::PageDown,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Up | KeyCode::Char('k'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusPrev,
-            Event::Key(KeyEvent {
+            } => Self::FocusPrev,
+
+            KeyEvent {
                 code: KeyCode::Down | KeyCode::Char('j'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusNext,
+            } => Self::FocusNext,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::PageUp,
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusPrevSameKind,
-            Event::Key(KeyEvent {
+            } => Self::FocusPrevSameKind,
+
+            KeyEvent {
                 code: KeyCode::PageDown,
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusNextSameKind,
+            } => Self::FocusNextSameKind,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Left | KeyCode::Char('H'),
                 modifiers: KeyModifiers::SHIFT,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusOuter {
+            } => Self::FocusOuter {
                 fold_section: false,
             },
-            Event::Key(KeyEvent {
+
+            KeyEvent {
                 code: KeyCode::Left | KeyCode::Char('h'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusOuter { fold_section: true },
-            Event::Key(KeyEvent {
+            } => Self::FocusOuter { fold_section: true },
+
+            KeyEvent {
                 code: KeyCode::Right | KeyCode::Char('l' | 'L'),
                 // The shift modifier is accepted for continuity with FocusOuter.
                 modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusInner,
+            } => Self::FocusInner,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Char('u'),
                 modifiers: KeyModifiers::CONTROL,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusPrevPage,
-            Event::Key(KeyEvent {
+            } => Self::FocusPrevPage,
+
+            KeyEvent {
                 code: KeyCode::Char('d'),
                 modifiers: KeyModifiers::CONTROL,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::FocusNextPage,
+            } => Self::FocusNextPage,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Char(' '),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::ToggleItem,
+            } => Self::ToggleItem,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Enter,
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::ToggleItemAndAdvance,
+            } => Self::ToggleItemAndAdvance,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Char('a'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::ToggleAll,
-            Event::Key(KeyEvent {
+            } => Self::ToggleAll,
+
+            KeyEvent {
                 code: KeyCode::Char('A'),
                 modifiers: KeyModifiers::SHIFT,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::ToggleAllUniform,
+            } => Self::ToggleAllUniform,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Char('f'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::ExpandItem,
-            Event::Key(KeyEvent {
+            } => Self::ExpandItem,
+
+            KeyEvent {
                 code: KeyCode::Char('F'),
                 modifiers: KeyModifiers::SHIFT,
                 kind: KeyEventKind::Press,
                 state: _,
-            }) => Self::ExpandAll,
+            } => Self::ExpandAll,
 
-            Event::Key(KeyEvent {
+            KeyEvent {
                 code: KeyCode::Char('e'),
                 modifiers: KeyModifiers::NONE,
                 kind: KeyEventKind::Press,
                 state: _event,
-            }) => Self::EditCommitMessage,
+            } => Self::EditCommitMessage,
 
-            Event::Mouse(MouseEvent {
-                kind: MouseEventKind::Down(MouseButton::Left),
-                column,
-                row,
-                modifiers: _,
-            }) => Self::Click {
-                row: row.into(),
-                column: column.into(),
-            },
+            KeyEvent {
+                code: KeyCode::Char('E'),
+                modifiers: KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Self::EditCommitMetadata,
 
-            _event => Self::None,
+            KeyEvent {
+                code: KeyCode::Char('m'),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Self::ToggleCommitMessagePreview,
+
+            KeyEvent {
+                code: KeyCode::Char('w'),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Self::ToggleWrapLines,
+
+            KeyEvent {
+                code: KeyCode::Char('D'),
+                modifiers: KeyModifiers::SHIFT,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Self::LoadFullDiff,
+
+            KeyEvent {
+                code: KeyCode::Char('v'),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                state: _,
+            } => Self::ToggleCommitViewMode,
+
+            _key_event => Self::None,
         }
     }
 }
@@ -358,6 +556,16 @@ pub enum TerminalKind {
     /// Use the `CrosstermBackend` backend.
     Crossterm,
 
+    /// Use the `CrosstermBackend` backend, but render the UI in the given
+    /// number of lines below the cursor rather than on the alternate screen.
+    /// Mouse input isn't captured, so that the terminal's scrollback can still
+    /// be scrolled. Once the user accepts their changes, the UI is replaced
+    /// with a summary of the selected files.
+    CrosstermInline {
+        /// The number of lines to render the UI in.
+        height: usize,
+    },
+
     /// Use the `TestingBackend` backend.
     Testing {
         /// The width of the virtual terminal.
@@ -382,6 +590,70 @@ pub trait RecordInput {
     /// This function will only be invoked if one of the provided `Commit`s had
     /// a non-`None` commit message.
     fn edit_commit_message(&mut self, message: &str) -> Result<String, RecordError>;
+
+    /// Like [`RecordInput::edit_commit_message`], but also given the files
+    /// being recorded, such as to list the selected files in a template. By
+    /// default, this calls `edit_commit_message`.
+    ///
+    /// See [`crate::helpers::edit_message_in_editor`] to edit the message in
+    /// the user's preferred editor.
+    fn edit_commit_message_for_files(
+        &mut self,
+        message: &str,
+        files: &[File],
+    ) -> Result<String, RecordError> {
+        let _ = files;
+        self.edit_commit_message(message)
+    }
+
+    /// Edit the author, trailers and branch of a commit. They're given as
+    /// `Key: value` lines, such as `Author: Jane Doe <jane@example.com>`,
+    /// `Branch: my-feature` and `Signed-off-by: Jane Doe <jane@example.com>`,
+    /// and should be returned in the same format.
+    ///
+    /// Returns `None` if this input can't edit metadata, in which case it's
+    /// edited with the built-in editor instead. This is the default, since the
+    /// metadata shouldn't be passed through the editor used for commit
+    /// messages, which may apply its own templates.
+    fn edit_commit_metadata(&mut self, metadata: &str) -> Result<Option<String>, RecordError> {
+        let _ = metadata;
+        Ok(None)
+    }
+
+    /// Load the full contents of the file at `file_idx`, if `file` is only a
+    /// placeholder for it, such as one with just its path and file mode. This
+    /// lets the UI be shown before every file has been read and diffed.
+    ///
+    /// This is called at most once per file, when the file is first scrolled
+    /// into view, expanded or toggled. The returned file replaces `file`, so it
+    /// should have the same paths. If every file was toggled before this file
+    /// was loaded, the returned file is toggled in the same way. By default,
+    /// this returns `None`, meaning that `file` is already complete.
+    fn load_file(
+        &mut self,
+        file_idx: usize,
+        file: &File,
+    ) -> Result<Option<File<'static>>, RecordError> {
+        let _ = (file_idx, file);
+        Ok(None)
+    }
+
+    /// Compute the full diff of the file at `file_idx`, whose changes are only
+    /// summarized by a [`crate::Section::Summary`], such as because the file
+    /// is very large. This is called when the user asks to see the full diff.
+    ///
+    /// The returned file replaces `file`, so it should have the same paths, and
+    /// its changes should be selected if the summarized changes were. By
+    /// default, this returns `None`, meaning that the full diff isn't
+    /// available.
+    fn load_full_diff(
+        &mut self,
+        file_idx: usize,
+        file: &File,
+    ) -> Result<Option<File<'static>>, RecordError> {
+        let _ = (file_idx, file);
+        Ok(None)
+    }
 }
 
 /// Copied from internal implementation of `tui`.
@@ -409,6 +681,12 @@ fn buffer_view(buffer: &Buffer) -> String {
     view
 }
 
+/// An update to the terminal which can't be made by just rendering the UI.
+enum TerminalEffect {
+    TakeScreenshot(TestingScreenshot),
+    Redraw,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 enum StateUpdate {
     None,
@@ -420,6 +698,7 @@ enum StateUpdate {
     Redraw,
     EnsureSelectionInViewport,
     ScrollTo(isize),
+    ScrollHorizontallyTo(usize),
     SelectItem {
         selection_key: SelectionKey,
         ensure_in_viewport: bool,
@@ -437,9 +716,21 @@ enum StateUpdate {
     },
     ClickMenuItem(Event),
     ToggleCommitViewMode,
+    ToggleWrapLines,
+    LoadFullDiff {
+        file_idx: usize,
+    },
     EditCommitMessage {
         commit_idx: usize,
     },
+    EditCommitMetadata {
+        commit_idx: usize,
+    },
+    ToggleCommitMessagePreview {
+        commit_idx: usize,
+    },
+    SetMessageEditor(Option<MessageEditor>),
+    SaveMessageEditor,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -448,27 +739,221 @@ enum CommitViewMode {
     Adjacent,
 }
 
-#[allow(clippy::enum_variant_names)]
-enum ToggleSideEffects {
-    ToggledModeChangeSection(SectionKey, FileMode, FileMode, bool),
-    ToggledChangedSection(SectionKey, bool),
-    ToggledChangedLine(LineKey, bool),
+/// A change to the selection of every change in a file which was made before
+/// the file was loaded with [`RecordInput::load_file`], and so has to be made
+/// again to the loaded file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PendingToggle {
+    /// Toggle each change, as with [`File::toggle_all`].
+    Invert,
+
+    /// Check or uncheck every change, as with [`File::set_checked`].
+    SetChecked(bool),
+}
+
+impl PendingToggle {
+    fn apply(self, file: &mut File) {
+        match self {
+            PendingToggle::Invert => file.toggle_all(),
+            PendingToggle::SetChecked(checked) => file.set_checked(checked),
+        }
+    }
+}
+
+/// Whether a change is shown as checked in the view of the given commit.
+/// Checked changes belong to the first commit, and unchecked changes belong to
+/// the second commit, so the second commit is shown with the opposite state.
+/// This is its own inverse, so it also converts a state shown in the view of
+/// the commit back to the state of the change.
+fn is_checked_for_commit(commit_idx: usize, is_checked: bool) -> bool {
+    (commit_idx == 0) == is_checked
+}
+
+/// Like [`is_checked_for_commit`], but for a group of changes.
+fn tristate_for_commit(commit_idx: usize, tristate: Tristate) -> Tristate {
+    match tristate {
+        Tristate::False | Tristate::True => Tristate::from(is_checked_for_commit(
+            commit_idx,
+            tristate == Tristate::True,
+        )),
+        Tristate::Partial => Tristate::Partial,
+    }
+}
+
+/// Which changes are shown in the view of a commit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ShownChanges {
+    /// All changes are shown, and their checkboxes indicate whether they
+    /// belong to the commit.
+    All,
+
+    /// Only the changes which belong to the commit are shown, which are the
+    /// changes with the given checked state. See [`is_checked_for_commit`].
+    Only { is_checked: bool },
+
+    /// No changes are shown, because none of them can belong to the commit.
+    Nothing,
+}
+
+impl ShownChanges {
+    /// Whether a change with the given checked state is shown.
+    fn includes(self, is_checked: bool) -> bool {
+        match self {
+            ShownChanges::All => true,
+            ShownChanges::Only {
+                is_checked: shown_is_checked,
+            } => is_checked == shown_is_checked,
+            ShownChanges::Nothing => false,
+        }
+    }
+
+    /// Whether any of a group of changes with the given state is shown.
+    fn includes_tristate(self, tristate: Tristate) -> bool {
+        match tristate {
+            Tristate::False => self.includes(false),
+            Tristate::True => self.includes(true),
+            Tristate::Partial => self.includes(false) || self.includes(true),
+        }
+    }
+}
+
+/// Whether the locale uses the UTF-8 encoding, as determined by the first
+/// non-empty variable out of `LC_ALL`, `LC_CTYPE` and `LANG`. If none are set,
+/// then it's assumed that Unicode is supported.
+fn locale_supports_unicode(get_env_var: impl Fn(&str) -> Option<OsString>) -> bool {
+    for name in ["LC_ALL", "LC_CTYPE", "LANG"] {
+        let value = match get_env_var(name) {
+            Some(value) if !value.is_empty() => value,
+            Some(_) | None => continue,
+        };
+        let value = value.to_string_lossy().to_ascii_lowercase();
+        return value.contains("utf-8") || value.contains("utf8");
+    }
+    true
+}
+
+/// Describe which files have selected changes, with one line per file, using
+/// the same markers as the checkboxes in the UI.
+fn selection_summary(files: &[File], use_unicode: bool) -> String {
+    let mut summary = String::new();
+    for file in files {
+        let marker = match (file.tristate(), use_unicode) {
+            (Tristate::False, _) => " ",
+            (Tristate::Partial, false) => "~",
+            (Tristate::Partial, true) => "◐",
+            (Tristate::True, false) => "*",
+            (Tristate::True, true) => "●",
+        };
+        writeln!(summary, "[{marker}] {}", file.path.display()).unwrap();
+    }
+    summary
+}
+
+/// Options controlling how the contents of lines are rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderOptions {
+    /// The number of columns between tab stops. Tabs are expanded up to the
+    /// next tab stop, so that tab-aligned code is rendered aligned.
+    pub tab_width: usize,
+
+    /// Whether to render markers for invisible characters, such as tabs and
+    /// line endings. If unset, tabs are rendered as plain spaces and line
+    /// endings are not rendered. Other control characters are always rendered
+    /// with a marker, since they would otherwise corrupt the display.
+    pub show_invisible_characters: bool,
+
+    /// Whether to highlight whitespace at the end of added lines.
+    pub highlight_trailing_whitespace: bool,
+
+    /// Whether to wrap lines which are too long to fit on the screen onto
+    /// continuation rows, rather than truncating them.
+    pub wrap_lines: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            tab_width: 4,
+            show_invisible_characters: true,
+            highlight_trailing_whitespace: false,
+            wrap_lines: false,
+        }
+    }
+}
+
+/// Whether the user has finished selecting changes in a [`Recorder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecorderStatus {
+    /// The user is still selecting changes.
+    Running,
+
+    /// The user confirmed their selection.
+    Accepted,
+
+    /// The user cancelled the operation.
+    Cancelled,
+
+    /// The user is still selecting changes, but something else has used the
+    /// terminal, such as an external editor run by
+    /// [`RecordInput::edit_commit_message`], so it should be cleared and drawn
+    /// again in full. This is only returned by [`Recorder::process_event`];
+    /// [`Recorder::status`] returns [`RecorderStatus::Running`] instead.
+    RedrawNeeded,
 }
 
 /// UI component to record the user's changes.
 pub struct Recorder<'state, 'input> {
     state: RecordState<'state>,
+
+    /// The number of selected items in each file and section of `state`,
+    /// which is kept up-to-date as items are toggled.
+    selection_counts: SelectionCounts,
+
     input: &'input mut dyn RecordInput,
     pending_events: Vec<Event>,
     use_unicode: bool,
+    render_options: RenderOptions,
+    theme: Theme,
     commit_view_mode: CommitViewMode,
     expanded_items: HashSet<SelectionKey>,
     expanded_menu_idx: Option<usize>,
+    expanded_commit_messages: HashSet<usize>,
     selection_key: SelectionKey,
     focused_commit_idx: usize,
     quit_dialog: Option<QuitDialog>,
     help_dialog: Option<HelpDialog>,
+    use_builtin_message_editor: bool,
+    message_editor: Option<MessageEditor>,
     scroll_offset_y: isize,
+    scroll_offset_x: usize,
+    status: RecorderStatus,
+    show_menu_bar: bool,
+    show_dialogs: bool,
+
+    /// Whether the recorder set up the terminal itself, in which case it has
+    /// to restore the terminal before running an external editor. This isn't
+    /// the case if it's driven by an external event loop.
+    manages_terminal: bool,
+
+    /// Whether any changes have been checked or unchecked since this was
+    /// last reset.
+    is_selection_changed: bool,
+
+    /// The area that the UI was last drawn in.
+    term_area: ratatui::layout::Rect,
+
+    /// Where each component was last drawn.
+    drawn_rects: DrawnRects<ComponentId>,
+
+    /// The heights of the files when they were last laid out.
+    file_heights: RefCell<FileHeightCache>,
+
+    /// The files which have already been passed to
+    /// [`RecordInput::load_file`].
+    loaded_files: HashSet<usize>,
+
+    /// The selection changes made to files which haven't been loaded yet.
+    pending_toggles: HashMap<usize, PendingToggle>,
 }
 
 impl<'state, 'input> Recorder<'state, 'input> {
@@ -482,25 +967,98 @@ impl<'state, 'input> Recorder<'state, 'input> {
             unimplemented!("more than two commits");
         }
 
+        let use_unicode = match input.terminal_kind() {
+            TerminalKind::Crossterm | TerminalKind::CrosstermInline { .. } => {
+                locale_supports_unicode(|name| std::env::var_os(name))
+            }
+            TerminalKind::Testing { .. } => true,
+        };
+        let selection_counts = SelectionCounts::new(&state.files);
         let mut recorder = Self {
             state,
+            selection_counts,
             input,
             pending_events: Default::default(),
-            use_unicode: true,
+            use_unicode,
+            render_options: Default::default(),
+            theme: Theme::default().respecting_no_color(),
             commit_view_mode: CommitViewMode::Inline,
             expanded_items: Default::default(),
             expanded_menu_idx: Default::default(),
+            expanded_commit_messages: Default::default(),
             selection_key: SelectionKey::None,
             focused_commit_idx: 0,
             quit_dialog: None,
             help_dialog: None,
+            use_builtin_message_editor: false,
+            message_editor: None,
             scroll_offset_y: 0,
+            scroll_offset_x: 0,
+            status: RecorderStatus::Running,
+            manages_terminal: false,
+            show_menu_bar: true,
+            show_dialogs: true,
+            is_selection_changed: false,
+            term_area: Default::default(),
+            drawn_rects: Default::default(),
+            file_heights: Default::default(),
+            loaded_files: Default::default(),
+            pending_toggles: Default::default(),
         };
         recorder.expand_initial_items();
+        recorder.selection_key = recorder.first_selection_key();
         recorder
     }
 
-    /// Run the terminal user interface and have the user interactively select
+    /// Set whether non-ASCII characters may be used to render the UI, such as
+    /// for checkboxes, dialog borders and the markers used in place of control
+    /// characters. By default, this is determined from the locale.
+    ///
+    /// This doesn't affect the contents of the files being rendered.
+    pub fn with_unicode(mut self, use_unicode: bool) -> Self {
+        self.use_unicode = use_unicode;
+        self
+    }
+
+    /// Set the options controlling how the contents of lines are rendered.
+    pub fn with_render_options(mut self, render_options: RenderOptions) -> Self {
+        self.render_options = render_options;
+        self
+    }
+
+    /// Set the colours and styles used to render the UI. The theme is used as
+    /// given, so call [`Theme::respecting_no_color`] on it first to fall back
+    /// to [`Theme::monochrome`] if the user has set the `NO_COLOR` environment
+    /// variable, as the default theme does.
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Set whether the menu bar is shown. It can be useful to hide it when
+    /// embedding the UI in another application.
+    pub fn with_menu_bar(mut self, show_menu_bar: bool) -> Self {
+        self.show_menu_bar = show_menu_bar;
+        self
+    }
+
+    /// Set whether the help and quit dialogs are shown. If not, then the help
+    /// event is ignored, and the user isn't asked to confirm before quitting
+    /// and discarding their changes.
+    pub fn with_dialogs(mut self, show_dialogs: bool) -> Self {
+        self.show_dialogs = show_dialogs;
+        self
+    }
+
+    /// Set whether commit messages are edited in a text editor built into the
+    /// UI, rather than with [`RecordInput::edit_commit_message`]. Press ctrl-s
+    /// to save the message, or escape to discard the changes to it.
+    pub fn with_builtin_message_editor(mut self, use_builtin_message_editor: bool) -> Self {
+        self.use_builtin_message_editor = use_builtin_message_editor;
+        self
+    }
+
+    /// Run the terminal user interface and have the user interactively select
     /// changes.
     pub fn run(self) -> Result<RecordState<'state>, RecordError> {
         #[cfg(feature = "debug")]
@@ -512,26 +1070,155 @@ impl<'state, 'input> Recorder<'state, 'input> {
         }
 
         match self.input.terminal_kind() {
-            TerminalKind::Crossterm => self.run_crossterm(),
+            TerminalKind::Crossterm => self.run_crossterm(None),
+            TerminalKind::CrosstermInline { height } => self.run_crossterm(Some(height)),
             TerminalKind::Testing { width, height } => self.run_testing(width, height),
         }
     }
 
+    /// Draw the UI into the given area of `buf`. This can be used with
+    /// [`Recorder::process_event`] to embed the UI in an application with its
+    /// own event loop instead of calling [`Recorder::run`]. To draw into a
+    /// [`ratatui::Frame`], pass `frame.area()` and `frame.buffer_mut()`.
+    ///
+    /// Events such as clicks and page scrolling are interpreted using the most
+    /// recently drawn layout, so the UI should be drawn after each event.
+    pub fn render(&mut self, area: ratatui::layout::Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+        self.draw_app(area, buf);
+
+        // Scrolling the selection into view needs to use the layout that
+        // resulted from the previous event. Other pending events have already
+        // been applied by `process_event`.
+        let pending_events = mem::take(&mut self.pending_events);
+        if pending_events.contains(&Event::EnsureSelectionInViewport) {
+            self.scroll_selection_into_view();
+            Clear.render(area, buf);
+            self.draw_app(area, buf);
+        }
+    }
+
+    /// Update the UI in response to the given event, such as one converted
+    /// from a `crossterm` event with `Event::from`, and return whether the
+    /// user has finished selecting changes. The positions of clicks are
+    /// relative to the buffer passed to [`Recorder::render`].
+    ///
+    /// Events which arrive after the user has finished are ignored.
+    ///
+    /// The caller is responsible for managing the terminal, so the recorder
+    /// doesn't restore it before calling [`RecordInput::edit_commit_message`]
+    /// or [`RecordInput::edit_commit_metadata`]; the input should do so itself
+    /// if it runs an external editor. Afterwards, this returns
+    /// [`RecorderStatus::RedrawNeeded`].
+    pub fn process_event(&mut self, event: Event) -> Result<RecorderStatus, RecordError> {
+        if self.status != RecorderStatus::Running {
+            return Ok(self.status);
+        }
+
+        let mut is_redraw_needed = false;
+        let mut apply_event = |recorder: &mut Self, event| -> Result<(), RecordError> {
+            match recorder.apply_event(event)? {
+                None | Some(TerminalEffect::TakeScreenshot(_)) => {}
+                Some(TerminalEffect::Redraw) => is_redraw_needed = true,
+            }
+            Ok(())
+        };
+        apply_event(self, event)?;
+
+        // Apply any events triggered by this one, such as by clicking a menu
+        // item, so that their errors can be reported. Scrolling the selection
+        // into view is deferred until the UI is next rendered.
+        let mut deferred_events = Vec::new();
+        while !self.pending_events.is_empty() && self.status == RecorderStatus::Running {
+            for event in mem::take(&mut self.pending_events) {
+                if event == Event::EnsureSelectionInViewport {
+                    deferred_events.push(event);
+                } else {
+                    apply_event(self, event)?;
+                }
+            }
+        }
+        self.pending_events = deferred_events;
+
+        // Files scrolled into view by this event are loaded using the most
+        // recently drawn layout, and shown when the UI is next rendered.
+        self.load_visible_files()?;
+        match self.status {
+            RecorderStatus::Running if is_redraw_needed => Ok(RecorderStatus::RedrawNeeded),
+            status => Ok(status),
+        }
+    }
+
+    /// Return whether any changes have been checked or unchecked since the
+    /// last call, and reset the flag.
+    pub(crate) fn take_is_selection_changed(&mut self) -> bool {
+        mem::take(&mut self.is_selection_changed)
+    }
+
+    /// Whether the user has finished selecting changes.
+    pub fn status(&self) -> RecorderStatus {
+        self.status
+    }
+
+    /// The current state of the user's selection.
+    pub fn state(&self) -> &RecordState<'state> {
+        &self.state
+    }
+
+    /// Finish recording and return the selected changes, as
+    /// [`Recorder::run`] would. If the user cancelled, then returns
+    /// [`RecordError::Cancelled`]. If the user hasn't finished yet, then
+    /// returns their selection so far. Files which haven't been loaded yet
+    /// (see [`RecordInput::load_file`]) are loaded first, so that the returned
+    /// state doesn't depend on which files were shown.
+    pub fn finish(mut self) -> Result<RecordState<'state>, RecordError> {
+        match self.status {
+            RecorderStatus::Running | RecorderStatus::RedrawNeeded | RecorderStatus::Accepted => {
+                self.load_all_files()?;
+                Ok(self.state)
+            }
+            RecorderStatus::Cancelled => Err(RecordError::Cancelled),
+        }
+    }
+
     /// Run the recorder UI using `crossterm` as the backend connected to stdout.
-    fn run_crossterm(self) -> Result<RecordState<'state>, RecordError> {
-        Self::set_up_crossterm()?;
-        Self::install_panic_hook();
+    /// If `inline_height` is set, then the UI is rendered in that many lines
+    /// below the cursor instead of on the alternate screen.
+    fn run_crossterm(
+        mut self,
+        inline_height: Option<usize>,
+    ) -> Result<RecordState<'state>, RecordError> {
+        let is_inline = inline_height.is_some();
+        self.manages_terminal = true;
+        Self::set_up_crossterm(is_inline)?;
+        Self::install_panic_hook(is_inline);
         let backend = CrosstermBackend::new(io::stdout());
-        let mut term =
-            Terminal::new(backend).map_err(|err| RecordError::SetUpTerminal(err.into()))?;
+        let viewport = match inline_height {
+            None => ratatui::Viewport::Fullscreen,
+            Some(height) => ratatui::Viewport::Inline(height.clamp_into_u16()),
+        };
+        let mut term = Terminal::with_options(backend, TerminalOptions { viewport })
+            .map_err(|err| RecordError::SetUpTerminal(err.into()))?;
         term.clear()
             .map_err(|err| RecordError::RenderFrame(err.into()))?;
+        let use_unicode = self.use_unicode;
         let result = self.run_inner(&mut term);
-        Self::clean_up_crossterm()?;
+        if is_inline {
+            // Clearing the inline viewport moves the cursor to its top, so the
+            // summary is printed in place of the UI.
+            term.clear()
+                .map_err(|err| RecordError::CleanUpTerminal(err.into()))?;
+        }
+        Self::clean_up_crossterm(is_inline)?;
+        if let (true, Ok(state)) = (is_inline, &result) {
+            let summary = selection_summary(&state.files, use_unicode);
+            crossterm::execute!(io::stdout(), Print(summary))
+                .map_err(|err| RecordError::CleanUpTerminal(err.into()))?;
+        }
         result
     }
 
-    fn install_panic_hook() {
+    fn install_panic_hook(is_inline: bool) {
         // HACK: installing a global hook here. This could be installed multiple
         // times, and there's no way to uninstall it once we return.
         //
@@ -544,25 +1231,33 @@ impl<'state, 'input> Recorder<'state, 'input> {
         // originally raised, which is frustrating.
         let original_hook = panic::take_hook();
         panic::set_hook(Box::new(move |panic| {
-            Self::clean_up_crossterm().unwrap();
+            Self::clean_up_crossterm(is_inline).unwrap();
             original_hook(panic);
         }));
     }
 
-    fn set_up_crossterm() -> Result<(), RecordError> {
+    fn set_up_crossterm(is_inline: bool) -> Result<(), RecordError> {
         if !is_raw_mode_enabled().map_err(|err| RecordError::SetUpTerminal(err.into()))? {
-            crossterm::execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)
+            if !is_inline {
+                crossterm::execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)
+                    .map_err(|err| RecordError::SetUpTerminal(err.into()))?;
+            }
+            crossterm::execute!(io::stdout(), EnableBracketedPaste)
                 .map_err(|err| RecordError::SetUpTerminal(err.into()))?;
             enable_raw_mode().map_err(|err| RecordError::SetUpTerminal(err.into()))?;
         }
         Ok(())
     }
 
-    fn clean_up_crossterm() -> Result<(), RecordError> {
+    fn clean_up_crossterm(is_inline: bool) -> Result<(), RecordError> {
         if is_raw_mode_enabled().map_err(|err| RecordError::CleanUpTerminal(err.into()))? {
             disable_raw_mode().map_err(|err| RecordError::CleanUpTerminal(err.into()))?;
-            crossterm::execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)
+            crossterm::execute!(io::stdout(), DisableBracketedPaste)
                 .map_err(|err| RecordError::CleanUpTerminal(err.into()))?;
+            if !is_inline {
+                crossterm::execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture)
+                    .map_err(|err| RecordError::CleanUpTerminal(err.into()))?;
+            }
         }
         Ok(())
     }
@@ -578,49 +1273,44 @@ impl<'state, 'input> Recorder<'state, 'input> {
         B: Backend + Any,
         B::Error: Send + Sync,
     {
-        self.selection_key = self.first_selection_key();
         let debug = if cfg!(feature = "debug") {
             std::env::var_os(ENV_VAR_DEBUG_UI).is_some()
         } else {
             false
         };
 
-        'outer: loop {
-            let menu_bar = self.make_menu_bar();
-            let app = self.make_app(menu_bar.clone(), None);
-            let term_height = usize::from(term.get_frame().area().height);
+        while self.status == RecorderStatus::Running {
+            term.draw(|frame| self.draw_app(frame.area(), frame.buffer_mut()))
+                .map_err(|err| RecordError::RenderFrame(err.into()))?;
 
-            let mut drawn_rects: Option<DrawnRects<ComponentId>> = None;
-            term.draw(|frame| {
-                drawn_rects = Some(Viewport::<ComponentId>::render_top_level(
-                    frame,
-                    0,
-                    self.scroll_offset_y,
-                    &app,
-                ));
-            })
-            .map_err(|err| RecordError::RenderFrame(err.into()))?;
-            let drawn_rects = drawn_rects.unwrap();
+            // If any of the files which were just drawn were placeholders,
+            // then draw them again with their full contents.
+            if self.load_visible_files()? {
+                continue;
+            }
 
             // Dump debug info. We may need to use information about the
             // rendered app, so we perform a re-render here.
             if debug {
                 let debug_info = AppDebugInfo {
-                    term_height,
+                    term_height: usize::from(self.term_area.height),
                     scroll_offset_y: self.scroll_offset_y,
                     selection_key: self.selection_key,
-                    selection_key_y: self.selection_key_y(&drawn_rects, self.selection_key),
-                    drawn_rects: drawn_rects.clone().into_iter().collect(),
+                    selection_key_y: self.selection_key_y(&self.drawn_rects, self.selection_key),
+                    drawn_rects: self.drawn_rects.clone().into_iter().collect(),
                 };
                 let debug_app = AppView {
                     debug_info: Some(debug_info),
-                    ..app.clone()
+                    ..self.make_app(self.show_menu_bar.then(|| self.make_menu_bar()))
                 };
                 term.draw(|frame| {
+                    let area = frame.area();
                     Viewport::<ComponentId>::render_top_level(
-                        frame,
+                        frame.buffer_mut(),
+                        area,
                         0,
                         self.scroll_offset_y,
+                        self.use_unicode,
                         &debug_app,
                     );
                 })
@@ -636,110 +1326,239 @@ impl<'state, 'input> Recorder<'state, 'input> {
                 mem::take(&mut self.pending_events)
             };
             for event in events {
-                match self.handle_event(event, term_height, &drawn_rects, &menu_bar)? {
-                    StateUpdate::None => {}
-                    StateUpdate::SetQuitDialog(quit_dialog) => {
-                        self.quit_dialog = quit_dialog;
-                    }
-                    StateUpdate::SetHelpDialog(help_dialog) => {
-                        self.help_dialog = help_dialog;
-                    }
-                    StateUpdate::QuitAccept => {
-                        if self.help_dialog.is_some() {
-                            self.help_dialog = None;
-                        } else {
-                            break 'outer;
-                        }
-                    }
-                    StateUpdate::QuitCancel => return Err(RecordError::Cancelled),
-                    StateUpdate::TakeScreenshot(screenshot) => {
+                match self.apply_event(event)? {
+                    None => {}
+                    Some(TerminalEffect::TakeScreenshot(screenshot)) => {
                         let backend: &dyn Any = term.backend();
                         let test_backend = backend
                             .downcast_ref::<TestBackend>()
                             .expect("TakeScreenshot event generated for non-testing backend");
                         screenshot.set(buffer_view(test_backend.buffer()));
                     }
-                    StateUpdate::Redraw => {
+                    Some(TerminalEffect::Redraw) => {
                         term.clear()
                             .map_err(|err| RecordError::RenderFrame(err.into()))?;
                     }
-                    StateUpdate::EnsureSelectionInViewport => {
-                        if let Some(scroll_offset_y) =
-                            self.ensure_in_viewport(term_height, &drawn_rects, self.selection_key)
-                        {
-                            self.scroll_offset_y = scroll_offset_y;
-                        }
-                    }
-                    StateUpdate::ScrollTo(scroll_offset_y) => {
-                        self.scroll_offset_y = scroll_offset_y.clamp(0, {
-                            let DrawnRect { rect, timestamp: _ } = drawn_rects[&ComponentId::App];
-                            rect.height.unwrap_isize() - 1
-                        });
+                }
+                if self.status != RecorderStatus::Running {
+                    break;
+                }
+            }
+        }
+
+        self.finish()
+    }
+
+    /// Draw the UI into the given area of the buffer and record where each
+    /// component was drawn, for use when handling later events.
+    fn draw_app(&mut self, area: ratatui::layout::Rect, buf: &mut Buffer) {
+        let app = self.make_app(self.show_menu_bar.then(|| self.make_menu_bar()));
+        let drawn_rects = Viewport::<ComponentId>::render_top_level(
+            buf,
+            area,
+            0,
+            self.scroll_offset_y,
+            self.use_unicode,
+            &app,
+        );
+        self.drawn_rects = drawn_rects;
+        self.term_area = area;
+    }
+
+    /// Update the state in response to the given event. Returns an effect to
+    /// be applied to the terminal, if any.
+    fn apply_event(&mut self, event: Event) -> Result<Option<TerminalEffect>, RecordError> {
+        let event = match event {
+            Event::Click { row, column } => {
+                // Clicks are relative to the terminal, but the UI may have
+                // been drawn in only part of it.
+                let ratatui::layout::Rect { x, y, .. } = self.term_area;
+                match (
+                    row.checked_sub(usize::from(y)),
+                    column.checked_sub(usize::from(x)),
+                ) {
+                    (Some(row), Some(column)) => Event::Click { row, column },
+                    (None, _) | (_, None) => return Ok(None),
+                }
+            }
+            event => event,
+        };
+
+        let menu_bar = self.make_menu_bar();
+        let term_height = usize::from(self.term_area.height);
+        match self.handle_event(event, term_height, &self.drawn_rects, &menu_bar)? {
+            StateUpdate::None => {}
+            StateUpdate::SetQuitDialog(Some(_)) if !self.show_dialogs => {
+                // There's no dialog to confirm that the changes should be
+                // discarded, so quit immediately.
+                self.status = RecorderStatus::Cancelled;
+            }
+            StateUpdate::SetQuitDialog(quit_dialog) => {
+                self.quit_dialog = quit_dialog;
+            }
+            StateUpdate::SetHelpDialog(Some(_)) if !self.show_dialogs => {}
+            StateUpdate::SetHelpDialog(help_dialog) => {
+                self.help_dialog = help_dialog;
+            }
+            StateUpdate::QuitAccept => {
+                if self.help_dialog.is_some() {
+                    self.help_dialog = None;
+                } else {
+                    self.status = RecorderStatus::Accepted;
+                }
+            }
+            StateUpdate::QuitCancel => {
+                self.status = RecorderStatus::Cancelled;
+            }
+            StateUpdate::TakeScreenshot(screenshot) => {
+                return Ok(Some(TerminalEffect::TakeScreenshot(screenshot)));
+            }
+            StateUpdate::Redraw => {
+                return Ok(Some(TerminalEffect::Redraw));
+            }
+            StateUpdate::EnsureSelectionInViewport => {
+                self.scroll_selection_into_view();
+            }
+            StateUpdate::ScrollTo(scroll_offset_y) => {
+                let max_scroll_offset_y = match self.drawn_rects.get(&ComponentId::App) {
+                    Some(DrawnRect { rect, timestamp: _ }) => rect.height.unwrap_isize() - 1,
+                    None => 0,
+                };
+                self.scroll_offset_y = scroll_offset_y.clamp(0, max_scroll_offset_y);
+            }
+            StateUpdate::ScrollHorizontallyTo(scroll_offset_x) => {
+                let term_width = usize::from(self.term_area.width);
+                self.scroll_offset_x =
+                    scroll_offset_x.min(self.max_scroll_offset_x(term_width, &self.drawn_rects));
+            }
+            StateUpdate::SelectItem {
+                selection_key,
+                ensure_in_viewport,
+            } => {
+                self.selection_key = selection_key;
+                self.expand_item_ancestors(selection_key);
+                if let Some(file_idx) = selection_key.file_idx() {
+                    self.load_file(file_idx)?;
+                }
+                if ensure_in_viewport {
+                    self.pending_events.push(Event::EnsureSelectionInViewport);
+                }
+            }
+            StateUpdate::ToggleItem(selection_key) => {
+                self.toggle_item(selection_key)?;
+                self.is_selection_changed = true;
+                self.select_nearest_shown_key();
+            }
+            StateUpdate::ToggleItemAndAdvance(selection_key, new_key) => {
+                self.toggle_item(selection_key)?;
+                self.is_selection_changed = true;
+                self.selection_key = new_key;
+                self.select_nearest_shown_key();
+                self.pending_events.push(Event::EnsureSelectionInViewport);
+            }
+            StateUpdate::ToggleAll => {
+                self.toggle_all()?;
+                self.is_selection_changed = true;
+                self.select_nearest_shown_key();
+            }
+            StateUpdate::ToggleAllUniform => {
+                self.toggle_all_uniform()?;
+                self.is_selection_changed = true;
+                self.select_nearest_shown_key();
+            }
+            StateUpdate::SetExpandItem(selection_key, is_expanded) => {
+                self.set_expand_item(selection_key, is_expanded)?;
+                self.pending_events.push(Event::EnsureSelectionInViewport);
+            }
+            StateUpdate::ToggleExpandItem(selection_key) => {
+                self.toggle_expand_item(selection_key)?;
+                self.pending_events.push(Event::EnsureSelectionInViewport);
+            }
+            StateUpdate::ToggleExpandAll => {
+                self.toggle_expand_all()?;
+                self.pending_events.push(Event::EnsureSelectionInViewport);
+            }
+            StateUpdate::UnfocusMenuBar => {
+                self.unfocus_menu_bar();
+            }
+            StateUpdate::ClickMenu { menu_idx } => {
+                self.click_menu_header(menu_idx);
+            }
+            StateUpdate::ClickMenuItem(event) => {
+                self.click_menu_item(event);
+            }
+            StateUpdate::ToggleCommitViewMode => {
+                self.toggle_commit_view_mode();
+            }
+            StateUpdate::ToggleWrapLines => {
+                self.render_options.wrap_lines = !self.render_options.wrap_lines;
+                self.pending_events.push(Event::EnsureSelectionInViewport);
+            }
+            StateUpdate::LoadFullDiff { file_idx } => {
+                self.load_full_diff(file_idx)?;
+                self.pending_events.push(Event::EnsureSelectionInViewport);
+            }
+            StateUpdate::EditCommitMessage { commit_idx } => {
+                if self.use_builtin_message_editor {
+                    self.open_message_editor(commit_idx);
+                } else {
+                    self.pending_events.push(Event::Redraw);
+                    self.edit_commit_message(commit_idx)?;
+                }
+            }
+            StateUpdate::EditCommitMetadata { commit_idx } => {
+                if self.use_builtin_message_editor {
+                    self.open_metadata_editor(commit_idx);
+                } else {
+                    self.pending_events.push(Event::Redraw);
+                    if !self.edit_commit_metadata(commit_idx)? {
+                        self.open_metadata_editor(commit_idx);
                     }
-                    StateUpdate::SelectItem {
-                        selection_key,
-                        ensure_in_viewport,
-                    } => {
-                        self.selection_key = selection_key;
-                        self.expand_item_ancestors(selection_key);
-                        if ensure_in_viewport {
-                            self.pending_events.push(Event::EnsureSelectionInViewport);
+                }
+            }
+            StateUpdate::ToggleCommitMessagePreview { commit_idx } => {
+                if !self.expanded_commit_messages.insert(commit_idx) {
+                    self.expanded_commit_messages.remove(&commit_idx);
+                }
+                self.pending_events.push(Event::EnsureSelectionInViewport);
+            }
+            StateUpdate::SetMessageEditor(message_editor) => {
+                self.message_editor = message_editor;
+            }
+            StateUpdate::SaveMessageEditor => {
+                if let Some(MessageEditor {
+                    commit_idx,
+                    kind,
+                    editor,
+                }) = self.message_editor.take()
+                {
+                    let commit = &mut self.state.commits[commit_idx];
+                    match kind {
+                        MessageEditorKind::Message => {
+                            commit.message = Some(editor.text());
+                        }
+                        MessageEditorKind::Metadata => {
+                            parse_commit_metadata(commit, &editor.text());
                         }
-                    }
-                    StateUpdate::ToggleItem(selection_key) => {
-                        self.toggle_item(selection_key)?;
-                    }
-                    StateUpdate::ToggleItemAndAdvance(selection_key, new_key) => {
-                        self.toggle_item(selection_key)?;
-                        self.selection_key = new_key;
-                        self.pending_events.push(Event::EnsureSelectionInViewport);
-                    }
-                    StateUpdate::ToggleAll => {
-                        self.toggle_all();
-                    }
-                    StateUpdate::ToggleAllUniform => {
-                        self.toggle_all_uniform();
-                    }
-                    StateUpdate::SetExpandItem(selection_key, is_expanded) => {
-                        self.set_expand_item(selection_key, is_expanded);
-                        self.pending_events.push(Event::EnsureSelectionInViewport);
-                    }
-                    StateUpdate::ToggleExpandItem(selection_key) => {
-                        self.toggle_expand_item(selection_key)?;
-                        self.pending_events.push(Event::EnsureSelectionInViewport);
-                    }
-                    StateUpdate::ToggleExpandAll => {
-                        self.toggle_expand_all()?;
-                        self.pending_events.push(Event::EnsureSelectionInViewport);
-                    }
-                    StateUpdate::UnfocusMenuBar => {
-                        self.unfocus_menu_bar();
-                    }
-                    StateUpdate::ClickMenu { menu_idx } => {
-                        self.click_menu_header(menu_idx);
-                    }
-                    StateUpdate::ClickMenuItem(event) => {
-                        self.click_menu_item(event);
-                    }
-                    StateUpdate::ToggleCommitViewMode => {
-                        self.commit_view_mode = match self.commit_view_mode {
-                            CommitViewMode::Inline => CommitViewMode::Adjacent,
-                            CommitViewMode::Adjacent => CommitViewMode::Inline,
-                        };
-                    }
-                    StateUpdate::EditCommitMessage { commit_idx } => {
-                        self.pending_events.push(Event::Redraw);
-                        self.edit_commit_message(commit_idx)?;
                     }
                 }
             }
         }
+        Ok(None)
+    }
 
-        Ok(self.state)
+    fn scroll_selection_into_view(&mut self) {
+        let term_height = usize::from(self.term_area.height);
+        if let Some(scroll_offset_y) =
+            self.ensure_in_viewport(term_height, &self.drawn_rects, self.selection_key)
+        {
+            self.scroll_offset_y = scroll_offset_y;
+        }
     }
 
     fn make_menu_bar(&self) -> MenuBar<'static> {
         MenuBar {
+            theme: self.theme,
             menus: vec![
                 Menu {
                     label: Cow::Borrowed("File"),
@@ -761,6 +1580,10 @@ impl<'state, 'input> Recorder<'state, 'input> {
                             label: Cow::Borrowed("Edit message (e)"),
                             event: Event::EditCommitMessage,
                         },
+                        MenuItem {
+                            label: Cow::Borrowed("Edit metadata (E)"),
+                            event: Event::EditCommitMetadata,
+                        },
                         MenuItem {
                             label: Cow::Borrowed("Toggle current (space)"),
                             event: Event::ToggleItem,
@@ -835,6 +1658,22 @@ impl<'state, 'input> Recorder<'state, 'input> {
                             label: Cow::Borrowed("Fold/unfold all (F)"),
                             event: Event::ExpandAll,
                         },
+                        MenuItem {
+                            label: Cow::Borrowed("Show/hide full message (m)"),
+                            event: Event::ToggleCommitMessagePreview,
+                        },
+                        MenuItem {
+                            label: Cow::Borrowed("Wrap/unwrap long lines (w)"),
+                            event: Event::ToggleWrapLines,
+                        },
+                        MenuItem {
+                            label: Cow::Borrowed("Show full diff of file (D)"),
+                            event: Event::LoadFullDiff,
+                        },
+                        MenuItem {
+                            label: Cow::Borrowed("Show/hide commits side-by-side (v)"),
+                            event: Event::ToggleCommitViewMode,
+                        },
                         MenuItem {
                             label: Cow::Borrowed("Scroll up (ctrl-up, ctrl-y)"),
                             event: Event::ScrollUp,
@@ -843,6 +1682,14 @@ impl<'state, 'input> Recorder<'state, 'input> {
                             label: Cow::Borrowed("Scroll down (ctrl-down, ctrl-e)"),
                             event: Event::ScrollDown,
                         },
+                        MenuItem {
+                            label: Cow::Borrowed("Scroll left (ctrl-left, shift-wheel)"),
+                            event: Event::ScrollLeft,
+                        },
+                        MenuItem {
+                            label: Cow::Borrowed("Scroll right (ctrl-right, shift-wheel)"),
+                            event: Event::ScrollRight,
+                        },
                         MenuItem {
                             label: Cow::Borrowed("Previous page (ctrl-page-up, ctrl-b)"),
                             event: Event::PageUp,
@@ -858,43 +1705,47 @@ impl<'state, 'input> Recorder<'state, 'input> {
         }
     }
 
-    fn make_app(
-        &'state self,
-        menu_bar: MenuBar<'static>,
-        debug_info: Option<AppDebugInfo>,
-    ) -> AppView<'state> {
+    fn make_app(&'state self, menu_bar: Option<MenuBar<'static>>) -> AppView<'state> {
         let RecordState {
-            is_read_only,
+            is_read_only: _,
             commits,
             files,
         } = &self.state;
-        let commit_views = match self.commit_view_mode {
-            CommitViewMode::Inline => {
-                vec![CommitView {
-                    debug_info: None,
-                    commit_message_view: CommitMessageView {
-                        commit_idx: self.focused_commit_idx,
-                        commit: &commits[self.focused_commit_idx],
-                    },
-                    file_views: self.make_file_views(
-                        self.focused_commit_idx,
-                        files,
-                        &debug_info,
-                        *is_read_only,
-                    ),
-                }]
-            }
-
-            CommitViewMode::Adjacent => commits
-                .iter()
-                .enumerate()
-                .map(|(commit_idx, commit)| CommitView {
-                    debug_info: None,
-                    commit_message_view: CommitMessageView { commit_idx, commit },
-                    file_views: self.make_file_views(commit_idx, files, &debug_info, *is_read_only),
-                })
-                .collect(),
+        let commit_idxs = match self.commit_view_mode {
+            CommitViewMode::Inline => self.focused_commit_idx..self.focused_commit_idx + 1,
+            CommitViewMode::Adjacent => 0..commits.len(),
         };
+        let selected_file_key = self
+            .selection_key
+            .commit_idx()
+            .zip(self.selection_key.file_idx())
+            .map(|(commit_idx, file_idx)| FileKey {
+                commit_idx,
+                file_idx,
+            });
+        let commit_views = commit_idxs
+            .map(|commit_idx| CommitView {
+                debug_info: None,
+                use_unicode: self.use_unicode,
+                render_options: self.render_options,
+                file_heights: &self.file_heights,
+                commit_message_view: CommitMessageView {
+                    theme: self.theme,
+                    commit_idx,
+                    commit: &commits[commit_idx],
+                    is_expanded: self.expanded_commit_messages.contains(&commit_idx),
+                },
+                file_keys: (0..files.len())
+                    .map(|file_idx| FileKey {
+                        commit_idx,
+                        file_idx,
+                    })
+                    .filter(|file_key| self.is_file_shown(*file_key))
+                    .collect(),
+                selected_file_key,
+                file_views: self,
+            })
+            .collect();
         AppView {
             debug_info: None,
             menu_bar,
@@ -902,156 +1753,173 @@ impl<'state, 'input> Recorder<'state, 'input> {
             commit_views,
             quit_dialog: self.quit_dialog.clone(),
             help_dialog: self.help_dialog.clone(),
+            message_editor: self.message_editor.as_ref().map(
+                |MessageEditor {
+                     commit_idx: _,
+                     kind,
+                     editor,
+                 }| MessageEditorDialog {
+                    theme: self.theme,
+                    kind: *kind,
+                    editor,
+                },
+            ),
         }
     }
 
-    fn make_file_views(
-        &'state self,
-        commit_idx: usize,
-        files: &'state [File<'state>],
-        debug_info: &Option<AppDebugInfo>,
-        is_read_only: bool,
-    ) -> Vec<FileView<'state>> {
-        files
-            .iter()
-            .enumerate()
-            .map(|(file_idx, file)| {
-                let file_key = FileKey {
-                    commit_idx,
-                    file_idx,
-                };
-                let file_toggled = self.file_tristate(file_key).unwrap();
-                let file_expanded = self.file_expanded(file_key);
-                let is_focused = match self.selection_key {
-                    SelectionKey::None | SelectionKey::Section(_) | SelectionKey::Line(_) => false,
-                    SelectionKey::File(selected_file_key) => file_key == selected_file_key,
-                };
-                FileView {
-                    debug: debug_info.is_some(),
-                    file_key,
-                    toggle_box: TristateBox {
-                        use_unicode: self.use_unicode,
-                        id: ComponentId::ToggleBox(SelectionKey::File(file_key)),
-                        icon_style: TristateIconStyle::Check,
-                        tristate: file_toggled,
-                        is_focused,
-                        is_read_only,
-                    },
-                    expand_box: TristateBox {
-                        use_unicode: self.use_unicode,
-                        id: ComponentId::ExpandBox(SelectionKey::File(file_key)),
-                        icon_style: TristateIconStyle::Expand,
-                        tristate: file_expanded,
-                        is_focused,
-                        is_read_only: false,
-                    },
-                    is_header_selected: is_focused,
-                    old_path: file.old_path.as_deref(),
-                    path: &file.path,
-                    section_views: {
-                        let mut section_views = Vec::new();
-                        let total_num_sections = file.sections.len();
-                        let total_num_editable_sections = file
-                            .sections
+    fn make_file_view(&self, file_key: FileKey, debug: bool) -> FileView<'_> {
+        let FileKey {
+            commit_idx,
+            file_idx,
+        } = file_key;
+        let file = &self.state.files[file_idx];
+        let is_read_only = self.state.is_read_only;
+        let shown_changes = self.shown_changes(commit_idx);
+        let file_toggled = self.file_tristate(file_key).unwrap();
+        let file_expanded = self.file_expanded(file_key);
+        let is_focused = match self.selection_key {
+            SelectionKey::None | SelectionKey::Section(_) | SelectionKey::Line(_) => false,
+            SelectionKey::File(selected_file_key) => file_key == selected_file_key,
+        };
+        FileView {
+            debug,
+            theme: self.theme,
+            file_key,
+            toggle_box: TristateBox {
+                use_unicode: self.use_unicode,
+                theme: self.theme,
+                id: ComponentId::ToggleBox(SelectionKey::File(file_key)),
+                icon_style: TristateIconStyle::Check,
+                tristate: file_toggled,
+                is_focused,
+                is_read_only,
+            },
+            expand_box: TristateBox {
+                use_unicode: self.use_unicode,
+                theme: self.theme,
+                id: ComponentId::ExpandBox(SelectionKey::File(file_key)),
+                icon_style: TristateIconStyle::Expand,
+                tristate: file_expanded,
+                is_focused,
+                is_read_only: false,
+            },
+            is_header_selected: is_focused,
+            old_path: file.old_path.as_deref(),
+            path: &file.path,
+            section_views: {
+                let mut section_views = Vec::new();
+                let total_num_sections = file.sections.len();
+                let total_num_editable_sections = file
+                    .sections
+                    .iter()
+                    .filter(|section| section.is_editable())
+                    .count();
+
+                let mut line_num = 1;
+                let mut editable_section_num = 0;
+                for (section_idx, section) in file.sections.iter().enumerate() {
+                    let section_key = SectionKey {
+                        commit_idx,
+                        file_idx,
+                        section_idx,
+                    };
+                    let line_start_num = line_num;
+                    line_num += match section {
+                        Section::Unchanged { lines } => lines.len(),
+                        Section::Changed { lines } => lines
                             .iter()
-                            .filter(|section| section.is_editable())
-                            .count();
+                            .filter(|changed_line| match changed_line.change_type {
+                                ChangeType::Added => false,
+                                ChangeType::Removed => true,
+                            })
+                            .count(),
+                        Section::FileMode { .. }
+                        | Section::Binary { .. }
+                        | Section::Submodule { .. }
+                        | Section::Summary { .. }
+                        | Section::Rename { .. } => 0,
+                    };
+                    if section.is_editable() {
+                        editable_section_num += 1;
+                        if !self.is_section_shown(section_key) {
+                            continue;
+                        }
+                    }
 
-                        let mut line_num = 1;
-                        let mut editable_section_num = 0;
-                        for (section_idx, section) in file.sections.iter().enumerate() {
-                            let section_key = SectionKey {
+                    let section_toggled = self.section_tristate(section_key).unwrap();
+                    let section_expanded = Tristate::from(
+                        self.expanded_items
+                            .contains(&SelectionKey::Section(section_key)),
+                    );
+                    let is_focused = match self.selection_key {
+                        SelectionKey::None | SelectionKey::File(_) | SelectionKey::Line(_) => false,
+                        SelectionKey::Section(selection_section_key) => {
+                            selection_section_key == section_key
+                        }
+                    };
+                    section_views.push(SectionView {
+                        use_unicode: self.use_unicode,
+                        theme: self.theme,
+                        render_options: self.render_options,
+                        scroll_offset_x: self.scroll_offset_x,
+                        is_read_only,
+                        section_key,
+                        toggle_box: TristateBox {
+                            use_unicode: self.use_unicode,
+                            theme: self.theme,
+                            is_read_only,
+                            id: ComponentId::ToggleBox(SelectionKey::Section(section_key)),
+                            tristate: section_toggled,
+                            icon_style: TristateIconStyle::Check,
+                            is_focused,
+                        },
+                        expand_box: TristateBox {
+                            use_unicode: self.use_unicode,
+                            theme: self.theme,
+                            is_read_only: false,
+                            id: ComponentId::ExpandBox(SelectionKey::Section(section_key)),
+                            tristate: section_expanded,
+                            icon_style: TristateIconStyle::Expand,
+                            is_focused,
+                        },
+                        selection: match self.selection_key {
+                            SelectionKey::None | SelectionKey::File(_) => None,
+                            SelectionKey::Section(selected_section_key) => {
+                                if selected_section_key == section_key {
+                                    Some(SectionSelection::SectionHeader)
+                                } else {
+                                    None
+                                }
+                            }
+                            SelectionKey::Line(LineKey {
                                 commit_idx,
                                 file_idx,
                                 section_idx,
-                            };
-                            let section_toggled = self.section_tristate(section_key).unwrap();
-                            let section_expanded = Tristate::from(
-                                self.expanded_items
-                                    .contains(&SelectionKey::Section(section_key)),
-                            );
-                            let is_focused = match self.selection_key {
-                                SelectionKey::None
-                                | SelectionKey::File(_)
-                                | SelectionKey::Line(_) => false,
-                                SelectionKey::Section(selection_section_key) => {
-                                    selection_section_key == section_key
+                                line_idx,
+                            }) => {
+                                let selected_section_key = SectionKey {
+                                    commit_idx,
+                                    file_idx,
+                                    section_idx,
+                                };
+                                if selected_section_key == section_key {
+                                    Some(SectionSelection::ChangedLine(line_idx))
+                                } else {
+                                    None
                                 }
-                            };
-                            if section.is_editable() {
-                                editable_section_num += 1;
                             }
-                            section_views.push(SectionView {
-                                use_unicode: self.use_unicode,
-                                is_read_only,
-                                section_key,
-                                toggle_box: TristateBox {
-                                    use_unicode: self.use_unicode,
-                                    is_read_only,
-                                    id: ComponentId::ToggleBox(SelectionKey::Section(section_key)),
-                                    tristate: section_toggled,
-                                    icon_style: TristateIconStyle::Check,
-                                    is_focused,
-                                },
-                                expand_box: TristateBox {
-                                    use_unicode: self.use_unicode,
-                                    is_read_only: false,
-                                    id: ComponentId::ExpandBox(SelectionKey::Section(section_key)),
-                                    tristate: section_expanded,
-                                    icon_style: TristateIconStyle::Expand,
-                                    is_focused,
-                                },
-                                selection: match self.selection_key {
-                                    SelectionKey::None | SelectionKey::File(_) => None,
-                                    SelectionKey::Section(selected_section_key) => {
-                                        if selected_section_key == section_key {
-                                            Some(SectionSelection::SectionHeader)
-                                        } else {
-                                            None
-                                        }
-                                    }
-                                    SelectionKey::Line(LineKey {
-                                        commit_idx,
-                                        file_idx,
-                                        section_idx,
-                                        line_idx,
-                                    }) => {
-                                        let selected_section_key = SectionKey {
-                                            commit_idx,
-                                            file_idx,
-                                            section_idx,
-                                        };
-                                        if selected_section_key == section_key {
-                                            Some(SectionSelection::ChangedLine(line_idx))
-                                        } else {
-                                            None
-                                        }
-                                    }
-                                },
-                                total_num_sections,
-                                editable_section_num,
-                                total_num_editable_sections,
-                                section,
-                                line_start_num: line_num,
-                            });
-
-                            line_num += match section {
-                                Section::Unchanged { lines } => lines.len(),
-                                Section::Changed { lines } => lines
-                                    .iter()
-                                    .filter(|changed_line| match changed_line.change_type {
-                                        ChangeType::Added => false,
-                                        ChangeType::Removed => true,
-                                    })
-                                    .count(),
-                                Section::FileMode { .. } | Section::Binary { .. } => 0,
-                            };
-                        }
-                        section_views
-                    },
+                        },
+                        total_num_sections,
+                        editable_section_num,
+                        total_num_editable_sections,
+                        section,
+                        shown_changes,
+                        line_start_num,
+                    });
                 }
-            })
-            .collect()
+                section_views
+            },
+        }
     }
 
     fn handle_event(
@@ -1061,8 +1929,27 @@ impl<'state, 'input> Recorder<'state, 'input> {
         drawn_rects: &DrawnRects<ComponentId>,
         menu_bar: &MenuBar,
     ) -> Result<StateUpdate, RecordError> {
+        if let Some(message_editor) = &self.message_editor {
+            return Ok(self.handle_message_editor_event(
+                message_editor,
+                event,
+                drawn_rects,
+                menu_bar,
+            ));
+        }
+
         let state_update = match (&self.quit_dialog, event) {
             (_, Event::None) => StateUpdate::None,
+            (_, Event::Key(key_event)) => {
+                return self.handle_event(
+                    Event::from_key(key_event),
+                    term_height,
+                    drawn_rects,
+                    menu_bar,
+                );
+            }
+            // Pasted text is only used by the commit message editor.
+            (_, Event::Paste(_)) => StateUpdate::None,
             (_, Event::Redraw) => StateUpdate::Redraw,
             (_, Event::EnsureSelectionInViewport) => StateUpdate::EnsureSelectionInViewport,
 
@@ -1134,6 +2021,8 @@ impl<'state, 'input> Recorder<'state, 'input> {
                 Some(_),
                 Event::ScrollUp
                 | Event::ScrollDown
+                | Event::ScrollLeft
+                | Event::ScrollRight
                 | Event::PageUp
                 | Event::PageDown
                 | Event::FocusPrev
@@ -1146,7 +2035,12 @@ impl<'state, 'input> Recorder<'state, 'input> {
                 | Event::ToggleAllUniform
                 | Event::ExpandItem
                 | Event::ExpandAll
-                | Event::EditCommitMessage,
+                | Event::EditCommitMessage
+                | Event::EditCommitMetadata
+                | Event::ToggleCommitMessagePreview
+                | Event::ToggleCommitViewMode
+                | Event::ToggleWrapLines
+                | Event::LoadFullDiff,
             ) => StateUpdate::None,
 
             (Some(_) | None, Event::TakeScreenshot(screenshot)) => {
@@ -1158,6 +2052,16 @@ impl<'state, 'input> Recorder<'state, 'input> {
             (None, Event::ScrollDown) => {
                 StateUpdate::ScrollTo(self.scroll_offset_y.saturating_add(1))
             }
+            (None, Event::ScrollLeft | Event::ScrollRight) if self.render_options.wrap_lines => {
+                // Wrapped lines always fit horizontally.
+                StateUpdate::None
+            }
+            (None, Event::ScrollLeft) => StateUpdate::ScrollHorizontallyTo(
+                self.scroll_offset_x.saturating_sub(HORIZONTAL_SCROLL_STEP),
+            ),
+            (None, Event::ScrollRight) => StateUpdate::ScrollHorizontallyTo(
+                self.scroll_offset_x.saturating_add(HORIZONTAL_SCROLL_STEP),
+            ),
             (None, Event::PageUp) => StateUpdate::ScrollTo(
                 self.scroll_offset_y
                     .saturating_sub(term_height.unwrap_isize()),
@@ -1167,16 +2071,14 @@ impl<'state, 'input> Recorder<'state, 'input> {
                     .saturating_add(term_height.unwrap_isize()),
             ),
             (None, Event::FocusPrev) => {
-                let (keys, index) = self.find_selection();
-                let selection_key = self.select_prev(&keys, index);
+                let selection_key = self.select_prev();
                 StateUpdate::SelectItem {
                     selection_key,
                     ensure_in_viewport: true,
                 }
             }
             (None, Event::FocusNext) => {
-                let (keys, index) = self.find_selection();
-                let selection_key = self.select_next(&keys, index);
+                let selection_key = self.select_next();
                 StateUpdate::SelectItem {
                     selection_key,
                     ensure_in_viewport: true,
@@ -1230,14 +2132,25 @@ impl<'state, 'input> Recorder<'state, 'input> {
             (None, Event::ExpandItem) => StateUpdate::ToggleExpandItem(self.selection_key),
             (None, Event::ExpandAll) => StateUpdate::ToggleExpandAll,
             (None, Event::EditCommitMessage) => StateUpdate::EditCommitMessage {
-                commit_idx: self.focused_commit_idx,
+                commit_idx: self.selected_commit_idx(),
+            },
+            (None, Event::EditCommitMetadata) => StateUpdate::EditCommitMetadata {
+                commit_idx: self.selected_commit_idx(),
+            },
+            (None, Event::ToggleCommitMessagePreview) => StateUpdate::ToggleCommitMessagePreview {
+                commit_idx: self.selected_commit_idx(),
+            },
+            (None, Event::ToggleCommitViewMode) => StateUpdate::ToggleCommitViewMode,
+            (None, Event::ToggleWrapLines) => StateUpdate::ToggleWrapLines,
+            (None, Event::LoadFullDiff) => match self.selection_key.file_idx() {
+                Some(file_idx) => StateUpdate::LoadFullDiff { file_idx },
+                None => StateUpdate::None,
             },
 
             (_, Event::Click { row, column }) => {
                 let component_id = self.find_component_at(drawn_rects, row, column);
                 self.click_component(menu_bar, component_id)
             }
-            (_, Event::ToggleCommitViewMode) => StateUpdate::ToggleCommitViewMode,
 
             // generally ignore escape key
             (_, Event::QuitEscape) => StateUpdate::None,
@@ -1245,26 +2158,127 @@ impl<'state, 'input> Recorder<'state, 'input> {
         Ok(state_update)
     }
 
-    fn first_selection_key(&self) -> SelectionKey {
-        match self.state.files.iter().enumerate().next() {
-            Some((file_idx, _)) => SelectionKey::File(FileKey {
-                commit_idx: self.focused_commit_idx,
-                file_idx,
-            }),
-            None => SelectionKey::None,
-        }
-    }
-
-    fn num_user_commit_messages(&self) -> Result<usize, RecordError> {
-        let RecordState {
-            files: _,
-            commits,
+    fn handle_message_editor_event(
+        &self,
+        message_editor: &MessageEditor,
+        event: Event,
+        drawn_rects: &DrawnRects<ComponentId>,
+        menu_bar: &MenuBar,
+    ) -> StateUpdate {
+        let mut message_editor = message_editor.clone();
+        let editor = &mut message_editor.editor;
+        match event {
+            Event::None => return StateUpdate::None,
+            Event::Redraw => return StateUpdate::Redraw,
+            Event::EnsureSelectionInViewport => return StateUpdate::EnsureSelectionInViewport,
+            Event::TakeScreenshot(screenshot) => return StateUpdate::TakeScreenshot(screenshot),
+
+            Event::QuitAccept => return StateUpdate::SaveMessageEditor,
+            Event::QuitCancel | Event::QuitInterrupt | Event::QuitEscape => {
+                return StateUpdate::SetMessageEditor(None)
+            }
+
+            Event::Click { row, column } => {
+                let component_id = self.find_component_at(drawn_rects, row, column);
+                return match component_id {
+                    ComponentId::MessageEditorSaveButton
+                    | ComponentId::MessageEditorCancelButton => {
+                        self.click_component(menu_bar, component_id)
+                    }
+                    // The editor is modal, so ignore clicks on the components
+                    // underneath it.
+                    _ => StateUpdate::None,
+                };
+            }
+
+            Event::Paste(text) => editor.insert_str(&text),
+            Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind: KeyEventKind::Press | KeyEventKind::Repeat,
+                state: _,
+            }) => match (code, modifiers) {
+                (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
+                    return StateUpdate::SaveMessageEditor
+                }
+                (KeyCode::Esc, KeyModifiers::NONE)
+                | (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
+                    return StateUpdate::SetMessageEditor(None)
+                }
+                (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                    editor.insert_char(c)
+                }
+                (KeyCode::Enter, KeyModifiers::NONE) => editor.insert_newline(),
+                (KeyCode::Backspace, KeyModifiers::NONE) => editor.delete_backward(),
+                (KeyCode::Delete, KeyModifiers::NONE) => editor.delete_forward(),
+                (KeyCode::Left, KeyModifiers::NONE) => editor.move_left(),
+                (KeyCode::Right, KeyModifiers::NONE) => editor.move_right(),
+                (KeyCode::Up, KeyModifiers::NONE) => editor.move_up(),
+                (KeyCode::Down, KeyModifiers::NONE) => editor.move_down(),
+                (KeyCode::Home, KeyModifiers::NONE) => editor.move_home(),
+                (KeyCode::End, KeyModifiers::NONE) => editor.move_end(),
+                _ => return StateUpdate::None,
+            },
+
+            Event::Key(_)
+            | Event::ScrollUp
+            | Event::ScrollDown
+            | Event::ScrollLeft
+            | Event::ScrollRight
+            | Event::PageUp
+            | Event::PageDown
+            | Event::FocusPrev
+            | Event::FocusPrevSameKind
+            | Event::FocusPrevPage
+            | Event::FocusNext
+            | Event::FocusNextSameKind
+            | Event::FocusNextPage
+            | Event::FocusInner
+            | Event::FocusOuter { .. }
+            | Event::ToggleItem
+            | Event::ToggleItemAndAdvance
+            | Event::ToggleAll
+            | Event::ToggleAllUniform
+            | Event::ExpandItem
+            | Event::ExpandAll
+            | Event::ToggleCommitViewMode
+            | Event::EditCommitMessage
+            | Event::EditCommitMetadata
+            | Event::ToggleCommitMessagePreview
+            | Event::ToggleWrapLines
+            | Event::LoadFullDiff
+            | Event::Help => return StateUpdate::None,
+        }
+        StateUpdate::SetMessageEditor(Some(message_editor))
+    }
+
+    fn first_selection_key(&self) -> SelectionKey {
+        let file_key = FileKey {
+            commit_idx: self.focused_commit_idx,
+            file_idx: 0,
+        };
+        if self.is_file_shown(file_key) {
+            SelectionKey::File(file_key)
+        } else {
+            self.next_file_key(file_key).unwrap_or(SelectionKey::None)
+        }
+    }
+
+    fn num_user_commit_messages(&self) -> Result<usize, RecordError> {
+        let RecordState {
+            files: _,
+            commits,
             is_read_only: _,
         } = &self.state;
         Ok(commits
             .iter()
             .map(|commit| {
-                let Commit { message } = commit;
+                let Commit {
+                    message,
+                    author: _,
+                    trailers: _,
+                    branch: _,
+                } = commit;
                 match message {
                     Some(message) if !message.is_empty() => 1,
                     _ => 0,
@@ -1294,118 +2308,521 @@ impl<'state, 'input> Recorder<'state, 'input> {
         Ok(result)
     }
 
-    fn all_selection_keys(&self) -> Vec<SelectionKey> {
-        let mut result = Vec::new();
-        for (commit_idx, _) in self.state.commits.iter().enumerate() {
-            if commit_idx > 0 {
-                // TODO: implement adjacent `CommitView s.
-                continue;
+    /// The file and section keys which can be expanded in the visible
+    /// commits. (Lines can't be expanded.)
+    fn expandable_keys(&self) -> impl Iterator<Item = SelectionKey> + '_ {
+        let commit_idxs = match self.commit_view_mode {
+            CommitViewMode::Inline => self.focused_commit_idx..self.focused_commit_idx + 1,
+            CommitViewMode::Adjacent => 0..self.state.commits.len(),
+        };
+        let files = &self.state.files;
+        commit_idxs.flat_map(move |commit_idx| {
+            files.iter().enumerate().flat_map(move |(file_idx, file)| {
+                let section_keys = file
+                    .sections
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, section)| match section {
+                        Section::Unchanged { .. } => false,
+                        Section::Changed { .. }
+                        | Section::FileMode { .. }
+                        | Section::Binary { .. }
+                        | Section::Submodule { .. }
+                        | Section::Summary { .. }
+                        | Section::Rename { .. } => true,
+                    })
+                    .map(move |(section_idx, _)| {
+                        SelectionKey::Section(SectionKey {
+                            commit_idx,
+                            file_idx,
+                            section_idx,
+                        })
+                    });
+                std::iter::once(SelectionKey::File(FileKey {
+                    commit_idx,
+                    file_idx,
+                }))
+                .chain(section_keys)
+            })
+        })
+    }
+
+    /// Which changes are shown in the view of the given commit.
+    fn shown_changes(&self, commit_idx: usize) -> ShownChanges {
+        match self.commit_view_mode {
+            CommitViewMode::Inline => {
+                if commit_idx == self.focused_commit_idx {
+                    ShownChanges::All
+                } else {
+                    ShownChanges::Nothing
+                }
             }
-            for (file_idx, file) in self.state.files.iter().enumerate() {
-                result.push(SelectionKey::File(FileKey {
+            CommitViewMode::Adjacent => match commit_idx {
+                0 => ShownChanges::Only { is_checked: true },
+                1 => ShownChanges::Only { is_checked: false },
+                _ => ShownChanges::Nothing,
+            },
+        }
+    }
+
+    /// Whether the given file is shown in the view of its commit, i.e. some
+    /// of its changes belong to the commit.
+    fn is_file_shown(&self, file_key: FileKey) -> bool {
+        let FileKey {
+            commit_idx,
+            file_idx,
+        } = file_key;
+        self.selection_counts.file(file_idx).is_some_and(|count| {
+            self.shown_changes(commit_idx)
+                .includes_tristate(count.tristate())
+        })
+    }
+
+    /// Whether the given section is shown in the view of its commit, i.e. it
+    /// has a selection key and some of its changes belong to the commit.
+    fn is_section_shown(&self, section_key: SectionKey) -> bool {
+        let SectionKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+        } = section_key;
+        let is_selectable = self
+            .state
+            .files
+            .get(file_idx)
+            .and_then(|file| file.sections.get(section_idx))
+            .is_some_and(|section| !matches!(section, Section::Unchanged { .. }));
+        is_selectable
+            && self
+                .selection_counts
+                .section(file_idx, section_idx)
+                .is_some_and(|count| {
+                    self.shown_changes(commit_idx)
+                        .includes_tristate(count.tristate())
+                })
+    }
+
+    /// Whether the given line is shown in the view of its commit, i.e. it
+    /// belongs to the commit.
+    fn is_line_shown(&self, line_key: LineKey) -> bool {
+        let LineKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+            line_idx,
+        } = line_key;
+        match self
+            .state
+            .files
+            .get(file_idx)
+            .and_then(|file| file.sections.get(section_idx))
+        {
+            Some(Section::Changed { lines }) => lines
+                .get(line_idx)
+                .is_some_and(|line| self.shown_changes(commit_idx).includes(line.is_checked)),
+            _ => false,
+        }
+    }
+
+    /// Whether the item for `selection_key` exists and is shown in the view
+    /// of its commit, regardless of whether its ancestors are expanded.
+    fn is_selection_key_shown(&self, selection_key: SelectionKey) -> bool {
+        match selection_key {
+            SelectionKey::None => false,
+            SelectionKey::File(file_key) => self.is_file_shown(file_key),
+            SelectionKey::Section(section_key) => self.is_section_shown(section_key),
+            SelectionKey::Line(line_key) => self.is_line_shown(line_key),
+        }
+    }
+
+    /// Whether the item for `selection_key` is currently shown, i.e. it exists
+    /// in the view of its commit and all of its ancestors are expanded.
+    fn is_selection_key_visible(&self, selection_key: SelectionKey) -> bool {
+        if !self.is_selection_key_shown(selection_key) {
+            return false;
+        }
+        match selection_key {
+            SelectionKey::None => false,
+            SelectionKey::File(_) => true,
+            SelectionKey::Section(SectionKey {
+                commit_idx,
+                file_idx,
+                section_idx: _,
+            }) => self.expanded_items.contains(&SelectionKey::File(FileKey {
+                commit_idx,
+                file_idx,
+            })),
+            SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+                line_idx: _,
+            }) => {
+                self.expanded_items.contains(&SelectionKey::File(FileKey {
+                    commit_idx,
+                    file_idx,
+                })) && self
+                    .expanded_items
+                    .contains(&SelectionKey::Section(SectionKey {
+                        commit_idx,
+                        file_idx,
+                        section_idx,
+                    }))
+            }
+        }
+    }
+
+    /// The index of the first section at or after `section_idx` in the given
+    /// file which is shown and has a selection key, i.e. which isn't an
+    /// unchanged section.
+    fn next_selectable_section_idx(&self, file_key: FileKey, section_idx: usize) -> Option<usize> {
+        let FileKey {
+            commit_idx,
+            file_idx,
+        } = file_key;
+        let num_sections = self.state.files.get(file_idx)?.sections.len();
+        (section_idx..num_sections).find(|section_idx| {
+            self.is_section_shown(SectionKey {
+                commit_idx,
+                file_idx,
+                section_idx: *section_idx,
+            })
+        })
+    }
+
+    /// The index of the last section before `section_idx` in the given file
+    /// which is shown and has a selection key, i.e. which isn't an unchanged
+    /// section.
+    fn prev_selectable_section_idx(&self, file_key: FileKey, section_idx: usize) -> Option<usize> {
+        let FileKey {
+            commit_idx,
+            file_idx,
+        } = file_key;
+        let num_sections = self.state.files.get(file_idx)?.sections.len();
+        (0..section_idx.min(num_sections))
+            .rev()
+            .find(|section_idx| {
+                self.is_section_shown(SectionKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx: *section_idx,
+                })
+            })
+    }
+
+    /// The index of the first line at or after `line_idx` in the given section
+    /// which is currently shown.
+    fn next_visible_line_idx(&self, section_key: SectionKey, line_idx: usize) -> Option<usize> {
+        let SectionKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+        } = section_key;
+        if !self
+            .expanded_items
+            .contains(&SelectionKey::Section(section_key))
+        {
+            return None;
+        }
+        match self.state.files.get(file_idx)?.sections.get(section_idx)? {
+            Section::Changed { lines } => (line_idx..lines.len()).find(|line_idx| {
+                self.shown_changes(commit_idx)
+                    .includes(lines[*line_idx].is_checked)
+            }),
+            _ => None,
+        }
+    }
+
+    /// The index of the last line before `line_idx` in the given section which
+    /// is currently shown.
+    fn prev_visible_line_idx(&self, section_key: SectionKey, line_idx: usize) -> Option<usize> {
+        let SectionKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+        } = section_key;
+        if !self
+            .expanded_items
+            .contains(&SelectionKey::Section(section_key))
+        {
+            return None;
+        }
+        match self.state.files.get(file_idx)?.sections.get(section_idx)? {
+            Section::Changed { lines } => (0..line_idx.min(lines.len())).rev().find(|line_idx| {
+                self.shown_changes(commit_idx)
+                    .includes(lines[*line_idx].is_checked)
+            }),
+            _ => None,
+        }
+    }
+
+    /// The key of the first shown file after the given one, possibly in the
+    /// next commit.
+    fn next_file_key(&self, file_key: FileKey) -> Option<SelectionKey> {
+        let FileKey {
+            commit_idx,
+            file_idx,
+        } = file_key;
+        let num_commits = match self.commit_view_mode {
+            CommitViewMode::Inline => commit_idx + 1,
+            CommitViewMode::Adjacent => self.state.commits.len(),
+        };
+        let mut next_file_idx = file_idx + 1;
+        for commit_idx in commit_idx..num_commits {
+            if let Some(file_idx) = (next_file_idx..self.state.files.len()).find(|file_idx| {
+                self.is_file_shown(FileKey {
+                    commit_idx,
+                    file_idx: *file_idx,
+                })
+            }) {
+                return Some(SelectionKey::File(FileKey {
+                    commit_idx,
+                    file_idx,
+                }));
+            }
+            next_file_idx = 0;
+        }
+        None
+    }
+
+    /// The key of the last shown file before the given one, possibly in the
+    /// previous commit.
+    fn prev_file_key(&self, file_key: FileKey) -> Option<FileKey> {
+        let FileKey {
+            commit_idx,
+            file_idx,
+        } = file_key;
+        let first_commit_idx = match self.commit_view_mode {
+            CommitViewMode::Inline => commit_idx,
+            CommitViewMode::Adjacent => 0,
+        };
+        let mut end_file_idx = file_idx;
+        for commit_idx in (first_commit_idx..=commit_idx).rev() {
+            if let Some(file_idx) =
+                (0..end_file_idx.min(self.state.files.len()))
+                    .rev()
+                    .find(|file_idx| {
+                        self.is_file_shown(FileKey {
+                            commit_idx,
+                            file_idx: *file_idx,
+                        })
+                    })
+            {
+                return Some(FileKey {
+                    commit_idx,
+                    file_idx,
+                });
+            }
+            end_file_idx = self.state.files.len();
+        }
+        None
+    }
+
+    /// The last visible key of the given file, which is the file key itself if
+    /// it's collapsed.
+    fn last_visible_key_in_file(&self, file_key: FileKey) -> SelectionKey {
+        let FileKey {
+            commit_idx,
+            file_idx,
+        } = file_key;
+        if !self.expanded_items.contains(&SelectionKey::File(file_key)) {
+            return SelectionKey::File(file_key);
+        }
+        match self.prev_selectable_section_idx(file_key, usize::MAX) {
+            Some(section_idx) => self.last_visible_key_in_section(SectionKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+            }),
+            None => SelectionKey::File(file_key),
+        }
+    }
+
+    /// The last visible key of the given section, which is the section key
+    /// itself if it's collapsed or has no shown lines.
+    fn last_visible_key_in_section(&self, section_key: SectionKey) -> SelectionKey {
+        let SectionKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+        } = section_key;
+        match self.prev_visible_line_idx(section_key, usize::MAX) {
+            Some(line_idx) => SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+                line_idx,
+            }),
+            None => SelectionKey::Section(section_key),
+        }
+    }
+
+    /// The visible key after the given key, in the order in which the items
+    /// are drawn. This only looks at the neighbouring items, so it's cheap
+    /// even for very large diffs. The given key doesn't need to be visible
+    /// itself, such as when it was just moved to another commit.
+    fn next_visible_key(&self, selection_key: SelectionKey) -> Option<SelectionKey> {
+        let (file_key, next_section_idx) = match selection_key {
+            SelectionKey::None => return None,
+            SelectionKey::File(file_key) => {
+                if !self.expanded_items.contains(&SelectionKey::File(file_key)) {
+                    return self.next_file_key(file_key);
+                }
+                (file_key, 0)
+            }
+            SelectionKey::Section(section_key) => {
+                let SectionKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx,
+                } = section_key;
+                if let Some(line_idx) = self.next_visible_line_idx(section_key, 0) {
+                    return Some(SelectionKey::Line(LineKey {
+                        commit_idx,
+                        file_idx,
+                        section_idx,
+                        line_idx,
+                    }));
+                }
+                (
+                    FileKey {
+                        commit_idx,
+                        file_idx,
+                    },
+                    section_idx + 1,
+                )
+            }
+            SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+                line_idx,
+            }) => {
+                let section_key = SectionKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx,
+                };
+                if let Some(line_idx) = self.next_visible_line_idx(section_key, line_idx + 1) {
+                    return Some(SelectionKey::Line(LineKey {
+                        commit_idx,
+                        file_idx,
+                        section_idx,
+                        line_idx,
+                    }));
+                }
+                (
+                    FileKey {
+                        commit_idx,
+                        file_idx,
+                    },
+                    section_idx + 1,
+                )
+            }
+        };
+        let FileKey {
+            commit_idx,
+            file_idx,
+        } = file_key;
+        match self.next_selectable_section_idx(file_key, next_section_idx) {
+            Some(section_idx) => Some(SelectionKey::Section(SectionKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+            })),
+            None => self.next_file_key(file_key),
+        }
+    }
+
+    /// The visible key before the given key, in the order in which the items
+    /// are drawn. See `next_visible_key`.
+    fn prev_visible_key(&self, selection_key: SelectionKey) -> Option<SelectionKey> {
+        match selection_key {
+            SelectionKey::None => None,
+            SelectionKey::File(file_key) => {
+                let prev_file_key = self.prev_file_key(file_key)?;
+                Some(self.last_visible_key_in_file(prev_file_key))
+            }
+            SelectionKey::Section(SectionKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+            }) => {
+                let file_key = FileKey {
+                    commit_idx,
+                    file_idx,
+                };
+                match self.prev_selectable_section_idx(file_key, section_idx) {
+                    Some(section_idx) => Some(self.last_visible_key_in_section(SectionKey {
+                        commit_idx,
+                        file_idx,
+                        section_idx,
+                    })),
+                    None if self.is_file_shown(file_key) => Some(SelectionKey::File(file_key)),
+                    None => self.prev_visible_key(SelectionKey::File(file_key)),
+                }
+            }
+            SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+                line_idx,
+            }) => {
+                let section_key = SectionKey {
                     commit_idx,
                     file_idx,
-                }));
-                for (section_idx, section) in file.sections.iter().enumerate() {
-                    match section {
-                        Section::Unchanged { .. } => {}
-                        Section::Changed { lines } => {
-                            result.push(SelectionKey::Section(SectionKey {
-                                commit_idx,
-                                file_idx,
-                                section_idx,
-                            }));
-                            for (line_idx, _line) in lines.iter().enumerate() {
-                                result.push(SelectionKey::Line(LineKey {
-                                    commit_idx,
-                                    file_idx,
-                                    section_idx,
-                                    line_idx,
-                                }));
-                            }
-                        }
-                        Section::FileMode {
-                            is_checked: _,
-                            mode: _,
-                        }
-                        | Section::Binary { .. } => {
-                            result.push(SelectionKey::Section(SectionKey {
-                                commit_idx,
-                                file_idx,
-                                section_idx,
-                            }));
-                        }
+                    section_idx,
+                };
+                match self.prev_visible_line_idx(section_key, line_idx) {
+                    Some(line_idx) => Some(SelectionKey::Line(LineKey {
+                        commit_idx,
+                        file_idx,
+                        section_idx,
+                        line_idx,
+                    })),
+                    None if self.is_section_shown(section_key) => {
+                        Some(SelectionKey::Section(section_key))
                     }
+                    None => self.prev_visible_key(SelectionKey::Section(section_key)),
                 }
             }
         }
-        result
     }
 
-    fn find_selection(&self) -> (Vec<SelectionKey>, Option<usize>) {
-        // FIXME: finding the selected key is an O(n) algorithm (instead of O(log(n)) or O(1)).
-        let visible_keys: Vec<_> = self
-            .all_selection_keys()
-            .iter()
-            .cloned()
-            .filter(|key| match key {
-                SelectionKey::None => false,
-                SelectionKey::File(_) => true,
-                SelectionKey::Section(section_key) => {
-                    let file_key = FileKey {
-                        commit_idx: section_key.commit_idx,
-                        file_idx: section_key.file_idx,
-                    };
-                    match self.file_expanded(file_key) {
-                        Tristate::False => false,
-                        Tristate::Partial | Tristate::True => true,
-                    }
-                }
-                SelectionKey::Line(line_key) => {
-                    let file_key = FileKey {
-                        commit_idx: line_key.commit_idx,
-                        file_idx: line_key.file_idx,
-                    };
-                    let section_key = SectionKey {
-                        commit_idx: line_key.commit_idx,
-                        file_idx: line_key.file_idx,
-                        section_idx: line_key.section_idx,
-                    };
-                    self.expanded_items.contains(&SelectionKey::File(file_key))
-                        && self
-                            .expanded_items
-                            .contains(&SelectionKey::Section(section_key))
-                }
-            })
-            .collect();
-        let index = visible_keys.iter().enumerate().find_map(|(k, v)| {
-            if v == &self.selection_key {
-                Some(k)
-            } else {
-                None
-            }
-        });
-        (visible_keys, index)
+    /// The visible keys after the given visible key, nearest first.
+    fn visible_keys_after(
+        &self,
+        selection_key: SelectionKey,
+    ) -> impl Iterator<Item = SelectionKey> + use<'_, 'state, 'input> {
+        std::iter::successors(self.next_visible_key(selection_key), |key| {
+            self.next_visible_key(*key)
+        })
     }
 
-    fn select_prev(&self, keys: &[SelectionKey], index: Option<usize>) -> SelectionKey {
-        match index {
-            None => self.first_selection_key(),
-            Some(index) => match index.checked_sub(1) {
-                Some(prev_index) => keys[prev_index],
-                None => keys[index],
-            },
+    /// The visible keys before the given visible key, nearest first.
+    fn visible_keys_before(
+        &self,
+        selection_key: SelectionKey,
+    ) -> impl Iterator<Item = SelectionKey> + use<'_, 'state, 'input> {
+        std::iter::successors(self.prev_visible_key(selection_key), |key| {
+            self.prev_visible_key(*key)
+        })
+    }
+
+    fn select_prev(&self) -> SelectionKey {
+        if !self.is_selection_key_visible(self.selection_key) {
+            return self.first_selection_key();
         }
+        self.prev_visible_key(self.selection_key)
+            .unwrap_or(self.selection_key)
     }
 
-    fn select_next(&self, keys: &[SelectionKey], index: Option<usize>) -> SelectionKey {
-        match index {
-            None => self.first_selection_key(),
-            Some(index) => match keys.get(index + 1) {
-                Some(key) => *key,
-                None => keys[index],
-            },
+    fn select_next(&self) -> SelectionKey {
+        if !self.is_selection_key_visible(self.selection_key) {
+            return self.first_selection_key();
         }
+        self.next_visible_key(self.selection_key)
+            .unwrap_or(self.selection_key)
     }
 
     // Returns the previous or next SelectionKey of the same kind as the current
@@ -1413,23 +2830,18 @@ impl<'state, 'input> Recorder<'state, 'input> {
     // key is returned instead. If `select_previous` is true, the previous key
     // is returned. Otherwise, the next key is returned.
     fn select_prev_or_next_of_same_kind(&self, select_previous: bool) -> SelectionKey {
-        let (keys, index) = self.find_selection();
-        match index {
-            None => self.first_selection_key(),
-            Some(index) => {
-                let mut iterate_keys: Box<dyn DoubleEndedIterator<Item = _>> = match select_previous
-                {
-                    true => Box::new(keys[..index].iter().rev()),
-                    false => Box::new(keys[index + 1..].iter()),
-                };
-
-                match iterate_keys
-                    .find(|k| std::mem::discriminant(*k) == std::mem::discriminant(&keys[index]))
-                {
-                    None => keys[index],
-                    Some(key) => *key,
-                }
-            }
+        if !self.is_selection_key_visible(self.selection_key) {
+            return self.first_selection_key();
+        }
+        let mut iterate_keys: Box<dyn Iterator<Item = _>> = match select_previous {
+            true => Box::new(self.visible_keys_before(self.selection_key)),
+            false => Box::new(self.visible_keys_after(self.selection_key)),
+        };
+        match iterate_keys
+            .find(|k| std::mem::discriminant(k) == std::mem::discriminant(&self.selection_key))
+        {
+            None => self.selection_key,
+            Some(key) => key,
         }
     }
 
@@ -1438,11 +2850,9 @@ impl<'state, 'input> Recorder<'state, 'input> {
         term_height: usize,
         drawn_rects: &DrawnRects<ComponentId>,
     ) -> SelectionKey {
-        let (keys, index) = self.find_selection();
-        let mut index = match index {
-            Some(index) => index,
-            None => return SelectionKey::None,
-        };
+        if !self.is_selection_key_visible(self.selection_key) {
+            return SelectionKey::None;
+        }
 
         let original_y = match self.selection_key_y(drawn_rects, self.selection_key) {
             Some(original_y) => original_y,
@@ -1451,16 +2861,20 @@ impl<'state, 'input> Recorder<'state, 'input> {
             }
         };
         let target_y = original_y.saturating_sub(term_height.unwrap_isize() / 2);
-        while index > 0 {
-            index -= 1;
-            let selection_key_y = self.selection_key_y(drawn_rects, keys[index]);
-            if let Some(selection_key_y) = selection_key_y {
-                if selection_key_y <= target_y {
-                    break;
-                }
+        let mut result = self.selection_key;
+        for key in self.visible_keys_before(self.selection_key) {
+            // Stop at the edge of the drawn area; the keys beyond it weren't
+            // drawn because they're far away from the viewport.
+            let selection_key_y = match Self::drawn_selection_rect(drawn_rects, key) {
+                Some(rect) => rect.y,
+                None => break,
+            };
+            result = key;
+            if selection_key_y <= target_y {
+                break;
             }
         }
-        keys[index]
+        result
     }
 
     fn select_next_page(
@@ -1468,51 +2882,76 @@ impl<'state, 'input> Recorder<'state, 'input> {
         term_height: usize,
         drawn_rects: &DrawnRects<ComponentId>,
     ) -> SelectionKey {
-        let (keys, index) = self.find_selection();
-        let mut index = match index {
-            Some(index) => index,
-            None => return SelectionKey::None,
-        };
+        if !self.is_selection_key_visible(self.selection_key) {
+            return SelectionKey::None;
+        }
 
         let original_y = match self.selection_key_y(drawn_rects, self.selection_key) {
             Some(original_y) => original_y,
             None => return SelectionKey::None,
         };
         let target_y = original_y.saturating_add(term_height.unwrap_isize() / 2);
-        while index + 1 < keys.len() {
-            index += 1;
-            let selection_key_y = self.selection_key_y(drawn_rects, keys[index]);
-            if let Some(selection_key_y) = selection_key_y {
-                if selection_key_y >= target_y {
-                    break;
-                }
+        let mut result = self.selection_key;
+        for key in self.visible_keys_after(self.selection_key) {
+            // Stop at the edge of the drawn area, as in `select_prev_page`.
+            let selection_key_y = match Self::drawn_selection_rect(drawn_rects, key) {
+                Some(rect) => rect.y,
+                None => break,
+            };
+            result = key;
+            if selection_key_y >= target_y {
+                break;
             }
         }
-        keys[index]
+        result
     }
 
     fn select_inner(&self) -> SelectionKey {
-        self.all_selection_keys()
-            .into_iter()
-            .skip_while(|selection_key| selection_key != &self.selection_key)
-            .skip(1)
-            .find(|selection_key| {
-                match (self.selection_key, selection_key) {
-                    (SelectionKey::None, _) => true,
-                    (_, SelectionKey::None) => false, // shouldn't happen
-
-                    (SelectionKey::File(_), SelectionKey::File(_)) => false,
-                    (SelectionKey::File(_), SelectionKey::Section(_)) => true,
-                    (SelectionKey::File(_), SelectionKey::Line(_)) => false, // shouldn't happen
-
-                    (SelectionKey::Section(_), SelectionKey::File(_))
-                    | (SelectionKey::Section(_), SelectionKey::Section(_)) => false,
-                    (SelectionKey::Section(_), SelectionKey::Line(_)) => true,
-
-                    (SelectionKey::Line(_), _) => false,
-                }
-            })
-            .unwrap_or(self.selection_key)
+        match self.selection_key {
+            SelectionKey::None | SelectionKey::Line(_) => None,
+            SelectionKey::File(FileKey {
+                commit_idx,
+                file_idx,
+            }) => self
+                .next_selectable_section_idx(
+                    FileKey {
+                        commit_idx,
+                        file_idx,
+                    },
+                    0,
+                )
+                .map(|section_idx| {
+                    SelectionKey::Section(SectionKey {
+                        commit_idx,
+                        file_idx,
+                        section_idx,
+                    })
+                }),
+            SelectionKey::Section(SectionKey {
+                commit_idx,
+                file_idx,
+                section_idx,
+            }) => match self
+                .state
+                .files
+                .get(file_idx)
+                .and_then(|file| file.sections.get(section_idx))
+            {
+                Some(Section::Changed { lines }) => lines
+                    .iter()
+                    .position(|line| self.shown_changes(commit_idx).includes(line.is_checked))
+                    .map(|line_idx| {
+                        SelectionKey::Line(LineKey {
+                            commit_idx,
+                            file_idx,
+                            section_idx,
+                            line_idx,
+                        })
+                    }),
+                _ => None,
+            },
+        }
+        .unwrap_or(self.selection_key)
     }
 
     fn select_outer(&self, fold_section: bool) -> StateUpdate {
@@ -1557,14 +2996,10 @@ impl<'state, 'input> Recorder<'state, 'input> {
     }
 
     fn advance_to_next_of_kind(&self) -> SelectionKey {
-        let (keys, index) = self.find_selection();
-        let index = match index {
-            Some(index) => index,
-            None => return SelectionKey::None,
-        };
-        keys.iter()
-            .skip(index + 1)
-            .copied()
+        if !self.is_selection_key_visible(self.selection_key) {
+            return SelectionKey::None;
+        }
+        self.visible_keys_after(self.selection_key)
             .find(|key| match (self.selection_key, key) {
                 (SelectionKey::None, _)
                 | (SelectionKey::File(_), SelectionKey::File(_))
@@ -1595,6 +3030,19 @@ impl<'state, 'input> Recorder<'state, 'input> {
         Some(rect.y)
     }
 
+    /// Where the item for `selection_key` was drawn, if it was drawn at all.
+    /// Items which are far from the viewport are not drawn; see
+    /// `Viewport::layout_rect`.
+    fn drawn_selection_rect(
+        drawn_rects: &DrawnRects<ComponentId>,
+        selection_key: SelectionKey,
+    ) -> Option<Rect> {
+        let id = ComponentId::SelectableItem(selection_key);
+        drawn_rects
+            .get(&id)
+            .map(|DrawnRect { rect, timestamp: _ }| *rect)
+    }
+
     fn selection_rect(
         &self,
         drawn_rects: &DrawnRects<ComponentId>,
@@ -1606,8 +3054,8 @@ impl<'state, 'input> Recorder<'state, 'input> {
                 ComponentId::SelectableItem(selection_key)
             }
         };
-        match drawn_rects.get(&id) {
-            Some(DrawnRect { rect, timestamp: _ }) => Some(*rect),
+        match Self::drawn_selection_rect(drawn_rects, selection_key) {
+            Some(rect) => Some(rect),
             None => {
                 if cfg!(debug_assertions) {
                     panic!(
@@ -1627,7 +3075,7 @@ impl<'state, 'input> Recorder<'state, 'input> {
         drawn_rects: &DrawnRects<ComponentId>,
         selection_key: SelectionKey,
     ) -> Option<isize> {
-        let menu_bar_height = 1;
+        let menu_bar_height = if self.show_menu_bar { 1 } else { 0 };
         let sticky_file_header_height = match selection_key {
             SelectionKey::None | SelectionKey::File(_) => 0,
             SelectionKey::Section(_) | SelectionKey::Line(_) => 1,
@@ -1674,6 +3122,34 @@ impl<'state, 'input> Recorder<'state, 'input> {
         Some(result)
     }
 
+    /// The furthest that the line contents can be scrolled horizontally
+    /// such that the end of the longest line is at the right edge of the
+    /// terminal.
+    fn max_scroll_offset_x(
+        &self,
+        term_width: usize,
+        drawn_rects: &DrawnRects<ComponentId>,
+    ) -> usize {
+        let max_end_x = drawn_rects
+            .iter()
+            .filter_map(|(id, drawn_rect)| match id {
+                ComponentId::SelectableItem(SelectionKey::Line(_)) => {
+                    let DrawnRect { rect, timestamp: _ } = drawn_rect;
+                    Some(rect.end_x())
+                }
+                _ => None,
+            })
+            .max();
+        match max_end_x {
+            // The drawn lines are already offset by the current scroll
+            // position.
+            Some(max_end_x) => (max_end_x + self.scroll_offset_x.unwrap_isize()
+                - term_width.unwrap_isize())
+            .clamp_into_usize(),
+            None => 0,
+        }
+    }
+
     fn find_component_at(
         &self,
         drawn_rects: &DrawnRects<ComponentId>,
@@ -1696,12 +3172,17 @@ impl<'state, 'input> Recorder<'state, 'input> {
                         | ComponentId::MenuItem(_)
                         | ComponentId::Menu(_)
                         | ComponentId::CommitEditMessageButton(_)
+                        | ComponentId::CommitMessagePreviewButton(_)
+                        | ComponentId::CommitEditMetadataButton(_)
                         | ComponentId::FileViewHeader(_)
                         | ComponentId::SelectableItem(_)
                         | ComponentId::ToggleBox(_)
                         | ComponentId::ExpandBox(_)
                         | ComponentId::HelpDialog
                         | ComponentId::HelpDialogQuitButton
+                        | ComponentId::MessageEditorDialog
+                        | ComponentId::MessageEditorSaveButton
+                        | ComponentId::MessageEditorCancelButton
                         | ComponentId::QuitDialog
                         | ComponentId::QuitDialogButton(_) => true,
                     }
@@ -1731,6 +3212,12 @@ impl<'state, 'input> Recorder<'state, 'input> {
             ComponentId::CommitEditMessageButton(commit_idx) => {
                 StateUpdate::EditCommitMessage { commit_idx }
             }
+            ComponentId::CommitEditMetadataButton(commit_idx) => {
+                StateUpdate::EditCommitMetadata { commit_idx }
+            }
+            ComponentId::CommitMessagePreviewButton(commit_idx) => {
+                StateUpdate::ToggleCommitMessagePreview { commit_idx }
+            }
             ComponentId::FileViewHeader(file_key) => StateUpdate::SelectItem {
                 selection_key: SelectionKey::File(file_key),
                 ensure_in_viewport: false,
@@ -1765,11 +3252,15 @@ impl<'state, 'input> Recorder<'state, 'input> {
             ComponentId::QuitDialogButton(QuitDialogButtonId::Quit) => StateUpdate::QuitCancel,
             ComponentId::HelpDialog => StateUpdate::None,
             ComponentId::HelpDialogQuitButton => StateUpdate::SetHelpDialog(None),
+            ComponentId::MessageEditorDialog => StateUpdate::None,
+            ComponentId::MessageEditorSaveButton => StateUpdate::SaveMessageEditor,
+            ComponentId::MessageEditorCancelButton => StateUpdate::SetMessageEditor(None),
         }
     }
 
     fn get_menu_item_event(&self, menu_bar: &MenuBar, item_idx: usize) -> Event {
         let MenuBar {
+            theme: _,
             menus,
             expanded_menu_idx,
         } = menu_bar;
@@ -1806,168 +3297,312 @@ impl<'state, 'input> Recorder<'state, 'input> {
             return Ok(());
         }
 
-        let side_effects = match selection {
-            SelectionKey::None => None,
+        if let Some(file_idx) = selection.file_idx() {
+            // The sections of a placeholder file are replaced when it's
+            // loaded, so only a toggle of the whole file still applies.
+            if self.load_file(file_idx)? && !matches!(selection, SelectionKey::File(_)) {
+                return Ok(());
+            }
+        }
+
+        match selection {
+            SelectionKey::None => {}
             SelectionKey::File(file_key) => {
                 let tristate = self.file_tristate(file_key)?;
-                let is_checked_new = match tristate {
-                    Tristate::False => true,
-                    Tristate::Partial | Tristate::True => false,
-                };
+                let is_checked_new = is_checked_for_commit(
+                    file_key.commit_idx,
+                    match tristate {
+                        Tristate::False => true,
+                        Tristate::Partial | Tristate::True => false,
+                    },
+                );
                 self.visit_file(file_key, |file| {
                     file.set_checked(is_checked_new);
                 })?;
-
-                None
+                self.selection_counts
+                    .set_file_checked(file_key.file_idx, is_checked_new);
             }
             SelectionKey::Section(section_key) => {
                 let tristate = self.section_tristate(section_key)?;
-                let is_checked_new = match tristate {
-                    Tristate::False => true,
-                    Tristate::Partial | Tristate::True => false,
-                };
+                let is_checked_new = is_checked_for_commit(
+                    section_key.commit_idx,
+                    match tristate {
+                        Tristate::False => true,
+                        Tristate::Partial | Tristate::True => false,
+                    },
+                );
+
+                let is_mode_section = self.visit_section(section_key, |section| {
+                    matches!(section, Section::FileMode { .. })
+                })?;
+                self.visit_file_for_section(section_key, |file| {
+                    file.set_section_checked(section_key.section_idx, is_checked_new);
+                })?;
+                self.selection_counts.set_section_checked(
+                    section_key.file_idx,
+                    section_key.section_idx,
+                    is_checked_new,
+                );
+                if is_mode_section {
+                    // Toggling a deletion or creation may change every
+                    // section in the file.
+                    let file_idx = section_key.file_idx;
+                    self.selection_counts
+                        .update_file(file_idx, &self.state.files[file_idx]);
+                } else {
+                    self.update_mode_section_counts(section_key.file_idx);
+                }
+            }
+            SelectionKey::Line(line_key) => {
+                let is_checked_old = self
+                    .visit_line(line_key, |line| Some(line.is_checked))?
+                    .unwrap_or_default();
+                self.visit_file_for_line(line_key, |file| {
+                    file.set_line_checked(line_key.section_idx, line_key.line_idx, !is_checked_old);
+                })?;
+                self.selection_counts.toggle_line(
+                    line_key.file_idx,
+                    line_key.section_idx,
+                    !is_checked_old,
+                );
+                self.update_mode_section_counts(line_key.file_idx);
+            }
+        }
 
-                let old_file_mode = self.visit_file_for_section(section_key, |f| f.file_mode)?;
+        // When the commits are shown side-by-side, the toggled changes move
+        // to another commit, which changes the height of the file in both.
+        if let Some(file_idx) = selection.file_idx() {
+            self.file_heights.borrow_mut().remove_file(file_idx);
+        }
+        Ok(())
+    }
 
-                self.visit_section(section_key, |section| {
-                    section.set_checked(is_checked_new);
+    /// Count the items in the file mode sections of the given file again,
+    /// after they were checked or unchecked as a side effect of toggling
+    /// another section.
+    fn update_mode_section_counts(&mut self, file_idx: usize) {
+        for (section_idx, section) in self.state.files[file_idx].sections.iter().enumerate() {
+            if let Section::FileMode { .. } = section {
+                self.selection_counts
+                    .update_section(file_idx, section_idx, section);
+            }
+        }
+    }
 
-                    if let Section::FileMode { mode, .. } = section {
-                        return Some(ToggleSideEffects::ToggledModeChangeSection(
-                            section_key,
-                            old_file_mode,
-                            *mode,
-                            is_checked_new,
-                        ));
-                    }
+    fn toggle_all(&mut self) -> Result<(), RecordError> {
+        if self.state.is_read_only {
+            return Ok(());
+        }
 
-                    if let Section::Changed { .. } = section {
-                        return Some(ToggleSideEffects::ToggledChangedSection(
-                            section_key,
-                            is_checked_new,
-                        ));
+        for (file_idx, file) in self.state.files.iter_mut().enumerate() {
+            file.toggle_all();
+            if !self.loaded_files.contains(&file_idx) {
+                match self.pending_toggles.get(&file_idx) {
+                    None => {
+                        self.pending_toggles.insert(file_idx, PendingToggle::Invert);
                     }
-
-                    None
-                })?
-            }
-            SelectionKey::Line(line_key) => self.visit_line(line_key, |line| {
-                line.is_checked = !line.is_checked;
-
-                Some(ToggleSideEffects::ToggledChangedLine(
-                    line_key,
-                    line.is_checked,
-                ))
-            })?,
-        };
-
-        if let Some(side_effects) = side_effects {
-            match side_effects {
-                ToggleSideEffects::ToggledModeChangeSection(
-                    section_key,
-                    old_mode,
-                    new_mode,
-                    toggled_to,
-                ) => {
-                    // If we check a deletion, all lines in the file must be deleted
-                    if toggled_to && new_mode == FileMode::Absent {
-                        self.visit_file_for_section(section_key, |file| {
-                            for section in &mut file.sections {
-                                if matches!(section, Section::Changed { .. }) {
-                                    section.set_checked(true);
-                                }
-                            }
-                        })?;
+                    Some(PendingToggle::Invert) => {
+                        self.pending_toggles.remove(&file_idx);
                     }
-
-                    // If we uncheck a creation, no lines in the file can be added
-                    if !toggled_to && old_mode == FileMode::Absent {
-                        self.visit_file_for_section(section_key, |file| {
-                            for section in &mut file.sections {
-                                section.set_checked(false);
-                            }
-                        })?;
+                    Some(PendingToggle::SetChecked(checked)) => {
+                        self.pending_toggles
+                            .insert(file_idx, PendingToggle::SetChecked(!checked));
                     }
                 }
-                ToggleSideEffects::ToggledChangedSection(section_key, toggled_to) => {
-                    self.visit_file_for_section(section_key, |file| {
-                        for section in &mut file.sections {
-                            if let Section::FileMode { mode, is_checked } = section {
-                                // If we removed a line and the file was being deleted, it can no longer
-                                // be deleted as it needs to contain that line
-                                if !toggled_to && *mode == FileMode::Absent {
-                                    *is_checked = false;
-                                }
+            }
+        }
+        self.selection_counts = SelectionCounts::new(&self.state.files);
+        self.file_heights.borrow_mut().clear();
+        Ok(())
+    }
 
-                                // If we added a line and the file was not being created, it must be created
-                                // in order to contain that line
-                                if toggled_to && file.file_mode == FileMode::Absent {
-                                    *is_checked = true;
-                                }
-                            }
-                        }
-                    })?;
-                }
-                ToggleSideEffects::ToggledChangedLine(line_key, toggled_to) => {
-                    self.visit_file_for_line(line_key, |file| {
-                        for section in &mut file.sections {
-                            if let Section::FileMode { mode, is_checked } = section {
-                                // If we removed a line and the file was being deleted, it can no longer
-                                // be deleted as it needs to contain that line
-                                if !toggled_to && *mode == FileMode::Absent {
-                                    *is_checked = false;
-                                }
+    fn toggle_all_uniform(&mut self) -> Result<(), RecordError> {
+        if self.state.is_read_only {
+            return Ok(());
+        }
 
-                                // If we added a line and the file was not being created, it must be created
-                                // in order to contain that line
-                                if toggled_to && file.file_mode == FileMode::Absent {
-                                    *is_checked = true;
-                                }
-                            }
-                        }
-                    })?;
+        let checked = {
+            let tristate = (0..self.state.files.len())
+                .filter_map(|file_idx| self.selection_counts.file(file_idx))
+                .map(|count| count.tristate())
+                .fold(None, |acc, elem| match (acc, elem) {
+                    (None, tristate) => Some(tristate),
+                    (Some(acc_tristate), tristate) if acc_tristate == tristate => Some(tristate),
+                    _ => Some(Tristate::Partial),
+                })
+                .unwrap_or(Tristate::False);
+            match tristate {
+                Tristate::False | Tristate::Partial => true,
+                Tristate::True => false,
+            }
+        };
+        for (file_idx, file) in self.state.files.iter_mut().enumerate() {
+            file.set_checked(checked);
+            self.selection_counts.set_file_checked(file_idx, checked);
+            if !self.loaded_files.contains(&file_idx) {
+                self.pending_toggles
+                    .insert(file_idx, PendingToggle::SetChecked(checked));
+            }
+        }
+        self.file_heights.borrow_mut().clear();
+        Ok(())
+    }
+
+    /// Replace the file at `file_idx` with the result of
+    /// [`RecordInput::load_file`], if it hasn't been loaded yet. Returns
+    /// whether the file was replaced.
+    fn load_file(&mut self, file_idx: usize) -> Result<bool, RecordError> {
+        if !self.loaded_files.insert(file_idx) {
+            return Ok(false);
+        }
+        let file = match self.state.files.get(file_idx) {
+            Some(file) => file,
+            None => {
+                return Err(RecordError::Bug(format!(
+                    "Out-of-bounds file index: {file_idx}"
+                )))
+            }
+        };
+        let pending_toggle = self.pending_toggles.remove(&file_idx);
+        match self.input.load_file(file_idx, file)? {
+            Some(mut file) => {
+                // The placeholder was toggled along with every other file, so
+                // the loaded file has to be toggled in the same way.
+                if let Some(pending_toggle) = pending_toggle {
+                    pending_toggle.apply(&mut file);
+                }
+                self.replace_file(file_idx, file);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Replace the file at `file_idx` with the result of
+    /// [`RecordInput::load_full_diff`], if its changes are only summarized,
+    /// and expand it to show the diff.
+    fn load_full_diff(&mut self, file_idx: usize) -> Result<(), RecordError> {
+        self.load_file(file_idx)?;
+        let file = match self.state.files.get(file_idx) {
+            Some(file) => file,
+            None => {
+                return Err(RecordError::Bug(format!(
+                    "Out-of-bounds file index: {file_idx}"
+                )))
+            }
+        };
+        let is_summarized = file
+            .sections
+            .iter()
+            .any(|section| matches!(section, Section::Summary { .. }));
+        if !is_summarized {
+            return Ok(());
+        }
+        if let Some(file) = self.input.load_full_diff(file_idx, file)? {
+            self.replace_file(file_idx, file);
+            for commit_idx in 0..self.state.commits.len() {
+                self.expanded_items.insert(SelectionKey::File(FileKey {
+                    commit_idx,
+                    file_idx,
+                }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the file at `file_idx` with `file`, updating the state which
+    /// depends on its sections.
+    fn replace_file(&mut self, file_idx: usize, file: File<'static>) {
+        self.state.files[file_idx] = file;
+        self.selection_counts
+            .update_file(file_idx, &self.state.files[file_idx]);
+        self.file_heights.borrow_mut().remove_file(file_idx);
+
+        // The old file's sections no longer exist, so forget about them, and
+        // expand the new sections as in `expand_initial_items`.
+        let section_keys: Vec<_> = self
+            .expanded_items
+            .iter()
+            .filter(|selection_key| match selection_key {
+                SelectionKey::None | SelectionKey::File(_) => false,
+                SelectionKey::Section(_) | SelectionKey::Line(_) => {
+                    selection_key.file_idx() == Some(file_idx)
+                }
+            })
+            .copied()
+            .collect();
+        for selection_key in section_keys {
+            self.expanded_items.remove(&selection_key);
+        }
+        for commit_idx in 0..self.state.commits.len() {
+            for (section_idx, section) in self.state.files[file_idx].sections.iter().enumerate() {
+                if section.is_editable() {
+                    self.expanded_items
+                        .insert(SelectionKey::Section(SectionKey {
+                            commit_idx,
+                            file_idx,
+                            section_idx,
+                        }));
                 }
             }
+        }
+        self.selection_key = match self.selection_key {
+            SelectionKey::Section(SectionKey {
+                commit_idx,
+                file_idx: selected_file_idx,
+                section_idx: _,
+            })
+            | SelectionKey::Line(LineKey {
+                commit_idx,
+                file_idx: selected_file_idx,
+                section_idx: _,
+                line_idx: _,
+            }) if selected_file_idx == file_idx => SelectionKey::File(FileKey {
+                commit_idx,
+                file_idx,
+            }),
+            selection_key => selection_key,
         };
-
-        Ok(())
     }
 
-    fn toggle_all(&mut self) {
-        if self.state.is_read_only {
-            return;
-        }
-
-        for file in &mut self.state.files {
-            file.toggle_all();
+    /// Load every file which hasn't been loaded yet.
+    fn load_all_files(&mut self) -> Result<(), RecordError> {
+        for file_idx in 0..self.state.files.len() {
+            self.load_file(file_idx)?;
         }
+        Ok(())
     }
 
-    fn toggle_all_uniform(&mut self) {
-        if self.state.is_read_only {
-            return;
+    /// Load the files which were drawn in the visible part of the screen.
+    /// Returns whether any of them were replaced, in which case the UI needs
+    /// to be drawn again.
+    fn load_visible_files(&mut self) -> Result<bool, RecordError> {
+        if self.loaded_files.len() == self.state.files.len() {
+            return Ok(false);
         }
 
-        let checked = {
-            let tristate = self
-                .state
-                .files
-                .iter()
-                .map(|file| file.tristate())
-                .fold(None, |acc, elem| match (acc, elem) {
-                    (None, tristate) => Some(tristate),
-                    (Some(acc_tristate), tristate) if acc_tristate == tristate => Some(tristate),
-                    _ => Some(Tristate::Partial),
-                })
-                .unwrap_or(Tristate::False);
-            match tristate {
-                Tristate::False | Tristate::Partial => true,
-                Tristate::True => false,
-            }
-        };
-        for file in &mut self.state.files {
-            file.set_checked(checked);
+        let top_y = self.scroll_offset_y;
+        let bottom_y = top_y + usize::from(self.term_area.height).unwrap_isize();
+        let visible_file_idxs: BTreeSet<usize> = self
+            .drawn_rects
+            .iter()
+            .filter_map(
+                |(component_id, DrawnRect { rect, timestamp: _ })| match component_id {
+                    ComponentId::SelectableItem(SelectionKey::File(FileKey {
+                        commit_idx: _,
+                        file_idx,
+                    })) if rect.y < bottom_y && top_y < rect.y + rect.height.unwrap_isize() => {
+                        Some(*file_idx)
+                    }
+                    _ => None,
+                },
+            )
+            .collect();
+        let mut is_any_loaded = false;
+        for file_idx in visible_file_idxs {
+            is_any_loaded |= self.load_file(file_idx)?;
         }
+        Ok(is_any_loaded)
     }
 
     fn expand_item_ancestors(&mut self, selection: SelectionKey) {
@@ -1982,6 +3617,7 @@ impl<'state, 'input> Recorder<'state, 'input> {
                     commit_idx,
                     file_idx,
                 }));
+                self.file_heights.borrow_mut().remove_file(file_idx);
             }
             SelectionKey::Line(LineKey {
                 commit_idx,
@@ -1999,22 +3635,35 @@ impl<'state, 'input> Recorder<'state, 'input> {
                         file_idx,
                         section_idx,
                     }));
+                self.file_heights.borrow_mut().remove_file(file_idx);
             }
         }
     }
 
-    fn set_expand_item(&mut self, selection: SelectionKey, is_expanded: bool) {
+    fn set_expand_item(
+        &mut self,
+        selection: SelectionKey,
+        is_expanded: bool,
+    ) -> Result<(), RecordError> {
         if is_expanded {
+            if let Some(file_idx) = selection.file_idx() {
+                self.load_file(file_idx)?;
+            }
             self.expanded_items.insert(selection);
         } else {
             self.expanded_items.remove(&selection);
         }
+        if let Some(file_idx) = selection.file_idx() {
+            self.file_heights.borrow_mut().remove_file(file_idx);
+        }
+        Ok(())
     }
 
     fn toggle_expand_item(&mut self, selection: SelectionKey) -> Result<(), RecordError> {
         match selection {
             SelectionKey::None => {}
             SelectionKey::File(file_key) => {
+                self.load_file(file_key.file_idx)?;
                 if !self.expanded_items.insert(SelectionKey::File(file_key)) {
                     self.expanded_items.remove(&SelectionKey::File(file_key));
                 }
@@ -2032,23 +3681,99 @@ impl<'state, 'input> Recorder<'state, 'input> {
                 // Do nothing.
             }
         }
+        if let Some(file_idx) = selection.file_idx() {
+            self.file_heights.borrow_mut().remove_file(file_idx);
+        }
         Ok(())
     }
 
+    fn toggle_commit_view_mode(&mut self) {
+        match self.commit_view_mode {
+            CommitViewMode::Inline => {
+                self.commit_view_mode = CommitViewMode::Adjacent;
+
+                // Start off the other commits with the same items expanded.
+                let expanded_items: Vec<_> = self.expanded_items.iter().copied().collect();
+                for selection_key in expanded_items {
+                    for commit_idx in 0..self.state.commits.len() {
+                        self.expanded_items
+                            .insert(selection_key.with_commit_idx(commit_idx));
+                    }
+                }
+
+                // Keep the same item selected, in the commit that it belongs
+                // to.
+                if let Some(selection_key) = (0..self.state.commits.len())
+                    .map(|commit_idx| self.selection_key.with_commit_idx(commit_idx))
+                    .find(|selection_key| self.is_selection_key_shown(*selection_key))
+                {
+                    self.selection_key = selection_key;
+                }
+            }
+            CommitViewMode::Adjacent => {
+                self.commit_view_mode = CommitViewMode::Inline;
+
+                // Keep the same item selected, but in the only visible commit.
+                self.selection_key = self.selection_key.with_commit_idx(self.focused_commit_idx);
+            }
+        }
+        self.expand_item_ancestors(self.selection_key);
+        self.select_nearest_shown_key();
+
+        // Each commit only shows some of the changes when the commits are
+        // shown side-by-side, so the files' heights are different.
+        self.file_heights.borrow_mut().clear();
+        self.pending_events.push(Event::EnsureSelectionInViewport);
+    }
+
+    /// If the selected item is no longer shown, such as because it was toggled
+    /// and moved to another commit, select the nearest item in the same
+    /// commit instead.
+    fn select_nearest_shown_key(&mut self) {
+        let selection_key = self.selection_key;
+        if selection_key == SelectionKey::None || self.is_selection_key_shown(selection_key) {
+            return;
+        }
+        let is_same_commit = |key: &SelectionKey| key.commit_idx() == selection_key.commit_idx();
+        self.selection_key = self
+            .next_visible_key(selection_key)
+            .filter(is_same_commit)
+            .or_else(|| self.prev_visible_key(selection_key).filter(is_same_commit))
+            .unwrap_or_else(|| self.first_selection_key());
+    }
+
+    /// The commit which actions such as editing the message apply to. When the
+    /// commits are shown side-by-side, this is the commit of the selected item.
+    fn selected_commit_idx(&self) -> usize {
+        match self.commit_view_mode {
+            CommitViewMode::Inline => self.focused_commit_idx,
+            CommitViewMode::Adjacent => self
+                .selection_key
+                .commit_idx()
+                .unwrap_or(self.focused_commit_idx),
+        }
+    }
+
     fn expand_initial_items(&mut self) {
         self.expanded_items = self
-            .all_selection_keys()
-            .into_iter()
+            .expandable_keys()
             .filter(|selection_key| match selection_key {
                 SelectionKey::None | SelectionKey::File(_) | SelectionKey::Line(_) => false,
                 SelectionKey::Section(_) => true,
             })
             .collect();
+        self.file_heights.borrow_mut().clear();
     }
 
+    /// Expand every file and section, or collapse all of them if they're all
+    /// already expanded. Files which haven't been loaded yet have their
+    /// sections expanded once they're loaded (see `replace_file`).
     fn toggle_expand_all(&mut self) -> Result<(), RecordError> {
-        let all_selection_keys: HashSet<_> = self.all_selection_keys().into_iter().collect();
-        self.expanded_items = if self.expanded_items == all_selection_keys {
+        self.file_heights.borrow_mut().clear();
+        let is_all_expanded = self
+            .expandable_keys()
+            .all(|selection_key| self.expanded_items.contains(&selection_key));
+        if is_all_expanded {
             // Select an ancestor file key that will still be visible.
             self.selection_key = match self.selection_key {
                 selection_key @ (SelectionKey::None | SelectionKey::File(_)) => selection_key,
@@ -2067,10 +3792,11 @@ impl<'state, 'input> Recorder<'state, 'input> {
                     file_idx,
                 }),
             };
-            Default::default()
+            self.expanded_items.clear();
         } else {
-            all_selection_keys
-        };
+            let expandable_keys: Vec<_> = self.expandable_keys().collect();
+            self.expanded_items.extend(expandable_keys);
+        }
         Ok(())
     }
 
@@ -2092,32 +3818,99 @@ impl<'state, 'input> Recorder<'state, 'input> {
         self.pending_events.push(event);
     }
 
+    fn open_message_editor(&mut self, commit_idx: usize) {
+        if let Some(message) = &self.state.commits[commit_idx].message {
+            self.message_editor = Some(MessageEditor {
+                commit_idx,
+                kind: MessageEditorKind::Message,
+                editor: TextEditor::new(message),
+            });
+        }
+    }
+
+    fn open_metadata_editor(&mut self, commit_idx: usize) {
+        if let Some(metadata) = format_commit_metadata(&self.state.commits[commit_idx]) {
+            self.message_editor = Some(MessageEditor {
+                commit_idx,
+                kind: MessageEditorKind::Metadata,
+                editor: TextEditor::new(&metadata),
+            });
+        }
+    }
+
+    /// Call `f` with the terminal restored to its normal state, such as to run
+    /// an external editor. If the recorder doesn't manage the terminal, then
+    /// it's left as it is.
+    fn with_terminal_suspended<T>(
+        manages_terminal: bool,
+        input: &mut dyn RecordInput,
+        f: impl FnOnce(&mut dyn RecordInput) -> Result<T, RecordError>,
+    ) -> Result<T, RecordError> {
+        if !manages_terminal {
+            return f(input);
+        }
+        match input.terminal_kind() {
+            TerminalKind::Testing { .. } => {}
+            TerminalKind::Crossterm => {
+                Self::clean_up_crossterm(false)?;
+            }
+            TerminalKind::CrosstermInline { .. } => {
+                Self::clean_up_crossterm(true)?;
+            }
+        }
+        let result = f(input);
+        match input.terminal_kind() {
+            TerminalKind::Testing { .. } => {}
+            TerminalKind::Crossterm => {
+                Self::set_up_crossterm(false)?;
+            }
+            TerminalKind::CrosstermInline { .. } => {
+                Self::set_up_crossterm(true)?;
+            }
+        }
+        result
+    }
+
     fn edit_commit_message(&mut self, commit_idx: usize) -> Result<(), RecordError> {
-        let message = &mut self.state.commits[commit_idx].message;
+        let RecordState {
+            is_read_only: _,
+            commits,
+            files,
+        } = &mut self.state;
+        let message = &mut commits[commit_idx].message;
         let message_str = match message.as_ref() {
             Some(message) => message,
             None => return Ok(()),
         };
-        let new_message = {
-            match self.input.terminal_kind() {
-                TerminalKind::Testing { .. } => {}
-                TerminalKind::Crossterm => {
-                    Self::clean_up_crossterm()?;
-                }
-            }
-            let result = self.input.edit_commit_message(message_str);
-            match self.input.terminal_kind() {
-                TerminalKind::Testing { .. } => {}
-                TerminalKind::Crossterm => {
-                    Self::set_up_crossterm()?;
-                }
-            }
-            result?
-        };
+        let new_message =
+            Self::with_terminal_suspended(self.manages_terminal, self.input, |input| {
+                input.edit_commit_message_for_files(message_str, files)
+            })?;
         *message = Some(new_message);
         Ok(())
     }
 
+    /// Edit the metadata of the commit with [`RecordInput::edit_commit_metadata`].
+    /// Returns `false` if the input can't edit metadata.
+    fn edit_commit_metadata(&mut self, commit_idx: usize) -> Result<bool, RecordError> {
+        let commit = &mut self.state.commits[commit_idx];
+        let metadata = match format_commit_metadata(commit) {
+            Some(metadata) => metadata,
+            None => return Ok(true),
+        };
+        let new_metadata =
+            Self::with_terminal_suspended(self.manages_terminal, self.input, |input| {
+                input.edit_commit_metadata(&metadata)
+            })?;
+        match new_metadata {
+            Some(new_metadata) => {
+                parse_commit_metadata(commit, &new_metadata);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
     fn file(&self, file_key: FileKey) -> Result<&File<'_>, RecordError> {
         let FileKey {
             commit_idx: _,
@@ -2131,24 +3924,6 @@ impl<'state, 'input> Recorder<'state, 'input> {
         }
     }
 
-    fn section(&self, section_key: SectionKey) -> Result<&Section<'_>, RecordError> {
-        let SectionKey {
-            commit_idx,
-            file_idx,
-            section_idx,
-        } = section_key;
-        let file = self.file(FileKey {
-            commit_idx,
-            file_idx,
-        })?;
-        match file.sections.get(section_idx) {
-            Some(section) => Ok(section),
-            None => Err(RecordError::Bug(format!(
-                "Out-of-bounds section key: {section_key:?}"
-            ))),
-        }
-    }
-
     fn visit_file_for_section<T>(
         &mut self,
         section_key: SectionKey,
@@ -2206,8 +3981,12 @@ impl<'state, 'input> Recorder<'state, 'input> {
     }
 
     fn file_tristate(&self, file_key: FileKey) -> Result<Tristate, RecordError> {
-        let file = self.file(file_key)?;
-        Ok(file.tristate())
+        match self.selection_counts.file(file_key.file_idx) {
+            Some(count) => Ok(tristate_for_commit(file_key.commit_idx, count.tristate())),
+            None => Err(RecordError::Bug(format!(
+                "Out-of-bounds file key: {file_key:?}"
+            ))),
+        }
     }
 
     fn file_expanded(&self, file_key: FileKey) -> Tristate {
@@ -2225,7 +4004,10 @@ impl<'state, 'input> Recorder<'state, 'input> {
                     match section {
                         Section::Unchanged { .. }
                         | Section::FileMode { .. }
-                        | Section::Binary { .. } => {
+                        | Section::Binary { .. }
+                        | Section::Submodule { .. }
+                        | Section::Summary { .. }
+                        | Section::Rename { .. } => {
                             // Not collapsible/expandable.
                             false
                         }
@@ -2276,8 +4058,17 @@ impl<'state, 'input> Recorder<'state, 'input> {
     }
 
     fn section_tristate(&self, section_key: SectionKey) -> Result<Tristate, RecordError> {
-        let section = self.section(section_key)?;
-        Ok(section.tristate())
+        let SectionKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+        } = section_key;
+        match self.selection_counts.section(file_idx, section_idx) {
+            Some(count) => Ok(tristate_for_commit(commit_idx, count.tristate())),
+            None => Err(RecordError::Bug(format!(
+                "Out-of-bounds section key: {section_key:?}"
+            ))),
+        }
     }
 
     fn visit_line<T>(
@@ -2297,7 +4088,12 @@ impl<'state, 'input> Recorder<'state, 'input> {
                 let line = &mut lines[line_idx];
                 Ok(f(line))
             }
-            Section::Unchanged { .. } | Section::FileMode { .. } | Section::Binary { .. } => {
+            Section::Unchanged { .. }
+            | Section::FileMode { .. }
+            | Section::Binary { .. }
+            | Section::Submodule { .. }
+            | Section::Summary { .. }
+            | Section::Rename { .. } => {
                 // Do nothing.
                 Ok(None)
             }
@@ -2315,6 +4111,8 @@ enum ComponentId {
     MenuItem(usize),
     CommitMessageView,
     CommitEditMessageButton(usize),
+    CommitMessagePreviewButton(usize),
+    CommitEditMetadataButton(usize),
     FileViewHeader(FileKey),
     SelectableItem(SelectionKey),
     ToggleBox(SelectionKey),
@@ -2323,6 +4121,9 @@ enum ComponentId {
     QuitDialogButton(QuitDialogButtonId),
     HelpDialog,
     HelpDialogQuitButton,
+    MessageEditorDialog,
+    MessageEditorSaveButton,
+    MessageEditorCancelButton,
 }
 
 #[derive(Clone, Debug)]
@@ -2334,6 +4135,7 @@ enum TristateIconStyle {
 #[derive(Clone, Debug)]
 struct TristateBox<Id> {
     use_unicode: bool,
+    theme: Theme,
     id: Id,
     tristate: Tristate,
     icon_style: TristateIconStyle,
@@ -2345,6 +4147,7 @@ impl<Id> TristateBox<Id> {
     fn text(&self) -> String {
         let Self {
             use_unicode,
+            theme: _,
             id: _,
             tristate,
             icon_style,
@@ -2385,9 +4188,9 @@ impl<Id: Clone + Debug + Eq + Hash> Component for TristateBox<Id> {
 
     fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
         let style = if self.is_read_only {
-            Style::default().fg(Color::Gray).add_modifier(Modifier::DIM)
+            self.theme.read_only_toggle_box
         } else {
-            Style::default().add_modifier(Modifier::BOLD)
+            self.theme.toggle_box
         };
         let span = Span::styled(self.text(), style);
         viewport.draw_span(x, y, &span);
@@ -2407,11 +4210,12 @@ struct AppDebugInfo {
 #[derive(Clone, Debug)]
 struct AppView<'a> {
     debug_info: Option<AppDebugInfo>,
-    menu_bar: MenuBar<'a>,
+    menu_bar: Option<MenuBar<'a>>,
     commit_view_mode: CommitViewMode,
     commit_views: Vec<CommitView<'a>>,
     quit_dialog: Option<QuitDialog>,
     help_dialog: Option<HelpDialog>,
+    message_editor: Option<MessageEditorDialog<'a>>,
 }
 
 impl Component for AppView<'_> {
@@ -2429,66 +4233,387 @@ impl Component for AppView<'_> {
             commit_views,
             quit_dialog,
             help_dialog,
+            message_editor,
         } = self;
 
         if let Some(debug_info) = debug_info {
             viewport.debug(format!("app debug info: {debug_info:#?}"));
         }
 
-        let viewport_rect = viewport.mask_rect();
-
-        let menu_bar_height = 1usize;
-        let commit_view_width = match commit_view_mode {
-            CommitViewMode::Inline => viewport.rect().width,
-            CommitViewMode::Adjacent => {
-                const MAX_COMMIT_VIEW_WIDTH: usize = 120;
-                MAX_COMMIT_VIEW_WIDTH
-                    .min(viewport.rect().width.saturating_sub(CommitView::MARGIN) / 2)
+        let viewport_rect = viewport.mask_rect();
+
+        let menu_bar_height = if menu_bar.is_some() { 1usize } else { 0 };
+        let commit_view_width = match commit_view_mode {
+            CommitViewMode::Inline => viewport.rect().width,
+            CommitViewMode::Adjacent => {
+                const MAX_COMMIT_VIEW_WIDTH: usize = 120;
+                MAX_COMMIT_VIEW_WIDTH
+                    .min(viewport.rect().width.saturating_sub(CommitView::MARGIN) / 2)
+            }
+        };
+        let commit_views_mask = Mask {
+            x: viewport_rect.x,
+            y: viewport_rect.y + menu_bar_height.unwrap_isize(),
+            width: Some(viewport_rect.width),
+            height: None,
+        };
+        viewport.with_mask(commit_views_mask, |viewport| {
+            let mut commit_view_x = 0;
+            for commit_view in commit_views {
+                let commit_view_mask = Mask {
+                    x: commit_views_mask.x + commit_view_x,
+                    y: commit_views_mask.y,
+                    width: Some(commit_view_width),
+                    height: None,
+                };
+                viewport.with_mask(commit_view_mask, |viewport| {
+                    viewport.draw_component(
+                        commit_view_x,
+                        menu_bar_height.unwrap_isize(),
+                        commit_view,
+                    )
+                });
+                commit_view_x += (CommitView::MARGIN + commit_view_width).unwrap_isize();
+            }
+        });
+
+        if let Some(menu_bar) = menu_bar {
+            viewport.draw_component(x, viewport_rect.y, menu_bar);
+        }
+
+        if let Some(quit_dialog) = quit_dialog {
+            viewport.draw_component(0, 0, quit_dialog);
+        }
+        if let Some(help_dialog) = help_dialog {
+            viewport.draw_component(0, 0, help_dialog);
+        }
+        if let Some(message_editor) = message_editor {
+            viewport.draw_component(0, 0, message_editor);
+        }
+    }
+}
+
+/// Format the author, trailers and branch of the commit as `Key: value` lines,
+/// to be edited by the user. Returns `None` if none of them are editable.
+fn format_commit_metadata(commit: &Commit) -> Option<String> {
+    let Commit {
+        message: _,
+        author,
+        trailers,
+        branch,
+    } = commit;
+    if author.is_none() && trailers.is_none() && branch.is_none() {
+        return None;
+    }
+
+    let mut metadata = String::new();
+    if let Some(author) = author {
+        writeln!(metadata, "Author: {author}").unwrap();
+    }
+    if let Some(branch) = branch {
+        writeln!(metadata, "Branch: {branch}").unwrap();
+    }
+    for Trailer { key, value } in trailers.iter().flatten() {
+        writeln!(metadata, "{key}: {value}").unwrap();
+    }
+    Some(metadata)
+}
+
+/// Update the author, trailers and branch of the commit from `Key: value`
+/// lines, as produced by [`format_commit_metadata`]. The `Author` and `Branch`
+/// keys (or `Bookmark`) set the corresponding fields, and any other keys are
+/// treated as trailers. Fields which are `None` are left as-is, and lines
+/// which are blank, start with `#` or don't have a value are ignored.
+fn parse_commit_metadata(commit: &mut Commit, metadata: &str) {
+    let Commit {
+        message: _,
+        author,
+        trailers,
+        branch,
+    } = commit;
+    if let Some(author) = author {
+        author.clear();
+    }
+    if let Some(branch) = branch {
+        branch.clear();
+    }
+    if let Some(trailers) = trailers {
+        trailers.clear();
+    }
+
+    for line in metadata.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        if key.is_empty() || value.is_empty() {
+            continue;
+        }
+        match (
+            key.to_ascii_lowercase().as_str(),
+            &mut *author,
+            &mut *branch,
+        ) {
+            ("author", Some(author), _) => {
+                *author = value.to_owned();
+            }
+            ("branch" | "bookmark", _, Some(branch)) => {
+                *branch = value.to_owned();
+            }
+            _ => {
+                if let Some(trailers) = trailers {
+                    trailers.push(Trailer {
+                        key: key.to_owned(),
+                        value: value.to_owned(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// A way in which a commit message doesn't follow the usual conventions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CommitMessageWarning {
+    Empty,
+    LongSubject,
+    MissingBlankLine,
+}
+
+impl CommitMessageWarning {
+    fn describe(&self) -> String {
+        match self {
+            CommitMessageWarning::Empty => "The message is empty".to_string(),
+            CommitMessageWarning::LongSubject => {
+                format!("The subject is longer than {MAX_SUBJECT_LENGTH} characters")
+            }
+            CommitMessageWarning::MissingBlankLine => {
+                "The subject isn't followed by a blank line".to_string()
+            }
+        }
+    }
+}
+
+fn commit_message_warnings(message: &str) -> Vec<CommitMessageWarning> {
+    if message.trim().is_empty() {
+        return vec![CommitMessageWarning::Empty];
+    }
+
+    let mut lines = message.lines();
+    let mut warnings = Vec::new();
+    if let Some(subject) = lines.next() {
+        if subject.trim_end().chars().count() > MAX_SUBJECT_LENGTH {
+            warnings.push(CommitMessageWarning::LongSubject);
+        }
+    }
+    if let Some(line) = lines.next() {
+        if !line.trim().is_empty() {
+            warnings.push(CommitMessageWarning::MissingBlankLine);
+        }
+    }
+    warnings
+}
+
+#[derive(Clone, Debug)]
+struct CommitMessageView<'a> {
+    theme: Theme,
+    commit_idx: usize,
+    commit: &'a Commit,
+    is_expanded: bool,
+}
+
+impl CommitMessageView<'_> {
+    /// The number of columns that the full message and the metadata are
+    /// indented by.
+    const PREVIEW_INDENT: usize = 4;
+
+    /// Draw the message header, followed by the full message if it's expanded.
+    /// Returns the row after the last one drawn.
+    fn draw_message(
+        &self,
+        viewport: &mut Viewport<ComponentId>,
+        x: isize,
+        y: isize,
+        message: &str,
+    ) -> isize {
+        let Self {
+            theme,
+            commit_idx,
+            commit: _,
+            is_expanded,
+        } = self;
+        let style = Style::default();
+        let preview_button_rect = viewport.draw_component(
+            x,
+            y,
+            &Button {
+                id: ComponentId::CommitMessagePreviewButton(*commit_idx),
+                label: Cow::Borrowed(if *is_expanded { "-" } else { "+" }),
+                style,
+                is_focused: false,
+            },
+        );
+        let button_rect = viewport.draw_component(
+            preview_button_rect.end_x() + 1,
+            y,
+            &Button {
+                id: ComponentId::CommitEditMessageButton(*commit_idx),
+                label: Cow::Borrowed("Edit message"),
+                style,
+                is_focused: false,
+            },
+        );
+        let warnings = commit_message_warnings(message);
+        let warning_icon = if viewport.use_unicode() { "⚠" } else { "!" };
+        let button_rect = if warnings.is_empty() {
+            button_rect
+        } else {
+            let num_warnings = warnings.len();
+            viewport.draw_span(
+                button_rect.end_x() + 2,
+                y,
+                &Span::styled(
+                    format!(
+                        "{warning_icon} {num_warnings} {}",
+                        if num_warnings == 1 {
+                            "warning"
+                        } else {
+                            "warnings"
+                        }
+                    ),
+                    theme.warning,
+                ),
+            )
+        };
+        let divider_rect =
+            viewport.draw_span(button_rect.end_x() + 1, y, &Span::raw(divider(viewport)));
+        viewport.draw_text(
+            divider_rect.end_x() + 1,
+            y,
+            Span::styled(
+                Cow::Borrowed({
+                    let first_line = match message.split_once('\n') {
+                        Some((before, _after)) => before,
+                        None => message,
+                    };
+                    let first_line = first_line.trim();
+                    if first_line.is_empty() {
+                        "(no message)"
+                    } else {
+                        first_line
+                    }
+                }),
+                style.add_modifier(Modifier::UNDERLINED),
+            ),
+        );
+        let mut y = y + 1;
+
+        if *is_expanded {
+            let x = x + Self::PREVIEW_INDENT.unwrap_isize();
+            for warning in &warnings {
+                viewport.draw_span(
+                    x,
+                    y,
+                    &Span::styled(
+                        format!("{warning_icon} {}", warning.describe()),
+                        theme.warning,
+                    ),
+                );
+                y += 1;
+            }
+
+            let gutter = if viewport.use_unicode() { "│ " } else { "| " };
+            let content_width = viewport
+                .mask_rect()
+                .width
+                .saturating_sub(Self::PREVIEW_INDENT + gutter.width())
+                .max(1);
+            for line in message.trim_end().lines() {
+                for row in wrap_words(line, content_width) {
+                    let gutter_rect = viewport.draw_span(x, y, &Span::raw(gutter));
+                    viewport.draw_text(gutter_rect.end_x(), y, Span::raw(row));
+                    y += 1;
+                }
+            }
+        }
+        y
+    }
+
+    /// Draw the author, branch and trailers of the commit. Returns the row
+    /// after the last one drawn.
+    fn draw_metadata(&self, viewport: &mut Viewport<ComponentId>, x: isize, y: isize) -> isize {
+        let Self {
+            theme: _,
+            commit_idx,
+            commit,
+            is_expanded: _,
+        } = self;
+        let Commit {
+            message: _,
+            author,
+            trailers,
+            branch,
+        } = commit;
+        let describe = |value: &str| {
+            if value.is_empty() {
+                "(none)".to_owned()
+            } else {
+                value.to_owned()
             }
         };
-        let commit_views_mask = Mask {
-            x: viewport_rect.x,
-            y: viewport_rect.y + menu_bar_height.unwrap_isize(),
-            width: Some(viewport_rect.width),
-            height: None,
-        };
-        viewport.with_mask(commit_views_mask, |viewport| {
-            let mut commit_view_x = 0;
-            for commit_view in commit_views {
-                let commit_view_mask = Mask {
-                    x: commit_views_mask.x + commit_view_x,
-                    y: commit_views_mask.y,
-                    width: Some(commit_view_width),
-                    height: None,
-                };
-                let commit_view_rect = viewport.with_mask(commit_view_mask, |viewport| {
-                    viewport.draw_component(
-                        commit_view_x,
-                        menu_bar_height.unwrap_isize(),
-                        commit_view,
-                    )
-                });
-                commit_view_x += (CommitView::MARGIN
-                    + commit_view_mask.apply(commit_view_rect).width)
-                    .unwrap_isize();
-            }
-        });
 
-        viewport.draw_component(x, viewport_rect.y, menu_bar);
-
-        if let Some(quit_dialog) = quit_dialog {
-            viewport.draw_component(0, 0, quit_dialog);
+        let mut rows = Vec::new();
+        let fields: Vec<String> = [("Author", author), ("Branch", branch)]
+            .into_iter()
+            .filter_map(|(key, value)| Some(format!("{key}: {}", describe(value.as_ref()?))))
+            .collect();
+        if !fields.is_empty() {
+            rows.push(fields.join(divider(viewport)));
         }
-        if let Some(help_dialog) = help_dialog {
-            viewport.draw_component(0, 0, help_dialog);
+        if let Some(trailers) = trailers {
+            rows.extend(
+                trailers
+                    .iter()
+                    .map(|Trailer { key, value }| format!("{key}: {value}")),
+            );
+            if rows.is_empty() {
+                rows.push("(no trailers)".to_owned());
+            }
+        }
+
+        let button_rect = viewport.draw_component(
+            x,
+            y,
+            &Button {
+                id: ComponentId::CommitEditMetadataButton(*commit_idx),
+                label: Cow::Borrowed("Edit metadata"),
+                style: Style::default(),
+                is_focused: false,
+            },
+        );
+        let divider_rect =
+            viewport.draw_span(button_rect.end_x() + 1, y, &Span::raw(divider(viewport)));
+        let rows_x = divider_rect.end_x() + 1;
+        let mut y = y;
+        for row in rows {
+            viewport.draw_text(rows_x, y, Span::raw(row));
+            y += 1;
         }
+        y
     }
 }
 
-#[derive(Clone, Debug)]
-struct CommitMessageView<'a> {
-    commit_idx: usize,
-    commit: &'a Commit,
+/// The separator between items drawn on the same row.
+fn divider<Id: Clone + Debug + Eq + Hash>(viewport: &Viewport<Id>) -> &'static str {
+    if viewport.use_unicode() {
+        " • "
+    } else {
+        " - "
+    }
 }
 
 impl Component for CommitMessageView<'_> {
@@ -2499,70 +4624,73 @@ impl Component for CommitMessageView<'_> {
     }
 
     fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
-        let Self { commit_idx, commit } = self;
-        match commit {
-            Commit { message: None } => {}
-            Commit {
-                message: Some(message),
-            } => {
-                viewport.draw_blank(Rect {
-                    x,
-                    y,
-                    width: viewport.mask_rect().width,
-                    height: 1,
-                });
-                let y = y + 1;
+        let Commit {
+            message,
+            author,
+            trailers,
+            branch,
+        } = self.commit;
+        let has_metadata = author.is_some() || trailers.is_some() || branch.is_some();
+        if message.is_none() && !has_metadata {
+            return;
+        }
 
-                let style = Style::default();
-                let button_rect = viewport.draw_component(
-                    x,
-                    y,
-                    &Button {
-                        id: ComponentId::CommitEditMessageButton(*commit_idx),
-                        label: Cow::Borrowed("Edit message"),
-                        style,
-                        is_focused: false,
-                    },
-                );
-                let divider_rect =
-                    viewport.draw_span(button_rect.end_x() + 1, y, &Span::raw(" • "));
-                viewport.draw_text(
-                    divider_rect.end_x() + 1,
-                    y,
-                    Span::styled(
-                        Cow::Borrowed({
-                            let first_line = match message.split_once('\n') {
-                                Some((before, _after)) => before,
-                                None => message,
-                            };
-                            let first_line = first_line.trim();
-                            if first_line.is_empty() {
-                                "(no message)"
-                            } else {
-                                first_line
-                            }
-                        }),
-                        style.add_modifier(Modifier::UNDERLINED),
-                    ),
-                );
-                let y = y + 1;
+        let width = viewport.mask_rect().width;
+        viewport.draw_blank(Rect {
+            x,
+            y,
+            width,
+            height: 1,
+        });
+        let mut y = y + 1;
 
-                viewport.draw_blank(Rect {
-                    x,
-                    y,
-                    width: viewport.mask_rect().width,
-                    height: 1,
-                });
-            }
+        let mut metadata_x = x;
+        if let Some(message) = message {
+            y = self.draw_message(viewport, x, y, message);
+            metadata_x += Self::PREVIEW_INDENT.unwrap_isize();
         }
+        if has_metadata {
+            y = self.draw_metadata(viewport, metadata_x, y);
+        }
+
+        viewport.draw_blank(Rect {
+            x,
+            y,
+            width,
+            height: 1,
+        });
+    }
+}
+
+/// Builds the [`FileView`]s of a [`CommitView`] on demand, so that only the
+/// files near the visible area have to be built.
+trait FileViewSource {
+    /// Build the view of the given file.
+    fn file_view(&self, file_key: FileKey, debug: bool) -> FileView<'_>;
+}
+
+impl FileViewSource for Recorder<'_, '_> {
+    fn file_view(&self, file_key: FileKey, debug: bool) -> FileView<'_> {
+        self.make_file_view(file_key, debug)
+    }
+}
+
+impl Debug for dyn FileViewSource + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileViewSource").finish_non_exhaustive()
     }
 }
 
 #[derive(Clone, Debug)]
 struct CommitView<'a> {
     debug_info: Option<&'a AppDebugInfo>,
+    use_unicode: bool,
+    render_options: RenderOptions,
+    file_heights: &'a RefCell<FileHeightCache>,
     commit_message_view: CommitMessageView<'a>,
-    file_views: Vec<FileView<'a>>,
+    file_keys: Vec<FileKey>,
+    selected_file_key: Option<FileKey>,
+    file_views: &'a dyn FileViewSource,
 }
 
 impl CommitView<'_> {
@@ -2579,12 +4707,17 @@ impl Component for CommitView<'_> {
     fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
         let Self {
             debug_info,
+            use_unicode,
+            render_options,
+            file_heights,
             commit_message_view,
+            file_keys,
+            selected_file_key,
             file_views,
         } = self;
 
         let commit_message_view_rect = viewport.draw_component(x, y, commit_message_view);
-        if file_views.is_empty() {
+        if file_keys.is_empty() {
             let message = "There are no changes to view.";
             let message_rect = centered_rect(
                 Rect {
@@ -2606,7 +4739,57 @@ impl Component for CommitView<'_> {
 
         let mut y = y;
         y += commit_message_view_rect.height.unwrap_isize();
-        for file_view in file_views {
+        let mut file_heights = file_heights.borrow_mut();
+        for file_key in file_keys {
+            let mask_rect = viewport.mask_rect();
+            let is_selected = selected_file_key.as_ref() == Some(file_key);
+
+            // Files which are far from the visible area are skipped entirely,
+            // without building their views, unless they contain the selection,
+            // which may need to be scrolled into view.
+            let cached_height = file_heights.get(
+                *render_options,
+                *use_unicode,
+                *file_key,
+                (mask_rect.end_x() - x).clamp_into_usize(),
+            );
+            if let Some(file_height) = cached_height {
+                let file_view_rect = Rect {
+                    x: mask_rect.x,
+                    y,
+                    width: mask_rect.width,
+                    height: file_height,
+                };
+                if !is_selected && !viewport.is_in_layout(file_view_rect) {
+                    viewport.skip_component(
+                        file_view_rect,
+                        ComponentId::SelectableItem(SelectionKey::File(*file_key)),
+                    );
+                    y += file_height.unwrap_isize();
+                    continue;
+                }
+            }
+
+            let file_view = &file_views.file_view(*file_key, debug_info.is_some());
+            let file_height = file_heights.get_or_insert(
+                *render_options,
+                *use_unicode,
+                file_view,
+                x,
+                mask_rect.end_x(),
+            );
+            let file_view_rect = Rect {
+                x: mask_rect.x,
+                y,
+                width: mask_rect.width,
+                height: file_height,
+            };
+            if !is_selected && !viewport.is_in_layout(file_view_rect) {
+                viewport.skip_component(file_view_rect, file_view.id());
+                y += file_height.unwrap_isize();
+                continue;
+            }
+
             let file_view_rect = {
                 let file_view_mask = Mask {
                     x,
@@ -2618,6 +4801,10 @@ impl Component for CommitView<'_> {
                     viewport.draw_component(x, y, file_view)
                 })
             };
+            debug_assert_eq!(
+                file_view_rect.height, file_height,
+                "file height doesn't match its layout"
+            );
 
             // Render a sticky header if necessary.
             let mask = viewport.mask();
@@ -2636,6 +4823,7 @@ impl Component for CommitView<'_> {
                             x,
                             mask.y,
                             &FileViewHeader {
+                                theme: file_view.theme,
                                 file_key: file_view.file_key,
                                 path: file_view.path,
                                 old_path: file_view.old_path,
@@ -2648,7 +4836,7 @@ impl Component for CommitView<'_> {
                 );
             }
 
-            y += file_view_rect.height.unwrap_isize();
+            y += file_height.unwrap_isize();
 
             if debug_info.is_some() {
                 viewport.debug(format!(
@@ -2715,6 +4903,7 @@ impl Component for Menu<'_> {
 
 #[derive(Clone, Debug)]
 struct MenuBar<'a> {
+    theme: Theme,
     menus: Vec<Menu<'a>>,
     expanded_menu_idx: Option<usize>,
 }
@@ -2728,12 +4917,13 @@ impl Component for MenuBar<'_> {
 
     fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
         let Self {
+            theme,
             menus,
             expanded_menu_idx,
         } = self;
 
         viewport.draw_blank(viewport.rect().top_row());
-        highlight_rect(viewport, viewport.rect().top_row());
+        highlight_rect(viewport, theme, viewport.rect().top_row());
         let mut x = x;
         for (i, menu) in menus.iter().enumerate() {
             let menu_header = Button {
@@ -2754,6 +4944,7 @@ impl Component for MenuBar<'_> {
 #[derive(Clone, Debug)]
 struct FileView<'a> {
     debug: bool,
+    theme: Theme,
     file_key: FileKey,
     toggle_box: TristateBox<ComponentId>,
     expand_box: TristateBox<ComponentId>,
@@ -2770,6 +4961,51 @@ impl FileView<'_> {
             Tristate::Partial | Tristate::True => true,
         }
     }
+
+    /// The sections which are shown when this file is expanded. Un-editable
+    /// context sections are only shown if one of the editable sections next to
+    /// them is expanded.
+    fn shown_section_views(&self) -> impl Iterator<Item = &SectionView<'_>> {
+        let expanded_sections: HashSet<usize> = self
+            .section_views
+            .iter()
+            .enumerate()
+            .filter_map(|(i, view)| {
+                if view.is_expanded() && view.section.is_editable() {
+                    return Some(i);
+                }
+                None
+            })
+            .collect();
+        self.section_views
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, section_view)| {
+                let context_section = !section_view.section.is_editable();
+                let prev_is_collapsed = i == 0 || !expanded_sections.contains(&(i - 1));
+                let next_is_collapsed = !expanded_sections.contains(&(i + 1));
+                if context_section && prev_is_collapsed && next_is_collapsed {
+                    None
+                } else {
+                    Some(section_view)
+                }
+            })
+    }
+
+    /// The number of rows which this file takes up when drawn at `x`, where
+    /// `end_x` is the end of the mask that it's drawn in.
+    fn height(&self, x: isize, end_x: isize) -> usize {
+        let header_height = 1;
+        if self.is_expanded() {
+            header_height
+                + self
+                    .shown_section_views()
+                    .map(|section_view| section_view.height(x + 2, end_x))
+                    .sum::<usize>()
+        } else {
+            header_height
+        }
+    }
 }
 
 impl Component for FileView<'_> {
@@ -2782,12 +5018,13 @@ impl Component for FileView<'_> {
     fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
         let Self {
             debug,
+            theme,
             file_key,
             toggle_box,
             expand_box,
             old_path,
             path,
-            section_views,
+            section_views: _,
             is_header_selected,
         } = self;
 
@@ -2795,6 +5032,7 @@ impl Component for FileView<'_> {
             x,
             y,
             &FileViewHeader {
+                theme: *theme,
                 file_key: *file_key,
                 path,
                 old_path: *old_path,
@@ -2805,39 +5043,120 @@ impl Component for FileView<'_> {
         );
         if self.is_expanded() {
             let x = x + 2;
+            let mask_rect = viewport.mask_rect();
             let mut section_y = y + file_view_header_rect.height.unwrap_isize();
-            let expanded_sections: HashSet<usize> = section_views
-                .iter()
-                .enumerate()
-                .filter_map(|(i, view)| {
-                    if view.is_expanded() && view.section.is_editable() {
-                        return Some(i);
+            for section_view in self.shown_section_views() {
+                // Sections which are far from the visible area are only laid
+                // out, unless they contain the selection, which may need to be
+                // scrolled into view.
+                let section_rect = Rect {
+                    x: mask_rect.x,
+                    y: section_y,
+                    width: mask_rect.width,
+                    height: section_view.height(x, mask_rect.end_x()),
+                };
+                if section_view.selection.is_some() || viewport.is_in_layout(section_rect) {
+                    let drawn_rect = viewport.draw_component(x, section_y, section_view);
+                    debug_assert_eq!(
+                        drawn_rect.height, section_rect.height,
+                        "section height doesn't match its layout"
+                    );
+                    if *debug {
+                        viewport.debug(format!("section dims: {drawn_rect:?}",));
                     }
-                    None
-                })
-                .collect();
-            for (i, section_view) in section_views.iter().enumerate() {
-                // Skip this section if it is an un-editable context section and
-                // none of the editable sections surrounding it are expanded.
-                let context_section = !section_view.section.is_editable();
-                let prev_is_collapsed = i == 0 || !expanded_sections.contains(&(i - 1));
-                let next_is_collapsed = !expanded_sections.contains(&(i + 1));
-                if context_section && prev_is_collapsed && next_is_collapsed {
-                    continue;
+                } else {
+                    viewport.skip_component(section_rect, section_view.id());
                 }
-
-                let section_rect = viewport.draw_component(x, section_y, section_view);
                 section_y += section_rect.height.unwrap_isize();
+            }
+        }
+    }
+}
 
-                if *debug {
-                    viewport.debug(format!("section dims: {section_rect:?}",));
-                }
+/// The heights of the [`FileView`]s from previous renders, so that files which
+/// aren't visible can be skipped without building their views. The heights of
+/// a file have to be removed with `FileHeightCache::remove_file` whenever its
+/// contents, its selected changes or its expanded items change.
+#[derive(Debug, Default)]
+struct FileHeightCache {
+    render_options: Option<(RenderOptions, bool)>,
+
+    /// The width and height of each file, by file index and then by commit
+    /// index.
+    heights: HashMap<usize, HashMap<usize, (usize, usize)>>,
+}
+
+impl FileHeightCache {
+    /// Get the height of the given file when drawn with the given width, if
+    /// it's been cached.
+    fn get(
+        &mut self,
+        render_options: RenderOptions,
+        use_unicode: bool,
+        file_key: FileKey,
+        width: usize,
+    ) -> Option<usize> {
+        if self.render_options != Some((render_options, use_unicode)) {
+            self.render_options = Some((render_options, use_unicode));
+            self.heights.clear();
+        }
+        let FileKey {
+            commit_idx,
+            file_idx,
+        } = file_key;
+        match self
+            .heights
+            .get(&file_idx)
+            .and_then(|heights| heights.get(&commit_idx))
+        {
+            Some((cached_width, height)) if *cached_width == width => Some(*height),
+            Some(_) | None => None,
+        }
+    }
+
+    /// Get the height of `file_view` when drawn at `x`, where `end_x` is the
+    /// end of the mask that it's drawn in. The height is only computed if it
+    /// hasn't been cached.
+    fn get_or_insert(
+        &mut self,
+        render_options: RenderOptions,
+        use_unicode: bool,
+        file_view: &FileView,
+        x: isize,
+        end_x: isize,
+    ) -> usize {
+        let width = (end_x - x).clamp_into_usize();
+        match self.get(render_options, use_unicode, file_view.file_key, width) {
+            Some(height) => height,
+            None => {
+                let FileKey {
+                    commit_idx,
+                    file_idx,
+                } = file_view.file_key;
+                let height = file_view.height(x, end_x);
+                self.heights
+                    .entry(file_idx)
+                    .or_default()
+                    .insert(commit_idx, (width, height));
+                height
             }
         }
     }
+
+    /// Forget the heights of the file at `file_idx`, such as after its
+    /// contents have changed.
+    fn remove_file(&mut self, file_idx: usize) {
+        self.heights.remove(&file_idx);
+    }
+
+    /// Forget the heights of all files.
+    fn clear(&mut self) {
+        self.heights.clear();
+    }
 }
 
 struct FileViewHeader<'a> {
+    theme: Theme,
     file_key: FileKey,
     path: &'a Path,
     old_path: Option<&'a Path>,
@@ -2851,6 +5170,7 @@ impl Component for FileViewHeader<'_> {
 
     fn id(&self) -> Self::Id {
         let Self {
+            theme: _,
             file_key,
             path: _,
             old_path: _,
@@ -2863,6 +5183,7 @@ impl Component for FileViewHeader<'_> {
 
     fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
         let Self {
+            theme,
             file_key: _,
             path,
             old_path,
@@ -2907,7 +5228,7 @@ impl Component for FileViewHeader<'_> {
                             path.to_string_lossy(),
                         ),
                         if *is_selected {
-                            Style::default().fg(Color::Blue)
+                            theme.focused_path
                         } else {
                             Style::default()
                         },
@@ -2919,6 +5240,7 @@ impl Component for FileViewHeader<'_> {
         if *is_selected {
             highlight_rect(
                 viewport,
+                theme,
                 Rect {
                     x: viewport.mask_rect().x,
                     y,
@@ -2939,6 +5261,9 @@ enum SectionSelection {
 #[derive(Clone, Debug)]
 struct SectionView<'a> {
     use_unicode: bool,
+    theme: Theme,
+    render_options: RenderOptions,
+    scroll_offset_x: usize,
     is_read_only: bool,
     section_key: SectionKey,
     toggle_box: TristateBox<ComponentId>,
@@ -2948,6 +5273,7 @@ struct SectionView<'a> {
     editable_section_num: usize,
     total_num_editable_sections: usize,
     section: &'a Section<'a>,
+    shown_changes: ShownChanges,
     line_start_num: usize,
 }
 
@@ -2962,6 +5288,187 @@ impl SectionView<'_> {
             Tristate::True => true,
         }
     }
+
+    /// The rows to draw for an unchanged section with the given lines, which
+    /// are shown as context for the surrounding changed sections. A row of
+    /// `None` represents an ellipsis drawn in place of the omitted lines.
+    fn context_rows<'line>(
+        &self,
+        lines: &'line [Cow<'_, str>],
+    ) -> Vec<Option<(usize, &'line str)>> {
+        let section_idx = self.section_key.section_idx;
+        let is_first_section = section_idx == 0;
+        let is_last_section = section_idx + 1 == self.total_num_sections;
+        let lines: Vec<_> = lines.iter().map(|line| line.as_ref()).enumerate().collect();
+        let before_ellipsis_lines = &lines[..min(NUM_CONTEXT_LINES, lines.len())];
+        let after_ellipsis_lines = &lines[lines.len().saturating_sub(NUM_CONTEXT_LINES)..];
+
+        match (before_ellipsis_lines, after_ellipsis_lines) {
+            ([.., (last_before_idx, _)], [(first_after_idx, _), ..])
+                if *last_before_idx + 1 >= *first_after_idx
+                    && !is_first_section
+                    && !is_last_section =>
+            {
+                // The context before and after the ellipsis would overlap,
+                // so show all of the lines instead.
+                return lines.into_iter().map(Some).collect();
+            }
+            _ => {}
+        };
+
+        let mut rows = Vec::new();
+        if !is_first_section {
+            rows.extend(before_ellipsis_lines.iter().copied().map(Some));
+        }
+        if lines.len() > NUM_CONTEXT_LINES {
+            rows.push(None);
+        }
+        if !is_last_section {
+            rows.extend(after_ellipsis_lines.iter().copied().map(Some));
+        }
+        rows
+    }
+
+    /// The changed lines of this section which are shown, along with their
+    /// indexes.
+    fn shown_lines<'line>(
+        &self,
+        lines: &'line [SectionChangedLine<'line>],
+    ) -> impl Iterator<Item = (usize, &'line SectionChangedLine<'line>)> + use<'line> {
+        let shown_changes = self.shown_changes;
+        lines
+            .iter()
+            .enumerate()
+            .filter(move |(_, line)| shown_changes.includes(line.is_checked))
+    }
+
+    /// Whether the changed line at `line_idx` in this section is selected.
+    fn is_line_selected(&self, line_idx: usize) -> bool {
+        match self.selection {
+            Some(SectionSelection::ChangedLine(selected_line_idx)) => line_idx == selected_line_idx,
+            Some(SectionSelection::SectionHeader) | None => false,
+        }
+    }
+
+    /// The view for the changed line at `line_idx` in this section.
+    fn changed_line_view<'line>(
+        &self,
+        line_idx: usize,
+        line: &'line SectionChangedLine<'_>,
+    ) -> SectionLineView<'line> {
+        let SectionChangedLine {
+            is_checked,
+            change_type,
+            line,
+        } = line;
+        let SectionKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+        } = self.section_key;
+        let is_focused = self.is_line_selected(line_idx);
+        let line_key = LineKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+            line_idx,
+        };
+        let toggle_box = TristateBox {
+            use_unicode: self.use_unicode,
+            theme: self.theme,
+            id: ComponentId::ToggleBox(SelectionKey::Line(line_key)),
+            icon_style: TristateIconStyle::Check,
+            tristate: Tristate::from(is_checked_for_commit(commit_idx, *is_checked)),
+            is_focused,
+            is_read_only: self.is_read_only,
+        };
+        SectionLineView {
+            use_unicode: self.use_unicode,
+            theme: self.theme,
+            render_options: self.render_options,
+            scroll_offset_x: self.scroll_offset_x,
+            line_key,
+            inner: SectionLineViewInner::Changed {
+                toggle_box,
+                change_type: *change_type,
+                line: line.as_ref(),
+            },
+        }
+    }
+
+    /// The number of rows which the changed line at `line_idx` takes up. See
+    /// `SectionLineView::height`.
+    fn changed_line_height(
+        &self,
+        line_idx: usize,
+        line: &SectionChangedLine<'_>,
+        x: isize,
+        end_x: isize,
+    ) -> usize {
+        if self.render_options.wrap_lines {
+            self.changed_line_view(line_idx, line).height(x, end_x)
+        } else {
+            // Avoid building the view when we already know the answer.
+            1
+        }
+    }
+
+    /// The number of rows which this section takes up when drawn at `x`,
+    /// where `end_x` is the end of the mask that it's drawn in.
+    fn height(&self, x: isize, end_x: isize) -> usize {
+        let SectionKey {
+            commit_idx,
+            file_idx,
+            section_idx,
+        } = self.section_key;
+        match self.section {
+            Section::Unchanged { lines } => {
+                let height = self
+                    .context_rows(lines)
+                    .into_iter()
+                    .map(|row| match row {
+                        Some((line_idx, line)) => SectionLineView {
+                            use_unicode: self.use_unicode,
+                            theme: self.theme,
+                            render_options: self.render_options,
+                            scroll_offset_x: self.scroll_offset_x,
+                            line_key: LineKey {
+                                commit_idx,
+                                file_idx,
+                                section_idx,
+                                line_idx,
+                            },
+                            inner: SectionLineViewInner::Unchanged {
+                                line,
+                                line_num: self.line_start_num + line_idx,
+                            },
+                        }
+                        .height(x + 2, end_x),
+                        None => 1,
+                    })
+                    .sum();
+                // The first row is always blanked, even if there are no lines.
+                max(height, 1)
+            }
+            Section::Changed { lines } => {
+                let lines_height: usize = if self.is_expanded() {
+                    self.shown_lines(lines)
+                        .map(|(line_idx, line)| {
+                            self.changed_line_height(line_idx, line, x + 2, end_x)
+                        })
+                        .sum()
+                } else {
+                    0
+                };
+                1 + lines_height
+            }
+            Section::FileMode { .. }
+            | Section::Binary { .. }
+            | Section::Submodule { .. }
+            | Section::Summary { .. }
+            | Section::Rename { .. } => 1,
+        }
+    }
 }
 
 impl Component for SectionView<'_> {
@@ -2974,15 +5481,19 @@ impl Component for SectionView<'_> {
     fn draw(&self, viewport: &mut Viewport<Self::Id>, x: isize, y: isize) {
         let Self {
             use_unicode,
+            theme,
+            render_options,
+            scroll_offset_x,
             is_read_only,
             section_key,
             toggle_box,
             expand_box,
             selection,
-            total_num_sections,
+            total_num_sections: _,
             editable_section_num,
             total_num_editable_sections,
             section,
+            shown_changes: _,
             line_start_num,
         } = self;
         viewport.draw_blank(Rect {
@@ -2999,104 +5510,42 @@ impl Component for SectionView<'_> {
         } = *section_key;
         match section {
             Section::Unchanged { lines } => {
-                if lines.is_empty() {
-                    return;
-                }
-
-                let lines: Vec<_> = lines.iter().enumerate().collect();
-                let is_first_section = section_idx == 0;
-                let is_last_section = section_idx + 1 == *total_num_sections;
-                let before_ellipsis_lines = &lines[..min(NUM_CONTEXT_LINES, lines.len())];
-                let after_ellipsis_lines = &lines[lines.len().saturating_sub(NUM_CONTEXT_LINES)..];
-
-                match (before_ellipsis_lines, after_ellipsis_lines) {
-                    ([.., (last_before_idx, _)], [(first_after_idx, _), ..])
-                        if *last_before_idx + 1 >= *first_after_idx
-                            && !is_first_section
-                            && !is_last_section =>
-                    {
-                        let first_before_idx = before_ellipsis_lines.first().unwrap().0;
-                        let last_after_idx = after_ellipsis_lines.last().unwrap().0;
-                        let overlapped_lines = &lines[first_before_idx..=last_after_idx];
-                        let overlapped_lines = if is_first_section {
-                            &overlapped_lines
-                                [overlapped_lines.len().saturating_sub(NUM_CONTEXT_LINES)..]
-                        } else if is_last_section {
-                            &overlapped_lines[..lines.len().min(NUM_CONTEXT_LINES)]
-                        } else {
-                            overlapped_lines
-                        };
-                        for (dy, (line_idx, line)) in overlapped_lines.iter().enumerate() {
+                let mut dy = 0;
+                for row in self.context_rows(lines) {
+                    match row {
+                        Some((line_idx, line)) => {
                             let line_view = SectionLineView {
+                                use_unicode: *use_unicode,
+                                theme: *theme,
+                                render_options: *render_options,
+                                scroll_offset_x: *scroll_offset_x,
                                 line_key: LineKey {
                                     commit_idx,
                                     file_idx,
                                     section_idx,
-                                    line_idx: *line_idx,
+                                    line_idx,
                                 },
                                 inner: SectionLineViewInner::Unchanged {
-                                    line: line.as_ref(),
+                                    line,
                                     line_num: line_start_num + line_idx,
                                 },
                             };
-                            viewport.draw_component(x + 2, y + dy.unwrap_isize(), &line_view);
+                            let line_rect = viewport.draw_component(x + 2, y + dy, &line_view);
+                            dy += line_rect.height.unwrap_isize();
+                        }
+                        None => {
+                            let ellipsis = if *use_unicode {
+                                "\u{22EE}" // Vertical Ellipsis
+                            } else {
+                                ":"
+                            };
+                            viewport.draw_span(
+                                x + 6, // align with line numbering
+                                y + dy,
+                                &Span::styled(ellipsis, theme.unchanged_line),
+                            );
+                            dy += 1;
                         }
-                        return;
-                    }
-                    _ => {}
-                };
-
-                let mut dy = 0;
-                if !is_first_section {
-                    for (line_idx, line) in before_ellipsis_lines {
-                        let line_view = SectionLineView {
-                            line_key: LineKey {
-                                commit_idx,
-                                file_idx,
-                                section_idx,
-                                line_idx: *line_idx,
-                            },
-                            inner: SectionLineViewInner::Unchanged {
-                                line: line.as_ref(),
-                                line_num: line_start_num + line_idx,
-                            },
-                        };
-                        viewport.draw_component(x + 2, y + dy, &line_view);
-                        dy += 1;
-                    }
-                }
-
-                let should_render_ellipsis = lines.len() > NUM_CONTEXT_LINES;
-                if should_render_ellipsis {
-                    let ellipsis = if *use_unicode {
-                        "\u{22EE}" // Vertical Ellipsis
-                    } else {
-                        ":"
-                    };
-                    viewport.draw_span(
-                        x + 6, // align with line numbering
-                        y + dy,
-                        &Span::styled(ellipsis, Style::default().add_modifier(Modifier::DIM)),
-                    );
-                    dy += 1;
-                }
-
-                if !is_last_section {
-                    for (line_idx, line) in after_ellipsis_lines {
-                        let line_view = SectionLineView {
-                            line_key: LineKey {
-                                commit_idx,
-                                file_idx,
-                                section_idx,
-                                line_idx: *line_idx,
-                            },
-                            inner: SectionLineViewInner::Unchanged {
-                                line: line.as_ref(),
-                                line_num: line_start_num + line_idx,
-                            },
-                        };
-                        viewport.draw_component(x + 2, y + dy, &line_view);
-                        dy += 1;
                     }
                 }
             }
@@ -3105,7 +5554,7 @@ impl Component for SectionView<'_> {
                 // Draw expand box at end of line.
                 let expand_box_width = expand_box.text().width().unwrap_isize();
                 let expand_box_rect = viewport.draw_component(
-                    viewport.mask_rect().width.unwrap_isize() - expand_box_width,
+                    viewport.mask_rect().end_x() - expand_box_width,
                     y,
                     expand_box,
                 );
@@ -3137,6 +5586,7 @@ impl Component for SectionView<'_> {
                     Some(SectionSelection::SectionHeader) => {
                         highlight_rect(
                             viewport,
+                            theme,
                             Rect {
                                 x: viewport.mask_rect().x,
                                 y,
@@ -3149,56 +5599,46 @@ impl Component for SectionView<'_> {
                 }
 
                 if self.is_expanded() {
-                    // Draw changed lines.
-                    let y = y + 1;
-                    for (line_idx, line) in lines.iter().enumerate() {
-                        let SectionChangedLine {
-                            is_checked,
-                            change_type,
-                            line,
-                        } = line;
-                        let is_focused = match selection {
-                            Some(SectionSelection::ChangedLine(selected_line_idx)) => {
-                                line_idx == *selected_line_idx
-                            }
-                            Some(SectionSelection::SectionHeader) | None => false,
-                        };
-                        let line_key = LineKey {
-                            commit_idx,
-                            file_idx,
-                            section_idx,
-                            line_idx,
-                        };
-                        let toggle_box = TristateBox {
-                            use_unicode: *use_unicode,
-                            id: ComponentId::ToggleBox(SelectionKey::Line(line_key)),
-                            icon_style: TristateIconStyle::Check,
-                            tristate: Tristate::from(*is_checked),
-                            is_focused,
-                            is_read_only: *is_read_only,
-                        };
-                        let line_view = SectionLineView {
-                            line_key,
-                            inner: SectionLineViewInner::Changed {
-                                toggle_box,
-                                change_type: *change_type,
-                                line: line.as_ref(),
-                            },
+                    // Draw changed lines. Only the lines near the visible area
+                    // (and the selected line, so that it can be scrolled into
+                    // view) are actually drawn.
+                    let mask_rect = viewport.mask_rect();
+                    let lines_y = y + 1;
+                    let mut y = lines_y;
+                    for (line_idx, line) in self.shown_lines(lines) {
+                        let is_focused = self.is_line_selected(line_idx);
+                        let line_height =
+                            self.changed_line_height(line_idx, line, x + 2, mask_rect.end_x());
+                        let line_rect = Rect {
+                            x: mask_rect.x,
+                            y,
+                            width: mask_rect.width,
+                            height: line_height,
                         };
-                        let y = y + line_idx.unwrap_isize();
-                        viewport.draw_component(x + 2, y, &line_view);
-                        if is_focused {
-                            highlight_rect(
-                                viewport,
-                                Rect {
-                                    x: viewport.mask_rect().x,
-                                    y,
-                                    width: viewport.mask_rect().width,
-                                    height: 1,
-                                },
-                            );
+                        if is_focused || viewport.is_in_layout(line_rect) {
+                            let line_view = self.changed_line_view(line_idx, line);
+                            let line_rect = viewport.draw_component(x + 2, y, &line_view);
+                            if is_focused {
+                                highlight_rect(
+                                    viewport,
+                                    theme,
+                                    Rect {
+                                        x: mask_rect.x,
+                                        y,
+                                        width: mask_rect.width,
+                                        height: line_rect.height,
+                                    },
+                                );
+                            }
                         }
+                        y += line_height.unwrap_isize();
                     }
+                    viewport.reserve_rect(Rect {
+                        x: mask_rect.x,
+                        y: lines_y,
+                        width: mask_rect.width,
+                        height: (y - lines_y).clamp_into_usize(),
+                    });
                 }
             }
 
@@ -3215,9 +5655,10 @@ impl Component for SectionView<'_> {
                 let selection_key = SelectionKey::Section(section_key);
                 let toggle_box = TristateBox {
                     use_unicode: *use_unicode,
+                    theme: *theme,
                     id: ComponentId::ToggleBox(selection_key),
                     icon_style: TristateIconStyle::Check,
-                    tristate: Tristate::from(*is_checked),
+                    tristate: Tristate::from(is_checked_for_commit(commit_idx, *is_checked)),
                     is_focused,
                     is_read_only: *is_read_only,
                 };
@@ -3231,10 +5672,11 @@ impl Component for SectionView<'_> {
                     FileMode::Absent => "File deleted".to_owned(),
                 };
 
-                viewport.draw_text(x, y, Span::styled(text, Style::default().fg(Color::Blue)));
+                viewport.draw_text(x, y, Span::styled(text, theme.section_info));
                 if is_focused {
                     highlight_rect(
                         viewport,
+                        theme,
                         Rect {
                             x: viewport.mask_rect().x,
                             y,
@@ -3261,9 +5703,10 @@ impl Component for SectionView<'_> {
                 };
                 let toggle_box = TristateBox {
                     use_unicode: *use_unicode,
+                    theme: *theme,
                     id: ComponentId::ToggleBox(SelectionKey::Section(section_key)),
                     icon_style: TristateIconStyle::Check,
-                    tristate: Tristate::from(*is_checked),
+                    tristate: Tristate::from(is_checked_for_commit(commit_idx, *is_checked)),
                     is_focused,
                     is_read_only: *is_read_only,
                 };
@@ -3287,11 +5730,144 @@ impl Component for SectionView<'_> {
                     result.push(description.join(" -> "));
                     format!("({})", result.join(" "))
                 };
-                viewport.draw_text(x, y, Span::styled(text, Style::default().fg(Color::Blue)));
+                viewport.draw_text(x, y, Span::styled(text, theme.section_info));
+
+                if is_focused {
+                    highlight_rect(
+                        viewport,
+                        theme,
+                        Rect {
+                            x: viewport.mask_rect().x,
+                            y,
+                            width: viewport.mask_rect().width,
+                            height: 1,
+                        },
+                    );
+                }
+            }
+
+            Section::Submodule {
+                is_checked,
+                old_commit_id,
+                new_commit_id,
+            } => {
+                let is_focused = match selection {
+                    Some(SectionSelection::SectionHeader) => true,
+                    Some(SectionSelection::ChangedLine(_)) | None => false,
+                };
+                let section_key = SectionKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx,
+                };
+                let toggle_box = TristateBox {
+                    use_unicode: *use_unicode,
+                    theme: *theme,
+                    id: ComponentId::ToggleBox(SelectionKey::Section(section_key)),
+                    icon_style: TristateIconStyle::Check,
+                    tristate: Tristate::from(is_checked_for_commit(commit_idx, *is_checked)),
+                    is_focused,
+                    is_read_only: *is_read_only,
+                };
+                let toggle_box_rect = viewport.draw_component(x, y, &toggle_box);
+                let x = x + toggle_box_rect.width.unwrap_isize() + 1;
+
+                let text = {
+                    let commit_ids: Vec<_> = [old_commit_id, new_commit_id]
+                        .iter()
+                        .copied()
+                        .flatten()
+                        .map(|s| s.as_ref())
+                        .collect();
+                    format!("(submodule commit: {})", commit_ids.join(" -> "))
+                };
+                viewport.draw_text(x, y, Span::styled(text, theme.section_info));
+
+                if is_focused {
+                    highlight_rect(
+                        viewport,
+                        theme,
+                        Rect {
+                            x: viewport.mask_rect().x,
+                            y,
+                            width: viewport.mask_rect().width,
+                            height: 1,
+                        },
+                    );
+                }
+            }
+
+            Section::Summary {
+                is_checked,
+                description,
+                old_contents: _,
+                new_contents: _,
+            } => {
+                let is_focused = match selection {
+                    Some(SectionSelection::SectionHeader) => true,
+                    Some(SectionSelection::ChangedLine(_)) | None => false,
+                };
+                let section_key = SectionKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx,
+                };
+                let toggle_box = TristateBox {
+                    use_unicode: *use_unicode,
+                    theme: *theme,
+                    id: ComponentId::ToggleBox(SelectionKey::Section(section_key)),
+                    icon_style: TristateIconStyle::Check,
+                    tristate: Tristate::from(is_checked_for_commit(commit_idx, *is_checked)),
+                    is_focused,
+                    is_read_only: *is_read_only,
+                };
+                let toggle_box_rect = viewport.draw_component(x, y, &toggle_box);
+                let x = x + toggle_box_rect.width.unwrap_isize() + 1;
+
+                let text = format!("({description})");
+                viewport.draw_text(x, y, Span::styled(text, theme.section_info));
+
+                if is_focused {
+                    highlight_rect(
+                        viewport,
+                        theme,
+                        Rect {
+                            x: viewport.mask_rect().x,
+                            y,
+                            width: viewport.mask_rect().width,
+                            height: 1,
+                        },
+                    );
+                }
+            }
+
+            Section::Rename { is_checked } => {
+                let is_focused = match selection {
+                    Some(SectionSelection::SectionHeader) => true,
+                    Some(SectionSelection::ChangedLine(_)) | None => false,
+                };
+                let section_key = SectionKey {
+                    commit_idx,
+                    file_idx,
+                    section_idx,
+                };
+                let toggle_box = TristateBox {
+                    use_unicode: *use_unicode,
+                    theme: *theme,
+                    id: ComponentId::ToggleBox(SelectionKey::Section(section_key)),
+                    icon_style: TristateIconStyle::Check,
+                    tristate: Tristate::from(is_checked_for_commit(commit_idx, *is_checked)),
+                    is_focused,
+                    is_read_only: *is_read_only,
+                };
+                let toggle_box_rect = viewport.draw_component(x, y, &toggle_box);
+                let x = x + toggle_box_rect.width.unwrap_isize() + 1;
 
+                viewport.draw_text(x, y, Span::styled("File renamed", theme.section_info));
                 if is_focused {
                     highlight_rect(
                         viewport,
+                        theme,
                         Rect {
                             x: viewport.mask_rect().x,
                             y,
@@ -3320,10 +5896,9 @@ enum SectionLineViewInner<'a> {
 
 fn replace_control_character(character: char) -> Option<&'static str> {
     match character {
-        // Characters end up writing over each-other and end up
-        // displaying incorrectly if ignored. Replacing tabs
-        // with a known length string fixes the issue for now.
-        '\t' => Some("→   "),
+        // Tabs are expanded according to their column; see
+        // `push_spans_from_line`.
+        '\t' => Some("→"),
         '\n' => Some("⏎"),
         '\r' => Some("␍"),
 
@@ -3368,31 +5943,177 @@ fn replace_control_character(character: char) -> Option<&'static str> {
     }
 }
 
+/// Like [`replace_control_character`], but using only ASCII characters. Control
+/// characters are rendered in caret notation, as in `cat -v`.
+fn replace_control_character_ascii(character: char) -> Option<&'static str> {
+    match character {
+        // Tabs are expanded according to their column; see
+        // `push_spans_from_line`.
+        '\t' => Some(">"),
+        '\n' => Some("$"),
+        '\x00' => Some("^@"),
+        '\x01' => Some("^A"),
+        '\x02' => Some("^B"),
+        '\x03' => Some("^C"),
+        '\x04' => Some("^D"),
+        '\x05' => Some("^E"),
+        '\x06' => Some("^F"),
+        '\x07' => Some("^G"),
+        '\x08' => Some("^H"),
+        '\x0B' => Some("^K"),
+        '\x0C' => Some("^L"),
+        '\x0D' => Some("^M"),
+        '\x0E' => Some("^N"),
+        '\x0F' => Some("^O"),
+        '\x10' => Some("^P"),
+        '\x11' => Some("^Q"),
+        '\x12' => Some("^R"),
+        '\x13' => Some("^S"),
+        '\x14' => Some("^T"),
+        '\x15' => Some("^U"),
+        '\x16' => Some("^V"),
+        '\x17' => Some("^W"),
+        '\x18' => Some("^X"),
+        '\x19' => Some("^Y"),
+        '\x1A' => Some("^Z"),
+        '\x1B' => Some("^["),
+        '\x1C' => Some("^\\"),
+        '\x1D' => Some("^]"),
+        '\x1E' => Some("^^"),
+        '\x1F' => Some("^_"),
+        '\x7F' => Some("^?"),
+
+        c if c.width().unwrap_or_default() == 0 => Some("?"),
+
+        _ => None,
+    }
+}
+
 /// Split the line into a sequence of [`Span`]s where control characters are
 /// replaced with styled [`Span`]'s and push them to the [`spans`] argument.
-fn push_spans_from_line<'line>(line: &'line str, spans: &mut Vec<Span<'line>>) {
-    const CONTROL_CHARACTER_STYLE: Style = Style::new().fg(Color::DarkGray);
+///
+/// Tabs are expanded to the next tab stop, where columns are counted from the
+/// start of `line`. If `highlight_trailing_whitespace` is set, any whitespace
+/// before the line ending is styled so that it stands out.
+fn push_spans_from_line<'line>(
+    line: &'line str,
+    use_unicode: bool,
+    theme: &Theme,
+    render_options: &RenderOptions,
+    highlight_trailing_whitespace: bool,
+    spans: &mut Vec<Span<'line>>,
+) {
+    let content = line.trim_end_matches(['\n', '\r']);
+    let (content, trailing_whitespace) = if highlight_trailing_whitespace {
+        content.split_at(content.trim_end().len())
+    } else {
+        (content, "")
+    };
+    let line_ending = if render_options.show_invisible_characters {
+        &line[content.len() + trailing_whitespace.len()..]
+    } else {
+        ""
+    };
+
+    let mut column = 0;
+    push_spans_from_segment(
+        content,
+        use_unicode,
+        theme,
+        render_options,
+        Style::new(),
+        &mut column,
+        spans,
+    );
+    push_spans_from_segment(
+        trailing_whitespace,
+        use_unicode,
+        theme,
+        render_options,
+        theme.trailing_whitespace,
+        &mut column,
+        spans,
+    );
+    push_spans_from_segment(
+        line_ending,
+        use_unicode,
+        theme,
+        render_options,
+        Style::new(),
+        &mut column,
+        spans,
+    );
+}
+
+fn push_spans_from_segment<'line>(
+    segment: &'line str,
+    use_unicode: bool,
+    theme: &Theme,
+    render_options: &RenderOptions,
+    style: Style,
+    column: &mut usize,
+    spans: &mut Vec<Span<'line>>,
+) {
+    let RenderOptions {
+        tab_width,
+        show_invisible_characters,
+        highlight_trailing_whitespace: _,
+        wrap_lines: _,
+    } = render_options;
 
     let mut last_index = 0;
-    // Find index of the start of each character to replace
-    for (idx, char) in line.match_indices(|char| replace_control_character(char).is_some()) {
-        // Push the string leading up to the character and the styled replacement string
-        if let Some(replacement_string) = char.chars().next().and_then(replace_control_character) {
-            spans.push(Span::raw(&line[last_index..idx]));
-            spans.push(Span::styled(replacement_string, CONTROL_CHARACTER_STYLE));
-            // Move the "cursor" to just after the character we're replacing
-            last_index = idx + char.len();
+    for (idx, character) in segment.char_indices() {
+        let replacement: Cow<'static, str> = match character {
+            '\t' => {
+                let tab_width = (*tab_width).max(1);
+                let num_columns = tab_width - *column % tab_width;
+                if *show_invisible_characters && use_unicode {
+                    Cow::Owned(format!("→{}", " ".repeat(num_columns - 1)))
+                } else if *show_invisible_characters {
+                    Cow::Owned(format!(">{}", "-".repeat(num_columns - 1)))
+                } else {
+                    Cow::Owned(" ".repeat(num_columns))
+                }
+            }
+            character => match if use_unicode {
+                replace_control_character(character)
+            } else {
+                replace_control_character_ascii(character)
+            } {
+                Some(replacement) => Cow::Borrowed(replacement),
+                None => {
+                    *column += character.width().unwrap_or_default();
+                    continue;
+                }
+            },
+        };
+
+        // Push the string leading up to the character and the styled
+        // replacement string.
+        if last_index < idx {
+            spans.push(Span::styled(&segment[last_index..idx], style));
         }
+        *column += replacement.width();
+        spans.push(Span::styled(
+            replacement,
+            theme.control_character.patch(style),
+        ));
+        // Move the "cursor" to just after the character we're replacing.
+        last_index = idx + character.len_utf8();
     }
-    // Append anything remaining after the last replacement
-    let remaining_line = &line[last_index..];
-    if !remaining_line.is_empty() {
-        spans.push(Span::raw(remaining_line));
+    // Append anything remaining after the last replacement.
+    let remaining = &segment[last_index..];
+    if !remaining.is_empty() {
+        spans.push(Span::styled(remaining, style));
     }
 }
 
 #[derive(Clone, Debug)]
 struct SectionLineView<'a> {
+    use_unicode: bool,
+    theme: Theme,
+    render_options: RenderOptions,
+    scroll_offset_x: usize,
     line_key: LineKey,
     inner: SectionLineViewInner<'a>,
 }
@@ -3412,39 +6133,294 @@ impl Component for SectionLineView<'_> {
             height: 1,
         });
 
-        match &self.inner {
+        let x = match &self.inner {
+            SectionLineViewInner::Unchanged { .. } => x,
+            SectionLineViewInner::Changed { toggle_box, .. } => {
+                let toggle_box_rect = viewport.draw_component(x, y, toggle_box);
+                toggle_box_rect.end_x() + 1
+            }
+        };
+        let (prefix, spans, style) = self.contents();
+        self.draw_line_contents(viewport, x, y, prefix, spans, style);
+    }
+}
+
+impl SectionLineView<'_> {
+    /// The prefix to draw before the contents of the line, the spans making up
+    /// the contents, and the style of the whole line.
+    fn contents(&self) -> (Span<'_>, Vec<Span<'_>>, Style) {
+        let Self {
+            use_unicode,
+            theme,
+            render_options,
+            scroll_offset_x: _,
+            line_key: _,
+            inner,
+        } = self;
+        match inner {
             SectionLineViewInner::Unchanged { line, line_num } => {
                 // Pad the number in 5 columns because that will align the
                 // beginning of the actual text with the `+`/`-` of the changed
                 // lines.
                 let line_number = Span::raw(format!("{line_num:5} "));
-                let mut spans = vec![line_number];
-                push_spans_from_line(line, &mut spans);
-
-                const UI_UNCHANGED_STYLE: Style = Style::new().add_modifier(Modifier::DIM);
-                viewport.draw_text(x, y, Line::from(spans).style(UI_UNCHANGED_STYLE));
+                let mut spans = Vec::new();
+                push_spans_from_line(line, *use_unicode, theme, render_options, false, &mut spans);
+                (line_number, spans, theme.unchanged_line)
             }
 
             SectionLineViewInner::Changed {
-                toggle_box,
+                toggle_box: _,
                 change_type,
                 line,
             } => {
-                let toggle_box_rect = viewport.draw_component(x, y, toggle_box);
-                let x = toggle_box_rect.end_x() + 1;
-
                 let (change_type_text, changed_line_style) = match change_type {
-                    ChangeType::Added => ("+ ", Style::default().fg(Color::Green)),
-                    ChangeType::Removed => ("- ", Style::default().fg(Color::Red)),
+                    ChangeType::Added => ("+ ", theme.added_line),
+                    ChangeType::Removed => ("- ", theme.removed_line),
+                };
+
+                let mut spans = Vec::new();
+                let highlight_trailing_whitespace = match change_type {
+                    ChangeType::Added => render_options.highlight_trailing_whitespace,
+                    ChangeType::Removed => false,
                 };
+                push_spans_from_line(
+                    line,
+                    *use_unicode,
+                    theme,
+                    render_options,
+                    highlight_trailing_whitespace,
+                    &mut spans,
+                );
+                (Span::raw(change_type_text), spans, changed_line_style)
+            }
+        }
+    }
+
+    /// The number of rows which this line takes up when drawn at `x`, where
+    /// `end_x` is the end of the mask that it's drawn in. This is more than one
+    /// only if the line is wrapped.
+    fn height(&self, x: isize, end_x: isize) -> usize {
+        if !self.render_options.wrap_lines {
+            return 1;
+        }
+        let x = match &self.inner {
+            SectionLineViewInner::Unchanged { .. } => x,
+            SectionLineViewInner::Changed { toggle_box, .. } => {
+                x + toggle_box.text().width().unwrap_isize() + 1
+            }
+        };
+        let (prefix, spans, _style) = self.contents();
+        let content_width = (end_x - x - prefix.width().unwrap_isize()).clamp_into_usize();
+        let total_width: usize = spans.iter().map(Span::width).sum();
+        if content_width == 0 || total_width <= content_width {
+            1
+        } else {
+            wrap_spans(spans, content_width).len()
+        }
+    }
+
+    /// Draw the `spans` making up the contents of the line after `prefix`. If
+    /// line wrapping is enabled and the contents don't fit in the viewport,
+    /// then they're continued onto subsequent rows, with a marker in place of
+    /// the prefix. Otherwise, the contents are scrolled horizontally by
+    /// `scroll_offset_x`, while the prefix stays in place.
+    fn draw_line_contents<'line>(
+        &self,
+        viewport: &mut Viewport<ComponentId>,
+        x: isize,
+        y: isize,
+        prefix: Span<'line>,
+        spans: Vec<Span<'line>>,
+        style: Style,
+    ) {
+        let prefix_width = prefix.width();
+        let content_width =
+            (viewport.mask_rect().end_x() - x - prefix_width.unwrap_isize()).clamp_into_usize();
+        let total_width: usize = spans.iter().map(Span::width).sum();
+        if !self.render_options.wrap_lines {
+            if self.scroll_offset_x == 0 {
+                let mut line_spans = vec![prefix];
+                line_spans.extend(spans);
+                viewport.draw_text(x, y, Line::from(line_spans).style(style));
+            } else {
+                self.draw_scrolled_line_contents(viewport, x, y, prefix, spans, style);
+            }
+            return;
+        }
+        if content_width == 0 || total_width <= content_width {
+            let mut line_spans = vec![prefix];
+            line_spans.extend(spans);
+            viewport.draw_text(x, y, Line::from(line_spans).style(style));
+            return;
+        }
+
+        let mut prefix = Some(prefix);
+        for (dy, row) in wrap_spans(spans, content_width).into_iter().enumerate() {
+            let y = y + dy.unwrap_isize();
+            let row_prefix = match prefix.take() {
+                Some(prefix) => prefix,
+                None => {
+                    viewport.draw_blank(Rect {
+                        x: viewport.mask_rect().x,
+                        y,
+                        width: viewport.mask_rect().width,
+                        height: 1,
+                    });
+                    Span::styled(
+                        format!(
+                            "{:>width$} ",
+                            if self.use_unicode { "↪" } else { "\\" },
+                            width = prefix_width.saturating_sub(1)
+                        ),
+                        self.theme.control_character,
+                    )
+                }
+            };
+            let mut line_spans = vec![row_prefix];
+            line_spans.extend(row);
+            viewport.draw_text(x, y, Line::from(line_spans).style(style));
+        }
+    }
+
+    fn draw_scrolled_line_contents<'line>(
+        &self,
+        viewport: &mut Viewport<ComponentId>,
+        x: isize,
+        y: isize,
+        prefix: Span<'line>,
+        spans: Vec<Span<'line>>,
+        style: Style,
+    ) {
+        let prefix_rect = viewport.draw_span(
+            x,
+            y,
+            &Span::styled(prefix.content, style.patch(prefix.style)),
+        );
+
+        // Only the contents are scrolled, so draw them into a mask which
+        // starts after the prefix.
+        let mask_rect = viewport.mask_rect();
+        let content_mask = Mask {
+            x: prefix_rect.end_x(),
+            y,
+            width: Some((mask_rect.end_x() - prefix_rect.end_x()).clamp_into_usize()),
+            height: Some(1),
+        };
+        let content_end_x = viewport.with_mask(content_mask, |viewport| {
+            let mut span_x = prefix_rect.end_x() - self.scroll_offset_x.unwrap_isize();
+            for span in spans {
+                let Span {
+                    style: span_style,
+                    content,
+                } = span;
+                let span_rect =
+                    viewport.draw_span(span_x, y, &Span::styled(content, style.patch(span_style)));
+                span_x = span_rect.end_x();
+            }
+            span_x
+        });
+        if content_end_x > mask_rect.end_x() {
+            let ellipsis = if self.use_unicode { "…" } else { ">" };
+            viewport.draw_span(mask_rect.end_x() - 1, y, &Span::styled(ellipsis, style));
+        }
+    }
+}
+
+/// Split the given text into rows which are each at most `width` columns wide,
+/// breaking at whitespace where possible. A line which is empty results in a
+/// single empty row.
+fn wrap_words(line: &str, width: usize) -> Vec<&str> {
+    let mut rows = Vec::new();
+    let mut line = line;
+    loop {
+        let mut row_width = 0;
+        let mut split_idx = None;
+        let mut last_space_idx = None;
+        for (idx, character) in line.char_indices() {
+            let character_width = character.width().unwrap_or_default();
+            if row_width + character_width > width && idx > 0 {
+                split_idx = Some(idx);
+                break;
+            }
+            if character.is_whitespace() {
+                last_space_idx = Some(idx);
+            }
+            row_width += character_width;
+        }
+
+        match split_idx {
+            None => {
+                rows.push(line);
+                break;
+            }
+            Some(split_idx) => {
+                let (row, rest) = match last_space_idx {
+                    Some(space_idx) if space_idx > 0 => {
+                        (&line[..space_idx], line[space_idx..].trim_start())
+                    }
+                    Some(_) | None => line.split_at(split_idx),
+                };
+                rows.push(row);
+                line = rest;
+                if line.is_empty() {
+                    break;
+                }
+            }
+        }
+    }
+    rows
+}
 
-                let mut spans = vec![Span::raw(change_type_text)];
-                push_spans_from_line(line, &mut spans);
+/// Split the given spans into rows which are each at most `width` columns wide.
+fn wrap_spans(spans: Vec<Span<'_>>, width: usize) -> Vec<Vec<Span<'_>>> {
+    let mut rows = vec![Vec::new()];
+    let mut row_width = 0;
+    for span in spans {
+        let Span { style, content } = span;
+        let mut content = content;
+        while !content.is_empty() {
+            let mut split_idx = content.len();
+            let mut content_width = 0;
+            for (idx, character) in content.char_indices() {
+                let character_width = character.width().unwrap_or_default();
+                if row_width + content_width + character_width > width {
+                    split_idx = idx;
+                    break;
+                }
+                content_width += character_width;
+            }
+            if split_idx == 0 && row_width == 0 {
+                // The character is wider than the whole row, so it has to be
+                // drawn (and truncated) anyways.
+                split_idx = content.chars().next().map_or(0, char::len_utf8);
+                content_width = width;
+            }
 
-                viewport.draw_text(x, y, Line::from(spans).style(changed_line_style));
+            let (head, tail) = match content {
+                Cow::Borrowed(content) => {
+                    let (head, tail) = content.split_at(split_idx);
+                    (Cow::Borrowed(head), Cow::Borrowed(tail))
+                }
+                Cow::Owned(content) => {
+                    let (head, tail) = content.split_at(split_idx);
+                    (Cow::Owned(head.to_owned()), Cow::Owned(tail.to_owned()))
+                }
+            };
+            if !head.is_empty() {
+                rows.last_mut().unwrap().push(Span {
+                    style,
+                    content: head,
+                });
+                row_width += content_width;
+            }
+            if !tail.is_empty() {
+                rows.push(Vec::new());
+                row_width = 0;
             }
+            content = tail;
         }
     }
+    rows
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -3542,7 +6518,7 @@ impl Component for HelpDialog {
 
     fn draw(&self, viewport: &mut Viewport<Self::Id>, _: isize, _: isize) {
         let title = "Help";
-        let body = Text::from(vec![
+        let mut body = Text::from(vec![
             Line::from("You can click the menus with a mouse, or use these keyboard shortcuts:"),
             Line::from(""),
             Line::from(vec![
@@ -3570,19 +6546,38 @@ impl Component for HelpDialog {
                 Span::styled("Scrolling", Style::new().bold().underlined()),
             ]),
             Line::from("    Edit commit message     e           Scroll up/down          ^y/^e"),
-            Line::from("                                                             or ^↑/^↓"),
+            Line::from("    Edit commit metadata    E                                or ^↑/^↓"),
+            Line::from("    Wrap long lines         w           Page up/down            ^b/^f"),
+            Line::from(
+                "    Show full message       m                                or ^PgUp/^PgDn",
+            ),
+            Line::from("    Side-by-side commits    v           Previous/Next page      ^u/^d"),
             Line::from(vec![
                 Span::raw("    "),
                 Span::styled("Selection", Style::new().bold().underlined()),
-                Span::raw("                           Page up/down            ^b/^f"),
+                Span::raw("                           Scroll left/right       ^←/^→"),
             ]),
-            Line::from(
-                "    Toggle current          Space                            or ^PgUp/^PgDn",
-            ),
-            Line::from("    Toggle and advance      Enter       Previous/Next page      ^u/^d"),
-            Line::from("    Invert all              a"),
+            Line::from("    Toggle current          Space"),
+            Line::from(vec![
+                Span::raw("    Toggle and advance      Enter       "),
+                Span::styled("Large files", Style::new().bold().underlined()),
+            ]),
+            Line::from("    Invert all              a           Show full diff          D"),
             Line::from("    Invert all uniformly    A"),
         ]);
+        if !viewport.use_unicode() {
+            for span in body.iter_mut().flat_map(|line| line.iter_mut()) {
+                if span.content.contains(['↓', '↑', '←', '→']) {
+                    span.content = Cow::Owned(
+                        span.content
+                            .replace('↓', "Down")
+                            .replace('↑', "Up")
+                            .replace('←', "Left")
+                            .replace('→', "Right"),
+                    );
+                }
+            }
+        }
 
         let quit_button = Button {
             id: ComponentId::HelpDialogQuitButton,
@@ -3602,6 +6597,249 @@ impl Component for HelpDialog {
     }
 }
 
+/// The state of the built-in commit message editor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct MessageEditor {
+    commit_idx: usize,
+    kind: MessageEditorKind,
+    editor: TextEditor,
+}
+
+/// What's being edited in the built-in message editor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MessageEditorKind {
+    Message,
+    Metadata,
+}
+
+/// A row of a commit message, after wrapping it to fit in the editor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct MessageEditorRow {
+    line_idx: usize,
+    start_col: usize,
+    contents: String,
+}
+
+/// Wrap the lines of the editor to fit in `width` columns. Returns the wrapped
+/// rows, and the row and column of the cursor within them.
+fn wrap_message_editor_lines(
+    editor: &TextEditor,
+    width: usize,
+) -> (Vec<MessageEditorRow>, (usize, usize)) {
+    let (cursor_row, cursor_col) = editor.cursor();
+    let mut rows = Vec::new();
+    let mut cursor = (0, 0);
+    for (line_idx, line) in editor.lines().iter().enumerate() {
+        let mut row = MessageEditorRow {
+            line_idx,
+            start_col: 0,
+            contents: String::new(),
+        };
+        let mut row_width = 0;
+        let mut num_chars = 0;
+        for (col, c) in line.chars().enumerate() {
+            let char_width = c.width().unwrap_or_default();
+            if row_width + char_width > width && !row.contents.is_empty() {
+                let next_row = MessageEditorRow {
+                    line_idx,
+                    start_col: col,
+                    contents: String::new(),
+                };
+                rows.push(mem::replace(&mut row, next_row));
+                row_width = 0;
+            }
+            if line_idx == cursor_row && col == cursor_col {
+                cursor = (rows.len(), row_width);
+            }
+            row.contents.push(c);
+            row_width += char_width;
+            num_chars += 1;
+        }
+
+        if line_idx == cursor_row && cursor_col >= num_chars {
+            // The cursor is after the end of the line, so it needs a column of
+            // its own.
+            if row_width >= width && !row.contents.is_empty() {
+                let next_row = MessageEditorRow {
+                    line_idx,
+                    start_col: num_chars,
+                    contents: String::new(),
+                };
+                rows.push(mem::replace(&mut row, next_row));
+                row_width = 0;
+            }
+            cursor = (rows.len(), row_width);
+        }
+        rows.push(row);
+    }
+    (rows, cursor)
+}
+
+#[derive(Clone, Debug)]
+struct MessageEditorDialog<'a> {
+    theme: Theme,
+    kind: MessageEditorKind,
+    editor: &'a TextEditor,
+}
+
+impl Component for MessageEditorDialog<'_> {
+    type Id = ComponentId;
+
+    fn id(&self) -> Self::Id {
+        ComponentId::MessageEditorDialog
+    }
+
+    fn draw(&self, viewport: &mut Viewport<Self::Id>, _x: isize, _y: isize) {
+        let Self {
+            theme,
+            kind,
+            editor,
+        } = self;
+        let rect = centered_rect(
+            viewport.rect(),
+            RectSize {
+                width: 40,
+                height: 8,
+            },
+            80,
+            80,
+        );
+        let block = Block::default()
+            .title(match kind {
+                MessageEditorKind::Message => "Edit message",
+                MessageEditorKind::Metadata => "Edit metadata",
+            })
+            .borders(Borders::all());
+        let block = if viewport.use_unicode() {
+            block
+        } else {
+            block.border_set(ASCII_BORDER_SET)
+        };
+        let tui_rect = viewport.translate_rect(rect);
+        viewport.draw_widget(tui_rect, Clear);
+        viewport.draw_widget(tui_rect, block);
+
+        let inner_rect = Rect {
+            x: rect.x + 1,
+            y: rect.y + 1,
+            width: rect.width.saturating_sub(2),
+            height: rect.height.saturating_sub(2),
+        };
+        // The last row is used for the subject length ruler, or a hint about
+        // the format of the metadata.
+        let text_height = inner_rect.height.saturating_sub(1);
+        let (rows, (cursor_row, cursor_col)) = wrap_message_editor_lines(editor, inner_rect.width);
+        let first_row = (cursor_row + 1).saturating_sub(text_height);
+        let mask = Mask {
+            x: inner_rect.x,
+            y: inner_rect.y,
+            width: Some(inner_rect.width),
+            height: Some(inner_rect.height),
+        };
+        viewport.with_mask(mask, |viewport| {
+            for (row_y, row) in rows.iter().skip(first_row).take(text_height).enumerate() {
+                let MessageEditorRow {
+                    line_idx,
+                    start_col,
+                    contents,
+                } = row;
+                let y = inner_rect.y + row_y.unwrap_isize();
+
+                // Highlight the part of the subject that's too long.
+                let num_normal_chars = if *kind == MessageEditorKind::Message && *line_idx == 0 {
+                    MAX_SUBJECT_LENGTH.saturating_sub(*start_col)
+                } else {
+                    usize::MAX
+                };
+                let split_idx = contents
+                    .char_indices()
+                    .nth(num_normal_chars)
+                    .map(|(idx, _)| idx)
+                    .unwrap_or(contents.len());
+                let (normal, overflow) = contents.split_at(split_idx);
+                let normal_rect = viewport.draw_span(inner_rect.x, y, &Span::raw(normal));
+                viewport.draw_span(
+                    normal_rect.end_x(),
+                    y,
+                    &Span::styled(overflow, theme.warning),
+                );
+            }
+
+            if let Some(cursor_y) = cursor_row.checked_sub(first_row) {
+                highlight_rect(
+                    viewport,
+                    theme,
+                    Rect {
+                        x: inner_rect.x + cursor_col.unwrap_isize(),
+                        y: inner_rect.y + cursor_y.unwrap_isize(),
+                        width: 1,
+                        height: 1,
+                    },
+                );
+            }
+
+            let ruler = match kind {
+                MessageEditorKind::Message => {
+                    let subject_len = editor.lines()[0].chars().count();
+                    let ruler_style = if subject_len > MAX_SUBJECT_LENGTH {
+                        theme.warning
+                    } else {
+                        Style::default()
+                    };
+                    Span::styled(
+                        format!("Subject: {subject_len}/{MAX_SUBJECT_LENGTH}"),
+                        ruler_style,
+                    )
+                }
+                MessageEditorKind::Metadata => Span::raw("Key: value"),
+            };
+            let ruler_y = inner_rect.y + text_height.unwrap_isize();
+            let ruler_rect = viewport.draw_span(inner_rect.x, ruler_y, &ruler);
+            let divider = divider(viewport);
+            viewport.draw_span(
+                ruler_rect.end_x(),
+                ruler_y,
+                &Span::styled(
+                    format!("{divider}ctrl-s: save{divider}esc: cancel"),
+                    theme.unchanged_line,
+                ),
+            );
+        });
+
+        let save_button = Button {
+            id: ComponentId::MessageEditorSaveButton,
+            label: Cow::Borrowed("Save"),
+            style: Style::default(),
+            is_focused: false,
+        };
+        let cancel_button = Button {
+            id: ComponentId::MessageEditorCancelButton,
+            label: Cow::Borrowed("Cancel"),
+            style: Style::default(),
+            is_focused: false,
+        };
+        let mut bottom_x = rect.x + rect.width.unwrap_isize() - 1;
+        let bottom_y = rect.y + rect.height.unwrap_isize() - 1;
+        for button in [save_button, cancel_button] {
+            bottom_x -= button.width().unwrap_isize();
+            let button_rect = viewport.draw_component(bottom_x, bottom_y, &button);
+            bottom_x = button_rect.x - 1;
+        }
+    }
+}
+
+/// Border symbols for dialogs when only ASCII characters should be used.
+const ASCII_BORDER_SET: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
 struct Button<'a, Id> {
     id: Id,
     label: Cow<'a, str>,
@@ -3678,11 +6916,15 @@ impl<Id: Clone + Debug + Eq + Hash> Component for Dialog<'_, Id> {
                 20,
             );
 
-            let paragraph = Paragraph::new((*body.as_ref()).clone()).block(
-                Block::default()
-                    .title(title.as_ref())
-                    .borders(Borders::all()),
-            );
+            let block = Block::default()
+                .title(title.as_ref())
+                .borders(Borders::all());
+            let block = if viewport.use_unicode() {
+                block
+            } else {
+                block.border_set(ASCII_BORDER_SET)
+            };
+            let paragraph = Paragraph::new((*body.as_ref()).clone()).block(block);
             let tui_rect = viewport.translate_rect(rect);
             viewport.draw_widget(tui_rect, Clear);
             viewport.draw_widget(tui_rect, paragraph);
@@ -3700,14 +6942,19 @@ impl<Id: Clone + Debug + Eq + Hash> Component for Dialog<'_, Id> {
     }
 }
 
-fn highlight_rect<Id: Clone + Debug + Eq + Hash>(viewport: &mut Viewport<Id>, rect: Rect) {
-    viewport.set_style(rect, Style::default().add_modifier(Modifier::REVERSED));
+fn highlight_rect<Id: Clone + Debug + Eq + Hash>(
+    viewport: &mut Viewport<Id>,
+    theme: &Theme,
+    rect: Rect,
+) {
+    viewport.set_style(rect, theme.highlight);
 }
 
 #[cfg(test)]
 mod tests {
     use std::borrow::Cow;
 
+    use crate::helpers::tests::file_with_added_lines;
     use crate::helpers::TestingInput;
 
     use super::*;
@@ -3749,15 +6996,486 @@ mod tests {
         assert_eq!(recorder.run().unwrap(), state);
     }
 
-    fn test_push_lines_from_span_impl(line: &str) {
+    #[test]
+    fn test_edit_commit_metadata_default() -> Result<(), RecordError> {
+        struct MessageInput;
+        impl RecordInput for MessageInput {
+            fn terminal_kind(&self) -> TerminalKind {
+                TerminalKind::Testing {
+                    width: 80,
+                    height: 24,
+                }
+            }
+
+            fn next_events(&mut self) -> Result<Vec<Event>, RecordError> {
+                Ok(vec![Event::None])
+            }
+
+            fn edit_commit_message(&mut self, _message: &str) -> Result<String, RecordError> {
+                Ok("edited message".to_owned())
+            }
+        }
+
+        // The metadata isn't passed to `edit_commit_message` by default, but
+        // is edited with the built-in editor instead.
+        let state = RecordState {
+            is_read_only: false,
+            commits: vec![Commit {
+                author: Some("Jane Doe <jane@example.com>".to_owned()),
+                ..Default::default()
+            }],
+            files: Vec::new(),
+        };
+        let mut input = MessageInput;
+        let mut recorder = Recorder::new(state, &mut input);
+        recorder.process_event(Event::EditCommitMetadata)?;
+        assert_matches!(
+            &recorder.message_editor,
+            Some(MessageEditor {
+                commit_idx: 0,
+                kind: MessageEditorKind::Metadata,
+                editor,
+            }) if editor.text() == "Author: Jane Doe <jane@example.com>\n"
+        );
+        assert_eq!(recorder.state().commits[0].message, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_selection_summary() {
+        let files = [
+            file_with_added_lines("foo", &[true, true]),
+            file_with_added_lines("bar/baz", &[true, false]),
+            file_with_added_lines("qux", &[false]),
+        ];
+        insta::assert_snapshot!(selection_summary(&files, true), @"
+        [●] foo
+        [◐] bar/baz
+        [ ] qux
+        ");
+        insta::assert_snapshot!(selection_summary(&files, false), @"
+        [*] foo
+        [~] bar/baz
+        [ ] qux
+        ");
+    }
+
+    #[test]
+    fn test_draw_only_near_viewport() -> Result<(), RecordError> {
+        let state = RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files: vec![File {
+                old_path: None,
+                path: Cow::Borrowed(Path::new("foo")),
+                file_mode: FileMode::FILE_DEFAULT,
+                sections: vec![Section::Changed {
+                    lines: vec![
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: ChangeType::Added,
+                            line: Cow::Borrowed("foo\n"),
+                        };
+                        10_000
+                    ],
+                }],
+            }],
+        };
+        let mut input = TestingInput::new(80, 10, []);
+        let mut recorder = Recorder::new(state, &mut input);
+        let area = ratatui::layout::Rect::new(0, 0, 80, 10);
+        let mut buf = Buffer::empty(area);
+        let drawn_line_ys = |recorder: &Recorder| -> Vec<isize> {
+            let mut ys: Vec<_> = recorder
+                .drawn_rects
+                .iter()
+                .filter_map(|(id, DrawnRect { rect, timestamp: _ })| match id {
+                    ComponentId::SelectableItem(SelectionKey::Line(_)) => Some(rect.y),
+                    _ => None,
+                })
+                .collect();
+            ys.sort();
+            ys
+        };
+
+        recorder.process_event(Event::ExpandAll)?;
+        recorder.render(area, &mut buf);
+        // Only the lines up to one screenful below the viewport are drawn,
+        // but the whole file is laid out.
+        assert_eq!(drawn_line_ys(&recorder), (3..20).collect::<Vec<_>>());
+        assert_matches!(
+            recorder.drawn_rects.get(&ComponentId::App),
+            Some(DrawnRect { rect, timestamp: _ }) if rect.height == 10_003
+        );
+
+        // The selected line is always drawn, so that it can be scrolled into
+        // view.
+        recorder.selection_key = SelectionKey::Line(LineKey {
+            commit_idx: 0,
+            file_idx: 0,
+            section_idx: 0,
+            line_idx: 9_999,
+        });
+        recorder.render(area, &mut buf);
+        assert_eq!(drawn_line_ys(&recorder).last(), Some(&10_002));
+        recorder.process_event(Event::EnsureSelectionInViewport)?;
+        recorder.render(area, &mut buf);
+        assert_eq!(recorder.scroll_offset_y, 9_993);
+        assert_eq!(
+            drawn_line_ys(&recorder),
+            (9_983..10_003).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
+    /// Every selection key in the visible commits, in the order that they're
+    /// drawn.
+    fn all_selection_keys(recorder: &Recorder) -> Vec<SelectionKey> {
+        let mut result = Vec::new();
+        for (commit_idx, _) in recorder.state.commits.iter().enumerate() {
+            match recorder.commit_view_mode {
+                CommitViewMode::Inline if commit_idx != recorder.focused_commit_idx => continue,
+                CommitViewMode::Inline | CommitViewMode::Adjacent => {}
+            }
+            for (file_idx, file) in recorder.state.files.iter().enumerate() {
+                result.push(SelectionKey::File(FileKey {
+                    commit_idx,
+                    file_idx,
+                }));
+                for (section_idx, section) in file.sections.iter().enumerate() {
+                    match section {
+                        Section::Unchanged { .. } => {}
+                        Section::Changed { lines } => {
+                            result.push(SelectionKey::Section(SectionKey {
+                                commit_idx,
+                                file_idx,
+                                section_idx,
+                            }));
+                            for (line_idx, _line) in lines.iter().enumerate() {
+                                result.push(SelectionKey::Line(LineKey {
+                                    commit_idx,
+                                    file_idx,
+                                    section_idx,
+                                    line_idx,
+                                }));
+                            }
+                        }
+                        Section::FileMode {
+                            is_checked: _,
+                            mode: _,
+                        }
+                        | Section::Binary { .. }
+                        | Section::Submodule { .. }
+                        | Section::Summary { .. }
+                        | Section::Rename { .. } => {
+                            result.push(SelectionKey::Section(SectionKey {
+                                commit_idx,
+                                file_idx,
+                                section_idx,
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_visible_keys_match_all_selection_keys() {
+        let mut file = file_with_added_lines("foo", &[true, false]);
+        file.sections.insert(
+            0,
+            Section::Unchanged {
+                lines: vec![Cow::Borrowed("unchanged\n")],
+            },
+        );
+        file.sections.push(Section::FileMode {
+            is_checked: false,
+            mode: FileMode::Unix(0o100755),
+        });
+        file.sections.push(Section::Changed { lines: vec![] });
+        let state = RecordState {
+            is_read_only: false,
+            commits: vec![Commit::default(), Commit::default()],
+            files: vec![
+                file_with_added_lines("bar", &[true]),
+                File {
+                    old_path: None,
+                    path: Cow::Borrowed(Path::new("baz")),
+                    file_mode: FileMode::FILE_DEFAULT,
+                    sections: Default::default(),
+                },
+                file,
+            ],
+        };
+        let mut input = TestingInput::new(80, 24, []);
+        let mut recorder = Recorder::new(state, &mut input);
+
+        for commit_view_mode in [CommitViewMode::Inline, CommitViewMode::Adjacent] {
+            recorder.commit_view_mode = commit_view_mode;
+            let expandable_keys: Vec<_> = recorder.expandable_keys().collect();
+            assert_eq!(
+                expandable_keys,
+                all_selection_keys(&recorder)
+                    .into_iter()
+                    .filter(|key| match key {
+                        SelectionKey::None | SelectionKey::Line(_) => false,
+                        SelectionKey::File(_) | SelectionKey::Section(_) => true,
+                    })
+                    .collect::<Vec<_>>()
+            );
+            // Try every combination of expanded items.
+            for mask in 0..1_usize << expandable_keys.len() {
+                recorder.expanded_items = expandable_keys
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| (mask >> i) & 1 == 1)
+                    .map(|(_, key)| *key)
+                    .collect();
+                let expected: Vec<_> = all_selection_keys(&recorder)
+                    .into_iter()
+                    .filter(|key| recorder.is_selection_key_visible(*key))
+                    .collect();
+
+                let first = expected[0];
+                let forward: Vec<_> = std::iter::once(first)
+                    .chain(recorder.visible_keys_after(first))
+                    .collect();
+                assert_eq!(forward, expected);
+
+                let last = *expected.last().unwrap();
+                let mut backward: Vec<_> = std::iter::once(last)
+                    .chain(recorder.visible_keys_before(last))
+                    .collect();
+                backward.reverse();
+                assert_eq!(backward, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_locale_supports_unicode() {
+        let locale = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+        assert!(locale_supports_unicode(locale(&[])));
+        assert!(locale_supports_unicode(locale(&[("LANG", "en_US.UTF-8")])));
+        assert!(locale_supports_unicode(locale(&[("LANG", "en_US.utf8")])));
+        assert!(!locale_supports_unicode(locale(&[("LANG", "C")])));
+        assert!(!locale_supports_unicode(locale(&[
+            ("LC_ALL", "POSIX"),
+            ("LANG", "en_US.UTF-8"),
+        ])));
+        assert!(locale_supports_unicode(locale(&[
+            ("LC_ALL", ""),
+            ("LC_CTYPE", "C.UTF-8"),
+            ("LANG", "C"),
+        ])));
+    }
+
+    #[test]
+    fn test_commit_message_warnings() {
+        use CommitMessageWarning::*;
+
+        assert_eq!(commit_message_warnings(""), vec![Empty]);
+        assert_eq!(commit_message_warnings(" \n\n"), vec![Empty]);
+        assert_eq!(commit_message_warnings("Subject\n"), vec![]);
+        assert_eq!(commit_message_warnings("Subject\n\nBody\n"), vec![]);
+        assert_eq!(
+            commit_message_warnings("Subject\nBody\n"),
+            vec![MissingBlankLine]
+        );
+        assert_eq!(
+            commit_message_warnings(&"x".repeat(MAX_SUBJECT_LENGTH)),
+            vec![]
+        );
+        assert_eq!(
+            commit_message_warnings(&format!("{}\nBody", "x".repeat(MAX_SUBJECT_LENGTH + 1))),
+            vec![LongSubject, MissingBlankLine]
+        );
+    }
+
+    #[test]
+    fn test_wrap_words() {
+        assert_eq!(wrap_words("", 10), vec![""]);
+        assert_eq!(wrap_words("foo bar baz", 11), vec!["foo bar baz"]);
+        assert_eq!(wrap_words("foo bar baz", 10), vec!["foo bar", "baz"]);
+        assert_eq!(wrap_words("foo   bar", 4), vec!["foo", "bar"]);
+        assert_eq!(
+            wrap_words("abcdefghij klm", 4),
+            vec!["abcd", "efgh", "ij", "klm"]
+        );
+    }
+
+    #[test]
+    fn test_push_spans_from_line_trailing_whitespace() {
+        let render_options = RenderOptions {
+            tab_width: 4,
+            show_invisible_characters: true,
+            highlight_trailing_whitespace: true,
+            wrap_lines: false,
+        };
+        let mut spans = Vec::new();
+        push_spans_from_line(
+            "ab\t \n",
+            true,
+            &Theme::dark(),
+            &render_options,
+            true,
+            &mut spans,
+        );
+        insta::assert_debug_snapshot!(spans, @r#"
+        [
+            Span::from("ab"),
+            Span::from("→ ").dark_gray().on_red(),
+            Span::from(" ").on_red(),
+            Span::from("⏎").dark_gray(),
+        ]
+        "#);
+    }
+
+    fn test_push_lines_from_span_impl(
+        line: &str,
+        use_unicode: bool,
+        render_options: RenderOptions,
+    ) {
         let mut spans = Vec::new();
-        push_spans_from_line(line, &mut spans); // assert no panic
+        push_spans_from_line(
+            line,
+            use_unicode,
+            &Theme::dark(),
+            &render_options,
+            true,
+            &mut spans,
+        );
+        // assert no panic
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_toggle_item_selection_counts(
+            toggles in proptest::collection::vec(
+                (0..2usize, 0..2usize, 0..4usize, 0..4usize, 0..3u8),
+                0..20,
+            ),
+        ) {
+            let changed_section = |num_lines| Section::Changed {
+                lines: vec![
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: ChangeType::Added,
+                        line: Cow::Borrowed("foo\n"),
+                    };
+                    num_lines
+                ],
+            };
+            let state = RecordState {
+                is_read_only: false,
+                commits: Default::default(),
+                files: vec![
+                    File {
+                        old_path: None,
+                        path: Cow::Borrowed(Path::new("deleted")),
+                        file_mode: FileMode::FILE_DEFAULT,
+                        sections: vec![
+                            Section::FileMode {
+                                is_checked: false,
+                                mode: FileMode::Absent,
+                            },
+                            changed_section(3),
+                            Section::Unchanged {
+                                lines: vec![Cow::Borrowed("bar\n")],
+                            },
+                            changed_section(2),
+                        ],
+                    },
+                    File {
+                        old_path: None,
+                        path: Cow::Borrowed(Path::new("created")),
+                        file_mode: FileMode::Absent,
+                        sections: vec![
+                            Section::FileMode {
+                                is_checked: false,
+                                mode: FileMode::FILE_DEFAULT,
+                            },
+                            changed_section(2),
+                        ],
+                    },
+                ],
+            };
+            let mut input = TestingInput::new(80, 24, []);
+            let mut recorder = Recorder::new(state, &mut input);
+            for (commit_idx, file_idx, section_idx, line_idx, kind) in toggles {
+                let num_sections = recorder.state.files[file_idx].sections.len();
+                let section_idx = section_idx % num_sections;
+                let selection_key = match kind {
+                    0 => SelectionKey::File(FileKey {
+                        commit_idx,
+                        file_idx,
+                    }),
+                    1 => SelectionKey::Section(SectionKey {
+                        commit_idx,
+                        file_idx,
+                        section_idx,
+                    }),
+                    _ => match &recorder.state.files[file_idx].sections[section_idx] {
+                        Section::Changed { lines } => SelectionKey::Line(LineKey {
+                            commit_idx,
+                            file_idx,
+                            section_idx,
+                            line_idx: line_idx % lines.len(),
+                        }),
+                        _ => continue,
+                    },
+                };
+                recorder.toggle_item(selection_key).unwrap();
+
+                for (file_idx, file) in recorder.state.files.iter().enumerate() {
+                    let file_key = FileKey {
+                        commit_idx: 0,
+                        file_idx,
+                    };
+                    proptest::prop_assert_eq!(
+                        recorder.file_tristate(file_key).unwrap(),
+                        file.tristate()
+                    );
+                    for (section_idx, section) in file.sections.iter().enumerate() {
+                        let section_key = SectionKey {
+                            commit_idx: 0,
+                            file_idx,
+                            section_idx,
+                        };
+                        proptest::prop_assert_eq!(
+                            recorder.section_tristate(section_key).unwrap(),
+                            section.tristate()
+                        );
+                    }
+                }
+            }
+        }
     }
 
     proptest::proptest! {
         #[test]
-        fn test_push_lines_from_span(line in ".*") {
-            test_push_lines_from_span_impl(line.as_str());
+        fn test_push_lines_from_span(
+            line in ".*",
+            tab_width in 0..16usize,
+            show_invisible_characters: bool,
+            use_unicode: bool,
+        ) {
+            let render_options = RenderOptions {
+                tab_width,
+                show_invisible_characters,
+                highlight_trailing_whitespace: true,
+                wrap_lines: false,
+            };
+            test_push_lines_from_span_impl(line.as_str(), use_unicode, render_options);
         }
     }
 }
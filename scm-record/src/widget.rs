@@ -0,0 +1,122 @@
+//! Embed the change selector as one part of a larger `ratatui` application.
+//!
+//! Render a [`RecordWidget`] each frame with a [`RecordWidgetState`] holding
+//! the [`Recorder`], and route input events to
+//! [`RecordWidgetState::handle_event`]:
+//!
+//! ```no_run
+//! # use ratatui::Frame;
+//! # use scm_record::widget::{RecordWidget, RecordWidgetState};
+//! # use scm_record::{Event, RecordError, Recorder, RecorderStatus};
+//! # fn next_event() -> Event { Event::None }
+//! # fn draw(frame: &mut Frame, state: &mut RecordWidgetState) {
+//! frame.render_stateful_widget(RecordWidget::new(), frame.area(), state);
+//! # }
+//! # fn example(recorder: Recorder) -> Result<(), RecordError> {
+//! let mut state = RecordWidgetState::new(recorder.with_menu_bar(false))
+//!     .on_selection_change(|record_state| {
+//!         // Update other panes to show the selected changes.
+//!     });
+//! loop {
+//!     match state.handle_event(next_event())? {
+//!         RecorderStatus::Running => {}
+//!         RecorderStatus::RedrawNeeded => {
+//!             // Clear the terminal, such as with `Terminal::clear`.
+//!         }
+//!         RecorderStatus::Accepted | RecorderStatus::Cancelled => break,
+//!     }
+//!     // Draw the application, including the widget.
+//! }
+//! let record_state = state.finish()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::marker::PhantomData;
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::widgets::StatefulWidget;
+
+use crate::{Event, RecordError, RecordState, Recorder, RecorderStatus};
+
+/// A `ratatui` widget which draws the change selector. Its state is stored in
+/// a [`RecordWidgetState`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RecordWidget<'state, 'input> {
+    _state: PhantomData<RecordWidgetState<'state, 'input>>,
+}
+
+impl RecordWidget<'_, '_> {
+    /// Constructor.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<'state, 'input> StatefulWidget for RecordWidget<'state, 'input> {
+    type State = RecordWidgetState<'state, 'input>;
+
+    fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
+        state.recorder.render(area, buf);
+    }
+}
+
+type SelectionChangeCallback<'state, 'input> = Box<dyn FnMut(&RecordState<'state>) + 'input>;
+
+/// The state of a [`RecordWidget`].
+///
+/// The appearance of the widget, including whether the menu bar and dialogs
+/// are shown, is configured on the [`Recorder`] before it's passed to
+/// [`RecordWidgetState::new`].
+pub struct RecordWidgetState<'state, 'input> {
+    recorder: Recorder<'state, 'input>,
+    on_selection_change: Option<SelectionChangeCallback<'state, 'input>>,
+}
+
+impl<'state, 'input> RecordWidgetState<'state, 'input> {
+    /// Constructor.
+    pub fn new(recorder: Recorder<'state, 'input>) -> Self {
+        Self {
+            recorder,
+            on_selection_change: None,
+        }
+    }
+
+    /// Call `callback` with the new state whenever the user checks or unchecks
+    /// any changes.
+    pub fn on_selection_change(
+        mut self,
+        callback: impl FnMut(&RecordState<'state>) + 'input,
+    ) -> Self {
+        self.on_selection_change = Some(Box::new(callback));
+        self
+    }
+
+    /// Update the widget in response to the given event and return whether the
+    /// user has finished selecting changes. See [`Recorder::process_event`].
+    pub fn handle_event(&mut self, event: Event) -> Result<RecorderStatus, RecordError> {
+        let status = self.recorder.process_event(event)?;
+        if self.recorder.take_is_selection_changed() {
+            if let Some(on_selection_change) = &mut self.on_selection_change {
+                on_selection_change(self.recorder.state());
+            }
+        }
+        Ok(status)
+    }
+
+    /// Whether the user has finished selecting changes.
+    pub fn status(&self) -> RecorderStatus {
+        self.recorder.status()
+    }
+
+    /// The current state of the user's selection.
+    pub fn state(&self) -> &RecordState<'state> {
+        self.recorder.state()
+    }
+
+    /// Return the selected changes. See [`Recorder::finish`].
+    pub fn finish(self) -> Result<RecordState<'state>, RecordError> {
+        self.recorder.finish()
+    }
+}
@@ -2,15 +2,34 @@ use std::path::Path;
 use std::{borrow::Cow, iter};
 
 use assert_matches::assert_matches;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use insta::{assert_debug_snapshot, assert_snapshot};
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::widgets::StatefulWidget;
 use scm_record::helpers::{make_binary_description, TestingInput};
+use scm_record::theme::Theme;
+use scm_record::widget::{RecordWidget, RecordWidgetState};
 use scm_record::{
-    ChangeType, Commit, Event, File, FileMode, RecordError, RecordState, Recorder, Section,
-    SectionChangedLine, TestingScreenshot,
+    ChangeType, Commit, Event, File, FileMode, RecordError, RecordInput, RecordState, Recorder,
+    RecorderStatus, RenderOptions, Section, SectionChangedLine, SelectedContents, TerminalKind,
+    TestingScreenshot, Trailer, Tristate,
 };
 
 type TestResult = Result<(), scm_record::RecordError>;
 
+/// Render the contents of the buffer with one quoted line per row.
+fn buffer_lines(buf: &Buffer) -> String {
+    let width = usize::from(buf.area.width);
+    buf.content
+        .chunks(width)
+        .map(|row| {
+            let line: String = row.iter().map(|cell| cell.symbol()).collect();
+            format!("{line:?}\n")
+        })
+        .collect()
+}
+
 fn example_contents() -> RecordState<'static> {
     RecordState {
         is_read_only: false,
@@ -669,15 +688,21 @@ fn test_file_mode_change() -> TestResult {
         ],
     );
     let recorder = Recorder::new(state, &mut input);
-    insta::assert_debug_snapshot!(recorder.run()?, @r###"
+    insta::assert_debug_snapshot!(recorder.run()?, @r#"
     RecordState {
         is_read_only: false,
         commits: [
             Commit {
                 message: None,
+                author: None,
+                trailers: None,
+                branch: None,
             },
             Commit {
                 message: None,
+                author: None,
+                trailers: None,
+                branch: None,
             },
         ],
         files: [
@@ -714,7 +739,7 @@ fn test_file_mode_change() -> TestResult {
             },
         ],
     }
-    "###);
+    "#);
     insta::assert_snapshot!(before_toggle, @r###"
     "[File] [Edit] [Select] [View]                                                   "
     "( ) foo                                                                      (-)"
@@ -1045,15 +1070,21 @@ fn test_record_binary_file() -> TestResult {
     "                                                                                "
     "###);
 
-    assert_debug_snapshot!(state, @r###"
+    assert_debug_snapshot!(state, @r#"
     RecordState {
         is_read_only: false,
         commits: [
             Commit {
                 message: None,
+                author: None,
+                trailers: None,
+                branch: None,
             },
             Commit {
                 message: None,
+                author: None,
+                trailers: None,
+                branch: None,
             },
         ],
         files: [
@@ -1077,11 +1108,12 @@ fn test_record_binary_file() -> TestResult {
             },
         ],
     }
-    "###);
+    "#);
 
     let (selected, unselected) = state.files[0].get_selected_contents();
-    assert_debug_snapshot!(selected, @r###"
+    assert_debug_snapshot!(selected, @r#"
     SelectedChanges {
+        path: "foo",
         file_mode: Unix(
             33188,
         ),
@@ -1094,15 +1126,16 @@ fn test_record_binary_file() -> TestResult {
             ),
         },
     }
-    "###);
-    assert_debug_snapshot!(unselected, @r"
+    "#);
+    assert_debug_snapshot!(unselected, @r#"
     SelectedChanges {
+        path: "foo",
         file_mode: Unix(
             33188,
         ),
         contents: Unchanged,
     }
-    ");
+    "#);
 
     Ok(())
 }
@@ -1142,15 +1175,21 @@ fn test_record_binary_file_noop() -> TestResult {
     "                                                                                "
     "###);
 
-    assert_debug_snapshot!(state, @r###"
+    assert_debug_snapshot!(state, @r#"
     RecordState {
         is_read_only: false,
         commits: [
             Commit {
                 message: None,
+                author: None,
+                trailers: None,
+                branch: None,
             },
             Commit {
                 message: None,
+                author: None,
+                trailers: None,
+                branch: None,
             },
         ],
         files: [
@@ -1174,19 +1213,21 @@ fn test_record_binary_file_noop() -> TestResult {
             },
         ],
     }
-    "###);
+    "#);
 
     let (selected, unselected) = state.files[0].get_selected_contents();
-    assert_debug_snapshot!(selected, @r"
+    assert_debug_snapshot!(selected, @r#"
     SelectedChanges {
+        path: "foo",
         file_mode: Unix(
             33188,
         ),
         contents: Unchanged,
     }
-    ");
-    assert_debug_snapshot!(unselected, @r###"
+    "#);
+    assert_debug_snapshot!(unselected, @r#"
     SelectedChanges {
+        path: "foo",
         file_mode: Unix(
             33188,
         ),
@@ -1199,7 +1240,7 @@ fn test_record_binary_file_noop() -> TestResult {
             ),
         },
     }
-    "###);
+    "#);
 
     Ok(())
 }
@@ -1230,19 +1271,195 @@ fn test_state_binary_selected_contents() -> TestResult {
         format!("{selection:?}")
     };
 
-    assert_snapshot!(test(false, false), @r###"(SelectedChanges { file_mode: Unix(33188), contents: Unchanged }, SelectedChanges { file_mode: Unix(33188), contents: Binary { old_description: Some("abc123 (123 bytes)"), new_description: Some("def456 (456 bytes)") } })"###);
+    assert_snapshot!(test(false, false), @r#"(SelectedChanges { path: "foo", file_mode: Unix(33188), contents: Unchanged }, SelectedChanges { path: "foo", file_mode: Unix(33188), contents: Binary { old_description: Some("abc123 (123 bytes)"), new_description: Some("def456 (456 bytes)") } })"#);
 
     // FIXME: should the selected contents be `Present { contents: "" }`? (Or
     // possibly `Absent`?)
-    assert_snapshot!(test(true, false), @r###"(SelectedChanges { file_mode: Unix(33188), contents: Unchanged }, SelectedChanges { file_mode: Unix(33188), contents: Binary { old_description: Some("abc123 (123 bytes)"), new_description: Some("def456 (456 bytes)") } })"###);
+    assert_snapshot!(test(true, false), @r#"(SelectedChanges { path: "foo", file_mode: Unix(33188), contents: Unchanged }, SelectedChanges { path: "foo", file_mode: Unix(33188), contents: Binary { old_description: Some("abc123 (123 bytes)"), new_description: Some("def456 (456 bytes)") } })"#);
 
     // NB: The result for this situation, where we've selected both a text and
     // binary segment for inclusion, is arbitrary. The caller should avoid
     // generating both kinds of sections in the same file (or we should improve
     // the UI to never allow selecting both).
-    assert_snapshot!(test(false, true), @r###"(SelectedChanges { file_mode: Unix(33188), contents: Binary { old_description: Some("abc123 (123 bytes)"), new_description: Some("def456 (456 bytes)") } }, SelectedChanges { file_mode: Unix(33188), contents: Unchanged })"###);
+    assert_snapshot!(test(false, true), @r#"(SelectedChanges { path: "foo", file_mode: Unix(33188), contents: Binary { old_description: Some("abc123 (123 bytes)"), new_description: Some("def456 (456 bytes)") } }, SelectedChanges { path: "foo", file_mode: Unix(33188), contents: Unchanged })"#);
+
+    assert_snapshot!(test(true, true), @r#"(SelectedChanges { path: "foo", file_mode: Unix(33188), contents: Binary { old_description: Some("abc123 (123 bytes)"), new_description: Some("def456 (456 bytes)") } }, SelectedChanges { path: "foo", file_mode: Unix(33188), contents: Unchanged })"#);
+
+    Ok(())
+}
+
+#[test]
+fn test_record_submodule() -> TestResult {
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files: vec![File {
+            old_path: None,
+            path: Cow::Borrowed(Path::new("sub")),
+            file_mode: FileMode::GITLINK,
+            sections: vec![Section::Submodule {
+                is_checked: false,
+                old_commit_id: Some(Cow::Borrowed("abc123")),
+                new_commit_id: Some(Cow::Borrowed("def456")),
+            }],
+        }],
+    };
+
+    let initial = TestingScreenshot::default();
+    let toggled = TestingScreenshot::default();
+    let mut input = TestingInput::new(
+        80,
+        6,
+        [
+            Event::ExpandAll,
+            initial.event(),
+            Event::ToggleItem,
+            toggled.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let recorder = Recorder::new(state, &mut input);
+    let state = recorder.run()?;
+
+    insta::assert_snapshot!(initial, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "( ) sub                                                                      (-)"
+    "  [ ] (submodule commit: abc123 -> def456)                                      "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "#);
+    insta::assert_snapshot!(toggled, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "(●) sub                                                                      (-)"
+    "  [●] (submodule commit: abc123 -> def456)                                      "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "#);
+
+    let (selected, unselected) = state.files[0].get_selected_contents();
+    assert_debug_snapshot!(selected, @r#"
+    SelectedChanges {
+        path: "sub",
+        file_mode: Unix(
+            57344,
+        ),
+        contents: Submodule {
+            commit_id: "def456",
+        },
+    }
+    "#);
+    assert_debug_snapshot!(unselected, @r#"
+    SelectedChanges {
+        path: "sub",
+        file_mode: Unix(
+            57344,
+        ),
+        contents: Submodule {
+            commit_id: "abc123",
+        },
+    }
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_record_rename() -> TestResult {
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files: vec![File {
+            old_path: Some(Cow::Borrowed(Path::new("foo"))),
+            path: Cow::Borrowed(Path::new("bar")),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections: vec![
+                Section::Rename { is_checked: false },
+                Section::Unchanged {
+                    lines: vec![Cow::Borrowed("unchanged\n")],
+                },
+                Section::Changed {
+                    lines: vec![
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: ChangeType::Removed,
+                            line: Cow::Borrowed("before\n"),
+                        },
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: ChangeType::Added,
+                            line: Cow::Borrowed("after\n"),
+                        },
+                    ],
+                },
+            ],
+        }],
+    };
+
+    let initial = TestingScreenshot::default();
+    let toggled = TestingScreenshot::default();
+    let mut input = TestingInput::new(
+        80,
+        8,
+        [
+            Event::ExpandAll,
+            initial.event(),
+            Event::FocusNext,
+            Event::ToggleItem,
+            toggled.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let recorder = Recorder::new(state, &mut input);
+    let state = recorder.run()?;
+
+    insta::assert_snapshot!(initial, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "( ) foo => bar                                                               (-)"
+    "  [ ] File renamed                                                              "
+    "        1 unchanged⏎                                                            "
+    "  [ ] Section 2/2                                                            [-]"
+    "    [ ] - before⏎                                                               "
+    "    [ ] + after⏎                                                                "
+    "                                                                                "
+    "#);
+    insta::assert_snapshot!(toggled, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "[◐] foo => bar                                                               [-]"
+    "  (●) File renamed                                                              "
+    "        1 unchanged⏎                                                            "
+    "  [ ] Section 2/2                                                            [-]"
+    "    [ ] - before⏎                                                               "
+    "    [ ] + after⏎                                                                "
+    "                                                                                "
+    "#);
 
-    assert_snapshot!(test(true, true), @r###"(SelectedChanges { file_mode: Unix(33188), contents: Binary { old_description: Some("abc123 (123 bytes)"), new_description: Some("def456 (456 bytes)") } }, SelectedChanges { file_mode: Unix(33188), contents: Unchanged })"###);
+    // Only the rename was selected, so the old contents end up at the new
+    // path, and the new contents at the old path.
+    let (selected, unselected) = state.files[0].get_selected_contents();
+    assert_debug_snapshot!(selected, @r#"
+    SelectedChanges {
+        path: "bar",
+        file_mode: Unix(
+            33188,
+        ),
+        contents: Text {
+            contents: "unchanged\nbefore\n",
+        },
+    }
+    "#);
+    assert_debug_snapshot!(unselected, @r#"
+    SelectedChanges {
+        path: "foo",
+        file_mode: Unix(
+            33188,
+        ),
+        contents: Text {
+            contents: "unchanged\nafter\n",
+        },
+    }
+    "#);
 
     Ok(())
 }
@@ -2258,14 +2475,14 @@ fn test_expand_menu() -> TestResult {
     "                                                                                "
     "                                                                                "
     "###);
-    insta::assert_snapshot!(after_click, @r###"
+    insta::assert_snapshot!(after_click, @r#"
     "[File] [Edit] [Select] [View]                                                   "
     "(◐) foo[Edit message (e)]                                                    (+)"
-    "[●] baz[Toggle current (space)]                                              [+]"
+    "[●] baz[Edit metadata (E)]                                                   [+]"
+    "       [Toggle current (space)]                                                 "
     "       [Toggle current and advance (enter)]                                     "
     "       [Invert all items (a)]                                                   "
-    "       [Invert all items uniformly (A)]                                         "
-    "###);
+    "#);
     insta::assert_snapshot!(after_click_different, @r###"
     "[File] [Edit] [Select] [View]                                                   "
     "[Confirm (c)]                                                                (+)"
@@ -2395,15 +2612,21 @@ fn test_read_only() -> TestResult {
     "                                                                                "
     "###);
 
-    insta::assert_debug_snapshot!(state, @r###"
+    insta::assert_debug_snapshot!(state, @r#"
     RecordState {
         is_read_only: true,
         commits: [
             Commit {
                 message: None,
+                author: None,
+                trailers: None,
+                branch: None,
             },
             Commit {
                 message: None,
+                author: None,
+                trailers: None,
+                branch: None,
             },
         ],
         files: [
@@ -2515,7 +2738,7 @@ fn test_read_only() -> TestResult {
             },
         ],
     }
-    "###);
+    "#);
 
     Ok(())
 }
@@ -2549,15 +2772,21 @@ fn test_toggle_unchanged_line() -> TestResult {
     "       20 this is some text⏎                                                    "
     "###);
 
-    insta::assert_debug_snapshot!(state, @r###"
+    insta::assert_debug_snapshot!(state, @r#"
     RecordState {
         is_read_only: false,
         commits: [
             Commit {
                 message: None,
+                author: None,
+                trailers: None,
+                branch: None,
             },
             Commit {
                 message: None,
+                author: None,
+                trailers: None,
+                branch: None,
             },
         ],
         files: [
@@ -2669,7 +2898,7 @@ fn test_toggle_unchanged_line() -> TestResult {
             },
         ],
     }
-    "###);
+    "#);
 
     Ok(())
 }
@@ -2711,14 +2940,14 @@ fn test_max_file_view_width() -> TestResult {
     let recorder = Recorder::new(state.clone(), &mut input);
     recorder.run()?;
 
-    insta::assert_snapshot!(initial_wide, @r###"
+    insta::assert_snapshot!(initial_wide, @r#"
     "[File] [Edit] [Select] [View]                                                                                                                                                                                                                             "
-    "( ) very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/ve…(-) [ ] very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/ve…[+]         "
-    "        1 very very very very very very very very very very very very very very very very very very very very very very…                                                                                                                                  "
-    "  [ ] Section 1/1                                                                                                    [-]                                                                                                                                  "
-    "    [ ] + very very very very very very very very very very very very very very very very very very very very very very…                                                                                                                                  "
+    "                                                                                                                         (●) very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/very/ve…(-)         "
+    "                              There are no changes to view.                                                                      1 very very very very very very very very very very very very very very very very very very very very very very…         "
+    "                                                                                                                           [●] Section 1/1                                                                                                    [-]         "
+    "                                                                                                                             [●] + very very very very very very very very very very very very very very very very very very very very very very…         "
     "                                                                                                                                                                                                                                                          "
-    "###);
+    "#);
 
     let initial_narrow = TestingScreenshot::default();
     let mut input = TestingInput::new(
@@ -2746,6 +2975,7 @@ fn test_commit_message_view() -> TestResult {
     let mut state = example_contents();
     state.commits = vec![Commit {
         message: Some("".to_string()),
+        ..Default::default()
     }];
 
     let initial = TestingScreenshot::default();
@@ -2776,10 +3006,10 @@ fn test_commit_message_view() -> TestResult {
     let recorder = Recorder::new(state, &mut input);
     recorder.run()?;
 
-    insta::assert_snapshot!(initial, @r###"
+    insta::assert_snapshot!(initial, @r#"
     "[File] [Edit] [Select] [View]                                                   "
     "                                                                                "
-    "[Edit message]  •  (no message)                                                 "
+    "[+] [Edit message]  ⚠ 1 warning  •  (no message)                                "
     "                                                                                "
     "(◐) foo/bar                                                                  (-)"
     "        ⋮                                                                       "
@@ -2801,11 +3031,11 @@ fn test_commit_message_view() -> TestResult {
     "    [●] + after text 1⏎                                                         "
     "    [●] + after text 2⏎                                                         "
     "        5 this is some trailing text⏎                                           "
-    "###);
-    insta::assert_snapshot!(after_edit, @r###"
+    "#);
+    insta::assert_snapshot!(after_edit, @r#"
     "[File] [Edit] [Select] [View]                                                   "
     "                                                                                "
-    "[Edit message]  •  Hello, world!                                                "
+    "[+] [Edit message]  •  Hello, world!                                            "
     "                                                                                "
     "(◐) foo/bar                                                                  (-)"
     "        ⋮                                                                       "
@@ -2827,10 +3057,10 @@ fn test_commit_message_view() -> TestResult {
     "    [●] + after text 1⏎                                                         "
     "    [●] + after text 2⏎                                                         "
     "        5 this is some trailing text⏎                                           "
-    "###);
-    insta::assert_snapshot!(after_scroll1, @r###"
+    "#);
+    insta::assert_snapshot!(after_scroll1, @r#"
     "[File] [Edit] [Select] [View]                                                   "
-    "[Edit message]  •  Hello, world!                                                "
+    "[+] [Edit message]  •  Hello, world!                                            "
     "                                                                                "
     "(◐) foo/bar                                                                  (-)"
     "        ⋮                                                                       "
@@ -2853,7 +3083,7 @@ fn test_commit_message_view() -> TestResult {
     "    [●] + after text 2⏎                                                         "
     "        5 this is some trailing text⏎                                           "
     "                                                                                "
-    "###);
+    "#);
     insta::assert_snapshot!(after_scroll2, @r###"
     "[File] [Edit] [Select] [View]                                                   "
     "(◐) foo/bar                                                                  (-)"
@@ -2889,6 +3119,7 @@ fn test_quit_dialog_when_commit_message_provided() -> TestResult {
     let mut state = example_contents();
     state.commits = vec![Commit {
         message: Some("hello".to_string()),
+        ..Default::default()
     }];
 
     let changed_message_and_files = TestingScreenshot::default();
@@ -2914,10 +3145,10 @@ fn test_quit_dialog_when_commit_message_provided() -> TestResult {
     let recorder = Recorder::new(state, &mut input);
     assert_matches!(recorder.run(), Err(RecordError::Cancelled));
 
-    insta::assert_snapshot!(changed_message_and_files, @r###"
+    insta::assert_snapshot!(changed_message_and_files, @r#"
     "[File] [Edit] [Select] [View]                                                   "
     "                                                                                "
-    "[Edit message]  •  hello                                                        "
+    "[+] [Edit message]  •  hello                                                    "
     "                                                                                "
     "(◐) foo/bar                                                                  (+)"
     "[●] baz                                                                      [+]"
@@ -2939,11 +3170,11 @@ fn test_quit_dialog_when_commit_message_provided() -> TestResult {
     "                                                                                "
     "                                                                                "
     "                                                                                "
-    "###);
-    insta::assert_snapshot!(changed_message_only, @r###"
+    "#);
+    insta::assert_snapshot!(changed_message_only, @r#"
     "[File] [Edit] [Select] [View]                                                   "
     "                                                                                "
-    "[Edit message]  •  hello                                                        "
+    "[+] [Edit message]  •  hello                                                    "
     "                                                                                "
     "( ) foo/bar                                                                  (+)"
     "[ ] baz                                                                      [+]"
@@ -2965,7 +3196,7 @@ fn test_quit_dialog_when_commit_message_provided() -> TestResult {
     "                                                                                "
     "                                                                                "
     "                                                                                "
-    "###);
+    "#);
 
     Ok(())
 }
@@ -3652,7 +3883,7 @@ fn test_tabs_in_files() -> TestResult {
     let recorder = Recorder::new(state, &mut input);
     recorder.run()?;
 
-    insta::assert_snapshot!(initial, @r###"
+    insta::assert_snapshot!(initial, @r#"
     "[File] [Edit] [Select] [View]                                                   "
     "(●) foo/bar                                                                  (-)"
     "        ⋮                                                                       "
@@ -3660,18 +3891,18 @@ fn test_tabs_in_files() -> TestResult {
     "        9 →   this is some indented text⏎                                       "
     "       10 →   this is some indented text⏎                                       "
     "  [●] Section 1/1                                                            [-]"
-    "    [●] - before text→   1⏎                                                     "
+    "    [●] - before text→1⏎                                                        "
     "    [●] + after text 1⏎                                                         "
     "    [●] - before text 2⏎                                                        "
-    "    [●] + after text→   2⏎                                                      "
+    "    [●] + after text→ 2⏎                                                        "
     "    [●] - →   before text 3⏎                                                    "
-    "    [●] + →   after text→   3⏎                                                  "
-    "    [●] - →   before text→   4⏎                                                 "
+    "    [●] + →   after text→ 3⏎                                                    "
+    "    [●] - →   before text→4⏎                                                    "
     "    [●] + →   after text 4⏎                                                     "
-    "    [●] - →   before text→   5                                                  "
-    "    [●] + →   after text→   5                                                   "
-    "       16 this is some trailing→   text⏎                                        "
-    "###);
+    "    [●] - →   before text→5                                                     "
+    "    [●] + →   after text→ 5                                                     "
+    "       16 this is some trailing→  text⏎                                         "
+    "#);
 
     Ok(())
 }
@@ -3760,49 +3991,64 @@ fn test_carriage_return() -> TestResult {
 }
 
 #[test]
-fn test_some_control_characters() -> TestResult {
+fn test_render_options() -> TestResult {
     let state = RecordState {
         is_read_only: false,
         commits: Default::default(),
         files: vec![File {
             old_path: None,
-            path: Cow::Borrowed(Path::new("foo")),
+            path: Cow::Borrowed(Path::new("Makefile")),
             file_mode: FileMode::FILE_DEFAULT,
-            sections: vec![Section::Changed {
-                lines: vec![SectionChangedLine {
-                    is_checked: false,
-                    change_type: ChangeType::Added,
-                    line: Cow::Borrowed("nul:\0, bel:\x07, esc:\x1b, del:\x7f\n"),
-                }],
-            }],
+            sections: vec![
+                Section::Unchanged {
+                    lines: vec![Cow::Borrowed("all:\tfoo\n")],
+                },
+                Section::Changed {
+                    lines: vec![
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: ChangeType::Removed,
+                            line: Cow::Borrowed("\tcc -o foo\tfoo.c\r\n"),
+                        },
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: ChangeType::Added,
+                            line: Cow::Borrowed("\tcc -o foo\tfoo.c  \n"),
+                        },
+                    ],
+                },
+            ],
         }],
     };
 
     let initial = TestingScreenshot::default();
     let mut input = TestingInput::new(
         80,
-        8,
+        6,
         [Event::ExpandAll, initial.event(), Event::QuitAccept],
     );
-    let recorder = Recorder::new(state, &mut input);
+    let recorder = Recorder::new(state, &mut input).with_render_options(RenderOptions {
+        tab_width: 8,
+        show_invisible_characters: false,
+        highlight_trailing_whitespace: true,
+        wrap_lines: false,
+    });
     recorder.run()?;
 
-    insta::assert_snapshot!(initial, @r###"
+    insta::assert_snapshot!(initial, @r#"
     "[File] [Edit] [Select] [View]                                                   "
-    "( ) foo                                                                      (-)"
+    "( ) Makefile                                                                 (-)"
+    "        1 all:    foo                                                           "
     "  [ ] Section 1/1                                                            [-]"
-    "    [ ] + nul:␀, bel:␇, esc:␛, del:␡⏎                                           "
-    "                                                                                "
-    "                                                                                "
-    "                                                                                "
-    "                                                                                "
-    "###);
+    "    [ ] -         cc -o foo       foo.c                                         "
+    "    [ ] +         cc -o foo       foo.c                                         "
+    "#);
 
     Ok(())
 }
 
 #[test]
-fn test_non_printing_characters() -> TestResult {
+fn test_ascii_only() -> TestResult {
     let state = RecordState {
         is_read_only: false,
         commits: Default::default(),
@@ -3810,35 +4056,1463 @@ fn test_non_printing_characters() -> TestResult {
             old_path: None,
             path: Cow::Borrowed(Path::new("foo")),
             file_mode: FileMode::FILE_DEFAULT,
-            sections: vec![Section::Changed {
-                lines: vec![SectionChangedLine {
-                    is_checked: false,
-                    change_type: ChangeType::Added,
-                    line: Cow::Borrowed("zwj:\u{200d}, zwnj:\u{200c}"),
-                }],
-            }],
+            sections: vec![
+                Section::Unchanged {
+                    lines: vec![Cow::Borrowed("a\tb\n")],
+                },
+                Section::Changed {
+                    lines: vec![
+                        SectionChangedLine {
+                            is_checked: true,
+                            change_type: ChangeType::Removed,
+                            line: Cow::Borrowed("old\r\n"),
+                        },
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: ChangeType::Added,
+                            line: Cow::Borrowed("new\x1b[0m\u{200b}\n"),
+                        },
+                    ],
+                },
+            ],
         }],
     };
 
     let initial = TestingScreenshot::default();
+    let help = TestingScreenshot::default();
     let mut input = TestingInput::new(
-        80,
-        8,
-        [Event::ExpandAll, initial.event(), Event::QuitAccept],
+        100,
+        22,
+        [
+            Event::ExpandAll,
+            initial.event(),
+            Event::Help,
+            help.event(),
+            Event::Help,
+            Event::QuitAccept,
+        ],
     );
-    let recorder = Recorder::new(state, &mut input);
+    let recorder = Recorder::new(state, &mut input).with_unicode(false);
     recorder.run()?;
 
-    insta::assert_snapshot!(initial, @r###"
-    "[File] [Edit] [Select] [View]                                                   "
-    "( ) foo                                                                      (-)"
-    "  [ ] Section 1/1                                                            [-]"
-    "    [ ] + zwj:�, zwnj:�                                                         "
-    "                                                                                "
-    "                                                                                "
-    "                                                                                "
-    "                                                                                "
-    "###);
+    insta::assert_snapshot!(initial, @r#"
+    "[File] [Edit] [Select] [View]                                                                       "
+    "(~) foo                                                                                          (-)"
+    "        1 a>--b$                                                                                    "
+    "  [~] Section 1/1                                                                                [-]"
+    "    [*] - old^M$                                                                                    "
+    "    [ ] + new^[[0m?$                                                                                "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "                                                                                                    "
+    "#);
+    insta::assert_snapshot!(help, @r#"
+    "[File] +Help-------------------------------------------------------------------------------+        "
+    "(~) foo|You can click the menus with a mouse, or use these keyboard shortcuts:             |     (-)"
+    "       |                                                                                   |        "
+    "  [~] S|    General                             Navigation                                 |     [-]"
+    "    [*]|    Quit/Cancel             q           Next/Prev               j/k or Down/Up     |        "
+    "    [ ]|    Confirm changes         c           Next/Prev of same type  PgDn/PgUp          |        "
+    "       |    Force quit              ^c          Move out & fold         h or Left          |        "
+    "       |                                        Move out & don't fold   H or Shift-Left    |        "
+    "       |    View controls                       Move in & unfold        l or Right         |        "
+    "       |    Expand/Collapse         f                                                      |        "
+    "       |    Expand/Collapse all     F           Scrolling                                  |        "
+    "       |    Edit commit message     e           Scroll up/down          ^y/^e              |        "
+    "       |    Edit commit metadata    E                                or ^Up/^Down          |        "
+    "       |    Wrap long lines         w           Page up/down            ^b/^f              |        "
+    "       |    Show full message       m                                or ^PgUp/^PgDn        |        "
+    "       |    Side-by-side commits    v           Previous/Next page      ^u/^d              |        "
+    "       |    Selection                           Scroll left/right       ^Left/^Right       |        "
+    "       |    Toggle current          Space                                                  |        "
+    "       |    Toggle and advance      Enter       Large files                                |        "
+    "       |    Invert all              a           Show full diff          D                  |        "
+    "       |    Invert all uniformly    A                                                      |        "
+    "       +----------------------------------------------------------------------------(Close)+        "
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_wrap_lines() -> TestResult {
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files: vec![File {
+            old_path: None,
+            path: Cow::Borrowed(Path::new("foo")),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections: vec![
+                Section::Unchanged {
+                    lines: vec![Cow::Borrowed(
+                        "this unchanged line is too long to fit on a single row\n",
+                    )],
+                },
+                Section::Changed {
+                    lines: vec![
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: ChangeType::Added,
+                            line: Cow::Borrowed(
+                                "this added line is much too long to fit on a single row, or even on two rows\n",
+                            ),
+                        },
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: ChangeType::Added,
+                            line: Cow::Borrowed("short line\n"),
+                        },
+                    ],
+                },
+            ],
+        }],
+    };
+
+    let unwrapped = TestingScreenshot::default();
+    let wrapped = TestingScreenshot::default();
+    let mut input = TestingInput::new(
+        40,
+        10,
+        [
+            Event::ExpandAll,
+            unwrapped.event(),
+            Event::ToggleWrapLines,
+            wrapped.event(),
+            // Click on the last continuation row of the long added line.
+            Event::Click { row: 7, column: 30 },
+            Event::ToggleItem,
+            Event::QuitAccept,
+        ],
+    );
+    let recorder = Recorder::new(state, &mut input);
+    let state = recorder.run()?;
+
+    insta::assert_snapshot!(unwrapped, @r#"
+    "[File] [Edit] [Select] [View]           "
+    "( ) foo                              (-)"
+    "        1 this unchanged line is too lo…"
+    "  [ ] Section 1/1                    [-]"
+    "    [ ] + this added line is much too l…"
+    "    [ ] + short line⏎                   "
+    "                                        "
+    "                                        "
+    "                                        "
+    "                                        "
+    "#);
+    insta::assert_snapshot!(wrapped, @r#"
+    "[File] [Edit] [Select] [View]           "
+    "( ) foo                              (-)"
+    "        1 this unchanged line is too lon"
+    "        ↪ g to fit on a single row⏎     "
+    "  [ ] Section 1/1                    [-]"
+    "    [ ] + this added line is much too lo"
+    "        ↪ ng to fit on a single row, or "
+    "        ↪ even on two rows⏎             "
+    "    [ ] + short line⏎                   "
+    "                                        "
+    "#);
+    assert_debug_snapshot!(state.files[0].sections[1], @r#"
+    Changed {
+        lines: [
+            SectionChangedLine {
+                is_checked: true,
+                change_type: Added,
+                line: "this added line is much too long to fit on a single row, or even on two rows\n",
+            },
+            SectionChangedLine {
+                is_checked: false,
+                change_type: Added,
+                line: "short line\n",
+            },
+        ],
+    }
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_scroll_horizontally() -> TestResult {
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files: vec![File {
+            old_path: None,
+            path: Cow::Borrowed(Path::new("foo")),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections: vec![
+                Section::Unchanged {
+                    lines: vec![Cow::Borrowed("0123456789abcdefghijklmnopqrstuvwxyz\n")],
+                },
+                Section::Changed {
+                    lines: vec![
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: ChangeType::Removed,
+                            line: Cow::Borrowed("0123456789abcdefghijklmnopqrstuvwxyz\n"),
+                        },
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: ChangeType::Added,
+                            line: Cow::Borrowed("short line\n"),
+                        },
+                    ],
+                },
+            ],
+        }],
+    };
+
+    let scrolled = TestingScreenshot::default();
+    let scrolled_to_end = TestingScreenshot::default();
+    let scrolled_back = TestingScreenshot::default();
+    let mut input = TestingInput::new(
+        40,
+        6,
+        [
+            Event::ExpandAll,
+            Event::ScrollRight,
+            scrolled.event(),
+            Event::ScrollRight,
+            Event::ScrollRight,
+            Event::ScrollRight,
+            scrolled_to_end.event(),
+            Event::ScrollLeft,
+            Event::ScrollLeft,
+            Event::ScrollLeft,
+            Event::ScrollLeft,
+            scrolled_back.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let recorder = Recorder::new(state, &mut input);
+    recorder.run()?;
+
+    insta::assert_snapshot!(scrolled, @r#"
+    "[File] [Edit] [Select] [View]           "
+    "( ) foo                              (-)"
+    "        1 456789abcdefghijklmnopqrstuvw…"
+    "  [ ] Section 1/1                    [-]"
+    "    [ ] - 456789abcdefghijklmnopqrstuvw…"
+    "    [ ] + t line⏎                       "
+    "#);
+    insta::assert_snapshot!(scrolled_to_end, @r#"
+    "[File] [Edit] [Select] [View]           "
+    "( ) foo                              (-)"
+    "        1 789abcdefghijklmnopqrstuvwxyz⏎"
+    "  [ ] Section 1/1                    [-]"
+    "    [ ] - 789abcdefghijklmnopqrstuvwxyz⏎"
+    "    [ ] + ine⏎                          "
+    "#);
+    insta::assert_snapshot!(scrolled_back, @r#"
+    "[File] [Edit] [Select] [View]           "
+    "( ) foo                              (-)"
+    "        1 0123456789abcdefghijklmnopqrs…"
+    "  [ ] Section 1/1                    [-]"
+    "    [ ] - 0123456789abcdefghijklmnopqrs…"
+    "    [ ] + short line⏎                   "
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_some_control_characters() -> TestResult {
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files: vec![File {
+            old_path: None,
+            path: Cow::Borrowed(Path::new("foo")),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections: vec![Section::Changed {
+                lines: vec![SectionChangedLine {
+                    is_checked: false,
+                    change_type: ChangeType::Added,
+                    line: Cow::Borrowed("nul:\0, bel:\x07, esc:\x1b, del:\x7f\n"),
+                }],
+            }],
+        }],
+    };
+
+    let initial = TestingScreenshot::default();
+    let mut input = TestingInput::new(
+        80,
+        8,
+        [Event::ExpandAll, initial.event(), Event::QuitAccept],
+    );
+    let recorder = Recorder::new(state, &mut input);
+    recorder.run()?;
+
+    insta::assert_snapshot!(initial, @r###"
+    "[File] [Edit] [Select] [View]                                                   "
+    "( ) foo                                                                      (-)"
+    "  [ ] Section 1/1                                                            [-]"
+    "    [ ] + nul:␀, bel:␇, esc:␛, del:␡⏎                                           "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "###);
+
+    Ok(())
+}
+
+#[test]
+fn test_non_printing_characters() -> TestResult {
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files: vec![File {
+            old_path: None,
+            path: Cow::Borrowed(Path::new("foo")),
+            file_mode: FileMode::FILE_DEFAULT,
+            sections: vec![Section::Changed {
+                lines: vec![SectionChangedLine {
+                    is_checked: false,
+                    change_type: ChangeType::Added,
+                    line: Cow::Borrowed("zwj:\u{200d}, zwnj:\u{200c}"),
+                }],
+            }],
+        }],
+    };
+
+    let initial = TestingScreenshot::default();
+    let mut input = TestingInput::new(
+        80,
+        8,
+        [Event::ExpandAll, initial.event(), Event::QuitAccept],
+    );
+    let recorder = Recorder::new(state, &mut input);
+    recorder.run()?;
+
+    insta::assert_snapshot!(initial, @r###"
+    "[File] [Edit] [Select] [View]                                                   "
+    "( ) foo                                                                      (-)"
+    "  [ ] Section 1/1                                                            [-]"
+    "    [ ] + zwj:�, zwnj:�                                                         "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "###);
+
+    Ok(())
+}
+
+#[test]
+fn test_step_based_api() -> TestResult {
+    let mut input = TestingInput::new(0, 0, []);
+    let mut recorder = Recorder::new(example_contents(), &mut input);
+    let mut buf = Buffer::empty(Rect::new(0, 0, 84, 8));
+    let area = Rect::new(2, 1, 80, 6);
+    recorder.render(area, &mut buf);
+    insta::assert_snapshot!(buffer_lines(&buf), @r#"
+    "                                                                                    "
+    "  [File] [Edit] [Select] [View]                                                     "
+    "  (◐) foo/bar                                                                  (+)  "
+    "  [●] baz                                                                      [+]  "
+    "                                                                                    "
+    "                                                                                    "
+    "                                                                                    "
+    "                                                                                    "
+    "#);
+
+    // Clicks are relative to the buffer, so this selects the second file.
+    assert_eq!(
+        recorder.process_event(Event::Click { row: 3, column: 3 })?,
+        RecorderStatus::Running
+    );
+    recorder.render(area, &mut buf);
+    insta::assert_snapshot!(buffer_lines(&buf), @r#"
+    "                                                                                    "
+    "  [File] [Edit] [Select] [View]                                                     "
+    "  [◐] foo/bar                                                                  [+]  "
+    "  (●) baz                                                                      (+)  "
+    "                                                                                    "
+    "                                                                                    "
+    "                                                                                    "
+    "                                                                                    "
+    "#);
+
+    // Clicks outside of the drawn area are ignored.
+    recorder.process_event(Event::Click { row: 0, column: 0 })?;
+    assert_eq!(recorder.status(), RecorderStatus::Running);
+
+    assert_eq!(
+        recorder.process_event(Event::QuitAccept)?,
+        RecorderStatus::Accepted
+    );
+    // Events after the session has ended are ignored.
+    assert_eq!(
+        recorder.process_event(Event::ToggleAll)?,
+        RecorderStatus::Accepted
+    );
+    let state = recorder.finish()?;
+    assert_eq!(state.files.len(), 2);
+
+    let mut input = TestingInput::new(0, 0, []);
+    let mut recorder = Recorder::new(example_contents(), &mut input);
+    // The first event opens the quit dialog, since there are changes selected.
+    assert_eq!(
+        recorder.process_event(Event::QuitCancel)?,
+        RecorderStatus::Running
+    );
+    assert_eq!(
+        recorder.process_event(Event::ToggleItem)?,
+        RecorderStatus::Cancelled
+    );
+    assert_matches!(recorder.finish(), Err(RecordError::Cancelled));
+
+    // Editing the commit message uses the terminal, so it has to be redrawn.
+    let mut input = TestingInput::new(0, 0, []);
+    input.commit_messages.push_back("new message".to_owned());
+    let mut state = example_contents();
+    state.commits = vec![Commit {
+        message: Some("old message".to_owned()),
+        ..Default::default()
+    }];
+    let mut recorder = Recorder::new(state, &mut input);
+    assert_eq!(
+        recorder.process_event(Event::EditCommitMessage)?,
+        RecorderStatus::RedrawNeeded
+    );
+    assert_eq!(recorder.status(), RecorderStatus::Running);
+    assert_eq!(
+        recorder.process_event(Event::FocusNext)?,
+        RecorderStatus::Running
+    );
+    let state = recorder.finish()?;
+    assert_eq!(state.commits[0].message.as_deref(), Some("new message"));
+
+    Ok(())
+}
+
+#[test]
+fn test_record_widget() -> TestResult {
+    let mut input = TestingInput::new(0, 0, []);
+    let recorder = Recorder::new(example_contents(), &mut input)
+        .with_menu_bar(false)
+        .with_dialogs(false);
+    let mut num_checked_lines = Vec::new();
+    let mut state = RecordWidgetState::new(recorder).on_selection_change(|state| {
+        let num_checked = state
+            .files
+            .iter()
+            .flat_map(|file| &file.sections)
+            .map(|section| match section {
+                Section::Changed { lines } => lines.iter().filter(|line| line.is_checked).count(),
+                _ => 0,
+            })
+            .sum::<usize>();
+        num_checked_lines.push(num_checked);
+    });
+
+    let area = Rect::new(0, 0, 80, 4);
+    let mut buf = Buffer::empty(area);
+    StatefulWidget::render(RecordWidget::new(), area, &mut buf, &mut state);
+    insta::assert_snapshot!(buffer_lines(&buf), @r#"
+    "(◐) foo/bar                                                                  (+)"
+    "[●] baz                                                                      [+]"
+    "                                                                                "
+    "                                                                                "
+    "#);
+
+    // Only toggling changes triggers the callback.
+    state.handle_event(Event::FocusNext)?;
+    state.handle_event(Event::ToggleItem)?;
+    state.handle_event(Event::ToggleAll)?;
+    // The help dialog isn't shown.
+    state.handle_event(Event::Help)?;
+    StatefulWidget::render(RecordWidget::new(), area, &mut buf, &mut state);
+    insta::assert_snapshot!(buffer_lines(&buf), @r#"
+    "[◐] foo/bar                                                                  [+]"
+    "(●) baz                                                                      (+)"
+    "                                                                                "
+    "                                                                                "
+    "#);
+
+    // There's no quit dialog to confirm discarding the changes.
+    assert_eq!(
+        state.handle_event(Event::QuitCancel)?,
+        RecorderStatus::Cancelled
+    );
+    assert_matches!(state.finish(), Err(RecordError::Cancelled));
+    insta::assert_debug_snapshot!(num_checked_lines, @"
+    [
+        3,
+        5,
+    ]
+    ");
+
+    Ok(())
+}
+
+#[test]
+fn test_builtin_message_editor() -> TestResult {
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    let mut state = example_contents();
+    state.commits = vec![Commit {
+        message: Some("".to_string()),
+        ..Default::default()
+    }];
+
+    let opened = TestingScreenshot::default();
+    let edited = TestingScreenshot::default();
+    let saved = TestingScreenshot::default();
+    let mut events = vec![Event::EditCommitMessage, opened.event()];
+    events.extend("Fix the frobnicator".chars().map(|c| key(KeyCode::Char(c))));
+    events.extend([
+        key(KeyCode::Enter),
+        key(KeyCode::Enter),
+        Event::Paste(
+            "A body line which is long enough that it has to be wrapped onto another row.\r\n"
+                .to_string(),
+        ),
+        key(KeyCode::Backspace),
+        edited.event(),
+        Event::Key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL)),
+        saved.event(),
+        // Changes are discarded if the editor is cancelled.
+        Event::EditCommitMessage,
+        key(KeyCode::Char('x')),
+        key(KeyCode::Esc),
+        Event::QuitAccept,
+    ]);
+    let mut input = TestingInput::new(60, 12, events);
+    let recorder = Recorder::new(state, &mut input).with_builtin_message_editor(true);
+    let state = recorder.run()?;
+
+    insta::assert_snapshot!(opened, @r#"
+    "[File] [Edit] [Select] [View]                               "
+    "      ┌Edit message──────────────────────────────────┐      "
+    "[+] [E│                                              │      "
+    "      │                                              │      "
+    "(◐) fo│                                              │   (+)"
+    "[●] ba│                                              │   [+]"
+    "      │                                              │      "
+    "      │                                              │      "
+    "      │Subject: 0/72 • ctrl-s: save • esc: cancel    │      "
+    "      └───────────────────────────────[Cancel]─[Save]┘      "
+    "                                                            "
+    "                                                            "
+    "#);
+    insta::assert_snapshot!(edited, @r#"
+    "[File] [Edit] [Select] [View]                               "
+    "      ┌Edit message──────────────────────────────────┐      "
+    "[+] [E│Fix the frobnicator                           │      "
+    "      │                                              │      "
+    "(◐) fo│A body line which is long enough that it has t│   (+)"
+    "[●] ba│o be wrapped onto another row.                │   [+]"
+    "      │                                              │      "
+    "      │                                              │      "
+    "      │Subject: 19/72 • ctrl-s: save • esc: cancel   │      "
+    "      └───────────────────────────────[Cancel]─[Save]┘      "
+    "                                                            "
+    "                                                            "
+    "#);
+    insta::assert_snapshot!(saved, @r#"
+    "[File] [Edit] [Select] [View]                               "
+    "                                                            "
+    "[+] [Edit message]  •  Fix the frobnicator                  "
+    "                                                            "
+    "(◐) foo/bar                                              (+)"
+    "[●] baz                                                  [+]"
+    "                                                            "
+    "                                                            "
+    "                                                            "
+    "                                                            "
+    "                                                            "
+    "                                                            "
+    "#);
+    assert_eq!(
+        state.commits[0].message.as_deref(),
+        Some(
+            "Fix the frobnicator\n\nA body line which is long enough that it has to be wrapped onto another row."
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_builtin_message_editor_long_subject() -> TestResult {
+    let mut state = example_contents();
+    state.commits = vec![Commit {
+        message: Some(format!("{}\n", "x".repeat(80))),
+        ..Default::default()
+    }];
+
+    let opened = TestingScreenshot::default();
+    let mut input = TestingInput::new(
+        60,
+        10,
+        [
+            Event::EditCommitMessage,
+            Event::Key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE)),
+            opened.event(),
+            Event::QuitEscape,
+            Event::QuitAccept,
+        ],
+    );
+    let recorder = Recorder::new(state, &mut input).with_builtin_message_editor(true);
+    recorder.run()?;
+
+    insta::assert_snapshot!(opened, @r#"
+    "[File] [Edit] [Select] [View]                               "
+    "      ┌Edit message──────────────────────────────────┐      "
+    "[+] [E│xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx│xxxxx…"
+    "      │xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx            │      "
+    "(◐) fo│                                              │   (+)"
+    "[●] ba│                                              │   [+]"
+    "      │                                              │      "
+    "      │Subject: 80/72 • ctrl-s: save • esc: cancel   │      "
+    "      └───────────────────────────────[Cancel]─[Save]┘      "
+    "                                                            "
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_message_preview() -> TestResult {
+    let mut state = example_contents();
+    state.commits = vec![Commit {
+        message: Some(
+            "Fix the frobnicator so that it handles every kind of frob it's ever given\n\
+             A body line which is long enough that it has to be wrapped onto another row.\n\
+             \n\
+             Another paragraph.\n"
+                .to_string(),
+        ),
+        ..Default::default()
+    }];
+
+    let collapsed = TestingScreenshot::default();
+    let expanded = TestingScreenshot::default();
+    let clicked = TestingScreenshot::default();
+    let mut input = TestingInput::new(
+        80,
+        14,
+        [
+            collapsed.event(),
+            Event::Key(KeyEvent::new(KeyCode::Char('m'), KeyModifiers::NONE)),
+            expanded.event(),
+            Event::Click { row: 2, column: 1 },
+            clicked.event(),
+            Event::QuitAccept,
+        ],
+    );
+    let recorder = Recorder::new(state, &mut input);
+    recorder.run()?;
+
+    insta::assert_snapshot!(collapsed, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "                                                                                "
+    "[+] [Edit message]  ⚠ 2 warnings  •  Fix the frobnicator so that it handles eve…"
+    "                                                                                "
+    "(◐) foo/bar                                                                  (+)"
+    "[●] baz                                                                      [+]"
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "#);
+    insta::assert_snapshot!(expanded, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "                                                                                "
+    "[-] [Edit message]  ⚠ 2 warnings  •  Fix the frobnicator so that it handles eve…"
+    "    ⚠ The subject is longer than 72 characters                                  "
+    "    ⚠ The subject isn't followed by a blank line                                "
+    "    │ Fix the frobnicator so that it handles every kind of frob it's ever given "
+    "    │ A body line which is long enough that it has to be wrapped onto another   "
+    "    │ row.                                                                      "
+    "    │                                                                           "
+    "    │ Another paragraph.                                                        "
+    "                                                                                "
+    "(◐) foo/bar                                                                  (+)"
+    "[●] baz                                                                      [+]"
+    "                                                                                "
+    "#);
+    insta::assert_snapshot!(clicked, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "                                                                                "
+    "[+] [Edit message]  ⚠ 2 warnings  •  Fix the frobnicator so that it handles eve…"
+    "                                                                                "
+    "(◐) foo/bar                                                                  (+)"
+    "[●] baz                                                                      [+]"
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_commit_metadata() -> TestResult {
+    let mut state = example_contents();
+    state.commits = vec![Commit {
+        message: Some("Fix the frobnicator\n".to_string()),
+        author: Some("Jane Doe <jane@example.com>".to_string()),
+        trailers: Some(vec![Trailer {
+            key: "Change-Id".to_string(),
+            value: "I0123456789".to_string(),
+        }]),
+        branch: Some("".to_string()),
+    }];
+
+    let initial = TestingScreenshot::default();
+    let edited = TestingScreenshot::default();
+    let mut input = TestingInput {
+        width: 80,
+        height: 10,
+        events: Box::new(
+            [
+                initial.event(),
+                Event::EditCommitMetadata,
+                edited.event(),
+                Event::QuitAccept,
+            ]
+            .into_iter(),
+        ),
+        commit_messages: ["Author: John Doe <john@example.com>\n\
+             bookmark: frobnicator\n\
+             Signed-off-by: John Doe <john@example.com>\n\
+             # Comments and lines without values are ignored.\n\
+             Reviewed-by:\n\
+             Change-Id: I0123456789\n"
+            .to_string()]
+        .into_iter()
+        .collect(),
+    };
+    let recorder = Recorder::new(state, &mut input);
+    let state = recorder.run()?;
+
+    insta::assert_snapshot!(initial, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "                                                                                "
+    "[+] [Edit message]  •  Fix the frobnicator                                      "
+    "    [Edit metadata]  •  Author: Jane Doe <jane@example.com> • Branch: (none)    "
+    "                        Change-Id: I0123456789                                  "
+    "                                                                                "
+    "(◐) foo/bar                                                                  (+)"
+    "[●] baz                                                                      [+]"
+    "                                                                                "
+    "                                                                                "
+    "#);
+    insta::assert_snapshot!(edited, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "                                                                                "
+    "[+] [Edit message]  •  Fix the frobnicator                                      "
+    "    [Edit metadata]  •  Author: John Doe <john@example.com> • Branch: frobnicat…"
+    "                        Signed-off-by: John Doe <john@example.com>              "
+    "                        Change-Id: I0123456789                                  "
+    "                                                                                "
+    "(◐) foo/bar                                                                  (+)"
+    "[●] baz                                                                      [+]"
+    "                                                                                "
+    "#);
+    insta::assert_debug_snapshot!(state.commits[0], @r#"
+    Commit {
+        message: Some(
+            "Fix the frobnicator\n",
+        ),
+        author: Some(
+            "John Doe <john@example.com>",
+        ),
+        trailers: Some(
+            [
+                Trailer {
+                    key: "Signed-off-by",
+                    value: "John Doe <john@example.com>",
+                },
+                Trailer {
+                    key: "Change-Id",
+                    value: "I0123456789",
+                },
+            ],
+        ),
+        branch: Some(
+            "frobnicator",
+        ),
+    }
+    "#);
+
+    Ok(())
+}
+
+#[test]
+fn test_builtin_commit_metadata_editor() -> TestResult {
+    let mut state = example_contents();
+    state.commits = vec![Commit {
+        trailers: Some(Vec::new()),
+        ..Default::default()
+    }];
+
+    let initial = TestingScreenshot::default();
+    let opened = TestingScreenshot::default();
+    let mut events = vec![
+        initial.event(),
+        Event::Key(KeyEvent::new(KeyCode::Char('E'), KeyModifiers::SHIFT)),
+        opened.event(),
+    ];
+    events.extend(
+        "Signed-off-by: Jane Doe"
+            .chars()
+            .map(|c| Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))),
+    );
+    events.push(Event::Key(KeyEvent::new(
+        KeyCode::Char('s'),
+        KeyModifiers::CONTROL,
+    )));
+    events.push(Event::QuitAccept);
+    let mut input = TestingInput::new(60, 10, events);
+    let recorder = Recorder::new(state, &mut input).with_builtin_message_editor(true);
+    let state = recorder.run()?;
+
+    insta::assert_snapshot!(initial, @r#"
+    "[File] [Edit] [Select] [View]                               "
+    "                                                            "
+    "[Edit metadata]  •  (no trailers)                           "
+    "                                                            "
+    "(◐) foo/bar                                              (+)"
+    "[●] baz                                                  [+]"
+    "                                                            "
+    "                                                            "
+    "                                                            "
+    "                                                            "
+    "#);
+    insta::assert_snapshot!(opened, @r#"
+    "[File] [Edit] [Select] [View]                               "
+    "      ┌Edit metadata─────────────────────────────────┐      "
+    "[Edit │                                              │      "
+    "      │                                              │      "
+    "(◐) fo│                                              │   (+)"
+    "[●] ba│                                              │   [+]"
+    "      │                                              │      "
+    "      │Key: value • ctrl-s: save • esc: cancel       │      "
+    "      └───────────────────────────────[Cancel]─[Save]┘      "
+    "                                                            "
+    "#);
+    assert_eq!(
+        state.commits[0].trailers,
+        Some(vec![Trailer {
+            key: "Signed-off-by".to_string(),
+            value: "Jane Doe".to_string(),
+        }])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_adjacent_commit_view() -> TestResult {
+    let mut state = example_contents();
+    state.commits = vec![
+        Commit {
+            message: Some("First commit".to_string()),
+            ..Default::default()
+        },
+        Commit {
+            message: Some("Second commit".to_string()),
+            ..Default::default()
+        },
+    ];
+
+    let adjacent = TestingScreenshot::default();
+    let moved_to_first = TestingScreenshot::default();
+    let moved_to_second = TestingScreenshot::default();
+    let inline = TestingScreenshot::default();
+    let mut input = TestingInput {
+        width: 100,
+        height: 14,
+        events: Box::new(
+            [
+                Event::ExpandAll,
+                Event::Key(KeyEvent::new(KeyCode::Char('v'), KeyModifiers::NONE)),
+                adjacent.event(),
+                // Select and toggle the only line in the second commit.
+                Event::Click { row: 9, column: 55 },
+                Event::Click { row: 9, column: 55 },
+                moved_to_first.event(),
+                // Select and toggle the first line in the first commit.
+                Event::Click { row: 9, column: 5 },
+                Event::Click { row: 9, column: 5 },
+                moved_to_second.event(),
+                // Select the line in the second commit and edit its message.
+                Event::Click { row: 9, column: 55 },
+                Event::EditCommitMessage,
+                Event::Key(KeyEvent::new(KeyCode::Char('v'), KeyModifiers::NONE)),
+                inline.event(),
+                Event::QuitAccept,
+            ]
+            .into_iter(),
+        ),
+        commit_messages: ["Edited second commit".to_string()].into_iter().collect(),
+    };
+    let recorder = Recorder::new(state, &mut input);
+    let state = recorder.run()?;
+
+    insta::assert_snapshot!(adjacent, @r#"
+    "[File] [Edit] [Select] [View]                                                                       "
+    "[+] [Edit message]  •  First commit               [+] [Edit message]  •  Second commit              "
+    "                                                                                                    "
+    "(◐) foo/bar                                   (-) [◐] foo/bar                                   [-] "
+    "        ⋮                                                 ⋮                                         "
+    "       18 this is some text⏎                             18 this is some text⏎                      "
+    "       19 this is some text⏎                             19 this is some text⏎                      "
+    "       20 this is some text⏎                             20 this is some text⏎                      "
+    "  [◐] Section 1/1                             [-]   [◐] Section 1/1                             [-] "
+    "    [●] - before text 1⏎                              [●] + after text 2⏎                           "
+    "    [●] - before text 2⏎                                 23 this is some trailing text⏎             "
+    "    [●] + after text 1⏎                                                                             "
+    "       23 this is some trailing text⏎                                                               "
+    "[●] baz                                       [-]                                                   "
+    "#);
+    insta::assert_snapshot!(moved_to_first, @r#"
+    "[File] [Edit] [Select] [View]                                                                       "
+    "[+] [Edit message]  •  First commit               [+] [Edit message]  •  Second commit              "
+    "                                                                                                    "
+    "(●) foo/bar                                   (-)           There are no changes to view.           "
+    "        ⋮                                                                                           "
+    "       18 this is some text⏎                                                                        "
+    "       19 this is some text⏎                                                                        "
+    "       20 this is some text⏎                                                                        "
+    "  [●] Section 1/1                             [-]                                                   "
+    "    [●] - before text 1⏎                                                                            "
+    "    [●] - before text 2⏎                                                                            "
+    "    [●] + after text 1⏎                                                                             "
+    "    [●] + after text 2⏎                                                                             "
+    "       23 this is some trailing text⏎                                                               "
+    "#);
+    insta::assert_snapshot!(moved_to_second, @r#"
+    "[File] [Edit] [Select] [View]                                                                       "
+    "[+] [Edit message]  •  First commit               [+] [Edit message]  •  Second commit              "
+    "                                                                                                    "
+    "[◐] foo/bar                                   [-] [◐] foo/bar                                   [-] "
+    "        ⋮                                                 ⋮                                         "
+    "       18 this is some text⏎                             18 this is some text⏎                      "
+    "       19 this is some text⏎                             19 this is some text⏎                      "
+    "       20 this is some text⏎                             20 this is some text⏎                      "
+    "  [◐] Section 1/1                             [-]   [◐] Section 1/1                             [-] "
+    "    (●) - before text 2⏎                              [●] - before text 1⏎                          "
+    "    [●] + after text 1⏎                                  23 this is some trailing text⏎             "
+    "    [●] + after text 2⏎                                                                             "
+    "       23 this is some trailing text⏎                                                               "
+    "[●] baz                                       [-]                                                   "
+    "#);
+    insta::assert_snapshot!(inline, @r#"
+    "[File] [Edit] [Select] [View]                                                                       "
+    "[+] [Edit message]  •  First commit                                                                 "
+    "                                                                                                    "
+    "[◐] foo/bar                                                                                      [-]"
+    "        ⋮                                                                                           "
+    "       18 this is some text⏎                                                                        "
+    "       19 this is some text⏎                                                                        "
+    "       20 this is some text⏎                                                                        "
+    "  [◐] Section 1/1                                                                                [-]"
+    "    ( ) - before text 1⏎                                                                            "
+    "    [●] - before text 2⏎                                                                            "
+    "    [●] + after text 1⏎                                                                             "
+    "    [●] + after text 2⏎                                                                             "
+    "       23 this is some trailing text⏎                                                               "
+    "#);
+    assert_eq!(
+        state.commits[1].message.as_deref(),
+        Some("Edited second commit")
+    );
+    assert_matches!(
+        &state.files[0].sections[1],
+        Section::Changed { lines }
+            if lines.iter().map(|line| line.is_checked).eq([false, true, true, true])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_large_file_page_navigation() -> TestResult {
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files: vec![
+            File {
+                old_path: None,
+                path: Cow::Borrowed(Path::new("large")),
+                file_mode: FileMode::FILE_DEFAULT,
+                sections: vec![Section::Changed {
+                    lines: (1..=500)
+                        .map(|i| SectionChangedLine {
+                            is_checked: false,
+                            change_type: ChangeType::Added,
+                            line: Cow::Owned(format!("line {i}\n")),
+                        })
+                        .collect(),
+                }],
+            },
+            File {
+                old_path: None,
+                path: Cow::Borrowed(Path::new("small")),
+                file_mode: FileMode::FILE_DEFAULT,
+                sections: vec![Section::Changed {
+                    lines: vec![SectionChangedLine {
+                        is_checked: false,
+                        change_type: ChangeType::Added,
+                        line: Cow::Borrowed("small line\n"),
+                    }],
+                }],
+            },
+        ],
+    };
+    let paged = TestingScreenshot::default();
+    let scrolled_away = TestingScreenshot::default();
+    let paged_back = TestingScreenshot::default();
+    let mut input = TestingInput::new(
+        80,
+        8,
+        [Event::ExpandAll]
+            .into_iter()
+            .chain(iter::repeat_n(Event::FocusNextPage, 30))
+            .chain([paged.event(), Event::ToggleItem])
+            .chain(iter::repeat_n(Event::PageDown, 20))
+            .chain([
+                scrolled_away.event(),
+                Event::FocusNextPage,
+                paged_back.event(),
+                Event::FocusNextPage,
+                Event::ToggleItem,
+                Event::QuitAccept,
+            ]),
+    );
+    let recorder = Recorder::new(state, &mut input);
+    let state = recorder.run()?;
+
+    insta::assert_snapshot!(paged, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "[ ] large                                                                    [-]"
+    "    [ ] + line 114⏎                                                             "
+    "    [ ] + line 115⏎                                                             "
+    "    [ ] + line 116⏎                                                             "
+    "    [ ] + line 117⏎                                                             "
+    "    [ ] + line 118⏎                                                             "
+    "    ( ) + line 119⏎                                                             "
+    "#);
+    insta::assert_snapshot!(scrolled_away, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "[◐] large                                                                    [-]"
+    "    [ ] + line 274⏎                                                             "
+    "    [ ] + line 275⏎                                                             "
+    "    [ ] + line 276⏎                                                             "
+    "    [ ] + line 277⏎                                                             "
+    "    [ ] + line 278⏎                                                             "
+    "    [ ] + line 279⏎                                                             "
+    "#);
+    insta::assert_snapshot!(paged_back, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "[◐] large                                                                    [-]"
+    "    (●) + line 119⏎                                                             "
+    "    [ ] + line 120⏎                                                             "
+    "    [ ] + line 121⏎                                                             "
+    "    [ ] + line 122⏎                                                             "
+    "    [ ] + line 123⏎                                                             "
+    "    [ ] + line 124⏎                                                             "
+    "#);
+    let checked_lines: Vec<_> = state.files[0]
+        .sections
+        .iter()
+        .flat_map(|section| match section {
+            Section::Changed { lines } => lines.as_slice(),
+            _ => &[],
+        })
+        .filter(|line| line.is_checked)
+        .map(|line| line.line.trim_end())
+        .collect();
+    insta::assert_debug_snapshot!(checked_lines, @r#"
+    [
+        "line 119",
+        "line 123",
+    ]
+    "#);
+
+    Ok(())
+}
+
+/// Loads the full contents of placeholder files from `files`, and records
+/// which files were loaded, and how many of them were loaded before the
+/// changes were accepted.
+struct LoadingInput {
+    input: TestingInput,
+    files: Vec<File<'static>>,
+    loaded_file_idxs: Vec<usize>,
+    num_loaded_before_accept: Option<usize>,
+}
+
+impl RecordInput for LoadingInput {
+    fn terminal_kind(&self) -> TerminalKind {
+        self.input.terminal_kind()
+    }
+
+    fn next_events(&mut self) -> Result<Vec<Event>, RecordError> {
+        let events = self.input.next_events()?;
+        if events.contains(&Event::QuitAccept) {
+            self.num_loaded_before_accept = Some(self.loaded_file_idxs.len());
+        }
+        Ok(events)
+    }
+
+    fn edit_commit_message(&mut self, message: &str) -> Result<String, RecordError> {
+        self.input.edit_commit_message(message)
+    }
+
+    fn load_file(
+        &mut self,
+        file_idx: usize,
+        _file: &File,
+    ) -> Result<Option<File<'static>>, RecordError> {
+        self.loaded_file_idxs.push(file_idx);
+        Ok(Some(self.files[file_idx].clone()))
+    }
+}
+
+#[test]
+fn test_load_files_when_visible() -> TestResult {
+    let placeholder_file = |i: usize| File {
+        old_path: None,
+        path: Cow::Owned(format!("file{i}").into()),
+        file_mode: FileMode::FILE_DEFAULT,
+        sections: vec![],
+    };
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files: (0..20).map(placeholder_file).collect(),
+    };
+    let initial = TestingScreenshot::default();
+    let scrolled = TestingScreenshot::default();
+    let mut input = LoadingInput {
+        input: TestingInput::new(
+            80,
+            6,
+            [
+                initial.event(),
+                Event::ToggleItem,
+                Event::ScrollDown,
+                Event::ScrollDown,
+                Event::ScrollDown,
+                scrolled.event(),
+                Event::QuitAccept,
+            ],
+        ),
+        files: (0..20)
+            .map(|i| File {
+                sections: vec![Section::Changed {
+                    lines: vec![SectionChangedLine {
+                        is_checked: false,
+                        change_type: ChangeType::Added,
+                        line: Cow::Borrowed("foo\n"),
+                    }],
+                }],
+                ..placeholder_file(i)
+            })
+            .collect(),
+        loaded_file_idxs: Vec::new(),
+        num_loaded_before_accept: None,
+    };
+    let recorder = Recorder::new(state, &mut input);
+    let state = recorder.run()?;
+
+    insta::assert_snapshot!(initial, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "( ) file0                                                                    (+)"
+    "[ ] file1                                                                    [+]"
+    "[ ] file2                                                                    [+]"
+    "[ ] file3                                                                    [+]"
+    "[ ] file4                                                                    [+]"
+    "#);
+    insta::assert_snapshot!(scrolled, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "[ ] file3                                                                    [+]"
+    "[ ] file4                                                                    [+]"
+    "[ ] file5                                                                    [+]"
+    "[ ] file6                                                                    [+]"
+    "[ ] file7                                                                    [+]"
+    "#);
+    // Only the files which were shown were loaded while the UI was open, and
+    // the rest were loaded when the changes were accepted.
+    assert_eq!(input.num_loaded_before_accept, Some(8));
+    assert_eq!(input.loaded_file_idxs, (0..20).collect::<Vec<_>>());
+    assert!(state.files.iter().all(|file| file.sections.len() == 1));
+    assert_eq!(state.files[0].tristate(), Tristate::True);
+    assert_eq!(state.files[1].tristate(), Tristate::False);
+    Ok(())
+}
+
+#[test]
+fn test_toggle_all_without_loading_files() -> TestResult {
+    let placeholder_file = |i: usize| File {
+        old_path: None,
+        path: Cow::Owned(format!("file{i}").into()),
+        file_mode: FileMode::FILE_DEFAULT,
+        sections: vec![],
+    };
+    let loaded_file = |i: usize| File {
+        sections: vec![Section::Changed {
+            lines: vec![
+                SectionChangedLine {
+                    is_checked: true,
+                    change_type: ChangeType::Removed,
+                    line: Cow::Borrowed("foo\n"),
+                },
+                SectionChangedLine {
+                    is_checked: false,
+                    change_type: ChangeType::Added,
+                    line: Cow::Borrowed("bar\n"),
+                },
+            ],
+        }],
+        ..placeholder_file(i)
+    };
+
+    for (events, expected) in [
+        (vec![Event::ToggleAll], [false, true]),
+        (vec![Event::ToggleAll, Event::ToggleAll], [true, false]),
+        (
+            vec![Event::ToggleAll, Event::ToggleAllUniform, Event::ToggleAll],
+            [false, false],
+        ),
+    ] {
+        let state = RecordState {
+            is_read_only: false,
+            commits: Default::default(),
+            files: (0..20).map(placeholder_file).collect(),
+        };
+        let mut input = LoadingInput {
+            input: TestingInput::new(80, 6, events.clone().into_iter().chain([Event::QuitAccept])),
+            files: (0..20).map(loaded_file).collect(),
+            loaded_file_idxs: Vec::new(),
+            num_loaded_before_accept: None,
+        };
+        let recorder = Recorder::new(state, &mut input);
+        let state = recorder.run()?;
+
+        // Only the visible files were loaded while the UI was open, but the
+        // files loaded afterwards were toggled in the same way.
+        assert_eq!(input.num_loaded_before_accept, Some(5), "{events:?}");
+        for file in &state.files {
+            let checked_lines: Vec<_> = file
+                .sections
+                .iter()
+                .flat_map(|section| match section {
+                    Section::Changed { lines } => lines.as_slice(),
+                    _ => &[],
+                })
+                .map(|line| line.is_checked)
+                .collect();
+            assert_eq!(checked_lines, expected, "{events:?} {:?}", file.path);
+        }
+    }
+    Ok(())
+}
+
+struct FullDiffInput {
+    input: TestingInput,
+    full_file: File<'static>,
+}
+
+impl RecordInput for FullDiffInput {
+    fn terminal_kind(&self) -> TerminalKind {
+        self.input.terminal_kind()
+    }
+
+    fn next_events(&mut self) -> Result<Vec<Event>, RecordError> {
+        self.input.next_events()
+    }
+
+    fn edit_commit_message(&mut self, message: &str) -> Result<String, RecordError> {
+        self.input.edit_commit_message(message)
+    }
+
+    fn load_full_diff(
+        &mut self,
+        _file_idx: usize,
+        file: &File,
+    ) -> Result<Option<File<'static>>, RecordError> {
+        let mut full_file = self.full_file.clone();
+        full_file.set_checked(file.tristate() == Tristate::True);
+        Ok(Some(full_file))
+    }
+}
+
+#[test]
+fn test_summary_section() -> TestResult {
+    let summarized_file = File {
+        old_path: None,
+        path: Cow::Borrowed(Path::new("Cargo.lock")),
+        file_mode: FileMode::FILE_DEFAULT,
+        sections: vec![Section::Summary {
+            is_checked: false,
+            description: Cow::Borrowed("2 lines changed in generated file"),
+            old_contents: Cow::Borrowed("foo 1.0\n"),
+            new_contents: Cow::Borrowed("foo 1.1\n"),
+        }],
+    };
+    let state = RecordState {
+        is_read_only: false,
+        commits: Default::default(),
+        files: vec![summarized_file.clone()],
+    };
+
+    // Unless the summary is selected, the old contents are kept.
+    let (selected, unselected) = state.files[0].get_selected_contents();
+    assert_eq!(
+        selected.contents,
+        SelectedContents::Text {
+            contents: "foo 1.0\n".to_string()
+        }
+    );
+    assert_eq!(
+        unselected.contents,
+        SelectedContents::Text {
+            contents: "foo 1.1\n".to_string()
+        }
+    );
+
+    let expanded = TestingScreenshot::default();
+    let toggled = TestingScreenshot::default();
+    let full_diff = TestingScreenshot::default();
+    let mut input = FullDiffInput {
+        input: TestingInput::new(
+            80,
+            6,
+            [
+                Event::ExpandItem,
+                expanded.event(),
+                Event::ToggleItem,
+                toggled.event(),
+                Event::LoadFullDiff,
+                full_diff.event(),
+                Event::QuitAccept,
+            ],
+        ),
+        full_file: File {
+            sections: vec![Section::Changed {
+                lines: vec![
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: ChangeType::Removed,
+                        line: Cow::Borrowed("foo 1.0\n"),
+                    },
+                    SectionChangedLine {
+                        is_checked: false,
+                        change_type: ChangeType::Added,
+                        line: Cow::Borrowed("foo 1.1\n"),
+                    },
+                ],
+            }],
+            ..summarized_file
+        },
+    };
+    let recorder = Recorder::new(state, &mut input);
+    let state = recorder.run()?;
+
+    insta::assert_snapshot!(expanded, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "( ) Cargo.lock                                                               (-)"
+    "  [ ] (2 lines changed in generated file)                                       "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "#);
+    insta::assert_snapshot!(toggled, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "(●) Cargo.lock                                                               (-)"
+    "  [●] (2 lines changed in generated file)                                       "
+    "                                                                                "
+    "                                                                                "
+    "                                                                                "
+    "#);
+    insta::assert_snapshot!(full_diff, @r#"
+    "[File] [Edit] [Select] [View]                                                   "
+    "(●) Cargo.lock                                                               (-)"
+    "  [●] Section 1/1                                                            [-]"
+    "    [●] - foo 1.0⏎                                                              "
+    "    [●] + foo 1.1⏎                                                              "
+    "                                                                                "
+    "#);
+    let (selected, _unselected) = state.files[0].get_selected_contents();
+    assert_eq!(
+        selected.contents,
+        SelectedContents::Text {
+            contents: "foo 1.1\n".to_string()
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_with_theme() -> TestResult {
+    let mut input = TestingInput::new(0, 0, []);
+    let mut recorder = Recorder::new(example_contents(), &mut input).with_theme(Theme::light());
+    recorder.process_event(Event::ExpandAll)?;
+    let area = Rect::new(0, 0, 40, 12);
+    let mut buf = Buffer::empty(area);
+    recorder.render(area, &mut buf);
+
+    // Show the foreground colour of the text at the end of each row.
+    let width = usize::from(buf.area.width);
+    let rows: String = buf
+        .content
+        .chunks(width)
+        .map(|row| {
+            let line: String = row.iter().map(|cell| cell.symbol()).collect();
+            let fg = row
+                .iter()
+                .rev()
+                .find(|cell| cell.symbol().chars().all(char::is_alphanumeric))
+                .map(|cell| cell.fg);
+            format!("{line:?} {fg:?}\n")
+        })
+        .collect();
+    insta::assert_snapshot!(rows, @r#"
+    "[File] [Edit] [Select] [View]           " Some(Reset)
+    "(◐) foo/bar                          (-)" Some(Indexed(25))
+    "        ⋮                               " None
+    "       18 this is some text⏎            " Some(Indexed(242))
+    "       19 this is some text⏎            " Some(Indexed(242))
+    "       20 this is some text⏎            " Some(Indexed(242))
+    "  [◐] Section 1/1                    [-]" Some(Reset)
+    "    [●] - before text 1⏎                " Some(Indexed(124))
+    "    [●] - before text 2⏎                " Some(Indexed(124))
+    "    [●] + after text 1⏎                 " Some(Indexed(28))
+    "    [ ] + after text 2⏎                 " Some(Indexed(28))
+    "       23 this is some trailing text⏎   " Some(Indexed(242))
+    "#);
 
     Ok(())
 }